@@ -3,26 +3,66 @@
 
 //! Attribute types for nodes and functions.
 
-use crate::enums::{AttributeType, DataType};
+use crate::enums::AttributeType;
 use crate::graph::Graph;
-use crate::tensor::Tensor;
+use crate::tensor::{SparseTensor, Tensor};
+use crate::types::TypeProto;
+use std::collections::HashMap;
 use std::rc::Rc;
+use thiserror::Error;
+
+/// An error raised when reading an attribute of a node.
+///
+/// The `node` field identifies the node as `op_type(name)`.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum AttributeError {
+    /// The attribute is not set on the node.
+    #[error("{node}: missing attribute '{name}'")]
+    Missing { node: String, name: String },
+
+    /// The attribute has a different type than requested.
+    #[error("{node}: attribute '{name}' expects {expected}, got {actual}")]
+    TypeMismatch {
+        node: String,
+        name: String,
+        expected: AttributeType,
+        actual: AttributeType,
+    },
+
+    /// A string attribute is not valid UTF-8.
+    #[error("{node}: attribute '{name}' is not valid UTF-8")]
+    InvalidUtf8 { node: String, name: String },
+
+    /// The attribute references a function parameter and has no value yet.
+    #[error("{node}: attribute '{name}' is a reference to '{ref_attr_name}' and has no value")]
+    UnresolvedRef {
+        node: String,
+        name: String,
+        ref_attr_name: String,
+    },
+}
 
 /// An ONNX attribute value.
+///
+/// There is one variant for each [`AttributeType`] except `Undefined`.
+/// Strings are byte strings, as in `AttributeProto`; they are usually, but
+/// not necessarily, valid UTF-8.
 #[derive(Debug, Clone)]
 pub enum AttrValue {
     Float(f32),
     Int(i64),
-    String(String),
+    String(Vec<u8>),
     Tensor(Box<Tensor>),
     Graph(Rc<Graph>),
+    SparseTensor(Box<SparseTensor>),
+    TypeProto(TypeProto),
     Floats(Vec<f32>),
     Ints(Vec<i64>),
-    Strings(Vec<String>),
+    Strings(Vec<Vec<u8>>),
     Tensors(Vec<Box<Tensor>>),
     Graphs(Vec<Rc<Graph>>),
-    DataType(DataType),
-    DataTypes(Vec<DataType>),
+    SparseTensors(Vec<Box<SparseTensor>>),
+    TypeProtos(Vec<TypeProto>),
 }
 
 impl AttrValue {
@@ -34,13 +74,15 @@ impl AttrValue {
             AttrValue::String(_) => AttributeType::String,
             AttrValue::Tensor(_) => AttributeType::Tensor,
             AttrValue::Graph(_) => AttributeType::Graph,
+            AttrValue::SparseTensor(_) => AttributeType::SparseTensor,
+            AttrValue::TypeProto(_) => AttributeType::TypeProto,
             AttrValue::Floats(_) => AttributeType::Floats,
             AttrValue::Ints(_) => AttributeType::Ints,
             AttrValue::Strings(_) => AttributeType::Strings,
             AttrValue::Tensors(_) => AttributeType::Tensors,
             AttrValue::Graphs(_) => AttributeType::Graphs,
-            AttrValue::DataType(_) => AttributeType::TypeProto,
-            AttrValue::DataTypes(_) => AttributeType::TypeProtos,
+            AttrValue::SparseTensors(_) => AttributeType::SparseTensors,
+            AttrValue::TypeProtos(_) => AttributeType::TypeProtos,
         }
     }
 }
@@ -76,8 +118,24 @@ impl Attr {
         Self::new(name, AttrValue::Int(value))
     }
 
-    pub fn string(name: impl Into<String>, value: String) -> Self {
-        Self::new(name, AttrValue::String(value))
+    pub fn string(name: impl Into<String>, value: impl Into<Vec<u8>>) -> Self {
+        Self::new(name, AttrValue::String(value.into()))
+    }
+
+    pub fn tensor(name: impl Into<String>, value: Tensor) -> Self {
+        Self::new(name, AttrValue::Tensor(Box::new(value)))
+    }
+
+    pub fn graph(name: impl Into<String>, value: Graph) -> Self {
+        Self::new(name, AttrValue::Graph(Rc::new(value)))
+    }
+
+    pub fn sparse_tensor(name: impl Into<String>, value: SparseTensor) -> Self {
+        Self::new(name, AttrValue::SparseTensor(Box::new(value)))
+    }
+
+    pub fn type_proto(name: impl Into<String>, value: TypeProto) -> Self {
+        Self::new(name, AttrValue::TypeProto(value))
     }
 
     pub fn floats(name: impl Into<String>, values: Vec<f32>) -> Self {
@@ -88,8 +146,39 @@ impl Attr {
         Self::new(name, AttrValue::Ints(values))
     }
 
-    pub fn strings(name: impl Into<String>, values: Vec<String>) -> Self {
-        Self::new(name, AttrValue::Strings(values))
+    pub fn strings(
+        name: impl Into<String>,
+        values: impl IntoIterator<Item = impl Into<Vec<u8>>>,
+    ) -> Self {
+        Self::new(
+            name,
+            AttrValue::Strings(values.into_iter().map(Into::into).collect()),
+        )
+    }
+
+    pub fn tensors(name: impl Into<String>, values: Vec<Tensor>) -> Self {
+        Self::new(
+            name,
+            AttrValue::Tensors(values.into_iter().map(Box::new).collect()),
+        )
+    }
+
+    pub fn graphs(name: impl Into<String>, values: Vec<Graph>) -> Self {
+        Self::new(
+            name,
+            AttrValue::Graphs(values.into_iter().map(Rc::new).collect()),
+        )
+    }
+
+    pub fn sparse_tensors(name: impl Into<String>, values: Vec<SparseTensor>) -> Self {
+        Self::new(
+            name,
+            AttrValue::SparseTensors(values.into_iter().map(Box::new).collect()),
+        )
+    }
+
+    pub fn type_protos(name: impl Into<String>, values: Vec<TypeProto>) -> Self {
+        Self::new(name, AttrValue::TypeProtos(values))
     }
 }
 
@@ -117,6 +206,105 @@ impl RefAttr {
     }
 }
 
+/// An attribute attached to a node.
+///
+/// Nodes in a function body may reference one of the function's attribute
+/// parameters instead of carrying a concrete value.
+#[derive(Debug, Clone)]
+pub enum NodeAttr {
+    /// A concrete attribute value.
+    Value(Attr),
+    /// A reference to an attribute parameter of the enclosing function.
+    Ref(RefAttr),
+}
+
+impl NodeAttr {
+    /// Returns the name of the attribute.
+    pub fn name(&self) -> &str {
+        match self {
+            NodeAttr::Value(attr) => &attr.name,
+            NodeAttr::Ref(attr) => &attr.name,
+        }
+    }
+
+    /// Returns the attribute type.
+    pub fn attr_type(&self) -> AttributeType {
+        match self {
+            NodeAttr::Value(attr) => attr.attr_type(),
+            NodeAttr::Ref(attr) => attr.type_,
+        }
+    }
+
+    /// Returns true if this attribute is a reference.
+    pub fn is_ref(&self) -> bool {
+        matches!(self, NodeAttr::Ref(_))
+    }
+
+    /// Returns the concrete attribute, if this is not a reference.
+    pub fn as_value(&self) -> Option<&Attr> {
+        match self {
+            NodeAttr::Value(attr) => Some(attr),
+            NodeAttr::Ref(_) => None,
+        }
+    }
+
+    /// Returns the concrete attribute mutably, if this is not a reference.
+    pub fn as_value_mut(&mut self) -> Option<&mut Attr> {
+        match self {
+            NodeAttr::Value(attr) => Some(attr),
+            NodeAttr::Ref(_) => None,
+        }
+    }
+
+    /// Returns the reference attribute, if this is a reference.
+    pub fn as_ref_attr(&self) -> Option<&RefAttr> {
+        match self {
+            NodeAttr::Value(_) => None,
+            NodeAttr::Ref(attr) => Some(attr),
+        }
+    }
+
+    /// Returns the name of the referenced parameter, which is serialized as
+    /// `AttributeProto.ref_attr_name`.
+    pub fn ref_attr_name(&self) -> Option<&str> {
+        self.as_ref_attr().map(|attr| attr.ref_attr_name.as_str())
+    }
+
+    /// Resolves the attribute to a concrete value.
+    ///
+    /// A reference is looked up by its `ref_attr_name` in `bindings` and the
+    /// bound value is returned under this attribute's name. Returns `None`
+    /// if the reference is unbound, or bound to a value whose type differs
+    /// from the reference's `type_`.
+    pub fn resolve(&self, bindings: &HashMap<String, Attr>) -> Option<Attr> {
+        match self {
+            NodeAttr::Value(attr) => Some(attr.clone()),
+            NodeAttr::Ref(ref_attr) => {
+                let bound = bindings
+                    .get(&ref_attr.ref_attr_name)
+                    .filter(|bound| bound.attr_type() == ref_attr.type_)?;
+                Some(Attr {
+                    name: ref_attr.name.clone(),
+                    value: bound.value.clone(),
+                    doc_string: ref_attr.doc_string.clone(),
+                })
+            }
+        }
+    }
+}
+
+impl From<Attr> for NodeAttr {
+    fn from(attr: Attr) -> Self {
+        NodeAttr::Value(attr)
+    }
+}
+
+impl From<RefAttr> for NodeAttr {
+    fn from(attr: RefAttr) -> Self {
+        NodeAttr::Ref(attr)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -152,4 +340,80 @@ mod tests {
         assert_eq!(attr.name, "shape");
         assert_eq!(attr.attr_type(), AttributeType::Ints);
     }
+
+    #[test]
+    fn test_node_attr() {
+        let value: NodeAttr = Attr::int("axis", 1).into();
+        assert_eq!(value.name(), "axis");
+        assert!(!value.is_ref());
+        assert!(value.as_value().is_some());
+
+        let reference: NodeAttr = RefAttr::new("alpha", "beta", AttributeType::Float).into();
+        assert_eq!(reference.name(), "alpha");
+        assert_eq!(reference.attr_type(), AttributeType::Float);
+        assert!(reference.is_ref());
+        assert_eq!(reference.as_ref_attr().unwrap().ref_attr_name, "beta");
+        assert_eq!(reference.ref_attr_name(), Some("beta"));
+        assert_eq!(value.ref_attr_name(), None);
+    }
+
+    #[test]
+    fn test_node_attr_resolve() {
+        let mut bindings = HashMap::new();
+        bindings.insert("beta".to_string(), Attr::float("beta", 0.2));
+
+        let reference = NodeAttr::from(RefAttr::new("alpha", "beta", AttributeType::Float));
+        let resolved = reference.resolve(&bindings).unwrap();
+        assert_eq!(resolved.name, "alpha");
+        assert!(matches!(resolved.value, AttrValue::Float(v) if v == 0.2));
+
+        let unbound = NodeAttr::from(RefAttr::new("gamma", "delta", AttributeType::Float));
+        assert!(unbound.resolve(&bindings).is_none());
+
+        let mismatched = NodeAttr::from(RefAttr::new("axis", "beta", AttributeType::Int));
+        assert!(mismatched.resolve(&bindings).is_none());
+    }
+
+    #[test]
+    fn test_attr_byte_strings() {
+        let attr = Attr::string("data", vec![0xff, 0x00, 0x41]);
+        assert!(matches!(attr.value, AttrValue::String(ref s) if s == &[0xff, 0x00, 0x41]));
+
+        let attr = Attr::strings("names", ["a", "bc"]);
+        assert_eq!(attr.attr_type(), AttributeType::Strings);
+        assert!(matches!(attr.value, AttrValue::Strings(ref v) if v[1] == b"bc"));
+    }
+
+    #[test]
+    fn test_attr_all_types() {
+        use crate::enums::DataType;
+        use crate::shape::Shape;
+
+        let tensor = Tensor::new(DataType::Float, Shape::new(vec![2]));
+        let sparse = SparseTensor::new(
+            Tensor::new(DataType::Float, Shape::new(vec![1])),
+            Tensor::new(DataType::Int64, Shape::new(vec![1])),
+            vec![4],
+        );
+        let type_proto = TypeProto::optional(TypeProto::tensor(DataType::Float, None));
+
+        let attrs = vec![
+            Attr::float("a", 1.0),
+            Attr::int("a", 1),
+            Attr::string("a", "x"),
+            Attr::tensor("a", tensor.clone()),
+            Attr::graph("a", Graph::new()),
+            Attr::floats("a", vec![1.0]),
+            Attr::ints("a", vec![1]),
+            Attr::strings("a", ["x"]),
+            Attr::tensors("a", vec![tensor]),
+            Attr::graphs("a", vec![Graph::new()]),
+            Attr::sparse_tensor("a", sparse.clone()),
+            Attr::sparse_tensors("a", vec![sparse]),
+            Attr::type_proto("a", type_proto.clone()),
+            Attr::type_protos("a", vec![type_proto]),
+        ];
+        let types: Vec<_> = attrs.iter().map(|a| a.attr_type() as i32).collect();
+        assert_eq!(types, (1..=14).collect::<Vec<_>>());
+    }
 }
@@ -0,0 +1,314 @@
+// Copyright (c) ONNX Project Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Call graph of model-local functions.
+//!
+//! This module records which functions are called by the main graph and by
+//! each function body, including calls made inside subgraphs.
+
+use crate::attribute::AttrValue;
+use crate::function::FunctionId;
+use crate::model::Model;
+use crate::node::Node;
+use indexmap::{IndexMap, IndexSet};
+
+/// The call graph of the functions in a model.
+///
+/// Only calls to functions defined in the model are recorded; nodes that
+/// call standard operators are ignored.
+///
+/// # Examples
+///
+/// ```
+/// use onnx_ir_core::call_graph::CallGraph;
+/// use onnx_ir_core::{Function, Graph, Model, Node};
+///
+/// let mut graph = Graph::new();
+/// let mut call = Node::new("MyFunc");
+/// call.domain = "com.example".to_string();
+/// graph.append(call);
+///
+/// let mut model = Model::new(graph);
+/// model.add_function(Function::new("MyFunc", "com.example"));
+/// model.add_function(Function::new("Unused", "com.example"));
+///
+/// let call_graph = CallGraph::new(&model);
+/// let unused: Vec<_> = call_graph.unused_functions().map(|id| id.name.as_str()).collect();
+/// assert_eq!(unused, vec!["Unused"]);
+/// ```
+#[derive(Debug, Clone)]
+pub struct CallGraph {
+    /// Functions called directly from the main graph.
+    graph_callees: IndexSet<FunctionId>,
+    /// Functions called directly from each function body.
+    callees: IndexMap<FunctionId, IndexSet<FunctionId>>,
+}
+
+impl CallGraph {
+    /// Builds the call graph of a model.
+    pub fn new(model: &Model) -> Self {
+        let mut graph_callees = IndexSet::new();
+        collect_calls(model.graph.iter_nodes(), model, &mut graph_callees);
+
+        let callees = model
+            .functions
+            .iter()
+            .map(|(id, function)| {
+                let mut calls = IndexSet::new();
+                collect_calls(function.iter_nodes(), model, &mut calls);
+                (id.clone(), calls)
+            })
+            .collect();
+
+        Self {
+            graph_callees,
+            callees,
+        }
+    }
+
+    /// Returns the functions called directly from the main graph.
+    pub fn graph_callees(&self) -> impl Iterator<Item = &FunctionId> {
+        self.graph_callees.iter()
+    }
+
+    /// Returns the functions called directly from the body of `id`.
+    pub fn callees(&self, id: &FunctionId) -> impl Iterator<Item = &FunctionId> {
+        self.callees.get(id).into_iter().flatten()
+    }
+
+    /// Returns the functions whose bodies call `id` directly.
+    pub fn callers<'a>(&'a self, id: &'a FunctionId) -> impl Iterator<Item = &'a FunctionId> {
+        self.callees
+            .iter()
+            .filter(move |(_, calls)| calls.contains(id))
+            .map(|(caller, _)| caller)
+    }
+
+    /// Returns true if the main graph calls `id` directly.
+    pub fn is_called_by_graph(&self, id: &FunctionId) -> bool {
+        self.graph_callees.contains(id)
+    }
+
+    /// Returns the functions reachable from the main graph, in the order
+    /// they are first reached.
+    pub fn reachable_functions(&self) -> IndexSet<&FunctionId> {
+        let mut reachable = IndexSet::new();
+        let mut stack: Vec<&FunctionId> = self.graph_callees.iter().rev().collect();
+        while let Some(id) = stack.pop() {
+            if reachable.insert(id) {
+                stack.extend(self.callees(id).collect::<Vec<_>>().into_iter().rev());
+            }
+        }
+        reachable
+    }
+
+    /// Returns the functions that are not reachable from the main graph.
+    pub fn unused_functions(&self) -> impl Iterator<Item = &FunctionId> {
+        let reachable = self.reachable_functions();
+        self.callees
+            .keys()
+            .filter(move |id| !reachable.contains(id))
+    }
+
+    /// Finds a cycle of function calls.
+    ///
+    /// Returns the functions on the cycle, starting and ending with the same
+    /// function, or `None` if no function is recursive.
+    pub fn find_cycle(&self) -> Option<Vec<FunctionId>> {
+        let mut done = IndexSet::new();
+        for id in self.callees.keys() {
+            let mut path = Vec::new();
+            if let Some(cycle) = self.find_cycle_from(id, &mut path, &mut done) {
+                return Some(cycle);
+            }
+        }
+        None
+    }
+
+    /// Returns true if `id` calls itself, directly or indirectly.
+    pub fn is_recursive(&self, id: &FunctionId) -> bool {
+        let mut seen = IndexSet::new();
+        let mut stack: Vec<&FunctionId> = self.callees(id).collect();
+        while let Some(current) = stack.pop() {
+            if current == id {
+                return true;
+            }
+            if seen.insert(current) {
+                stack.extend(self.callees(current));
+            }
+        }
+        false
+    }
+
+    fn find_cycle_from<'a>(
+        &'a self,
+        id: &'a FunctionId,
+        path: &mut Vec<&'a FunctionId>,
+        done: &mut IndexSet<&'a FunctionId>,
+    ) -> Option<Vec<FunctionId>> {
+        if let Some(pos) = path.iter().position(|p| *p == id) {
+            let mut cycle: Vec<_> = path[pos..].iter().map(|p| (*p).clone()).collect();
+            cycle.push(id.clone());
+            return Some(cycle);
+        }
+        if done.contains(id) {
+            return None;
+        }
+        path.push(id);
+        for callee in self.callees(id) {
+            if let Some(cycle) = self.find_cycle_from(callee, path, done) {
+                return Some(cycle);
+            }
+        }
+        path.pop();
+        done.insert(id);
+        None
+    }
+}
+
+/// Records the model-local functions called by `nodes` and their subgraphs.
+fn collect_calls<'a>(
+    nodes: impl Iterator<Item = &'a Node>,
+    model: &Model,
+    calls: &mut IndexSet<FunctionId>,
+) {
+    for node in nodes {
+        let id = node.function_id();
+        if model.functions.contains_key(&id) {
+            calls.insert(id);
+        }
+        for attr in node.attributes.values().filter_map(|attr| attr.as_value()) {
+            match &attr.value {
+                AttrValue::Graph(graph) => collect_calls(graph.iter_nodes(), model, calls),
+                AttrValue::Graphs(graphs) => {
+                    for graph in graphs {
+                        collect_calls(graph.iter_nodes(), model, calls);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::attribute::Attr;
+    use crate::function::Function;
+    use crate::graph::Graph;
+    use std::rc::Rc;
+
+    fn call(name: &str) -> Node {
+        let mut node = Node::new(name);
+        node.domain = "custom".to_string();
+        node
+    }
+
+    fn function(name: &str, calls: &[&str]) -> Function {
+        let mut func = Function::new(name, "custom");
+        for callee in calls {
+            func.append(call(callee));
+        }
+        func
+    }
+
+    fn id(name: &str) -> FunctionId {
+        FunctionId::new("custom", name, "")
+    }
+
+    fn model(graph_calls: &[&str], functions: Vec<Function>) -> Model {
+        let mut graph = Graph::new();
+        for name in graph_calls {
+            graph.append(call(name));
+        }
+        let mut model = Model::new(graph);
+        for func in functions {
+            model.add_function(func);
+        }
+        model
+    }
+
+    #[test]
+    fn test_callers_and_callees() {
+        let model = model(
+            &["A"],
+            vec![
+                function("A", &["B", "C"]),
+                function("B", &["C"]),
+                function("C", &[]),
+            ],
+        );
+        let call_graph = CallGraph::new(&model);
+
+        assert!(call_graph.is_called_by_graph(&id("A")));
+        assert!(!call_graph.is_called_by_graph(&id("B")));
+        assert_eq!(
+            call_graph.callees(&id("A")).collect::<Vec<_>>(),
+            vec![&id("B"), &id("C")]
+        );
+        assert_eq!(
+            call_graph.callers(&id("C")).collect::<Vec<_>>(),
+            vec![&id("A"), &id("B")]
+        );
+        assert_eq!(call_graph.unused_functions().count(), 0);
+        assert!(call_graph.find_cycle().is_none());
+    }
+
+    #[test]
+    fn test_unused_functions() {
+        let model = model(
+            &["A"],
+            vec![
+                function("A", &[]),
+                function("B", &["C"]),
+                function("C", &[]),
+            ],
+        );
+        let call_graph = CallGraph::new(&model);
+
+        assert_eq!(
+            call_graph.unused_functions().collect::<Vec<_>>(),
+            vec![&id("B"), &id("C")]
+        );
+    }
+
+    #[test]
+    fn test_recursion() {
+        let model = model(
+            &["A"],
+            vec![
+                function("A", &["B"]),
+                function("B", &["C"]),
+                function("C", &["B"]),
+            ],
+        );
+        let call_graph = CallGraph::new(&model);
+
+        assert!(!call_graph.is_recursive(&id("A")));
+        assert!(call_graph.is_recursive(&id("B")));
+        assert_eq!(
+            call_graph.find_cycle(),
+            Some(vec![id("B"), id("C"), id("B")])
+        );
+    }
+
+    #[test]
+    fn test_calls_in_subgraphs() {
+        let mut branch = Graph::new();
+        branch.append(call("B"));
+        let mut if_node = Node::new("If");
+        if_node.set_attribute(Attr::new("then_branch", AttrValue::Graph(Rc::new(branch))));
+
+        let mut a = Function::new("A", "custom");
+        a.append(if_node);
+
+        let model = model(&["A"], vec![a, function("B", &[])]);
+        let call_graph = CallGraph::new(&model);
+
+        assert_eq!(
+            call_graph.callees(&id("A")).collect::<Vec<_>>(),
+            vec![&id("B")]
+        );
+    }
+}
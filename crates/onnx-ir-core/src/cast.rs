@@ -0,0 +1,428 @@
+// Copyright (c) ONNX Project Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Conversion of tensors between data types.
+//!
+//! The conversions follow the semantics of the ONNX `Cast` operator:
+//!
+//! - Floats are rounded to the nearest representable number, ties to even,
+//!   as described in [`crate::float`].
+//! - Floats become integers by truncation toward zero. NaN becomes zero.
+//! - Integers wrap around when narrowed, except 4 and 2-bit integers, which
+//!   clamp to their range after rounding floats to nearest, ties to even.
+//! - Any non-zero number is true, and booleans become 0 or 1.
+//! - Strings are parsed in plain or scientific notation.
+
+use crate::element::{
+    Bfloat16, Element, Float16, Float4E2M1, Float8E4M3Fn, Float8E4M3Fnuz, Float8E5M2,
+    Float8E5M2Fnuz, Float8E8M0,
+};
+use crate::enums::DataType;
+use crate::error::{IrError, Result};
+use crate::float::RoundMode;
+use crate::tensor::{pack_bits, unpack_bits, StringTensor, Tensor, TensorProtocol};
+
+/// An element of a tensor being cast.
+#[derive(Debug, Clone, Copy)]
+enum Scalar {
+    Float(f64),
+    Int(i128),
+    Bool(bool),
+}
+
+impl Scalar {
+    fn to_f64(self) -> f64 {
+        match self {
+            Scalar::Float(value) => value,
+            Scalar::Int(value) => value as f64,
+            Scalar::Bool(value) => f64::from(u8::from(value)),
+        }
+    }
+
+    fn to_f32(self) -> f32 {
+        match self {
+            Scalar::Float(value) => value as f32,
+            Scalar::Int(value) => value as f32,
+            Scalar::Bool(value) => f32::from(u8::from(value)),
+        }
+    }
+
+    /// Truncates toward zero. The result is wrapped to narrower types.
+    fn to_int(self) -> i128 {
+        match self {
+            Scalar::Float(value) => value as i128,
+            Scalar::Int(value) => value,
+            Scalar::Bool(value) => i128::from(value),
+        }
+    }
+
+    /// Rounds to nearest, ties to even, and clamps to `[min, max]`.
+    fn to_clamped(self, min: i128, max: i128) -> i128 {
+        match self {
+            Scalar::Float(value) if value.is_nan() => 0,
+            Scalar::Float(value) => value.clamp(min as f64, max as f64).round_ties_even() as i128,
+            _ => self.to_int().clamp(min, max),
+        }
+    }
+
+    fn to_bool(self) -> bool {
+        match self {
+            Scalar::Float(value) => value != 0.0,
+            Scalar::Int(value) => value != 0,
+            Scalar::Bool(value) => value,
+        }
+    }
+
+    /// Parses a string as a value of the given data type.
+    fn parse(input: &str, to: DataType) -> Result<Self> {
+        let trimmed = input.trim();
+        let error = |message: &str| IrError::Parse {
+            input: input.to_string(),
+            message: message.to_string(),
+        };
+        if to == DataType::Bool {
+            if trimmed.eq_ignore_ascii_case("true") {
+                return Ok(Scalar::Bool(true));
+            }
+            if trimmed.eq_ignore_ascii_case("false") {
+                return Ok(Scalar::Bool(false));
+            }
+        }
+        if to.is_integer() {
+            if let Ok(value) = trimmed.parse() {
+                return Ok(Scalar::Int(value));
+            }
+        }
+        trimmed
+            .parse()
+            .map(Scalar::Float)
+            .map_err(|_| error("expected a number"))
+    }
+}
+
+/// Decodes the elements of a tensor.
+fn read(tensor: &Tensor, to: DataType) -> Result<Vec<Scalar>> {
+    fn map<T: Element>(tensor: &Tensor, f: impl Fn(T) -> Scalar) -> Result<Vec<Scalar>> {
+        Ok(tensor.as_slice::<T>()?.iter().map(|&v| f(v)).collect())
+    }
+    let float = |value: f32| Scalar::Float(f64::from(value));
+    // Only used for sub-byte types, where the bit width is known.
+    let bits = tensor.dtype.bitwidth().unwrap_or(8) as u32;
+    let unpack = || unpack_bits(&tensor.data, bits, tensor.size());
+    // Moves the sign bit of a sub-byte integer into the sign of an `i8`.
+    let signed = |value: u8| i128::from(((value << (8 - bits)) as i8) >> (8 - bits));
+    match tensor.dtype {
+        DataType::Float => map(tensor, float),
+        DataType::Double => map(tensor, Scalar::Float),
+        DataType::Float16 => map(tensor, |v: Float16| float(v.to_f32())),
+        DataType::Bfloat16 => map(tensor, |v: Bfloat16| float(v.to_f32())),
+        DataType::Float8E4M3Fn => map(tensor, |v: Float8E4M3Fn| float(v.to_f32())),
+        DataType::Float8E4M3Fnuz => map(tensor, |v: Float8E4M3Fnuz| float(v.to_f32())),
+        DataType::Float8E5M2 => map(tensor, |v: Float8E5M2| float(v.to_f32())),
+        DataType::Float8E5M2Fnuz => map(tensor, |v: Float8E5M2Fnuz| float(v.to_f32())),
+        DataType::Float8E8M0 => map(tensor, |v: Float8E8M0| float(v.to_f32())),
+        DataType::Int8 => map(tensor, |v: i8| Scalar::Int(v.into())),
+        DataType::Int16 => map(tensor, |v: i16| Scalar::Int(v.into())),
+        DataType::Int32 => map(tensor, |v: i32| Scalar::Int(v.into())),
+        DataType::Int64 => map(tensor, |v: i64| Scalar::Int(v.into())),
+        DataType::Uint8 => map(tensor, |v: u8| Scalar::Int(v.into())),
+        DataType::Uint16 => map(tensor, |v: u16| Scalar::Int(v.into())),
+        DataType::Uint32 => map(tensor, |v: u32| Scalar::Int(v.into())),
+        DataType::Uint64 => map(tensor, |v: u64| Scalar::Int(v.into())),
+        DataType::Bool => map(tensor, Scalar::Bool),
+        DataType::Float4E2M1 => Ok(unpack()?
+            .into_iter()
+            .map(|v| float(Float4E2M1::from_bits(v).to_f32()))
+            .collect()),
+        DataType::Uint4 | DataType::Uint2 => Ok(unpack()?
+            .into_iter()
+            .map(|v| Scalar::Int(v.into()))
+            .collect()),
+        DataType::Int4 | DataType::Int2 => Ok(unpack()?
+            .into_iter()
+            .map(|v| Scalar::Int(signed(v)))
+            .collect()),
+        DataType::Undefined | DataType::String | DataType::Complex64 | DataType::Complex128 => {
+            Err(IrError::UnsupportedCast {
+                from: tensor.dtype,
+                to,
+            })
+        }
+    }
+}
+
+/// Encodes elements as the data of a tensor of type `to`.
+fn write(
+    values: &[Scalar],
+    from: DataType,
+    to: DataType,
+    saturate: bool,
+    round_mode: RoundMode,
+) -> Result<Vec<u8>> {
+    fn encode<T: Element>(values: &[Scalar], f: impl Fn(Scalar) -> T) -> Vec<u8> {
+        let mut data = Vec::with_capacity(values.len() * T::SIZE);
+        values.iter().for_each(|&v| f(v).write_le(&mut data));
+        data
+    }
+    let clamped = |min: i128, max: i128| -> Vec<u8> {
+        values
+            .iter()
+            .map(|v| v.to_clamped(min, max) as u8)
+            .collect()
+    };
+    let data = match to {
+        DataType::Float => encode(values, Scalar::to_f32),
+        DataType::Double => encode(values, Scalar::to_f64),
+        DataType::Float16 => encode(values, |v| Float16::from_f64(v.to_f64())),
+        DataType::Bfloat16 => encode(values, |v| Bfloat16::from_f64(v.to_f64())),
+        DataType::Float8E4M3Fn => encode(values, |v| Float8E4M3Fn::from_f32(v.to_f32(), saturate)),
+        DataType::Float8E4M3Fnuz => {
+            encode(values, |v| Float8E4M3Fnuz::from_f32(v.to_f32(), saturate))
+        }
+        DataType::Float8E5M2 => encode(values, |v| Float8E5M2::from_f32(v.to_f32(), saturate)),
+        DataType::Float8E5M2Fnuz => {
+            encode(values, |v| Float8E5M2Fnuz::from_f32(v.to_f32(), saturate))
+        }
+        DataType::Float8E8M0 => encode(values, |v| {
+            Float8E8M0::from_f32(v.to_f32(), saturate, round_mode)
+        }),
+        DataType::Int8 => encode(values, |v| v.to_int() as i8),
+        DataType::Int16 => encode(values, |v| v.to_int() as i16),
+        DataType::Int32 => encode(values, |v| v.to_int() as i32),
+        DataType::Int64 => encode(values, |v| v.to_int() as i64),
+        DataType::Uint8 => encode(values, |v| v.to_int() as u8),
+        DataType::Uint16 => encode(values, |v| v.to_int() as u16),
+        DataType::Uint32 => encode(values, |v| v.to_int() as u32),
+        DataType::Uint64 => encode(values, |v| v.to_int() as u64),
+        DataType::Bool => encode(values, Scalar::to_bool),
+        DataType::Float4E2M1 => {
+            let nibbles: Vec<u8> = values
+                .iter()
+                .map(|v| Float4E2M1::from_f32(v.to_f32()).to_bits())
+                .collect();
+            pack_bits(&nibbles, 4)
+        }
+        DataType::Int4 => pack_bits(&clamped(-8, 7), 4),
+        DataType::Uint4 => pack_bits(&clamped(0, 15), 4),
+        DataType::Int2 => pack_bits(&clamped(-2, 1), 2),
+        DataType::Uint2 => pack_bits(&clamped(0, 3), 2),
+        DataType::Undefined | DataType::String | DataType::Complex64 | DataType::Complex128 => {
+            return Err(IrError::UnsupportedCast { from, to });
+        }
+    };
+    Ok(data)
+}
+
+impl Tensor {
+    /// Converts the tensor to another data type, as the ONNX `Cast`
+    /// operator does.
+    ///
+    /// `saturate` controls how out of range values are converted to float 8
+    /// types, as the attribute of `Cast` does. Values are rounded up for
+    /// `float8e8m0`, the default of `Cast`; see
+    /// [`Tensor::cast_with_round_mode`] for the other modes. The result keeps
+    /// the name, documentation and metadata properties of the tensor.
+    ///
+    /// Returns an error for complex and string types. Use
+    /// [`Tensor::cast_to_strings`] and [`StringTensor::cast`] to convert
+    /// from and to strings.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use onnx_ir_core::{DataType, Shape, Tensor};
+    ///
+    /// let tensor = Tensor::from_vec(Shape::new(vec![3]), vec![1.5f32, -2.7, 300.0]);
+    /// let cast = tensor.cast(DataType::Int8, false).unwrap();
+    /// assert_eq!(cast.to_vec::<i8>().unwrap(), [1, -2, 44]);
+    /// ```
+    pub fn cast(&self, to: DataType, saturate: bool) -> Result<Tensor> {
+        self.cast_with_round_mode(to, saturate, RoundMode::Up)
+    }
+
+    /// Converts the tensor like [`Tensor::cast`], rounding values to
+    /// `float8e8m0` as `round_mode` says.
+    pub fn cast_with_round_mode(
+        &self,
+        to: DataType,
+        saturate: bool,
+        round_mode: RoundMode,
+    ) -> Result<Tensor> {
+        let data = if to == self.dtype {
+            self.data.clone()
+        } else {
+            write(&read(self, to)?, self.dtype, to, saturate, round_mode)?
+        };
+        let mut tensor = Tensor::try_from_bytes(to, self.shape.clone(), data)?;
+        tensor.name = self.name.clone();
+        tensor.doc_string = self.doc_string.clone();
+        tensor.metadata_props = self.metadata_props.clone();
+        Ok(tensor)
+    }
+
+    /// Converts the tensor to a string tensor, formatting each number in
+    /// its shortest exact representation.
+    pub fn cast_to_strings(&self) -> Result<StringTensor> {
+        let strings = read(self, DataType::String)?
+            .into_iter()
+            .map(|value| match value {
+                Scalar::Float(value) if self.dtype == DataType::Double => value.to_string(),
+                Scalar::Float(value) => (value as f32).to_string(),
+                Scalar::Int(value) => value.to_string(),
+                Scalar::Bool(value) => value.to_string(),
+            })
+            .collect();
+        let mut tensor = StringTensor::try_new(self.shape.clone(), strings)?;
+        tensor.name = self.name.clone();
+        tensor.doc_string = self.doc_string.clone();
+        tensor.metadata_props = self.metadata_props.clone();
+        Ok(tensor)
+    }
+}
+
+impl StringTensor {
+    /// Parses the strings as numbers of the given data type, as the ONNX
+    /// `Cast` operator does.
+    ///
+    /// Integers may be written in plain notation and floats in plain or
+    /// scientific notation. Booleans also accept `true` and `false`.
+    ///
+    /// Values are rounded up for `float8e8m0`, the default of `Cast`.
+    ///
+    /// Returns an error if a string is not a number.
+    pub fn cast(&self, to: DataType, saturate: bool) -> Result<Tensor> {
+        let values = self
+            .data
+            .iter()
+            .map(|input| Scalar::parse(input, to))
+            .collect::<Result<Vec<_>>>()?;
+        let data = write(&values, DataType::String, to, saturate, RoundMode::Up)?;
+        let mut tensor = Tensor::try_from_bytes(to, self.shape.clone(), data)?;
+        tensor.name = self.name.clone();
+        tensor.doc_string = self.doc_string.clone();
+        tensor.metadata_props = self.metadata_props.clone();
+        Ok(tensor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shape::Shape;
+
+    fn vector<T: Element>(values: Vec<T>) -> Tensor {
+        Tensor::from_vec(Shape::new(vec![values.len() as i64]), values)
+    }
+
+    #[test]
+    fn test_cast_numbers() {
+        let tensor = vector(vec![1.5f32, -2.5, f32::NAN, 3.99]);
+        let cast = tensor.cast(DataType::Int32, false).unwrap();
+        assert_eq!(cast.to_vec::<i32>().unwrap(), [1, -2, 0, 3]);
+        let cast = tensor.cast(DataType::Bool, false).unwrap();
+        assert_eq!(cast.to_vec::<bool>().unwrap(), [true, true, true, true]);
+
+        let tensor = vector(vec![-1i64, 256, 65537]);
+        let cast = tensor.cast(DataType::Uint8, false).unwrap();
+        assert_eq!(cast.to_vec::<u8>().unwrap(), [255, 0, 1]);
+        let cast = tensor.cast(DataType::Double, false).unwrap();
+        assert_eq!(cast.to_vec::<f64>().unwrap(), [-1.0, 256.0, 65537.0]);
+
+        let tensor = vector(vec![true, false]);
+        let cast = tensor.cast(DataType::Float16, false).unwrap();
+        assert_eq!(
+            cast.to_vec::<Float16>().unwrap(),
+            [Float16(0x3c00), Float16(0)]
+        );
+    }
+
+    #[test]
+    fn test_cast_floats() {
+        let mut tensor = vector(vec![1.0f32, 65520.0, 1e6]);
+        tensor.name = Some("w".to_string());
+        let half = tensor.cast(DataType::Float16, false).unwrap();
+        assert_eq!(half.name.as_deref(), Some("w"));
+        assert_eq!(
+            half.to_vec::<Float16>().unwrap(),
+            [Float16(0x3c00), Float16(0x7c00), Float16(0x7c00)]
+        );
+        let cast = tensor.cast(DataType::Float8E4M3Fn, true).unwrap();
+        assert_eq!(cast.as_bytes(), [0x38, 0x7e, 0x7e]);
+        let cast = tensor.cast(DataType::Float8E4M3Fn, false).unwrap();
+        assert_eq!(cast.as_bytes(), [0x38, 0x7f, 0x7f]);
+
+        let powers = vector(vec![3.0f32, 5.0]);
+        let cast = powers.cast(DataType::Float8E8M0, false).unwrap();
+        assert_eq!(cast.as_bytes(), [129, 130]);
+        let cast = powers
+            .cast_with_round_mode(DataType::Float8E8M0, false, RoundMode::Down)
+            .unwrap();
+        assert_eq!(cast.as_bytes(), [128, 129]);
+
+        let back = half.cast(DataType::Bfloat16, false).unwrap();
+        let back = back.cast(DataType::Float, false).unwrap();
+        assert_eq!(
+            back.to_vec::<f32>().unwrap(),
+            [1.0, f32::INFINITY, f32::INFINITY]
+        );
+    }
+
+    #[test]
+    fn test_cast_sub_byte() {
+        let tensor = vector(vec![-9.0f32, -1.5, 2.5, 7.6, 100.0]);
+        let cast = tensor.cast(DataType::Int4, false).unwrap();
+        // -8, -2, 2, 7, 7 as nibbles, low nibble first.
+        assert_eq!(cast.as_bytes(), [0xe8, 0x72, 0x07]);
+        let back = cast.cast(DataType::Int64, false).unwrap();
+        assert_eq!(back.to_vec::<i64>().unwrap(), [-8, -2, 2, 7, 7]);
+
+        let cast = tensor.cast(DataType::Uint2, false).unwrap();
+        assert_eq!(cast.as_bytes(), [0b1110_0000, 0b11]);
+
+        let cast = tensor.cast(DataType::Float4E2M1, false).unwrap();
+        let back = cast.cast(DataType::Float, false).unwrap();
+        assert_eq!(back.to_vec::<f32>().unwrap(), [-6.0, -1.5, 2.0, 6.0, 6.0]);
+
+        let mut truncated = cast;
+        truncated.data.truncate(2);
+        assert!(matches!(
+            truncated.cast(DataType::Float, false),
+            Err(IrError::ShapeMismatch {
+                expected: 3,
+                actual: 2
+            })
+        ));
+    }
+
+    #[test]
+    fn test_cast_strings() {
+        let tensor = vector(vec![0.1f32, -2.0, 1e-5]);
+        let strings = tensor.cast_to_strings().unwrap();
+        assert_eq!(strings.as_strings(), ["0.1", "-2", "0.00001"]);
+        let back = strings.cast(DataType::Float, false).unwrap();
+        assert_eq!(back.to_vec::<f32>().unwrap(), [0.1, -2.0, 1e-5]);
+
+        let strings = StringTensor::new(
+            Shape::new(vec![4]),
+            vec!["3".into(), "1E8".into(), "-2.7".into(), "true".into()],
+        );
+        let cast = strings.cast(DataType::Int64, false);
+        assert!(matches!(cast, Err(IrError::Parse { .. })));
+        let strings = StringTensor::new(Shape::new(vec![3]), strings.data[..3].to_vec());
+        let cast = strings.cast(DataType::Int64, false).unwrap();
+        assert_eq!(cast.to_vec::<i64>().unwrap(), [3, 100_000_000, -2]);
+
+        let err = tensor.cast(DataType::String, false).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Cannot cast Float to String; use Tensor::cast_to_strings instead"
+        );
+        assert!(matches!(
+            err,
+            IrError::UnsupportedCast {
+                from: DataType::Float,
+                to: DataType::String,
+            }
+        ));
+    }
+}
@@ -0,0 +1,586 @@
+// Copyright (c) ONNX Project Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Model checker.
+//!
+//! This module verifies the invariants of the IR and reports every problem
+//! found as a [`Diagnostic`], rather than stopping at the first one. It is
+//! meant as a gate before handing a model to a runtime.
+
+use crate::attribute::{AttrValue, NodeAttr};
+use crate::enums::DataType;
+use crate::function::{Function, FunctionId};
+use crate::graph::Graph;
+use crate::model::Model;
+use crate::node::Node;
+use crate::tensor::Tensor;
+use crate::value::Value;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+/// The severity of a diagnostic.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    /// The model is suspicious but may still be accepted by runtimes.
+    Warning,
+    /// The model violates the ONNX specification.
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// One step of the location of a diagnostic.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PathSegment {
+    /// A graph, by name.
+    Graph(String),
+    /// A model-local function.
+    Function(FunctionId),
+    /// A node, by position in its graph and label.
+    Node { index: usize, label: String },
+    /// An attribute of a node.
+    Attribute(String),
+    /// An initializer of a graph.
+    Initializer(String),
+}
+
+impl fmt::Display for PathSegment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PathSegment::Graph(name) => write!(f, "graph:{}", name),
+            PathSegment::Function(id) => write!(f, "function:{}", id),
+            PathSegment::Node { index, label } => write!(f, "node[{}]:{}", index, label),
+            PathSegment::Attribute(name) => write!(f, "attribute:{}", name),
+            PathSegment::Initializer(name) => write!(f, "initializer:{}", name),
+        }
+    }
+}
+
+/// A problem found by the checker.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// Location of the problem, from the outermost graph or function inwards.
+    pub path: Vec<PathSegment>,
+    pub message: String,
+}
+
+impl Diagnostic {
+    /// Returns true if this diagnostic is an error.
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: ", self.severity)?;
+        for (i, segment) in self.path.iter().enumerate() {
+            if i > 0 {
+                write!(f, " > ")?;
+            }
+            write!(f, "{}", segment)?;
+        }
+        if !self.path.is_empty() {
+            write!(f, ": ")?;
+        }
+        write!(f, "{}", self.message)
+    }
+}
+
+/// Checks a model and returns all diagnostics found.
+///
+/// See [`Model::check`].
+pub fn check_model(model: &Model) -> Vec<Diagnostic> {
+    let mut checker = Checker {
+        ir_version: model.ir_version,
+        diagnostics: Vec::new(),
+        path: Vec::new(),
+        scopes: Vec::new(),
+        opset_imports: &model.opset_imports,
+        function_attributes: None,
+    };
+
+    if !model.functions.is_empty() && model.ir_version < 8 {
+        checker.error(format!(
+            "model-local functions require IR version 8, but the model has IR version {}",
+            model.ir_version
+        ));
+    }
+
+    let name = model.graph.name.as_deref().unwrap_or("main");
+    checker.check_graph(&model.graph, name);
+
+    for (id, function) in &model.functions {
+        checker.check_function(id, function);
+    }
+    checker.diagnostics
+}
+
+struct Checker<'m> {
+    ir_version: i64,
+    diagnostics: Vec<Diagnostic>,
+    path: Vec<PathSegment>,
+    /// Values defined in the enclosing scopes, innermost last.
+    scopes: Vec<HashSet<String>>,
+    /// Opset imports in effect for the graph or function being checked.
+    opset_imports: &'m HashMap<String, i32>,
+    /// Attribute parameters of the function being checked, if any.
+    function_attributes: Option<HashSet<String>>,
+}
+
+impl<'m> Checker<'m> {
+    fn report(&mut self, severity: Severity, message: String) {
+        self.diagnostics.push(Diagnostic {
+            severity,
+            path: self.path.clone(),
+            message,
+        });
+    }
+
+    fn error(&mut self, message: String) {
+        self.report(Severity::Error, message);
+    }
+
+    fn warning(&mut self, message: String) {
+        self.report(Severity::Warning, message);
+    }
+
+    fn is_visible(&self, name: &str) -> bool {
+        self.scopes.iter().any(|scope| scope.contains(name))
+    }
+
+    /// Defines a value in the innermost scope, reporting redefinitions.
+    fn define(&mut self, name: &str) {
+        if name.is_empty() {
+            return;
+        }
+        let (current, outer) = self.scopes.split_last().expect("no scope to define in");
+        if current.contains(name) {
+            self.error(format!("value '{}' is defined more than once", name));
+        } else if outer.iter().any(|scope| scope.contains(name)) {
+            self.error(format!(
+                "value '{}' shadows a value from an outer scope",
+                name
+            ));
+        }
+        self.scopes
+            .last_mut()
+            .expect("no scope to define in")
+            .insert(name.to_string());
+    }
+
+    fn check_dtype(&mut self, dtype: DataType, what: &str) {
+        let required = min_ir_version(dtype);
+        if self.ir_version < required {
+            self.error(format!(
+                "{} uses data type {}, which requires IR version {}, but the model has IR version {}",
+                what, dtype, required, self.ir_version
+            ));
+        }
+    }
+
+    fn check_value_type(&mut self, value: &Value) {
+        if let Some(type_) = &value.type_ {
+            self.check_dtype(type_.elem_type, &format!("value '{}'", value.name));
+        }
+    }
+
+    fn check_function(&mut self, id: &FunctionId, function: &'m Function) {
+        self.path.push(PathSegment::Function(id.clone()));
+
+        if !function.overload.is_empty() && self.ir_version < 10 {
+            self.error(format!(
+                "function overloads require IR version 10, but the model has IR version {}",
+                self.ir_version
+            ));
+        }
+        if !function.attribute_defaults.is_empty() && self.ir_version < 9 {
+            self.error(format!(
+                "attribute default values require IR version 9, but the model has IR version {}",
+                self.ir_version
+            ));
+        }
+
+        let saved_opsets = self.opset_imports;
+        if function.opset_imports.is_empty() {
+            self.warning("function has no opset imports".to_string());
+        } else {
+            self.opset_imports = &function.opset_imports;
+        }
+        self.function_attributes = Some(function.attribute_names().map(String::from).collect());
+
+        let saved_scopes = std::mem::take(&mut self.scopes);
+        self.scopes.push(HashSet::new());
+        for input in &function.inputs {
+            self.define(&input.name);
+        }
+        self.check_nodes(function.iter_nodes());
+        self.check_outputs(&function.outputs);
+        self.scopes = saved_scopes;
+
+        self.function_attributes = None;
+        self.opset_imports = saved_opsets;
+        self.path.pop();
+    }
+
+    fn check_graph(&mut self, graph: &Graph, name: &str) {
+        self.path.push(PathSegment::Graph(name.to_string()));
+        self.scopes.push(HashSet::new());
+
+        for input in &graph.inputs {
+            self.define(&input.name);
+            self.check_value_type(input);
+        }
+        let mut initializers: Vec<_> = graph.initializers.iter().collect();
+        initializers.sort_by(|a, b| a.0.cmp(b.0));
+        for (key, value) in initializers {
+            self.path.push(PathSegment::Initializer(key.clone()));
+            self.check_initializer(key, value);
+            self.path.pop();
+            // An initializer may provide the default value of a graph input
+            if !graph.inputs.iter().any(|input| &input.name == key) {
+                self.define(key);
+            }
+        }
+
+        self.check_nodes(graph.iter_nodes());
+        self.check_outputs(&graph.outputs);
+
+        self.scopes.pop();
+        self.path.pop();
+    }
+
+    fn check_initializer(&mut self, key: &str, value: &Value) {
+        if key != value.name {
+            self.error(format!(
+                "initializer is stored under '{}' but its value is named '{}'",
+                key, value.name
+            ));
+        }
+        self.check_value_type(value);
+        let Some(tensor) = &value.const_value else {
+            self.warning("initializer has no constant value".to_string());
+            return;
+        };
+        self.check_tensor(tensor, "initializer");
+        if let Some(type_) = &value.type_ {
+            if type_.elem_type != tensor.dtype {
+                self.error(format!(
+                    "initializer data type {} does not match its value type {}",
+                    tensor.dtype, type_.elem_type
+                ));
+            }
+        }
+        if let Some(shape) = &value.shape {
+            if shape.dims() != tensor.shape.dims() {
+                self.error(format!(
+                    "initializer shape {} does not match its value shape {}",
+                    tensor.shape, shape
+                ));
+            }
+        }
+    }
+
+    fn check_tensor(&mut self, tensor: &Tensor, what: &str) {
+        self.check_dtype(tensor.dtype, what);
+    }
+
+    fn check_nodes<'a>(&mut self, nodes: impl Iterator<Item = &'a Node>) {
+        let nodes: Vec<_> = nodes.collect();
+        let all_outputs: HashSet<&str> = nodes
+            .iter()
+            .flat_map(|node| node.outputs.iter().map(|v| v.name.as_str()))
+            .collect();
+
+        for (index, node) in nodes.into_iter().enumerate() {
+            self.path.push(PathSegment::Node {
+                index,
+                label: node.label(),
+            });
+            self.check_node(node, &all_outputs);
+            self.path.pop();
+        }
+    }
+
+    fn check_node(&mut self, node: &Node, all_outputs: &HashSet<&str>) {
+        let domain = normalize_domain(&node.domain);
+        let imported = self.opset_imports.contains_key(domain)
+            || (domain.is_empty() && self.opset_imports.contains_key("ai.onnx"));
+        if !imported {
+            self.error(format!(
+                "domain '{}' of operator {} has no opset import",
+                node.domain, node.op_type
+            ));
+        }
+
+        for input in &node.inputs {
+            if input.name.is_empty() || self.is_visible(&input.name) {
+                continue;
+            }
+            if all_outputs.contains(input.name.as_str()) {
+                self.error(format!(
+                    "input '{}' is used before it is defined",
+                    input.name
+                ));
+            } else {
+                self.error(format!("input '{}' is not defined", input.name));
+            }
+        }
+
+        for (name, attr) in &node.attributes {
+            self.path.push(PathSegment::Attribute(name.clone()));
+            self.check_attribute(attr);
+            self.path.pop();
+        }
+
+        for output in &node.outputs {
+            self.define(&output.name);
+            self.check_value_type(output);
+        }
+    }
+
+    fn check_attribute(&mut self, attr: &NodeAttr) {
+        let attr = match attr {
+            NodeAttr::Ref(ref_attr) => {
+                match &self.function_attributes {
+                    None => self.error(format!(
+                        "reference to '{}' outside of a function",
+                        ref_attr.ref_attr_name
+                    )),
+                    Some(params) if !params.contains(&ref_attr.ref_attr_name) => {
+                        self.error(format!(
+                            "reference to '{}', which is not an attribute of the function",
+                            ref_attr.ref_attr_name
+                        ))
+                    }
+                    Some(_) => {}
+                }
+                return;
+            }
+            NodeAttr::Value(attr) => attr,
+        };
+
+        match &attr.value {
+            AttrValue::Tensor(tensor) => self.check_tensor(tensor, "tensor attribute"),
+            AttrValue::Tensors(tensors) => {
+                for tensor in tensors {
+                    self.check_tensor(tensor, "tensor attribute");
+                }
+            }
+            AttrValue::Graph(graph) => {
+                let name = graph.name.as_deref().unwrap_or(&attr.name);
+                self.check_graph(graph, name);
+            }
+            AttrValue::Graphs(graphs) => {
+                for graph in graphs {
+                    let name = graph.name.as_deref().unwrap_or(&attr.name);
+                    self.check_graph(graph, name);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn check_outputs(&mut self, outputs: &[Value]) {
+        let mut seen = HashSet::new();
+        for output in outputs {
+            if !seen.insert(output.name.as_str()) {
+                self.error(format!("output '{}' is listed more than once", output.name));
+            }
+            if !self.is_visible(&output.name) {
+                self.error(format!("output '{}' is not defined", output.name));
+            }
+        }
+    }
+}
+
+/// Returns the domain name used as the key of opset imports.
+fn normalize_domain(domain: &str) -> &str {
+    if domain == "ai.onnx" {
+        ""
+    } else {
+        domain
+    }
+}
+
+/// Returns the first IR version that supports a data type.
+fn min_ir_version(dtype: DataType) -> i64 {
+    match dtype {
+        DataType::Float8E4M3Fn
+        | DataType::Float8E4M3Fnuz
+        | DataType::Float8E5M2
+        | DataType::Float8E5M2Fnuz => 9,
+        DataType::Uint4 | DataType::Int4 => 10,
+        DataType::Float4E2M1 => 11,
+        DataType::Float8E8M0 | DataType::Uint2 | DataType::Int2 => 12,
+        _ => 3,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::attribute::{Attr, RefAttr};
+    use crate::enums::AttributeType;
+    use crate::shape::Shape;
+    use crate::test_support::{graph, model, node};
+    use crate::types::TensorType;
+
+    fn messages(model: &Model) -> Vec<String> {
+        model.check().iter().map(|d| d.message.clone()).collect()
+    }
+
+    #[test]
+    fn test_valid_model() {
+        let model = model(
+            graph(
+                vec![
+                    node("Relu", &["x"], &["y"]),
+                    node("Add", &["x", "y"], &["z"]),
+                ],
+                &["x"],
+                &["z"],
+            ),
+            18,
+        );
+        assert!(model.check().is_empty());
+    }
+
+    #[test]
+    fn test_definitions_and_uses() {
+        let model = model(
+            graph(
+                vec![
+                    node("Add", &["x", "y"], &["z"]),
+                    node("Relu", &["x"], &["y"]),
+                    node("Neg", &["w"], &["z"]),
+                ],
+                &["x"],
+                &["z", "z", "v"],
+            ),
+            18,
+        );
+        assert_eq!(
+            messages(&model),
+            vec![
+                "input 'y' is used before it is defined",
+                "input 'w' is not defined",
+                "value 'z' is defined more than once",
+                "output 'z' is listed more than once",
+                "output 'v' is not defined",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_missing_opset_import() {
+        let mut custom = node("Custom", &["x"], &["y"]);
+        custom.domain = "com.example".to_string();
+        let model = model(graph(vec![custom], &["x"], &["y"]), 18);
+
+        let diagnostics = model.check();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            diagnostics[0].to_string(),
+            "error: graph:main > node[0]:Custom: domain 'com.example' of operator Custom has no opset import"
+        );
+    }
+
+    #[test]
+    fn test_initializer_mismatch() {
+        let mut weight = Value::new("w");
+        weight.type_ = Some(TensorType::new(DataType::Float16));
+        weight.shape = Some(Shape::new(vec![2, 2]));
+        weight.const_value = Some(Tensor::new(DataType::Float, Shape::new(vec![4])));
+
+        let mut model = model(
+            graph(vec![node("Add", &["x", "w"], &["y"])], &["x"], &["y"]),
+            18,
+        );
+        model.graph.initializers.insert("w".to_string(), weight);
+
+        let diagnostics = model.check();
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(
+            diagnostics[0].path,
+            vec![
+                PathSegment::Graph("main".to_string()),
+                PathSegment::Initializer("w".to_string())
+            ]
+        );
+        assert!(diagnostics[0].message.contains("data type"));
+        assert!(diagnostics[1].message.contains("shape [4]"));
+    }
+
+    #[test]
+    fn test_subgraph_scoping() {
+        let mut branch = Graph::new();
+        branch.append(node("Relu", &["x"], &["a"]));
+        branch.append(node("Neg", &["late"], &["b"]));
+        branch.append(node("Identity", &["a"], &["x"]));
+        branch.outputs.push(Value::new("b"));
+
+        let mut if_node = node("If", &["cond"], &["y"]);
+        if_node.set_attribute(Attr::graph("then_branch", branch));
+        let model = model(
+            graph(
+                vec![if_node, node("Relu", &["y"], &["late"])],
+                &["x", "cond"],
+                &["late"],
+            ),
+            18,
+        );
+
+        let diagnostics = model.check();
+        let messages: Vec<_> = diagnostics.iter().map(|d| d.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            vec![
+                "input 'late' is not defined",
+                "value 'x' shadows a value from an outer scope",
+            ]
+        );
+        assert_eq!(diagnostics[0].path.len(), 5);
+        assert_eq!(
+            diagnostics[0].path[2],
+            PathSegment::Attribute("then_branch".to_string())
+        );
+    }
+
+    #[test]
+    fn test_ir_version_features() {
+        let mut func = Function::new("F", "custom");
+        func.overload = "v2".to_string();
+        func.set_opset_import("", 18);
+        func.add_input(Value::new("a"));
+        func.add_output(Value::new("b"));
+        let mut body = node("LeakyRelu", &["a"], &["b"]);
+        body.set_attribute(RefAttr::new("alpha", "undeclared", AttributeType::Float));
+        func.append(body);
+
+        let mut input = Value::new("x");
+        input.type_ = Some(TensorType::new(DataType::Int4));
+        let mut model = model(graph(vec![], &[], &[]), 18);
+        model.graph.inputs.push(input);
+        model.ir_version = 7;
+        model.add_function(func);
+
+        assert_eq!(
+            messages(&model),
+            vec![
+                "model-local functions require IR version 8, but the model has IR version 7",
+                "value 'x' uses data type Int4, which requires IR version 10, but the model has IR version 7",
+                "function overloads require IR version 10, but the model has IR version 7",
+                "reference to 'undeclared', which is not an attribute of the function",
+            ]
+        );
+    }
+}
@@ -0,0 +1,812 @@
+// Copyright (c) ONNX Project Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Symbolic dimension expressions.
+//!
+//! A [`DimExpr`] is an integer expression over named dimensions, such as
+//! `2*N`, `seq_len + 1` or `ceil(H/2)`. Expressions are kept in a canonical
+//! form: a sum of terms, each an integer coefficient times a product of
+//! symbols and divisions that cannot be simplified further. Constants are
+//! folded and like terms combined whenever an expression is built, so two
+//! expressions compare equal if they simplify to the same form.
+//!
+//! Expressions are written to `dim_param` strings through
+//! [`Display`](fmt::Display) and read back through [`FromStr`] or
+//! [`SymbolicDim::parse_expr`](crate::SymbolicDim::parse_expr); a
+//! `dim_param` is otherwise an opaque name. The syntax accepts integers,
+//! identifiers, `+`, `-`, `*`, floor division (`/` or `//`), `%`,
+//! parentheses and `floor(a/b)` and `ceil(a/b)`.
+//!
+//! Coefficients are `i64`. The operators never panic on overflow: an
+//! overflowing sum or product becomes an opaque symbol named after the
+//! operation, and an overflowing division is kept unsimplified. Use
+//! [`DimExpr::checked_add`] and its siblings to detect overflow instead, and
+//! parsing an expression whose constants overflow fails.
+
+use crate::error::{IrError, Result};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+use std::ops::{Add, Mul, Neg, Sub};
+use std::str::FromStr;
+
+/// An integer expression over symbolic dimensions.
+///
+/// # Examples
+///
+/// ```
+/// use onnx_ir_core::DimExpr;
+///
+/// let n = DimExpr::symbol("N");
+/// let expr = n.clone() * 2 + 1 - n;
+/// assert_eq!(expr.to_string(), "N + 1");
+/// assert_eq!(expr, "1 + N".parse().unwrap());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct DimExpr {
+    /// Coefficients of the terms, keyed by their sorted factors. The
+    /// constant term has no factors. Terms with a zero coefficient are
+    /// removed.
+    terms: BTreeMap<Vec<Factor>, i64>,
+}
+
+/// A factor of a term that is not an integer.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+enum Factor {
+    Symbol(String),
+    FloorDiv(DimExpr, DimExpr),
+    CeilDiv(DimExpr, DimExpr),
+    Mod(DimExpr, DimExpr),
+}
+
+impl DimExpr {
+    /// Creates a constant expression.
+    pub fn int(value: i64) -> Self {
+        Self::from_term(Vec::new(), value)
+    }
+
+    /// Creates an expression of a single symbol.
+    pub fn symbol(name: impl Into<String>) -> Self {
+        Self::from_term(vec![Factor::Symbol(name.into())], 1)
+    }
+
+    /// Creates an opaque symbol standing for an operation that overflows,
+    /// e.g. `(N + 9223372036854775807) + (1)`.
+    fn overflowed(a: &DimExpr, op: &str, b: &DimExpr) -> Self {
+        Self::symbol(format!("({}) {} ({})", a, op, b))
+    }
+
+    fn from_term(factors: Vec<Factor>, coefficient: i64) -> Self {
+        let mut terms = BTreeMap::new();
+        if coefficient != 0 {
+            terms.insert(factors, coefficient);
+        }
+        Self { terms }
+    }
+
+    /// Returns the value of a constant expression.
+    pub fn as_int(&self) -> Option<i64> {
+        match self.terms.iter().next() {
+            None => Some(0),
+            Some((factors, &value)) if factors.is_empty() && self.terms.len() == 1 => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Returns the name of an expression that is a single symbol.
+    pub fn as_symbol(&self) -> Option<&str> {
+        match self.single_term()? {
+            ([Factor::Symbol(name)], 1) => Some(name),
+            _ => None,
+        }
+    }
+
+    /// Returns true if the expression is a constant.
+    pub fn is_int(&self) -> bool {
+        self.as_int().is_some()
+    }
+
+    fn single_term(&self) -> Option<(&[Factor], i64)> {
+        match self.terms.len() {
+            1 => self.terms.iter().next().map(|(f, &c)| (f.as_slice(), c)),
+            _ => None,
+        }
+    }
+
+    /// Returns the names of the symbols in the expression, in order.
+    pub fn symbols(&self) -> BTreeSet<String> {
+        let mut symbols = BTreeSet::new();
+        self.collect_symbols(&mut symbols);
+        symbols
+    }
+
+    fn collect_symbols(&self, symbols: &mut BTreeSet<String>) {
+        for factor in self.terms.keys().flatten() {
+            match factor {
+                Factor::Symbol(name) => {
+                    symbols.insert(name.clone());
+                }
+                Factor::FloorDiv(a, b) | Factor::CeilDiv(a, b) | Factor::Mod(a, b) => {
+                    a.collect_symbols(symbols);
+                    b.collect_symbols(symbols);
+                }
+            }
+        }
+    }
+
+    /// Adds `other`, or returns `None` if a coefficient overflows.
+    pub fn checked_add(&self, other: &DimExpr) -> Option<DimExpr> {
+        let mut sum = self.clone();
+        for (factors, &coefficient) in &other.terms {
+            sum.add_term(factors.clone(), coefficient)?;
+        }
+        Some(sum)
+    }
+
+    /// Subtracts `other`, or returns `None` if a coefficient overflows.
+    pub fn checked_sub(&self, other: &DimExpr) -> Option<DimExpr> {
+        self.checked_add(&other.checked_neg()?)
+    }
+
+    /// Multiplies by `other`, or returns `None` if a coefficient overflows.
+    pub fn checked_mul(&self, other: &DimExpr) -> Option<DimExpr> {
+        let mut product = DimExpr::int(0);
+        for (a, &x) in &self.terms {
+            for (b, &y) in &other.terms {
+                let mut factors: Vec<Factor> = a.iter().chain(b).cloned().collect();
+                factors.sort();
+                product.add_term(factors, x.checked_mul(y)?)?;
+            }
+        }
+        Some(product)
+    }
+
+    /// Negates the expression, or returns `None` if a coefficient overflows.
+    pub fn checked_neg(&self) -> Option<DimExpr> {
+        let mut terms = self.terms.clone();
+        for coefficient in terms.values_mut() {
+            *coefficient = coefficient.checked_neg()?;
+        }
+        Some(DimExpr { terms })
+    }
+
+    /// Divides by `divisor`, rounding down.
+    pub fn floor_div(&self, divisor: &DimExpr) -> DimExpr {
+        self.divide(divisor, Rounding::Floor)
+    }
+
+    /// Divides by `divisor`, rounding up.
+    pub fn ceil_div(&self, divisor: &DimExpr) -> DimExpr {
+        self.divide(divisor, Rounding::Ceil)
+    }
+
+    /// Returns the remainder of the floor division by `divisor`.
+    pub fn modulo(&self, divisor: &DimExpr) -> DimExpr {
+        self.divide(divisor, Rounding::Mod)
+    }
+
+    /// Divides by `divisor` if the division is exact for every value of the
+    /// symbols, e.g. `6*N*T` by `2*N`.
+    pub fn exact_div(&self, divisor: &DimExpr) -> Option<DimExpr> {
+        let (divisor_factors, divisor_coefficient) = divisor.single_term()?;
+        let mut quotient = DimExpr::int(0);
+        for (factors, &coefficient) in &self.terms {
+            if coefficient.checked_rem(divisor_coefficient)? != 0 {
+                return None;
+            }
+            let mut remaining = factors.clone();
+            for factor in divisor_factors {
+                let index = remaining.iter().position(|f| f == factor)?;
+                remaining.remove(index);
+            }
+            quotient.add_term(remaining, coefficient.checked_div(divisor_coefficient)?)?;
+        }
+        Some(quotient)
+    }
+
+    /// Divides by `divisor`, keeping the division unsimplified if it cannot
+    /// be simplified or a coefficient overflows.
+    fn divide(&self, divisor: &DimExpr, rounding: Rounding) -> DimExpr {
+        self.checked_divide(divisor, rounding).unwrap_or_else(|| {
+            let factor = match rounding {
+                Rounding::Floor => Factor::FloorDiv(self.clone(), divisor.clone()),
+                Rounding::Ceil => Factor::CeilDiv(self.clone(), divisor.clone()),
+                Rounding::Mod => Factor::Mod(self.clone(), divisor.clone()),
+            };
+            DimExpr::from_term(vec![factor], 1)
+        })
+    }
+
+    /// Simplifies a division, or returns `None` if it cannot be simplified
+    /// or a coefficient overflows.
+    fn checked_divide(&self, divisor: &DimExpr, rounding: Rounding) -> Option<DimExpr> {
+        if let Some(quotient) = self.exact_div(divisor) {
+            return Some(match rounding {
+                Rounding::Mod => DimExpr::int(0),
+                _ => quotient,
+            });
+        }
+        let d = divisor.as_int().filter(|&d| d != 0)?;
+        // Terms that are multiples of the divisor divide exactly, and only
+        // the rest needs rounding
+        let mut whole = DimExpr::int(0);
+        let mut rest = DimExpr::int(0);
+        for (factors, &coefficient) in &self.terms {
+            if coefficient.checked_rem(d)? == 0 {
+                whole.add_term(factors.clone(), coefficient.checked_div(d)?)?;
+            } else {
+                rest.add_term(factors.clone(), coefficient)?;
+            }
+        }
+        let rest = match (rest.as_int(), rounding) {
+            (Some(r), Rounding::Floor) => DimExpr::int(floor_div(r, d)?),
+            (Some(r), Rounding::Ceil) => {
+                DimExpr::int(floor_div(r.checked_neg()?, d)?.checked_neg()?)
+            }
+            (Some(r), Rounding::Mod) => {
+                DimExpr::int(r.checked_sub(d.checked_mul(floor_div(r, d)?)?)?)
+            }
+            (None, _) if whole.terms.is_empty() => return None,
+            (None, _) => rest.divide(divisor, rounding),
+        };
+        match rounding {
+            Rounding::Mod => Some(rest),
+            _ => whole.checked_add(&rest),
+        }
+    }
+
+    /// Adds a term, or returns `None` if its coefficient overflows, in which
+    /// case the expression is unchanged.
+    fn add_term(&mut self, factors: Vec<Factor>, coefficient: i64) -> Option<()> {
+        let entry = self.terms.entry(factors).or_insert(0);
+        *entry = entry.checked_add(coefficient)?;
+        if *entry == 0 {
+            self.terms.retain(|_, c| *c != 0);
+        }
+        Some(())
+    }
+
+    /// Replaces symbols by values and simplifies the result.
+    ///
+    /// Symbols that are not bound are left in place.
+    pub fn substitute(&self, bindings: &HashMap<String, i64>) -> DimExpr {
+        self.replace_symbols(&|name| bindings.get(name).map(|&value| DimExpr::int(value)))
+    }
+
+    /// Replaces symbols by the expressions `replace` returns for them and
+    /// simplifies the result.
+    ///
+    /// Symbols for which `replace` returns `None` are left in place.
+    pub fn replace_symbols(&self, replace: &dyn Fn(&str) -> Option<DimExpr>) -> DimExpr {
+        let mut result = DimExpr::int(0);
+        for (factors, &coefficient) in &self.terms {
+            let mut term = DimExpr::int(coefficient);
+            for factor in factors {
+                term = term * factor.replace_symbols(replace);
+            }
+            result = result + term;
+        }
+        result
+    }
+
+    /// Evaluates the expression with every symbol bound to a value.
+    ///
+    /// Returns `None` if a symbol is not bound, a divisor is zero or the
+    /// computation overflows.
+    pub fn evaluate(&self, bindings: &HashMap<String, i64>) -> Option<i64> {
+        self.substitute(bindings).as_int()
+    }
+
+    /// Returns true if the expression needs parentheses as an operand of a
+    /// product or division.
+    fn is_compound(&self) -> bool {
+        match self.single_term() {
+            Some(([], c)) => c < 0,
+            Some((factors, c)) => c != 1 || factors.len() > 1,
+            None => !self.terms.is_empty(),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Rounding {
+    Floor,
+    Ceil,
+    Mod,
+}
+
+/// Divides integers, rounding towards negative infinity, or returns `None`
+/// on overflow.
+fn floor_div(a: i64, b: i64) -> Option<i64> {
+    let q = a.checked_div(b)?;
+    if a % b != 0 && (a < 0) != (b < 0) {
+        Some(q - 1)
+    } else {
+        Some(q)
+    }
+}
+
+impl Factor {
+    fn replace_symbols(&self, replace: &dyn Fn(&str) -> Option<DimExpr>) -> DimExpr {
+        match self {
+            Factor::Symbol(name) => replace(name).unwrap_or_else(|| DimExpr::symbol(name.clone())),
+            Factor::FloorDiv(a, b) => a
+                .replace_symbols(replace)
+                .floor_div(&b.replace_symbols(replace)),
+            Factor::CeilDiv(a, b) => a
+                .replace_symbols(replace)
+                .ceil_div(&b.replace_symbols(replace)),
+            Factor::Mod(a, b) => a
+                .replace_symbols(replace)
+                .modulo(&b.replace_symbols(replace)),
+        }
+    }
+}
+
+impl From<i64> for DimExpr {
+    fn from(value: i64) -> Self {
+        Self::int(value)
+    }
+}
+
+impl Add for DimExpr {
+    type Output = DimExpr;
+
+    fn add(self, other: DimExpr) -> DimExpr {
+        self.checked_add(&other)
+            .unwrap_or_else(|| DimExpr::overflowed(&self, "+", &other))
+    }
+}
+
+impl Add<i64> for DimExpr {
+    type Output = DimExpr;
+
+    fn add(self, other: i64) -> DimExpr {
+        self + DimExpr::int(other)
+    }
+}
+
+impl Neg for DimExpr {
+    type Output = DimExpr;
+
+    fn neg(self) -> DimExpr {
+        self.checked_neg()
+            .unwrap_or_else(|| DimExpr::overflowed(&DimExpr::int(0), "-", &self))
+    }
+}
+
+impl Sub for DimExpr {
+    type Output = DimExpr;
+
+    fn sub(self, other: DimExpr) -> DimExpr {
+        self.checked_sub(&other)
+            .unwrap_or_else(|| DimExpr::overflowed(&self, "-", &other))
+    }
+}
+
+impl Sub<i64> for DimExpr {
+    type Output = DimExpr;
+
+    fn sub(self, other: i64) -> DimExpr {
+        self - DimExpr::int(other)
+    }
+}
+
+impl Mul for DimExpr {
+    type Output = DimExpr;
+
+    fn mul(self, other: DimExpr) -> DimExpr {
+        self.checked_mul(&other)
+            .unwrap_or_else(|| DimExpr::overflowed(&self, "*", &other))
+    }
+}
+
+impl Mul<i64> for DimExpr {
+    type Output = DimExpr;
+
+    fn mul(self, other: i64) -> DimExpr {
+        self * DimExpr::int(other)
+    }
+}
+
+impl fmt::Display for Factor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let operand = |e: &DimExpr| {
+            if e.is_compound() {
+                format!("({})", e)
+            } else {
+                e.to_string()
+            }
+        };
+        match self {
+            Factor::Symbol(name) => write!(f, "{}", name),
+            Factor::FloorDiv(a, b) => write!(f, "floor({}/{})", operand(a), operand(b)),
+            Factor::CeilDiv(a, b) => write!(f, "ceil({}/{})", operand(a), operand(b)),
+            Factor::Mod(a, b) => write!(f, "({} % {})", operand(a), operand(b)),
+        }
+    }
+}
+
+impl fmt::Display for DimExpr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.terms.is_empty() {
+            return write!(f, "0");
+        }
+        // Terms of higher degree go first and the constant term last, as in
+        // `N*N + N + 1`
+        let mut terms: Vec<_> = self.terms.iter().collect();
+        terms.sort_by_key(|(factors, _)| std::cmp::Reverse(factors.len()));
+        for (i, (factors, &coefficient)) in terms.into_iter().enumerate() {
+            match (i, coefficient < 0) {
+                (0, true) => write!(f, "-")?,
+                (0, false) => {}
+                (_, true) => write!(f, " - ")?,
+                (_, false) => write!(f, " + ")?,
+            }
+            let magnitude = coefficient.unsigned_abs();
+            if factors.is_empty() {
+                write!(f, "{}", magnitude)?;
+                continue;
+            }
+            if magnitude != 1 {
+                write!(f, "{}*", magnitude)?;
+            }
+            for (j, factor) in factors.iter().enumerate() {
+                if j > 0 {
+                    write!(f, "*")?;
+                }
+                write!(f, "{}", factor)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for DimExpr {
+    type Err = IrError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut parser = Parser {
+            input: s,
+            tokens: tokenize(s)?,
+            position: 0,
+        };
+        let expr = parser.expr(false)?;
+        match parser.peek() {
+            None => Ok(expr),
+            Some(token) => Err(parser.error(format!("unexpected {}", token))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Int(i64),
+    Ident(String),
+    Op(&'static str),
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Int(value) => write!(f, "'{}'", value),
+            Token::Ident(name) => write!(f, "'{}'", name),
+            Token::Op(op) => write!(f, "'{}'", op),
+        }
+    }
+}
+
+fn parse_error(input: &str, message: impl Into<String>) -> IrError {
+    IrError::Parse {
+        input: input.to_string(),
+        message: message.into(),
+    }
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = input.char_indices().peekable();
+    while let Some(&(start, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c.is_ascii_digit() || c.is_alphabetic() || c == '_' {
+            let mut end = start;
+            while let Some(&(i, c)) = chars.peek() {
+                if !(c.is_alphanumeric() || c == '_') {
+                    break;
+                }
+                end = i + c.len_utf8();
+                chars.next();
+            }
+            let word = &input[start..end];
+            tokens.push(if c.is_ascii_digit() {
+                Token::Int(
+                    word.parse()
+                        .map_err(|_| parse_error(input, format!("invalid integer '{}'", word)))?,
+                )
+            } else {
+                Token::Ident(word.to_string())
+            });
+        } else {
+            chars.next();
+            let op = match c {
+                '/' if chars.next_if(|&(_, c)| c == '/').is_some() => "//",
+                '+' => "+",
+                '-' => "-",
+                '*' => "*",
+                '/' => "/",
+                '%' => "%",
+                '(' => "(",
+                ')' => ")",
+                _ => return Err(parse_error(input, format!("unexpected character '{}'", c))),
+            };
+            tokens.push(Token::Op(op));
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser<'s> {
+    input: &'s str,
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, message: impl Into<String>) -> IrError {
+        parse_error(self.input, message)
+    }
+
+    fn checked(&self, result: Option<DimExpr>) -> Result<DimExpr> {
+        result.ok_or_else(|| self.error("integer overflow"))
+    }
+
+    /// Divides, failing if a division of constants overflows rather than
+    /// keeping it unsimplified.
+    fn divide(&self, a: &DimExpr, b: &DimExpr, rounding: Rounding) -> Result<DimExpr> {
+        if a.is_int() && b.as_int().is_some_and(|d| d != 0) {
+            return self.checked(a.checked_divide(b, rounding));
+        }
+        Ok(a.divide(b, rounding))
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn eat(&mut self, op: &'static str) -> bool {
+        let found = self.peek() == Some(&Token::Op(op));
+        if found {
+            self.position += 1;
+        }
+        found
+    }
+
+    fn expect(&mut self, op: &'static str) -> Result<()> {
+        if self.eat(op) {
+            Ok(())
+        } else {
+            Err(self.error(format!("expected '{}'", op)))
+        }
+    }
+
+    /// Parses a sum. In the operand of `floor` or `ceil`, a `/` ends the
+    /// sum, since it divides the whole operand.
+    fn expr(&mut self, in_ratio: bool) -> Result<DimExpr> {
+        let mut expr = self.product(in_ratio)?;
+        loop {
+            if self.eat("+") {
+                let rhs = self.product(in_ratio)?;
+                expr = self.checked(expr.checked_add(&rhs))?;
+            } else if self.eat("-") {
+                let rhs = self.product(in_ratio)?;
+                expr = self.checked(expr.checked_sub(&rhs))?;
+            } else {
+                return Ok(expr);
+            }
+        }
+    }
+
+    fn product(&mut self, in_ratio: bool) -> Result<DimExpr> {
+        let mut expr = self.unary()?;
+        loop {
+            if self.eat("*") {
+                let rhs = self.unary()?;
+                expr = self.checked(expr.checked_mul(&rhs))?;
+            } else if self.eat("//") || (!in_ratio && self.eat("/")) {
+                let rhs = self.unary()?;
+                expr = self.divide(&expr, &rhs, Rounding::Floor)?;
+            } else if self.eat("%") {
+                let rhs = self.unary()?;
+                expr = self.divide(&expr, &rhs, Rounding::Mod)?;
+            } else {
+                return Ok(expr);
+            }
+        }
+    }
+
+    fn unary(&mut self) -> Result<DimExpr> {
+        if self.eat("-") {
+            let operand = self.unary()?;
+            return self.checked(operand.checked_neg());
+        }
+        match self.next() {
+            Some(Token::Int(value)) => Ok(DimExpr::int(value)),
+            Some(Token::Op("(")) => {
+                let expr = self.expr(false)?;
+                self.expect(")")?;
+                Ok(expr)
+            }
+            Some(Token::Ident(name)) if self.peek() == Some(&Token::Op("(")) => {
+                self.position += 1;
+                let numerator = self.expr(true)?;
+                let denominator = match self.eat("/") {
+                    true => self.expr(false)?,
+                    false => DimExpr::int(1),
+                };
+                self.expect(")")?;
+                match name.as_str() {
+                    "floor" => self.divide(&numerator, &denominator, Rounding::Floor),
+                    "ceil" => self.divide(&numerator, &denominator, Rounding::Ceil),
+                    _ => Err(self.error(format!("unknown function '{}'", name))),
+                }
+            }
+            Some(Token::Ident(name)) => Ok(DimExpr::symbol(name)),
+            Some(token) => Err(self.error(format!("unexpected {}", token))),
+            None => Err(self.error("unexpected end of expression")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(s: &str) -> DimExpr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_simplification() {
+        let n = DimExpr::symbol("N");
+        let t = DimExpr::symbol("T");
+        assert_eq!(DimExpr::int(2) * 3 + 4, DimExpr::int(10));
+        assert_eq!((n.clone() + t.clone()) * 2 - t.clone() * 2, n.clone() * 2);
+        assert_eq!(n.clone() - n.clone(), DimExpr::int(0));
+        assert_eq!((n.clone() + 1) * (n.clone() - 1), n.clone() * n.clone() - 1);
+        assert_eq!(n.clone() * t.clone(), t * n.clone());
+        assert_eq!(n.as_symbol(), Some("N"));
+        assert_eq!((n * 2).as_symbol(), None);
+    }
+
+    #[test]
+    fn test_division() {
+        let n = DimExpr::symbol("N");
+        let two = DimExpr::int(2);
+        assert_eq!((n.clone() * 4 + 2).floor_div(&two), n.clone() * 2 + 1);
+        assert_eq!((n.clone() * 2 + 1).floor_div(&two), n.clone());
+        assert_eq!((n.clone() * 2 + 1).ceil_div(&two), n.clone() + 1);
+        assert_eq!((n.clone() * 2 + 3).modulo(&two), DimExpr::int(1));
+        assert_eq!(DimExpr::int(-7).floor_div(&two), DimExpr::int(-4));
+        assert_eq!(DimExpr::int(-7).ceil_div(&two), DimExpr::int(-3));
+        assert_eq!(n.clone().ceil_div(&two).to_string(), "ceil(N/2)");
+        assert_eq!(
+            (n.clone() * 6 * DimExpr::symbol("T")).exact_div(&(n.clone() * 2)),
+            Some(DimExpr::symbol("T") * 3)
+        );
+        assert_eq!((n.clone() + 1).exact_div(&n), None);
+    }
+
+    #[test]
+    fn test_display() {
+        let n = DimExpr::symbol("N");
+        let seq_len = DimExpr::symbol("seq_len");
+        assert_eq!((n.clone() * 2).to_string(), "2*N");
+        assert_eq!((seq_len.clone() + 1).to_string(), "seq_len + 1");
+        assert_eq!(
+            (DimExpr::int(1) - seq_len.clone()).to_string(),
+            "-seq_len + 1"
+        );
+        assert_eq!(
+            (n.clone() * n.clone() - n.clone() * 3).to_string(),
+            "N*N - 3*N"
+        );
+        assert_eq!(
+            (n.clone() + 1).floor_div(&DimExpr::int(2)).to_string(),
+            "floor((N + 1)/2)"
+        );
+        assert_eq!(
+            (n + seq_len).modulo(&DimExpr::int(8)).to_string(),
+            "((N + seq_len) % 8)"
+        );
+        assert_eq!(DimExpr::int(0).to_string(), "0");
+    }
+
+    #[test]
+    fn test_parse_round_trip() {
+        for s in [
+            "N",
+            "2*N",
+            "seq_len + 1",
+            "-N + 1",
+            "N*N - 3*N",
+            "ceil(H/2)",
+            "floor((N + 1)/2)",
+            "W + 2*floor(H/3)",
+            "((N + seq_len) % 8)",
+        ] {
+            assert_eq!(parse(s).to_string(), s);
+        }
+        assert_eq!(parse("N * 2 + (3 - 1)"), parse("2 + 2*N"));
+        assert_eq!(parse("(H + 1) // 2"), parse("floor((H + 1)/2)"));
+        assert_eq!(parse("ceil(2*H/2)"), parse("H"));
+        assert_eq!(parse("7 / 2"), DimExpr::int(3));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!("N +".parse::<DimExpr>().is_err());
+        assert!("(N".parse::<DimExpr>().is_err());
+        assert!("sqrt(N)".parse::<DimExpr>().is_err());
+        let err = "N $ 2".parse::<DimExpr>().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Cannot parse 'N $ 2': unexpected character '$'"
+        );
+    }
+
+    #[test]
+    fn test_overflow() {
+        let max = DimExpr::int(i64::MAX);
+        assert_eq!(max.checked_add(&DimExpr::int(1)), None);
+        assert_eq!(max.checked_mul(&DimExpr::int(2)), None);
+        assert_eq!(DimExpr::int(i64::MIN).checked_neg(), None);
+        let sum = max.clone() + 1;
+        assert_eq!(sum.as_symbol(), Some("(9223372036854775807) + (1)"));
+        let min = DimExpr::int(i64::MIN);
+        assert_eq!(
+            min.floor_div(&DimExpr::int(-1)).to_string(),
+            "floor((-9223372036854775808)/(-1))"
+        );
+        let n = DimExpr::symbol("N");
+        let bindings = HashMap::from([("N".to_string(), i64::MAX)]);
+        assert_eq!((n * 2).evaluate(&bindings), None);
+
+        for s in [
+            "9223372036854775807 + 1",
+            "9223372036854775807 * 2",
+            "-(0 - 9223372036854775807 - 1)",
+            "(0-9223372036854775807-1)/(0-1)",
+            "ceil((0-9223372036854775807-1)/(0-1))",
+        ] {
+            let err = s.parse::<DimExpr>().unwrap_err();
+            assert!(matches!(err, IrError::Parse { .. }), "{}", s);
+            assert!(err.to_string().ends_with("integer overflow"), "{}", s);
+        }
+    }
+
+    #[test]
+    fn test_substitute() {
+        let expr = parse("ceil(H/2)*W + N");
+        let bindings = HashMap::from([("H".to_string(), 7), ("W".to_string(), 3)]);
+        assert_eq!(expr.substitute(&bindings), parse("N + 12"));
+        assert_eq!(expr.evaluate(&bindings), None);
+        let bindings = HashMap::from([
+            ("H".to_string(), 7),
+            ("W".to_string(), 3),
+            ("N".to_string(), 1),
+        ]);
+        assert_eq!(expr.evaluate(&bindings), Some(13));
+        let renamed = expr.replace_symbols(&|name| (name == "N").then(|| DimExpr::symbol("batch")));
+        assert_eq!(renamed.to_string(), "W*ceil(H/2) + batch");
+        assert_eq!(
+            expr.symbols().into_iter().collect::<Vec<_>>(),
+            vec!["H", "N", "W"]
+        );
+    }
+}
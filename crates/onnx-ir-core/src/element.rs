@@ -0,0 +1,276 @@
+// Copyright (c) ONNX Project Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Rust element types of tensor data.
+//!
+//! The [`Element`] trait links a Rust type to the [`DataType`] of tensors
+//! holding it and to its little-endian encoding in [`Tensor`] data. It is
+//! implemented for the primitive numeric types, `bool`, and newtypes for
+//! the floating point formats Rust has no type for, which hold the raw
+//! bits of a value.
+//!
+//! [`Tensor`]: crate::tensor::Tensor
+
+use crate::enums::DataType;
+use std::fmt;
+
+mod sealed {
+    pub trait Sealed {
+        /// Returns true if every element of `bytes` is a valid bit pattern
+        /// of the type.
+        fn is_valid(_bytes: &[u8]) -> bool {
+            true
+        }
+    }
+}
+
+/// A Rust type that can be the element type of a tensor.
+///
+/// This trait is sealed: the encoding of each type must match the ONNX
+/// specification, so it cannot be implemented outside this crate.
+pub trait Element: sealed::Sealed + Copy + Default + PartialEq + fmt::Debug + 'static {
+    /// The data type of tensors with this element type.
+    const DTYPE: DataType;
+
+    /// The number of bytes of one element.
+    const SIZE: usize;
+
+    /// Appends the little-endian encoding of the value to `out`.
+    fn write_le(self, out: &mut Vec<u8>);
+
+    /// Decodes a value from the first [`SIZE`](Element::SIZE) bytes of
+    /// `bytes`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `bytes` is shorter than one element.
+    fn read_le(bytes: &[u8]) -> Self;
+}
+
+macro_rules! impl_primitive {
+    ($($ty:ty => $dtype:ident),* $(,)?) => {$(
+        impl sealed::Sealed for $ty {}
+
+        impl Element for $ty {
+            const DTYPE: DataType = DataType::$dtype;
+            const SIZE: usize = std::mem::size_of::<$ty>();
+
+            fn write_le(self, out: &mut Vec<u8>) {
+                out.extend_from_slice(&self.to_le_bytes());
+            }
+
+            fn read_le(bytes: &[u8]) -> Self {
+                let bytes = bytes[..Self::SIZE].try_into().expect("slice has element size");
+                <$ty>::from_le_bytes(bytes)
+            }
+        }
+    )*};
+}
+
+impl_primitive! {
+    f32 => Float,
+    f64 => Double,
+    i8 => Int8,
+    i16 => Int16,
+    i32 => Int32,
+    i64 => Int64,
+    u8 => Uint8,
+    u16 => Uint16,
+    u32 => Uint32,
+    u64 => Uint64,
+}
+
+impl sealed::Sealed for bool {
+    fn is_valid(bytes: &[u8]) -> bool {
+        bytes.iter().all(|&b| b <= 1)
+    }
+}
+
+/// Booleans are stored as one byte, 0 or 1. Any other byte decodes as true.
+impl Element for bool {
+    const DTYPE: DataType = DataType::Bool;
+    const SIZE: usize = 1;
+
+    fn write_le(self, out: &mut Vec<u8>) {
+        out.push(u8::from(self));
+    }
+
+    fn read_le(bytes: &[u8]) -> Self {
+        bytes[0] != 0
+    }
+}
+
+macro_rules! float_newtype {
+    ($($(#[$doc:meta])* $name:ident($bits:ty) => $dtype:ident),* $(,)?) => {$(
+        $(#[$doc])*
+        ///
+        /// The value holds the raw bits of the number.
+        #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+        #[repr(transparent)]
+        pub struct $name(pub $bits);
+
+        impl $name {
+            /// Creates a value from its raw bits.
+            pub const fn from_bits(bits: $bits) -> Self {
+                Self(bits)
+            }
+
+            /// Returns the raw bits of the value.
+            pub const fn to_bits(self) -> $bits {
+                self.0
+            }
+        }
+
+        impl sealed::Sealed for $name {}
+
+        impl Element for $name {
+            const DTYPE: DataType = DataType::$dtype;
+            const SIZE: usize = std::mem::size_of::<$bits>();
+
+            fn write_le(self, out: &mut Vec<u8>) {
+                out.extend_from_slice(&self.0.to_le_bytes());
+            }
+
+            fn read_le(bytes: &[u8]) -> Self {
+                Self(<$bits>::read_le(bytes))
+            }
+        }
+    )*};
+}
+
+float_newtype! {
+    /// An IEEE 754 half-precision float.
+    Float16(u16) => Float16,
+    /// A bfloat16 float, the upper half of an `f32`.
+    Bfloat16(u16) => Bfloat16,
+    /// An 8-bit float with 4 exponent and 3 mantissa bits, without
+    /// infinities.
+    Float8E4M3Fn(u8) => Float8E4M3Fn,
+    /// An 8-bit float with 4 exponent and 3 mantissa bits, without
+    /// infinities or negative zero.
+    Float8E4M3Fnuz(u8) => Float8E4M3Fnuz,
+    /// An 8-bit float with 5 exponent and 2 mantissa bits.
+    Float8E5M2(u8) => Float8E5M2,
+    /// An 8-bit float with 5 exponent and 2 mantissa bits, without
+    /// infinities or negative zero.
+    Float8E5M2Fnuz(u8) => Float8E5M2Fnuz,
+    /// An 8-bit power of two, with 8 exponent bits and no sign.
+    Float8E8M0(u8) => Float8E8M0,
+}
+
+/// A 4-bit float with 2 exponent and 1 mantissa bits, without infinities or
+/// NaN.
+///
+/// The value holds the bits of the number in its lower four bits. Tensors
+/// store two of these per byte, so this type is not an [`Element`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[repr(transparent)]
+pub struct Float4E2M1(pub u8);
+
+impl Float4E2M1 {
+    /// Creates a value from its raw bits. Only the lower four bits are kept.
+    pub const fn from_bits(bits: u8) -> Self {
+        Self(bits & 0x0f)
+    }
+
+    /// Returns the raw bits of the value.
+    pub const fn to_bits(self) -> u8 {
+        self.0
+    }
+}
+
+/// Reinterprets little-endian bytes as a slice of elements without copying.
+///
+/// Returns `None` if the bytes are not aligned for `T`, their length is not
+/// a multiple of the element size, they hold an invalid `bool`, or the
+/// target is big-endian.
+pub(crate) fn cast_slice<T: Element>(bytes: &[u8]) -> Option<&[T]> {
+    if cfg!(target_endian = "big") || !T::is_valid(bytes) {
+        return None;
+    }
+    // SAFETY: every `Element` is a plain value of `T::SIZE` bytes whose
+    // little-endian encoding is its in-memory representation, and the bit
+    // patterns were checked above.
+    let (prefix, slice, suffix) = unsafe { bytes.align_to::<T>() };
+    (prefix.is_empty() && suffix.is_empty()).then_some(slice)
+}
+
+/// Mutable version of [`cast_slice`].
+pub(crate) fn cast_slice_mut<T: Element>(bytes: &mut [u8]) -> Option<&mut [T]> {
+    if cfg!(target_endian = "big") || !T::is_valid(bytes) {
+        return None;
+    }
+    // SAFETY: as in `cast_slice`. Any value of `T` written through the slice
+    // is a valid encoding, so the bytes stay valid for every element type.
+    let (prefix, slice, suffix) = unsafe { bytes.align_to_mut::<T>() };
+    (prefix.is_empty() && suffix.is_empty()).then_some(slice)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip<T: Element>(value: T) -> T {
+        let mut bytes = Vec::new();
+        value.write_le(&mut bytes);
+        assert_eq!(bytes.len(), T::SIZE);
+        T::read_le(&bytes)
+    }
+
+    #[test]
+    fn test_dtypes() {
+        assert_eq!(f32::DTYPE, DataType::Float);
+        assert_eq!(u64::DTYPE, DataType::Uint64);
+        assert_eq!(bool::DTYPE, DataType::Bool);
+        assert_eq!(Bfloat16::DTYPE, DataType::Bfloat16);
+        assert_eq!(Float8E5M2Fnuz::DTYPE, DataType::Float8E5M2Fnuz);
+        for (dtype, size) in [
+            (f64::DTYPE, f64::SIZE),
+            (i16::DTYPE, i16::SIZE),
+            (Float16::DTYPE, Float16::SIZE),
+            (Float8E4M3Fn::DTYPE, Float8E4M3Fn::SIZE),
+        ] {
+            assert_eq!(dtype.itemsize(), Some(size as f64));
+        }
+    }
+
+    #[test]
+    fn test_encoding() {
+        let mut bytes = Vec::new();
+        1.5f32.write_le(&mut bytes);
+        (-2i16).write_le(&mut bytes);
+        true.write_le(&mut bytes);
+        Float16::from_bits(0x3c00).write_le(&mut bytes);
+        assert_eq!(bytes, [0, 0, 0xc0, 0x3f, 0xfe, 0xff, 1, 0x00, 0x3c]);
+        assert_eq!(f32::read_le(&bytes), 1.5);
+        assert_eq!(i16::read_le(&bytes[4..]), -2);
+        assert!(bool::read_le(&[2]));
+        assert_eq!(Float16::read_le(&bytes[7..]).to_bits(), 0x3c00);
+    }
+
+    #[test]
+    fn test_cast_slice() {
+        let values = [1.0f32, -2.0, 0.5];
+        let mut bytes = Vec::new();
+        values.iter().for_each(|v| v.write_le(&mut bytes));
+        let aligned = bytes.as_ptr().align_offset(4) == 0;
+        assert_eq!(cast_slice::<f32>(&bytes).is_some(), aligned);
+        if let Some(slice) = cast_slice::<f32>(&bytes) {
+            assert_eq!(slice, values);
+        }
+        assert!(cast_slice::<f32>(&bytes[..5]).is_none());
+        let odd = bytes.as_ptr().align_offset(2) ^ 1;
+        assert!(cast_slice::<u16>(&bytes[odd..odd + 4]).is_none());
+        assert_eq!(cast_slice::<bool>(&[0, 1]), Some(&[false, true][..]));
+        assert!(cast_slice::<bool>(&[0, 2]).is_none());
+    }
+
+    #[test]
+    fn test_round_trip() {
+        assert_eq!(round_trip(f64::MIN_POSITIVE), f64::MIN_POSITIVE);
+        assert_eq!(round_trip(i64::MIN), i64::MIN);
+        assert_eq!(round_trip(u32::MAX), u32::MAX);
+        assert!(!round_trip(false));
+        assert_eq!(round_trip(Float8E5M2(0x7b)), Float8E5M2(0x7b));
+    }
+}
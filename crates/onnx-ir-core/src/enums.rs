@@ -6,6 +6,7 @@
 //! This module defines the type enums that correspond to `DataType` and
 //! `AttributeType` in the ONNX protobuf specification.
 
+use crate::element::Element;
 use std::fmt;
 
 /// Enum for the types of ONNX attributes.
@@ -31,9 +32,42 @@ pub enum AttributeType {
     TypeProtos = 14,
 }
 
+impl AttributeType {
+    /// Returns the name of the attribute type in the ONNX specification.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use onnx_ir_core::AttributeType;
+    ///
+    /// assert_eq!(AttributeType::Ints.onnx_name(), "INTS");
+    /// assert_eq!(AttributeType::TypeProto.onnx_name(), "TYPE_PROTO");
+    /// ```
+    pub fn onnx_name(&self) -> &'static str {
+        match self {
+            AttributeType::Undefined => "UNDEFINED",
+            AttributeType::Float => "FLOAT",
+            AttributeType::Int => "INT",
+            AttributeType::String => "STRING",
+            AttributeType::Tensor => "TENSOR",
+            AttributeType::Graph => "GRAPH",
+            AttributeType::Floats => "FLOATS",
+            AttributeType::Ints => "INTS",
+            AttributeType::Strings => "STRINGS",
+            AttributeType::Tensors => "TENSORS",
+            AttributeType::Graphs => "GRAPHS",
+            AttributeType::SparseTensor => "SPARSE_TENSOR",
+            AttributeType::SparseTensors => "SPARSE_TENSORS",
+            AttributeType::TypeProto => "TYPE_PROTO",
+            AttributeType::TypeProtos => "TYPE_PROTOS",
+        }
+    }
+}
+
 impl fmt::Display for AttributeType {
+    /// Formats the type with its ONNX specification name, e.g. `INTS`.
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{:?}", self)
+        f.write_str(self.onnx_name())
     }
 }
 
@@ -293,6 +327,50 @@ impl DataType {
         }
     }
 
+    /// Creates a DataType from its value in `TensorProto.DataType`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use onnx_ir_core::DataType;
+    ///
+    /// assert_eq!(DataType::from_i32(7), Some(DataType::Int64));
+    /// assert_eq!(DataType::from_i32(100), None);
+    /// ```
+    pub fn from_i32(value: i32) -> Option<DataType> {
+        let dtype = match value {
+            0 => DataType::Undefined,
+            1 => DataType::Float,
+            2 => DataType::Uint8,
+            3 => DataType::Int8,
+            4 => DataType::Uint16,
+            5 => DataType::Int16,
+            6 => DataType::Int32,
+            7 => DataType::Int64,
+            8 => DataType::String,
+            9 => DataType::Bool,
+            10 => DataType::Float16,
+            11 => DataType::Double,
+            12 => DataType::Uint32,
+            13 => DataType::Uint64,
+            14 => DataType::Complex64,
+            15 => DataType::Complex128,
+            16 => DataType::Bfloat16,
+            17 => DataType::Float8E4M3Fn,
+            18 => DataType::Float8E4M3Fnuz,
+            19 => DataType::Float8E5M2,
+            20 => DataType::Float8E5M2Fnuz,
+            21 => DataType::Uint4,
+            22 => DataType::Int4,
+            23 => DataType::Float4E2M1,
+            24 => DataType::Float8E8M0,
+            25 => DataType::Uint2,
+            26 => DataType::Int2,
+            _ => return None,
+        };
+        Some(dtype)
+    }
+
     /// Creates a DataType from a short name string.
     ///
     /// # Examples
@@ -336,6 +414,79 @@ impl DataType {
             _ => None,
         }
     }
+
+    /// Returns the name of the data type in ONNX type strings, as in
+    /// `tensor(float16)`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use onnx_ir_core::DataType;
+    ///
+    /// assert_eq!(DataType::Float.onnx_name(), "float");
+    /// assert_eq!(DataType::Float8E4M3Fn.onnx_name(), "float8e4m3fn");
+    /// ```
+    pub fn onnx_name(&self) -> &'static str {
+        match self {
+            DataType::Undefined => "undefined",
+            DataType::Float => "float",
+            DataType::Uint8 => "uint8",
+            DataType::Int8 => "int8",
+            DataType::Uint16 => "uint16",
+            DataType::Int16 => "int16",
+            DataType::Int32 => "int32",
+            DataType::Int64 => "int64",
+            DataType::String => "string",
+            DataType::Bool => "bool",
+            DataType::Float16 => "float16",
+            DataType::Double => "double",
+            DataType::Uint32 => "uint32",
+            DataType::Uint64 => "uint64",
+            DataType::Complex64 => "complex64",
+            DataType::Complex128 => "complex128",
+            DataType::Bfloat16 => "bfloat16",
+            DataType::Float8E4M3Fn => "float8e4m3fn",
+            DataType::Float8E4M3Fnuz => "float8e4m3fnuz",
+            DataType::Float8E5M2 => "float8e5m2",
+            DataType::Float8E5M2Fnuz => "float8e5m2fnuz",
+            DataType::Uint4 => "uint4",
+            DataType::Int4 => "int4",
+            DataType::Float4E2M1 => "float4e2m1",
+            DataType::Float8E8M0 => "float8e8m0",
+            DataType::Uint2 => "uint2",
+            DataType::Int2 => "int2",
+        }
+    }
+
+    /// Creates a DataType from its name in ONNX type strings.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use onnx_ir_core::DataType;
+    ///
+    /// assert_eq!(DataType::from_onnx_name("int4"), Some(DataType::Int4));
+    /// assert_eq!(DataType::from_onnx_name("f32"), None);
+    /// ```
+    pub fn from_onnx_name(name: &str) -> Option<DataType> {
+        (0..=26)
+            .filter_map(DataType::from_i32)
+            .find(|dtype| dtype.onnx_name() == name)
+    }
+
+    /// Returns the data type of tensors with elements of type `T`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use onnx_ir_core::{element::Float16, DataType};
+    ///
+    /// assert_eq!(DataType::of::<f32>(), DataType::Float);
+    /// assert_eq!(DataType::of::<Float16>(), DataType::Float16);
+    /// ```
+    pub fn of<T: Element>() -> DataType {
+        T::DTYPE
+    }
 }
 
 impl fmt::Display for DataType {
@@ -380,6 +531,12 @@ mod tests {
         assert!(!DataType::Float.is_string());
     }
 
+    #[test]
+    fn test_attribute_type_display() {
+        assert_eq!(AttributeType::Int.to_string(), "INT");
+        assert_eq!(AttributeType::SparseTensors.to_string(), "SPARSE_TENSORS");
+    }
+
     #[test]
     fn test_short_name_roundtrip() {
         let types = vec![
@@ -394,4 +551,11 @@ mod tests {
             assert_eq!(DataType::from_short_name(short), Some(dtype));
         }
     }
+
+    #[test]
+    fn test_onnx_name_roundtrip() {
+        for dtype in (0..=26).filter_map(DataType::from_i32) {
+            assert_eq!(DataType::from_onnx_name(dtype.onnx_name()), Some(dtype));
+        }
+    }
 }
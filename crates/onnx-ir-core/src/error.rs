@@ -0,0 +1,149 @@
+// Copyright (c) ONNX Project Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Error type for the IR.
+//!
+//! Fallible operations across the crate return [`IrError`], so a malformed
+//! model can be reported to the caller instead of panicking.
+
+use crate::attribute::AttributeError;
+use crate::enums::DataType;
+use crate::passes::PassError;
+use crate::shape::Shape;
+use thiserror::Error;
+
+/// Points to the conversions to use for strings, which have their own
+/// tensor type.
+fn cast_hint(from: DataType, to: DataType) -> &'static str {
+    match (from, to) {
+        (_, DataType::String) => "; use Tensor::cast_to_strings instead",
+        (DataType::String, _) => "; use StringTensor::cast instead",
+        _ => "",
+    }
+}
+
+/// A specialized `Result` type for IR operations.
+pub type Result<T, E = IrError> = std::result::Result<T, E>;
+
+/// An error raised by an IR operation.
+#[derive(Debug, Error)]
+pub enum IrError {
+    /// The amount of data does not match the shape.
+    #[error("Data length {actual} does not match expected size {expected}")]
+    ShapeMismatch { expected: usize, actual: usize },
+
+    /// An operation needs a static shape but the shape has symbolic dimensions.
+    #[error("Shape {0} contains symbolic dimensions")]
+    SymbolicShape(Shape),
+
+    /// A shape has a negative dimension where a size is needed.
+    #[error("Shape {0} has a negative dimension")]
+    NegativeDimension(Shape),
+
+    /// The size of a shape overflows or cannot be allocated.
+    #[error("Shape {0} is too large")]
+    ShapeTooLarge(Shape),
+
+    /// Two shapes cannot be broadcast or merged.
+    #[error("Shapes {0} and {1} are incompatible")]
+    IncompatibleShapes(Shape, Shape),
+
+    /// A frozen shape was modified.
+    #[error("Cannot modify frozen shape")]
+    FrozenShape,
+
+    /// A dimension index is out of bounds.
+    #[error("Dimension index {index} is out of bounds for rank {rank}")]
+    DimensionOutOfBounds { index: usize, rank: usize },
+
+    /// A tensor was accessed as a different data type than it holds.
+    #[error("Tensor holds {actual}, but {expected} was requested")]
+    DtypeMismatch {
+        expected: DataType,
+        actual: DataType,
+    },
+
+    /// A tensor cannot be converted between two data types.
+    #[error("Cannot cast {from} to {to}{}", cast_hint(*from, *to))]
+    UnsupportedCast { from: DataType, to: DataType },
+
+    /// A packed tensor was created with a data type that is not packed.
+    #[error("Data type {0} is not a packed sub-byte type")]
+    NotPacked(DataType),
+
+    /// A value does not fit in a data type.
+    #[error("Value {value} is out of range for {dtype}")]
+    OutOfRange { value: i64, dtype: DataType },
+
+    /// An element index is out of bounds.
+    #[error("Index {index} is out of bounds for {size} elements")]
+    IndexOutOfBounds { index: usize, size: usize },
+
+    /// The element size of a data type is not known.
+    #[error("Data type {0} does not have a known element size")]
+    UnknownDtype(DataType),
+
+    /// A graph mutation would break an invariant of the graph.
+    #[error("Invalid graph: {0}")]
+    InvalidGraph(String),
+
+    /// An attribute could not be read.
+    #[error(transparent)]
+    Attribute(#[from] AttributeError),
+
+    /// A pass failed.
+    #[error(transparent)]
+    Pass(#[from] PassError),
+
+    /// Reading or writing external data failed.
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
+    /// Serialized data could not be decoded.
+    #[error("Decode error: {0}")]
+    Decode(String),
+
+    /// A string could not be parsed.
+    #[error("Cannot parse '{input}': {message}")]
+    Parse { input: String, message: String },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_messages() {
+        let err = IrError::ShapeMismatch {
+            expected: 16,
+            actual: 8,
+        };
+        assert_eq!(
+            err.to_string(),
+            "Data length 8 does not match expected size 16"
+        );
+
+        let shape = Shape::new(vec![crate::shape::SymbolicDim::Symbol(Some("N".into()))]);
+        assert_eq!(
+            IrError::SymbolicShape(shape).to_string(),
+            "Shape [N] contains symbolic dimensions"
+        );
+        assert_eq!(
+            IrError::UnknownDtype(DataType::String).to_string(),
+            "Data type String does not have a known element size"
+        );
+    }
+
+    #[test]
+    fn test_error_conversions() {
+        let io = std::io::Error::new(std::io::ErrorKind::NotFound, "weights.bin");
+        assert!(matches!(IrError::from(io), IrError::Io(_)));
+
+        let attr = AttributeError::Missing {
+            node: "Conv".to_string(),
+            name: "pads".to_string(),
+        };
+        let err = IrError::from(attr);
+        assert_eq!(err.to_string(), "Conv: missing attribute 'pads'");
+    }
+}
@@ -0,0 +1,590 @@
+// Copyright (c) ONNX Project Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Conversions between `f32` and low-precision floating point formats.
+//!
+//! The conversions are bit-exact and follow the semantics of the ONNX `Cast`
+//! operator. Values are rounded to the nearest representable number, ties to
+//! even. With `saturate`, values out of the range of a float 8 format,
+//! infinities included, become its largest finite number of the same sign.
+//! Without it they become infinity if the format has one, and NaN otherwise.
+//!
+//! `float16` and `bfloat16` follow IEEE 754 and never saturate, while
+//! `float4e2m1` has neither infinity nor NaN and always saturates.
+//! `float8e8m0` holds only powers of two and is rounded as [`RoundMode`]
+//! says, like the `round_mode` attribute of `Cast`.
+
+use crate::element::{
+    Bfloat16, Float16, Float4E2M1, Float8E4M3Fn, Float8E4M3Fnuz, Float8E5M2, Float8E5M2Fnuz,
+    Float8E8M0,
+};
+
+/// How a value is rounded to a power of two for `float8e8m0`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RoundMode {
+    /// Round up to the next power of two, the default of `Cast`.
+    #[default]
+    Up,
+    /// Round down to the previous power of two.
+    Down,
+    /// Round to the nearest power of two, ties to an even exponent.
+    Nearest,
+}
+
+/// How a format encodes NaN.
+#[derive(Clone, Copy)]
+enum Nan {
+    /// Every magnitude above infinity is NaN.
+    AboveInfinity,
+    /// The largest magnitude is NaN.
+    Largest,
+    /// Negative zero is the only NaN.
+    NegativeZero,
+    /// The format has no NaN.
+    None,
+}
+
+/// A binary floating point format with a sign bit.
+struct Format {
+    mantissa_bits: u32,
+    sign_bit: u32,
+    bias: i32,
+    /// Magnitude bits of the largest finite number.
+    max: u32,
+    /// Magnitude bits of infinity.
+    infinity: Option<u32>,
+    nan: Nan,
+    /// Bits of the NaN produced by conversions, without its sign.
+    canonical_nan: u32,
+}
+
+const FLOAT16: Format = Format {
+    mantissa_bits: 10,
+    sign_bit: 0x8000,
+    bias: 15,
+    max: 0x7bff,
+    infinity: Some(0x7c00),
+    nan: Nan::AboveInfinity,
+    canonical_nan: 0x7e00,
+};
+
+const BFLOAT16: Format = Format {
+    mantissa_bits: 7,
+    sign_bit: 0x8000,
+    bias: 127,
+    max: 0x7f7f,
+    infinity: Some(0x7f80),
+    nan: Nan::AboveInfinity,
+    canonical_nan: 0x7fc0,
+};
+
+const FLOAT8E4M3FN: Format = Format {
+    mantissa_bits: 3,
+    sign_bit: 0x80,
+    bias: 7,
+    max: 0x7e,
+    infinity: None,
+    nan: Nan::Largest,
+    canonical_nan: 0x7f,
+};
+
+const FLOAT8E4M3FNUZ: Format = Format {
+    mantissa_bits: 3,
+    sign_bit: 0x80,
+    bias: 8,
+    max: 0x7f,
+    infinity: None,
+    nan: Nan::NegativeZero,
+    canonical_nan: 0x80,
+};
+
+const FLOAT8E5M2: Format = Format {
+    mantissa_bits: 2,
+    sign_bit: 0x80,
+    bias: 15,
+    max: 0x7b,
+    infinity: Some(0x7c),
+    nan: Nan::AboveInfinity,
+    canonical_nan: 0x7f,
+};
+
+const FLOAT8E5M2FNUZ: Format = Format {
+    mantissa_bits: 2,
+    sign_bit: 0x80,
+    bias: 16,
+    max: 0x7f,
+    infinity: None,
+    nan: Nan::NegativeZero,
+    canonical_nan: 0x80,
+};
+
+const FLOAT4E2M1: Format = Format {
+    mantissa_bits: 1,
+    sign_bit: 0x8,
+    bias: 1,
+    max: 0x7,
+    infinity: None,
+    nan: Nan::None,
+    canonical_nan: 0x7,
+};
+
+impl Format {
+    /// Returns the NaN produced for a NaN or an overflow of the given sign.
+    fn nan(&self, negative: bool) -> u32 {
+        match self.nan {
+            Nan::AboveInfinity | Nan::Largest if negative => self.sign_bit | self.canonical_nan,
+            _ => self.canonical_nan,
+        }
+    }
+
+    fn encode(&self, value: f64, saturate: bool) -> u32 {
+        let negative = value.is_sign_negative();
+        if value.is_nan() {
+            return self.nan(negative);
+        }
+        let sign = if negative { self.sign_bit } else { 0 };
+        let magnitude = if value.is_infinite() {
+            None
+        } else {
+            Some(self.round(value.abs()))
+        };
+        match magnitude {
+            Some(0) if matches!(self.nan, Nan::NegativeZero) => 0,
+            Some(magnitude) if magnitude <= u64::from(self.max) => sign | magnitude as u32,
+            _ if saturate => sign | self.max,
+            _ => match self.infinity {
+                Some(infinity) => sign | infinity,
+                None => self.nan(negative),
+            },
+        }
+    }
+
+    /// Rounds a finite non-negative value to the magnitude bits of the
+    /// nearest number of the format, ties to even. The result exceeds
+    /// [`Format::max`] if the value is out of range.
+    fn round(&self, value: f64) -> u64 {
+        let bits = value.to_bits();
+        let mut significand = bits & 0xf_ffff_ffff_ffff;
+        let mut exponent = (bits >> 52) as i32;
+        if exponent == 0 {
+            exponent = 1;
+        } else {
+            significand |= 1 << 52;
+        }
+        if significand == 0 {
+            return 0;
+        }
+        // The value is `significand * 2^(exponent - 1075)`. Find the exponent
+        // of its leading bit, and the quantum of the format at that exponent,
+        // clamped to the subnormal range.
+        let leading = exponent - 1023 - (significand.leading_zeros() as i32 - 11);
+        let target = leading.max(1 - self.bias);
+        let shift = target - self.mantissa_bits as i32 - (exponent - 1075);
+        let multiple = if shift > 60 {
+            0
+        } else {
+            let quotient = significand >> shift;
+            let remainder = significand & ((1 << shift) - 1);
+            let half = 1 << (shift - 1);
+            if remainder > half || (remainder == half && quotient & 1 == 1) {
+                quotient + 1
+            } else {
+                quotient
+            }
+        };
+        // Subnormals have a biased exponent of 0 and no implicit bit, which
+        // this also gives for `target + bias == 1`. A rounding carry into the
+        // implicit bit correctly bumps the exponent.
+        (((target + self.bias) as u64) << self.mantissa_bits) + multiple - (1 << self.mantissa_bits)
+    }
+
+    fn decode(&self, bits: u32) -> f32 {
+        let magnitude = bits & (self.sign_bit - 1);
+        let is_nan = match self.nan {
+            Nan::AboveInfinity => self.infinity.is_some_and(|infinity| magnitude > infinity),
+            Nan::Largest => magnitude == self.sign_bit - 1,
+            Nan::NegativeZero => bits == self.sign_bit,
+            Nan::None => false,
+        };
+        let value = if is_nan {
+            f32::NAN
+        } else if Some(magnitude) == self.infinity {
+            f32::INFINITY
+        } else {
+            let exponent = (magnitude >> self.mantissa_bits) as i32;
+            let mantissa = magnitude & ((1 << self.mantissa_bits) - 1);
+            let scale = exponent.max(1) - self.bias - self.mantissa_bits as i32;
+            let significand = if exponent == 0 {
+                mantissa
+            } else {
+                mantissa | (1 << self.mantissa_bits)
+            };
+            significand as f32 * pow2(scale)
+        };
+        if bits & self.sign_bit != 0 {
+            -value
+        } else {
+            value
+        }
+    }
+}
+
+/// Returns `2^exponent` for an exponent in the normal range of `f32`.
+fn pow2(exponent: i32) -> f32 {
+    f32::from_bits(((exponent + 127) as u32) << 23)
+}
+
+impl Float16 {
+    /// Converts an `f32`, rounding to nearest, ties to even.
+    ///
+    /// Values out of range become infinity.
+    pub fn from_f32(value: f32) -> Self {
+        Self::from_f64(f64::from(value))
+    }
+
+    /// Converts an `f64`, rounding to nearest, ties to even.
+    ///
+    /// Values out of range become infinity.
+    pub fn from_f64(value: f64) -> Self {
+        Self(FLOAT16.encode(value, false) as u16)
+    }
+
+    /// Converts the value to an `f32` exactly.
+    pub fn to_f32(self) -> f32 {
+        FLOAT16.decode(u32::from(self.0))
+    }
+}
+
+impl Bfloat16 {
+    /// Converts an `f32`, rounding to nearest, ties to even.
+    ///
+    /// Values out of range become infinity.
+    pub fn from_f32(value: f32) -> Self {
+        Self::from_f64(f64::from(value))
+    }
+
+    /// Converts an `f64`, rounding to nearest, ties to even.
+    ///
+    /// Values out of range become infinity.
+    pub fn from_f64(value: f64) -> Self {
+        // ONNX always produces a positive NaN.
+        if value.is_nan() {
+            return Self(0x7fc0);
+        }
+        Self(BFLOAT16.encode(value, false) as u16)
+    }
+
+    /// Converts the value to an `f32` exactly.
+    pub fn to_f32(self) -> f32 {
+        f32::from_bits(u32::from(self.0) << 16)
+    }
+}
+
+macro_rules! impl_float8 {
+    ($($name:ident => $format:ident),* $(,)?) => {$(
+        impl $name {
+            /// Converts an `f32`, rounding to nearest, ties to even.
+            ///
+            /// Out of range values saturate to the largest finite number if
+            /// `saturate` is set, as with the `saturate` attribute of `Cast`.
+            pub fn from_f32(value: f32, saturate: bool) -> Self {
+                Self($format.encode(f64::from(value), saturate) as u8)
+            }
+
+            /// Converts the value to an `f32` exactly.
+            pub fn to_f32(self) -> f32 {
+                $format.decode(u32::from(self.0))
+            }
+        }
+    )*};
+}
+
+impl_float8! {
+    Float8E4M3Fn => FLOAT8E4M3FN,
+    Float8E4M3Fnuz => FLOAT8E4M3FNUZ,
+    Float8E5M2 => FLOAT8E5M2,
+    Float8E5M2Fnuz => FLOAT8E5M2FNUZ,
+}
+
+impl Float8E8M0 {
+    /// Converts an `f32`, rounding to a power of two as `round_mode` says.
+    ///
+    /// The format has no sign, so the sign of the value is ignored. Out of
+    /// range values saturate to the largest number if `saturate` is set and
+    /// become NaN otherwise.
+    pub fn from_f32(value: f32, saturate: bool, round_mode: RoundMode) -> Self {
+        if value.is_nan() || (value.is_infinite() && !saturate) {
+            return Self(0xff);
+        }
+        let bits = value.to_bits() & 0x7fff_ffff;
+        let exponent = bits >> 23;
+        let mantissa = bits & 0x7f_ffff;
+        let exponent = match (exponent, round_mode) {
+            (0xff, _) => 0xff,
+            // Subnormals round to 2^-127, whose mantissa is 0x40_0000, or to
+            // 2^-126. Smaller values have no code and round to 2^-127.
+            (0, RoundMode::Up) => u32::from(mantissa > 0x40_0000),
+            (0, RoundMode::Down) => 0,
+            (0, RoundMode::Nearest) => u32::from(mantissa > 0x60_0000),
+            (_, RoundMode::Up) if mantissa > 0 => exponent + 1,
+            (_, RoundMode::Nearest)
+                if mantissa > 0x40_0000 || (mantissa == 0x40_0000 && exponent % 2 == 1) =>
+            {
+                exponent + 1
+            }
+            _ => exponent,
+        };
+        match exponent {
+            0xff if saturate => Self(0xfe),
+            _ => Self(exponent as u8),
+        }
+    }
+
+    /// Converts the value to an `f32` exactly.
+    pub fn to_f32(self) -> f32 {
+        match self.0 {
+            0xff => f32::NAN,
+            0 => f32::from_bits(0x40_0000),
+            exponent => f32::from_bits(u32::from(exponent) << 23),
+        }
+    }
+}
+
+impl Float4E2M1 {
+    /// Converts an `f32`, rounding to nearest, ties to even.
+    ///
+    /// Out of range values saturate to ±6 and NaN becomes 6.
+    pub fn from_f32(value: f32) -> Self {
+        Self(FLOAT4E2M1.encode(f64::from(value), true) as u8)
+    }
+
+    /// Converts the value to an `f32` exactly.
+    pub fn to_f32(self) -> f32 {
+        FLOAT4E2M1.decode(u32::from(self.0 & 0x0f))
+    }
+}
+
+macro_rules! impl_into_f32 {
+    ($($name:ident),* $(,)?) => {$(
+        impl From<$name> for f32 {
+            fn from(value: $name) -> f32 {
+                value.to_f32()
+            }
+        }
+    )*};
+}
+
+impl_into_f32!(
+    Float16,
+    Bfloat16,
+    Float8E4M3Fn,
+    Float8E4M3Fnuz,
+    Float8E5M2,
+    Float8E5M2Fnuz,
+    Float8E8M0,
+    Float4E2M1,
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_float16() {
+        let cases = [
+            (1.0, 0x3c00),
+            (-2.0, 0xc000),
+            (65504.0, 0x7bff),
+            (65519.0, 0x7bff),
+            (65520.0, 0x7c00),
+            (1.0 / 3.0, 0x3555),
+            (2f32.powi(-24), 0x0001),
+            (2f32.powi(-25), 0x0000),
+            (1.5 * 2f32.powi(-25), 0x0001),
+            (-0.0, 0x8000),
+            (f32::NEG_INFINITY, 0xfc00),
+        ];
+        for (value, bits) in cases {
+            assert_eq!(Float16::from_f32(value).to_bits(), bits, "{value}");
+        }
+        assert_eq!(Float16::from_bits(0x3555).to_f32(), 0.333_251_95);
+        assert_eq!(Float16::from_bits(0x0001).to_f32(), 2f32.powi(-24));
+        assert!(Float16::from_f32(f32::NAN).to_f32().is_nan());
+        for bits in 0..=u16::MAX {
+            let value = Float16::from_bits(bits).to_f32();
+            if !value.is_nan() {
+                assert_eq!(Float16::from_f32(value).to_bits(), bits);
+            }
+        }
+    }
+
+    #[test]
+    fn test_bfloat16() {
+        assert_eq!(Bfloat16::from_f32(1.0).to_bits(), 0x3f80);
+        // 1 + 2^-8 is halfway between 1 and 1 + 2^-7, so it rounds to even.
+        assert_eq!(Bfloat16::from_f32(1.0 + 2f32.powi(-8)).to_bits(), 0x3f80);
+        assert_eq!(
+            Bfloat16::from_f32(1.0 + 3.0 * 2f32.powi(-8)).to_bits(),
+            0x3f82
+        );
+        assert_eq!(Bfloat16::from_f32(f32::MAX).to_bits(), 0x7f80);
+        assert_eq!(Bfloat16::from_f32(f32::NAN).to_bits(), 0x7fc0);
+        assert_eq!(Bfloat16::from_bits(0xc0a0).to_f32(), -5.0);
+        assert_eq!(Bfloat16::from_f32(f32::from_bits(1)).to_bits(), 0x0000);
+        assert_eq!(
+            Bfloat16::from_f32(f32::from_bits(0x1_8000)).to_bits(),
+            0x0002
+        );
+        // Rounding an f64 directly avoids double rounding through f32.
+        let value = 1.0 + 2f64.powi(-8) + 2f64.powi(-40);
+        assert_eq!(Bfloat16::from_f64(value).to_bits(), 0x3f81);
+        assert_eq!(Float16::from_f64(value).to_bits(), 0x3c04);
+    }
+
+    #[test]
+    fn test_float8_decode() {
+        let cases = [
+            (Float8E4M3Fn(0x38).to_f32(), 1.0),
+            (Float8E4M3Fn(0x7e).to_f32(), 448.0),
+            (Float8E4M3Fn(0x01).to_f32(), 2f32.powi(-9)),
+            (Float8E4M3Fn(0x80).to_f32(), -0.0),
+            (Float8E4M3Fnuz(0x40).to_f32(), 1.0),
+            (Float8E4M3Fnuz(0xff).to_f32(), -240.0),
+            (Float8E4M3Fnuz(0x01).to_f32(), 2f32.powi(-10)),
+            (Float8E5M2(0x3c).to_f32(), 1.0),
+            (Float8E5M2(0x7b).to_f32(), 57344.0),
+            (Float8E5M2(0xfc).to_f32(), f32::NEG_INFINITY),
+            (Float8E5M2(0x01).to_f32(), 2f32.powi(-16)),
+            (Float8E5M2Fnuz(0x40).to_f32(), 1.0),
+            (Float8E5M2Fnuz(0x7f).to_f32(), 57344.0),
+            (Float8E5M2Fnuz(0x01).to_f32(), 2f32.powi(-17)),
+        ];
+        for (actual, expected) in cases {
+            assert_eq!(actual, expected);
+            assert_eq!(actual.is_sign_negative(), expected.is_sign_negative());
+        }
+        assert!(Float8E4M3Fn(0x7f).to_f32().is_nan());
+        assert!(Float8E4M3Fn(0xff).to_f32().is_nan());
+        assert!(Float8E4M3Fnuz(0x80).to_f32().is_nan());
+        assert!(Float8E5M2(0x7d).to_f32().is_nan());
+        assert!(Float8E5M2Fnuz(0x80).to_f32().is_nan());
+    }
+
+    #[test]
+    fn test_float8_round_trip() {
+        for bits in 0..=u8::MAX {
+            for saturate in [false, true] {
+                let value = Float8E4M3Fn(bits).to_f32();
+                if !value.is_nan() {
+                    assert_eq!(Float8E4M3Fn::from_f32(value, saturate).0, bits);
+                }
+                let value = Float8E4M3Fnuz(bits).to_f32();
+                if !value.is_nan() {
+                    assert_eq!(Float8E4M3Fnuz::from_f32(value, saturate).0, bits);
+                }
+                // Infinity saturates to the largest finite number.
+                let value = Float8E5M2(bits).to_f32();
+                if value.is_finite() || (value.is_infinite() && !saturate) {
+                    assert_eq!(Float8E5M2::from_f32(value, saturate).0, bits);
+                }
+                let value = Float8E5M2Fnuz(bits).to_f32();
+                if !value.is_nan() {
+                    assert_eq!(Float8E5M2Fnuz::from_f32(value, saturate).0, bits);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_float8_rounding_and_saturation() {
+        // Inputs of the ONNX Cast backend tests.
+        let inputs = [
+            0.478_925_47,
+            0.480_336_67,
+            0.499_684_87,
+            0.819_105_45,
+            0.470_312_48,
+            0.816_468,
+            0.210_871_95,
+            0.722_903_8,
+            f32::INFINITY,
+            f32::NEG_INFINITY,
+            1e6,
+            -1e6,
+        ];
+        let e4m3fn = [
+            0.46875, 0.46875, 0.5, 0.8125, 0.46875, 0.8125, 0.203_125, 0.75, 448.0, -448.0, 448.0,
+            -448.0,
+        ];
+        let e5m2 = [
+            0.5, 0.5, 0.5, 0.875, 0.5, 0.875, 0.218_75, 0.75, 57344.0, -57344.0, 57344.0, -57344.0,
+        ];
+        for ((&input, &fn_value), &e5m2_value) in inputs.iter().zip(&e4m3fn).zip(&e5m2) {
+            assert_eq!(Float8E4M3Fn::from_f32(input, true).to_f32(), fn_value);
+            assert_eq!(Float8E5M2::from_f32(input, true).to_f32(), e5m2_value);
+        }
+
+        assert_eq!(Float8E4M3Fn::from_f32(1e6, false).0, 0x7f);
+        assert_eq!(Float8E4M3Fn::from_f32(-1e6, false).0, 0xff);
+        assert_eq!(Float8E4M3Fn::from_f32(450.0, false).to_f32(), 448.0);
+        assert_eq!(Float8E4M3Fn::from_f32(470.0, false).0, 0x7f);
+        assert_eq!(Float8E4M3Fnuz::from_f32(f32::INFINITY, false).0, 0x80);
+        assert_eq!(Float8E4M3Fnuz::from_f32(-300.0, true).to_f32(), -240.0);
+        assert_eq!(Float8E5M2::from_f32(f32::NEG_INFINITY, false).0, 0xfc);
+        assert_eq!(Float8E5M2::from_f32(61440.0, false).0, 0x7c);
+        assert_eq!(Float8E5M2Fnuz::from_f32(1e6, false).0, 0x80);
+
+        assert_eq!(Float8E4M3Fn::from_f32(-0.0, true).0, 0x80);
+        assert_eq!(Float8E4M3Fnuz::from_f32(-0.0, true).0, 0x00);
+        assert_eq!(Float8E5M2Fnuz::from_f32(-1e-9, true).0, 0x00);
+        assert_eq!(Float8E5M2::from_f32(f32::NAN, true).0, 0x7f);
+        assert_eq!(Float8E4M3Fnuz::from_f32(f32::NAN, true).0, 0x80);
+    }
+
+    #[test]
+    fn test_float8e8m0_and_float4() {
+        let up = |value| Float8E8M0::from_f32(value, true, RoundMode::Up).0;
+        assert_eq!(up(1.0), 127);
+        assert_eq!(up(3.0), 129);
+        assert_eq!(up(-0.25), 125);
+        assert_eq!(up(f32::INFINITY), 0xfe);
+        assert_eq!(Float8E8M0::from_f32(f32::MAX, false, RoundMode::Up).0, 0xff);
+        let down = |value| Float8E8M0::from_f32(value, true, RoundMode::Down).0;
+        assert_eq!(down(3.0), 128);
+        assert_eq!(down(f32::MAX), 0xfe);
+        let nearest = |value| Float8E8M0::from_f32(value, true, RoundMode::Nearest).0;
+        assert_eq!(nearest(2.9), 128);
+        assert_eq!(nearest(3.1), 129);
+        // 3 = 1.5 * 2^1 and 6 = 1.5 * 2^2 are ties, which go to even exponents
+        assert_eq!(nearest(3.0), 128);
+        assert_eq!(nearest(6.0), 130);
+        assert_eq!(nearest(f32::from_bits(0x70_0000)), 1);
+        assert_eq!(Float8E8M0(0).to_f32(), 2f32.powi(-127));
+        assert_eq!(Float8E8M0(130).to_f32(), 8.0);
+        assert!(Float8E8M0(0xff).to_f32().is_nan());
+
+        let values = [0.0, 0.5, 1.0, 1.5, 2.0, 3.0, 4.0, 6.0];
+        for (bits, &value) in values.iter().enumerate() {
+            assert_eq!(Float4E2M1::from_bits(bits as u8).to_f32(), value);
+            assert_eq!(Float4E2M1::from_bits(bits as u8 | 0x8).to_f32(), -value);
+        }
+        // The thresholds of the ONNX reference implementation.
+        let cases = [
+            (0.25, 0x0),
+            (0.26, 0x1),
+            (0.75, 0x2),
+            (1.25, 0x2),
+            (1.75, 0x4),
+            (2.5, 0x4),
+            (3.5, 0x6),
+            (5.0, 0x6),
+            (5.1, 0x7),
+            (-0.0, 0x8),
+            (f32::NEG_INFINITY, 0xf),
+            (f32::NAN, 0x7),
+        ];
+        for (value, bits) in cases {
+            assert_eq!(Float4E2M1::from_f32(value).to_bits(), bits, "{value}");
+        }
+    }
+}
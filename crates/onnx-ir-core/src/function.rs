@@ -3,18 +3,71 @@
 
 //! Function definition support.
 
+use crate::attribute::{Attr, RefAttr};
+use crate::graph::{
+    check_index, check_inputs_not_produced, check_new_node, check_not_used,
+    check_not_used_by_outputs, check_replacement, debug_check, replace_node_uses,
+};
+use crate::linked_list::DoublyLinkedList;
 use crate::metadata::MetadataStore;
+use crate::node::Node;
 use crate::value::Value;
+use indexmap::IndexMap;
 use std::collections::HashMap;
+use std::fmt;
+
+/// Identifies a function by its domain, name and overload.
+///
+/// A node calls the function whose identifier matches the node's
+/// `(domain, op_type, overload)`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct FunctionId {
+    pub domain: String,
+    pub name: String,
+    pub overload: String,
+}
+
+impl FunctionId {
+    pub fn new(
+        domain: impl Into<String>,
+        name: impl Into<String>,
+        overload: impl Into<String>,
+    ) -> Self {
+        Self {
+            domain: domain.into(),
+            name: name.into(),
+            overload: overload.into(),
+        }
+    }
+}
+
+impl fmt::Display for FunctionId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}::{}", self.domain, self.name)?;
+        if !self.overload.is_empty() {
+            write!(f, ":{}", self.overload)?;
+        }
+        Ok(())
+    }
+}
 
 /// An ONNX function.
-#[derive(Debug)]
+///
+/// A function has a body of nodes, like a [`Graph`](crate::graph::Graph), and
+/// a list of attribute parameters. Nodes in the body may refer to those
+/// parameters through [`RefAttr`].
+#[derive(Debug, Clone)]
 pub struct Function {
     pub name: String,
     pub domain: String,
     pub overload: String,
     pub inputs: Vec<Value>,
     pub outputs: Vec<Value>,
+    pub nodes: DoublyLinkedList<Node>,
+    /// Names of attribute parameters without a default value.
+    pub attributes: Vec<String>,
+    /// Attribute parameters with a default value (IR version 9+).
+    pub attribute_defaults: IndexMap<String, Attr>,
     pub doc_string: String,
     pub opset_imports: HashMap<String, i32>,
     pub metadata_props: HashMap<String, String>,
@@ -29,6 +82,9 @@ impl Function {
             overload: String::new(),
             inputs: Vec::new(),
             outputs: Vec::new(),
+            nodes: DoublyLinkedList::new(),
+            attributes: Vec::new(),
+            attribute_defaults: IndexMap::new(),
             doc_string: String::new(),
             opset_imports: HashMap::new(),
             metadata_props: HashMap::new(),
@@ -36,6 +92,11 @@ impl Function {
         }
     }
 
+    /// Returns the identifier of the function.
+    pub fn id(&self) -> FunctionId {
+        FunctionId::new(&self.domain, &self.name, &self.overload)
+    }
+
     /// Adds an input value to the function.
     pub fn add_input(&mut self, value: Value) {
         self.inputs.push(value);
@@ -60,11 +121,201 @@ impl Function {
     pub fn num_outputs(&self) -> usize {
         self.outputs.len()
     }
+
+    /// Appends a node to the end of the function body.
+    ///
+    /// Like [`Graph::append`](crate::graph::Graph::append), this checks the
+    /// body stays consistent when [`DEBUG`](crate::DEBUG) is set.
+    pub fn append(&mut self, node: Node) {
+        if crate::debug_enabled() {
+            debug_check(check_new_node(&node, |name| self.defines_value(name)));
+        }
+        self.nodes.push_back(node);
+    }
+
+    /// Prepends a node to the beginning of the function body.
+    pub fn prepend(&mut self, node: Node) {
+        if crate::debug_enabled() {
+            debug_check(check_new_node(&node, |name| self.defines_value(name)));
+            debug_check(check_inputs_not_produced(self.nodes.iter(), &node));
+        }
+        self.nodes.push_front(node);
+    }
+
+    /// Inserts a node before the node at position `index`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn insert_before(&mut self, index: usize, node: Node) {
+        check_index(index, self.nodes.len());
+        self.insert_at(index, node);
+    }
+
+    /// Inserts a node after the node at position `index`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn insert_after(&mut self, index: usize, node: Node) {
+        check_index(index, self.nodes.len());
+        self.insert_at(index + 1, node);
+    }
+
+    fn insert_at(&mut self, position: usize, node: Node) {
+        if crate::debug_enabled() {
+            debug_check(check_new_node(&node, |name| self.defines_value(name)));
+            debug_check(check_inputs_not_produced(
+                self.nodes.iter().skip(position),
+                &node,
+            ));
+        }
+        self.nodes.insert(position, node);
+    }
+
+    /// Removes and returns the last node from the function body.
+    pub fn pop_last(&mut self) -> Option<Node> {
+        if crate::debug_enabled() {
+            if let Some(node) = self.nodes.back() {
+                debug_check(check_not_used(&self.nodes, &self.outputs, node));
+            }
+        }
+        self.nodes.pop_back()
+    }
+
+    /// Removes and returns the first node from the function body.
+    pub fn pop_first(&mut self) -> Option<Node> {
+        if crate::debug_enabled() {
+            if let Some(node) = self.nodes.front() {
+                debug_check(check_not_used(&self.nodes, &self.outputs, node));
+            }
+        }
+        self.nodes.pop_front()
+    }
+
+    /// Replaces every use of the value named `old` with `new`, in node
+    /// inputs, subgraphs and function outputs.
+    ///
+    /// Subgraphs that define their own value named `old` are left alone.
+    /// Returns the number of uses replaced.
+    pub fn replace_all_uses_with(&mut self, old: &str, new: &Value) -> usize {
+        if crate::debug_enabled() {
+            debug_check(check_replacement(self.nodes.iter(), old, &new.name));
+        }
+        let mut count = 0;
+        for node in self.nodes.iter_mut() {
+            count += replace_node_uses(node, old, new);
+        }
+        for output in self.outputs.iter_mut().filter(|v| v.name == old) {
+            *output = new.clone();
+            count += 1;
+        }
+        count
+    }
+
+    /// Returns true if a function input or node output has the given name.
+    pub fn defines_value(&self, name: &str) -> bool {
+        self.inputs.iter().any(|v| v.name == name)
+            || self
+                .iter_nodes()
+                .any(|n| n.outputs.iter().any(|v| v.name == name))
+    }
+
+    /// Returns the number of nodes in the function body.
+    pub fn num_nodes(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Returns true if the function body has no nodes.
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Returns an iterator over the nodes in the function body.
+    pub fn iter_nodes(&self) -> impl Iterator<Item = &Node> {
+        self.nodes.iter()
+    }
+
+    /// Returns an iterator that allows modifying the nodes in the function body.
+    pub fn iter_nodes_mut(&mut self) -> impl Iterator<Item = &mut Node> {
+        self.nodes.iter_mut()
+    }
+
+    /// Clears all nodes from the function body.
+    pub fn clear_nodes(&mut self) {
+        if crate::debug_enabled() {
+            for node in self.iter_nodes() {
+                debug_check(check_not_used_by_outputs(&self.outputs, node));
+            }
+        }
+        self.nodes.clear();
+    }
+
+    /// Declares an attribute parameter without a default value.
+    pub fn add_attribute(&mut self, name: impl Into<String>) {
+        let name = name.into();
+        if !self.has_attribute(&name) {
+            self.attributes.push(name);
+        }
+    }
+
+    /// Declares an attribute parameter with a default value.
+    ///
+    /// If the parameter was previously declared without a default, it is
+    /// moved to the list of parameters with defaults.
+    pub fn set_attribute_default(&mut self, attr: Attr) {
+        self.attributes.retain(|name| name != &attr.name);
+        self.attribute_defaults.insert(attr.name.clone(), attr);
+    }
+
+    /// Gets the default value of an attribute parameter.
+    pub fn get_attribute_default(&self, name: &str) -> Option<&Attr> {
+        self.attribute_defaults.get(name)
+    }
+
+    /// Returns true if the function declares the given attribute parameter.
+    pub fn has_attribute(&self, name: &str) -> bool {
+        self.attributes.iter().any(|n| n == name) || self.attribute_defaults.contains_key(name)
+    }
+
+    /// Returns the names of all attribute parameters, those without defaults first.
+    pub fn attribute_names(&self) -> impl Iterator<Item = &str> {
+        self.attributes
+            .iter()
+            .map(String::as_str)
+            .chain(self.attribute_defaults.keys().map(String::as_str))
+    }
+
+    /// Returns the attribute values bound by a call to this function.
+    ///
+    /// Parameters take the call site's attribute when given, and otherwise
+    /// their default. Call site attributes that are not parameters of the
+    /// function are ignored.
+    pub fn bind_attributes(&self, call: &Node) -> HashMap<String, Attr> {
+        let mut bindings: HashMap<String, Attr> = self
+            .attribute_defaults
+            .iter()
+            .map(|(name, attr)| (name.clone(), attr.clone()))
+            .collect();
+        for name in self.attribute_names() {
+            if let Some(attr) = call.get_attribute(name) {
+                bindings.insert(name.to_string(), attr.clone());
+            }
+        }
+        bindings
+    }
+
+    /// Returns the reference attributes used by nodes in the function body.
+    pub fn ref_attributes(&self) -> impl Iterator<Item = &RefAttr> {
+        self.nodes.iter().flat_map(Node::ref_attributes)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::attribute::AttrValue;
+    use crate::enums::AttributeType;
 
     #[test]
     fn test_function_new() {
@@ -75,6 +326,16 @@ mod tests {
         assert_eq!(func.num_outputs(), 0);
     }
 
+    #[test]
+    fn test_function_id() {
+        let mut func = Function::new("MyFunc", "com.example");
+        assert_eq!(func.id(), FunctionId::new("com.example", "MyFunc", ""));
+        assert_eq!(func.id().to_string(), "com.example::MyFunc");
+
+        func.overload = "v2".to_string();
+        assert_eq!(func.id().to_string(), "com.example::MyFunc:v2");
+    }
+
     #[test]
     fn test_function_inputs_outputs() {
         let mut func = Function::new("MyFunc", "com.example");
@@ -93,4 +354,102 @@ mod tests {
 
         assert_eq!(func.opset_imports.get(""), Some(&18));
     }
+
+    #[test]
+    fn test_function_body() {
+        let mut func = Function::new("MyFunc", "com.example");
+        func.append(Node::new("Mul"));
+        func.append(Node::new("Add"));
+        func.prepend(Node::new("Sub"));
+
+        assert_eq!(func.num_nodes(), 3);
+        let op_types: Vec<_> = func.iter_nodes().map(|n| n.op_type.as_str()).collect();
+        assert_eq!(op_types, vec!["Sub", "Mul", "Add"]);
+
+        assert_eq!(func.pop_first().unwrap().op_type, "Sub");
+        assert_eq!(func.pop_last().unwrap().op_type, "Add");
+        func.clear_nodes();
+        assert!(func.is_empty());
+    }
+
+    #[test]
+    fn test_function_insert() {
+        let mut func = Function::new("MyFunc", "com.example");
+        func.append(Node::new("Mul"));
+        func.insert_before(0, Node::new("Sub"));
+        func.insert_after(1, Node::new("Add"));
+
+        let op_types: Vec<_> = func.iter_nodes().map(|n| n.op_type.as_str()).collect();
+        assert_eq!(op_types, vec!["Sub", "Mul", "Add"]);
+    }
+
+    #[test]
+    fn test_function_replace_all_uses_with() {
+        let mut func = Function::new("MyFunc", "com.example");
+        func.add_input(Value::new("x"));
+        let mut relu = Node::new("Relu");
+        relu.add_input(Value::new("x"));
+        relu.add_output(Value::new("y"));
+        func.append(relu);
+        func.add_output(Value::new("y"));
+
+        let mut neg = Node::new("Neg");
+        neg.add_input(Value::new("x"));
+        neg.add_output(Value::new("z"));
+        func.append(neg);
+
+        assert_eq!(func.replace_all_uses_with("y", &Value::new("z")), 1);
+        assert_eq!(func.outputs[0].name, "z");
+    }
+
+    #[test]
+    fn test_function_attributes() {
+        let mut func = Function::new("MyFunc", "com.example");
+        func.add_attribute("axis");
+        func.add_attribute("alpha");
+        func.set_attribute_default(Attr::float("alpha", 0.5));
+
+        assert!(func.has_attribute("axis"));
+        assert!(func.has_attribute("alpha"));
+        assert!(!func.has_attribute("beta"));
+        assert_eq!(
+            func.attribute_names().collect::<Vec<_>>(),
+            vec!["axis", "alpha"]
+        );
+        assert!(func.get_attribute_default("axis").is_none());
+        assert!(func.get_attribute_default("alpha").is_some());
+    }
+
+    #[test]
+    fn test_function_ref_attributes() {
+        let mut func = Function::new("MyFunc", "com.example");
+        func.add_attribute("slope");
+
+        let mut node = Node::new("LeakyRelu");
+        node.set_attribute(RefAttr::new("alpha", "slope", AttributeType::Float));
+        func.append(node);
+
+        let refs: Vec<_> = func.ref_attributes().collect();
+        assert_eq!(refs.len(), 1);
+        assert_eq!(refs[0].ref_attr_name, "slope");
+    }
+
+    #[test]
+    fn test_function_bind_attributes() {
+        let mut func = Function::new("MyFunc", "com.example");
+        func.add_attribute("axis");
+        func.set_attribute_default(Attr::float("alpha", 0.5));
+        func.set_attribute_default(Attr::float("beta", 1.0));
+
+        let mut call = Node::new("MyFunc");
+        call.set_attribute(Attr::int("axis", 1));
+        call.set_attribute(Attr::float("beta", 2.0));
+        call.set_attribute(Attr::int("unrelated", 3));
+
+        let bindings = func.bind_attributes(&call);
+        assert_eq!(bindings.len(), 3);
+        assert!(matches!(bindings["alpha"].value, AttrValue::Float(v) if v == 0.5));
+        assert!(matches!(bindings["beta"].value, AttrValue::Float(v) if v == 2.0));
+        assert!(!bindings.contains_key("unrelated"));
+    }
 }
@@ -2,15 +2,22 @@
 // SPDX-License-Identifier: Apache-2.0
 
 //! Graph container with mutation support.
+//!
+//! When [`DEBUG`](crate::DEBUG) is set, every mutation checks that it keeps
+//! the graph consistent and panics otherwise, so that a bug in a pass is
+//! reported where it happens rather than much later.
 
+use crate::attribute::{Attr, AttrValue, NodeAttr};
+use crate::error::{IrError, Result};
 use crate::linked_list::DoublyLinkedList;
 use crate::metadata::MetadataStore;
 use crate::node::Node;
 use crate::value::Value;
 use std::collections::HashMap;
+use std::rc::Rc;
 
 /// A computational graph.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Graph {
     pub name: Option<String>,
     pub inputs: Vec<Value>,
@@ -40,24 +47,125 @@ impl Graph {
 
     /// Appends a node to the end of the graph.
     pub fn append(&mut self, node: Node) {
+        if crate::debug_enabled() {
+            debug_check(self.check_new_node(&node));
+        }
         self.nodes.push_back(node);
     }
 
     /// Prepends a node to the beginning of the graph.
     pub fn prepend(&mut self, node: Node) {
+        if crate::debug_enabled() {
+            debug_check(self.check_new_node(&node));
+            debug_check(check_inputs_not_produced(self.nodes.iter(), &node));
+        }
         self.nodes.push_front(node);
     }
 
+    /// Inserts a node before the node at position `index`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn insert_before(&mut self, index: usize, node: Node) {
+        check_index(index, self.nodes.len());
+        self.insert_at(index, node);
+    }
+
+    /// Inserts a node after the node at position `index`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn insert_after(&mut self, index: usize, node: Node) {
+        check_index(index, self.nodes.len());
+        self.insert_at(index + 1, node);
+    }
+
+    fn insert_at(&mut self, position: usize, node: Node) {
+        if crate::debug_enabled() {
+            debug_check(self.check_new_node(&node));
+            debug_check(check_inputs_not_produced(
+                self.nodes.iter().skip(position),
+                &node,
+            ));
+        }
+        self.nodes.insert(position, node);
+    }
+
+    /// Appends a node to the end of the graph, checking that it does not
+    /// redefine a value already defined in the graph.
+    pub fn try_append(&mut self, node: Node) -> Result<()> {
+        self.check_new_node(&node)?;
+        self.append(node);
+        Ok(())
+    }
+
+    /// Prepends a node to the beginning of the graph, checking that it does
+    /// not redefine a value already defined in the graph.
+    pub fn try_prepend(&mut self, node: Node) -> Result<()> {
+        self.check_new_node(&node)?;
+        self.prepend(node);
+        Ok(())
+    }
+
+    /// Returns true if a graph input, initializer or node output has the given name.
+    pub fn defines_value(&self, name: &str) -> bool {
+        self.inputs.iter().any(|v| v.name == name)
+            || self.initializers.contains_key(name)
+            || self
+                .iter_nodes()
+                .any(|n| n.outputs.iter().any(|v| v.name == name))
+    }
+
+    fn check_new_node(&self, node: &Node) -> Result<()> {
+        check_new_node(node, |name| self.defines_value(name))
+    }
+
     /// Removes and returns the last node from the graph.
     pub fn pop_last(&mut self) -> Option<Node> {
+        if crate::debug_enabled() {
+            if let Some(node) = self.nodes.back() {
+                debug_check(check_not_used(&self.nodes, &self.outputs, node));
+            }
+        }
         self.nodes.pop_back()
     }
 
     /// Removes and returns the first node from the graph.
     pub fn pop_first(&mut self) -> Option<Node> {
+        if crate::debug_enabled() {
+            if let Some(node) = self.nodes.front() {
+                debug_check(check_not_used(&self.nodes, &self.outputs, node));
+            }
+        }
         self.nodes.pop_front()
     }
 
+    /// Replaces every use of the value named `old` with `new`, in node
+    /// inputs, subgraphs and graph outputs.
+    ///
+    /// Subgraphs that define their own value named `old` are left alone.
+    /// Returns the number of uses replaced.
+    pub fn replace_all_uses_with(&mut self, old: &str, new: &Value) -> usize {
+        if crate::debug_enabled() {
+            debug_check(check_replacement(self.nodes.iter(), old, &new.name));
+        }
+        self.replace_uses(old, new)
+    }
+
+    fn replace_uses(&mut self, old: &str, new: &Value) -> usize {
+        let mut count = 0;
+        for node in self.nodes.iter_mut() {
+            count += replace_node_uses(node, old, new);
+        }
+        for output in self.outputs.iter_mut().filter(|v| v.name == old) {
+            *output = new.clone();
+            count += 1;
+        }
+        count
+    }
+
     /// Returns the number of nodes in the graph.
     pub fn num_nodes(&self) -> usize {
         self.nodes.len()
@@ -73,12 +181,209 @@ impl Graph {
         self.nodes.iter()
     }
 
+    /// Returns an iterator that allows modifying the nodes in the graph.
+    pub fn iter_nodes_mut(&mut self) -> impl Iterator<Item = &mut Node> {
+        self.nodes.iter_mut()
+    }
+
     /// Clears all nodes from the graph.
     pub fn clear_nodes(&mut self) {
+        if crate::debug_enabled() {
+            for node in self.iter_nodes() {
+                debug_check(check_not_used_by_outputs(&self.outputs, node));
+            }
+        }
         self.nodes.clear();
     }
 }
 
+/// Panics if a debug-mode invariant check failed.
+pub(crate) fn debug_check(result: Result<()>) {
+    if let Err(err) = result {
+        panic!("{}", err);
+    }
+}
+
+/// Returns an error if `node` redefines a value for which `defines` is true,
+/// or defines the same value twice.
+pub(crate) fn check_new_node(node: &Node, defines: impl Fn(&str) -> bool) -> Result<()> {
+    for (i, output) in node.outputs.iter().enumerate() {
+        if output.name.is_empty() {
+            continue;
+        }
+        if defines(&output.name) || node.outputs[..i].iter().any(|v| v.name == output.name) {
+            return Err(IrError::InvalidGraph(format!(
+                "{} redefines value '{}'",
+                node.label(),
+                output.name
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Panics if `index` does not refer to one of `len` nodes.
+pub(crate) fn check_index(index: usize, len: usize) {
+    assert!(
+        index < len,
+        "Node index {} is out of bounds for {} nodes",
+        index,
+        len
+    );
+}
+
+/// Returns an error if one of `nodes` produces an input of `node`, which
+/// would then be used before it is defined if `node` were inserted before
+/// them.
+pub(crate) fn check_inputs_not_produced<'a>(
+    nodes: impl Iterator<Item = &'a Node>,
+    node: &Node,
+) -> Result<()> {
+    for producer in nodes {
+        if let Some(input) = node
+            .inputs
+            .iter()
+            .filter(|v| !v.name.is_empty())
+            .find(|v| producer.outputs.iter().any(|o| o.name == v.name))
+        {
+            return Err(IrError::InvalidGraph(format!(
+                "{} uses value '{}' before {} defines it",
+                node.label(),
+                input.name,
+                producer.label()
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Returns an error if one of `nodes` or `outputs` uses an output of `node`,
+/// which is about to be removed.
+pub(crate) fn check_not_used(
+    nodes: &DoublyLinkedList<Node>,
+    outputs: &[Value],
+    node: &Node,
+) -> Result<()> {
+    for output in node.outputs.iter().filter(|v| !v.name.is_empty()) {
+        if let Some(consumer) = nodes.iter().find(|n| uses_value(n, &output.name)) {
+            return Err(IrError::InvalidGraph(format!(
+                "output '{}' of {} is used by {}",
+                output.name,
+                node.label(),
+                consumer.label()
+            )));
+        }
+        if outputs.iter().any(|v| v.name == output.name) {
+            return Err(IrError::InvalidGraph(format!(
+                "output '{}' of {} is a graph output",
+                output.name,
+                node.label()
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Returns an error if one of `outputs` is an output of `node`, which is
+/// about to be removed.
+pub(crate) fn check_not_used_by_outputs(outputs: &[Value], node: &Node) -> Result<()> {
+    match node
+        .outputs
+        .iter()
+        .find(|v| outputs.iter().any(|o| o.name == v.name))
+    {
+        Some(output) => Err(IrError::InvalidGraph(format!(
+            "output '{}' of {} is a graph output",
+            output.name,
+            node.label()
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Returns an error if replacing the uses of `old` with `name` in `nodes`
+/// would make a node use `name` before the node defining it.
+pub(crate) fn check_replacement<'a>(
+    nodes: impl Iterator<Item = &'a Node>,
+    old: &str,
+    name: &str,
+) -> Result<()> {
+    let mut consumer: Option<&Node> = None;
+    for node in nodes {
+        if consumer.is_none() && (uses_value(node, old) || uses_value(node, name)) {
+            consumer = Some(node);
+        }
+        if node.outputs.iter().any(|v| v.name == name) {
+            return match consumer {
+                Some(consumer) => Err(IrError::InvalidGraph(format!(
+                    "{} uses value '{}' before {} defines it",
+                    consumer.label(),
+                    name,
+                    node.label()
+                ))),
+                None => Ok(()),
+            };
+        }
+    }
+    Ok(())
+}
+
+/// Returns true if `node` or one of its subgraphs uses the value `name`.
+///
+/// A subgraph that defines its own value named `name` does not count as a use.
+fn uses_value(node: &Node, name: &str) -> bool {
+    if node.inputs.iter().any(|v| v.name == name) {
+        return true;
+    }
+    subgraphs(node).any(|graph| !graph.defines_value(name) && graph_uses_value(graph, name))
+}
+
+fn graph_uses_value(graph: &Graph, name: &str) -> bool {
+    graph.iter_nodes().any(|n| uses_value(n, name)) || graph.outputs.iter().any(|v| v.name == name)
+}
+
+/// Returns the subgraphs held by the attributes of `node`.
+pub(crate) fn subgraphs(node: &Node) -> impl Iterator<Item = &Graph> {
+    node.attributes
+        .values()
+        .filter_map(|attr| attr.as_value())
+        .flat_map(|attr| match &attr.value {
+            AttrValue::Graph(graph) => std::slice::from_ref(graph),
+            AttrValue::Graphs(graphs) => graphs.as_slice(),
+            _ => &[],
+        })
+        .map(|graph| graph.as_ref())
+}
+
+/// Replaces the uses of `old` in `node` and its subgraphs with `new`.
+pub(crate) fn replace_node_uses(node: &mut Node, old: &str, new: &Value) -> usize {
+    let mut count = 0;
+    for input in node.inputs.iter_mut().filter(|v| v.name == old) {
+        *input = new.clone();
+        count += 1;
+    }
+    for attr in node.attributes.values_mut() {
+        let graphs = match attr {
+            NodeAttr::Value(Attr {
+                value: AttrValue::Graph(graph),
+                ..
+            }) => std::slice::from_mut(graph),
+            NodeAttr::Value(Attr {
+                value: AttrValue::Graphs(graphs),
+                ..
+            }) => graphs.as_mut_slice(),
+            _ => continue,
+        };
+        for graph in graphs {
+            // Only copy-on-write subgraphs that actually use the value
+            if !graph.defines_value(old) && graph_uses_value(graph, old) {
+                count += Rc::make_mut(graph).replace_uses(old, new);
+            }
+        }
+    }
+    count
+}
+
 impl Default for Graph {
     fn default() -> Self {
         Self::new()
@@ -128,6 +433,55 @@ mod tests {
         assert_eq!(graph.num_nodes(), 2);
     }
 
+    #[test]
+    fn test_graph_insert() {
+        let mut graph = Graph::new();
+        graph.append(Node::new("Add"));
+        graph.append(Node::new("Mul"));
+
+        graph.insert_before(1, Node::new("Sub"));
+        graph.insert_after(2, Node::new("Div"));
+        graph.insert_after(0, Node::new("Neg"));
+
+        let op_types: Vec<_> = graph.iter_nodes().map(|n| n.op_type.as_str()).collect();
+        assert_eq!(op_types, vec!["Add", "Neg", "Sub", "Mul", "Div"]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Node index 2 is out of bounds for 2 nodes")]
+    fn test_graph_insert_out_of_bounds() {
+        let mut graph = Graph::new();
+        graph.append(Node::new("Add"));
+        graph.append(Node::new("Mul"));
+        graph.insert_after(2, Node::new("Sub"));
+    }
+
+    #[test]
+    fn test_graph_try_append() {
+        let mut graph = Graph::new();
+        graph.inputs.push(Value::new("x"));
+
+        let mut add = Node::new("Add");
+        add.add_input(Value::new("x"));
+        add.add_output(Value::new("y"));
+        assert!(graph.try_append(add).is_ok());
+        assert!(graph.defines_value("y"));
+
+        let mut mul = Node::new("Mul");
+        mul.name = Some("mul".to_string());
+        mul.add_output(Value::new("y"));
+        let err = graph.try_prepend(mul).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Invalid graph: Mul(mul) redefines value 'y'"
+        );
+
+        let mut relu = Node::new("Relu");
+        relu.add_output(Value::new("x"));
+        assert!(graph.try_append(relu).is_err());
+        assert_eq!(graph.num_nodes(), 1);
+    }
+
     #[test]
     fn test_graph_pop() {
         let mut graph = Graph::new();
@@ -171,4 +525,67 @@ mod tests {
 
         assert_eq!(op_types, vec!["Add", "Mul", "Sub"]);
     }
+
+    #[test]
+    fn test_graph_replace_all_uses_with() {
+        let mut branch = Graph::new();
+        let mut neg = Node::new("Neg");
+        neg.add_input(Value::new("y"));
+        neg.add_output(Value::new("b"));
+        branch.append(neg);
+        branch.outputs.push(Value::new("b"));
+
+        let mut graph = Graph::new();
+        let mut relu = Node::new("Relu");
+        relu.add_input(Value::new("y"));
+        relu.set_attribute(Attr::graph("body", branch));
+        graph.append(relu);
+        graph.outputs.push(Value::new("y"));
+
+        let mut new = Value::new("x");
+        new.doc_string = Some("replacement".to_string());
+        assert_eq!(graph.replace_all_uses_with("y", &new), 3);
+
+        let relu = graph.iter_nodes().next().unwrap();
+        assert_eq!(relu.inputs[0].doc_string.as_deref(), Some("replacement"));
+        assert_eq!(
+            relu.attr_graph("body")
+                .unwrap()
+                .iter_nodes()
+                .next()
+                .unwrap()
+                .inputs[0]
+                .name,
+            "x"
+        );
+        assert_eq!(graph.outputs[0].name, "x");
+    }
+
+    #[test]
+    fn test_graph_invariant_checks() {
+        let mut graph = Graph::new();
+        let mut relu = Node::new("Relu");
+        relu.add_input(Value::new("x"));
+        relu.add_output(Value::new("y"));
+        let mut neg = Node::new("Neg");
+        neg.add_input(Value::new("y"));
+        neg.add_output(Value::new("z"));
+        graph.append(relu.clone());
+        graph.append(neg.clone());
+
+        assert!(check_not_used(&graph.nodes, &graph.outputs, &relu).is_err());
+        assert!(check_not_used(&graph.nodes, &graph.outputs, &neg).is_ok());
+        assert!(check_inputs_not_produced(graph.iter_nodes(), &neg).is_err());
+        assert!(check_replacement(graph.iter_nodes(), "x", "y").is_err());
+        assert!(check_replacement(graph.iter_nodes(), "y", "y").is_ok());
+
+        graph.pop_first();
+        graph.append(relu);
+        assert_eq!(
+            check_replacement(graph.iter_nodes(), "y", "y")
+                .unwrap_err()
+                .to_string(),
+            "Invalid graph: Neg uses value 'y' before Relu defines it"
+        );
+    }
 }
@@ -40,21 +40,37 @@
 //! ## Module Organization
 //!
 //! - [`enums`]: ONNX data type and attribute type enumerations
+//! - [`element`]: Rust element types of tensor data
+//! - [`float`]: Conversions of low-precision floating point formats
+//! - [`error`]: Error type shared by fallible operations
 //! - [`metadata`]: Metadata storage for IR objects
 //! - [`name_authority`]: Name generation and uniqueness management
 //! - [`linked_list`]: Doubly-linked list for safe node container
 //! - [`shape`]: Shape and symbolic dimension types
+//! - [`dim_expr`]: Symbolic dimension expressions
 //! - [`tensor`]: Tensor protocols and implementations
 //! - [`types`]: ONNX type system (TensorType, OptionalType, etc.)
 //! - [`value`]: Value representation with usage tracking
 //! - [`node`]: Node with attributes and input/output management
 //! - [`graph`]: Graph container with mutation support
 //! - [`function`]: Function definition support
+//! - [`call_graph`]: Call graph of model-local functions
 //! - [`model`]: Top-level model container
+//! - [`checker`]: Model validation with structured diagnostics
+//! - [`schema`]: Operator schemas for the default domain and `ai.onnx.ml`
+//! - [`passes`]: Transformation passes such as function inlining and shape
+//!   inference
 //! - Serialization/deserialization to ONNX protobuf (planned)
 
 pub mod attribute;
+pub mod call_graph;
+mod cast;
+pub mod checker;
+pub mod dim_expr;
+pub mod element;
 pub mod enums;
+pub mod error;
+pub mod float;
 pub mod function;
 pub mod graph;
 pub mod linked_list;
@@ -62,26 +78,45 @@ pub mod metadata;
 pub mod model;
 pub mod name_authority;
 pub mod node;
+pub mod passes;
+pub mod schema;
 pub mod shape;
 pub mod tensor;
+#[cfg(test)]
+mod test_support;
 pub mod types;
 pub mod value;
 
 // Re-export commonly used types
-pub use attribute::{Attr, RefAttr};
+pub use attribute::{Attr, AttributeError, NodeAttr, RefAttr};
+pub use dim_expr::DimExpr;
+pub use element::Element;
 pub use enums::{AttributeType, DataType};
-pub use function::Function;
+pub use error::{IrError, Result};
+pub use function::{Function, FunctionId};
 pub use graph::{Graph, GraphView};
 pub use metadata::MetadataStore;
 pub use model::Model;
 pub use node::Node;
+pub use schema::{OpSchema, SchemaRegistry};
 pub use shape::{Shape, SymbolicDim};
-pub use tensor::{ExternalTensor, LazyTensor, PackedTensor, StringTensor, Tensor};
-pub use types::{OptionalType, SequenceType, SparseTensorType, TensorType};
+pub use tensor::{
+    ExternalTensor, LazyTensor, PackedTensor, SparseTensor, StringTensor, Tensor, TensorProvider,
+    TensorSliceMut,
+};
+pub use types::{OptionalType, SequenceType, SparseTensorType, TensorType, TypeKind, TypeProto};
 pub use value::Value;
 
 /// Version of the ONNX IR implementation
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
 
 /// Debug mode flag for additional validation
+///
+/// When set, graph mutations check the invariants of the graph and panic at
+/// the point where a mutation would corrupt it.
 pub static DEBUG: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+
+/// Returns true if [`DEBUG`] is set.
+pub(crate) fn debug_enabled() -> bool {
+    DEBUG.load(std::sync::atomic::Ordering::Relaxed)
+}
@@ -158,6 +158,29 @@ impl<T> DoublyLinkedList<T> {
         self.insert_before_link(first, new_link);
     }
 
+    /// Inserts an element at position `index`, before the element currently
+    /// at that position.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is greater than the length of the list.
+    pub fn insert(&mut self, index: usize, value: T) {
+        assert!(
+            index <= self.length,
+            "Index {} is out of bounds for length {}",
+            index,
+            self.length
+        );
+        let mut before = self.root.next.replace(None).unwrap();
+        self.root.next.set(Some(before.clone()));
+        for _ in 0..index {
+            let next = before.next.replace(None).unwrap();
+            before.next.set(Some(next.clone()));
+            before = next;
+        }
+        self.insert_before_link(before, LinkBox::new(value));
+    }
+
     /// Removes and returns the element at the back of the list.
     pub fn pop_back(&mut self) -> Option<T> {
         if self.is_empty() {
@@ -241,6 +264,18 @@ impl<T> DoublyLinkedList<T> {
         }
     }
 
+    /// Returns an iterator that allows modifying each element.
+    pub fn iter_mut(&mut self) -> IterMut<'_, T> {
+        let next = self.root.next.replace(None);
+        self.root.next.set(next.clone());
+
+        IterMut {
+            current: next,
+            root: self.root.clone(),
+            _marker: PhantomData,
+        }
+    }
+
     /// Helper to insert a link before another link.
     fn insert_before_link(&mut self, before: Rc<LinkBox<T>>, new_link: Rc<LinkBox<T>>) {
         let prev = before.prev.take().unwrap();
@@ -261,6 +296,16 @@ impl<T> Default for DoublyLinkedList<T> {
     }
 }
 
+impl<T: Clone> Clone for DoublyLinkedList<T> {
+    fn clone(&self) -> Self {
+        let mut list = Self::new();
+        for value in self.iter() {
+            list.push_back(value.clone());
+        }
+        list
+    }
+}
+
 impl<T: fmt::Debug> fmt::Debug for DoublyLinkedList<T> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_list().entries(self.iter()).finish()
@@ -303,6 +348,39 @@ impl<'a, T> Iterator for Iter<'a, T> {
     }
 }
 
+/// A mutable iterator over the elements of a `DoublyLinkedList`.
+pub struct IterMut<'a, T> {
+    current: Option<Rc<LinkBox<T>>>,
+    root: Rc<LinkBox<T>>,
+    _marker: PhantomData<&'a mut T>,
+}
+
+impl<'a, T> Iterator for IterMut<'a, T> {
+    type Item = &'a mut T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let current = self.current.clone()?;
+
+            if Rc::ptr_eq(&current, &self.root) {
+                return None;
+            }
+
+            let next = current.next.replace(None);
+            current.next.set(next.clone());
+            self.current = next;
+
+            if !current.is_erased() {
+                unsafe {
+                    // SAFETY: The list is mutably borrowed for 'a, so it cannot
+                    // be modified elsewhere, and each link is yielded only once
+                    return (*current.value.as_ptr()).as_mut();
+                }
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -352,6 +430,20 @@ mod tests {
         assert!(list.is_empty());
     }
 
+    #[test]
+    fn test_doubly_linked_list_insert() {
+        let mut list = DoublyLinkedList::new();
+
+        list.insert(0, 2);
+        list.insert(0, 1);
+        list.insert(2, 4);
+        list.insert(2, 3);
+
+        let collected: Vec<_> = list.iter().copied().collect();
+        assert_eq!(collected, vec![1, 2, 3, 4]);
+        assert_eq!(list.len(), 4);
+    }
+
     #[test]
     fn test_doubly_linked_list_iter() {
         let mut list = DoublyLinkedList::new();
@@ -364,6 +456,35 @@ mod tests {
         assert_eq!(collected, vec![1, 2, 3]);
     }
 
+    #[test]
+    fn test_doubly_linked_list_iter_mut() {
+        let mut list = DoublyLinkedList::new();
+
+        list.push_back(1);
+        list.push_back(2);
+        list.push_back(3);
+
+        for value in list.iter_mut() {
+            *value *= 10;
+        }
+
+        let collected: Vec<_> = list.iter().copied().collect();
+        assert_eq!(collected, vec![10, 20, 30]);
+    }
+
+    #[test]
+    fn test_doubly_linked_list_clone() {
+        let mut list = DoublyLinkedList::new();
+        list.push_back(1);
+        list.push_back(2);
+
+        let mut cloned = list.clone();
+        cloned.push_back(3);
+
+        assert_eq!(list.iter().copied().collect::<Vec<_>>(), vec![1, 2]);
+        assert_eq!(cloned.iter().copied().collect::<Vec<_>>(), vec![1, 2, 3]);
+    }
+
     #[test]
     fn test_doubly_linked_list_clear() {
         let mut list = DoublyLinkedList::new();
@@ -3,9 +3,13 @@
 
 //! Top-level model container.
 
-use crate::function::Function;
+use crate::checker::{self, Diagnostic};
+use crate::function::{Function, FunctionId};
 use crate::graph::Graph;
 use crate::metadata::MetadataStore;
+use crate::node::Node;
+use crate::schema::{validation, SchemaRegistry, ValidationError};
+use indexmap::IndexMap;
 use std::collections::HashMap;
 
 /// An ONNX model.
@@ -18,7 +22,7 @@ pub struct Model {
     pub domain: Option<String>,
     pub model_version: Option<i64>,
     pub doc_string: Option<String>,
-    pub functions: HashMap<String, Function>,
+    pub functions: IndexMap<FunctionId, Function>,
     pub opset_imports: HashMap<String, i32>,
     pub metadata_props: HashMap<String, String>,
     pub meta: MetadataStore,
@@ -34,7 +38,7 @@ impl Model {
             domain: None,
             model_version: None,
             doc_string: None,
-            functions: HashMap::new(),
+            functions: IndexMap::new(),
             opset_imports: HashMap::new(),
             metadata_props: HashMap::new(),
             meta: MetadataStore::new(),
@@ -42,15 +46,50 @@ impl Model {
     }
 
     /// Adds a function to the model.
-    pub fn add_function(&mut self, function: Function) {
-        let key = format!("{}::{}", function.domain, function.name);
-        self.functions.insert(key, function);
+    ///
+    /// Returns the function previously registered with the same identifier, if any.
+    pub fn add_function(&mut self, function: Function) -> Option<Function> {
+        self.functions.insert(function.id(), function)
     }
 
-    /// Gets a function by domain and name.
+    /// Gets a function by domain and name, without an overload.
     pub fn get_function(&self, domain: &str, name: &str) -> Option<&Function> {
-        let key = format!("{}::{}", domain, name);
-        self.functions.get(&key)
+        self.get_function_by_id(&FunctionId::new(domain, name, ""))
+    }
+
+    /// Gets a function by its identifier.
+    pub fn get_function_by_id(&self, id: &FunctionId) -> Option<&Function> {
+        self.functions.get(id)
+    }
+
+    /// Gets a mutable reference to a function by its identifier.
+    pub fn get_function_mut(&mut self, id: &FunctionId) -> Option<&mut Function> {
+        self.functions.get_mut(id)
+    }
+
+    /// Removes a function by its identifier, preserving the order of the others.
+    pub fn remove_function(&mut self, id: &FunctionId) -> Option<Function> {
+        self.functions.shift_remove(id)
+    }
+
+    /// Returns the function called by a node, if the node calls a model-local function.
+    pub fn function_for(&self, node: &Node) -> Option<&Function> {
+        self.functions.get(&node.function_id())
+    }
+
+    /// Checks the invariants of the model.
+    ///
+    /// Returns every problem found, or an empty list if the model is valid.
+    /// See [`checker`] for the checks performed.
+    pub fn check(&self) -> Vec<Diagnostic> {
+        checker::check_model(self)
+    }
+
+    /// Checks every node of the model against its operator schema.
+    ///
+    /// See [`validation::validate_model`].
+    pub fn validate_nodes(&self, registry: &SchemaRegistry) -> Vec<ValidationError> {
+        validation::validate_model(self, registry)
     }
 
     /// Sets an opset import.
@@ -102,4 +141,28 @@ mod tests {
         assert!(model.get_function("com.example", "MyFunc").is_some());
         assert!(model.get_function("com.example", "Unknown").is_none());
     }
+
+    #[test]
+    fn test_model_function_overloads() {
+        let mut model = Model::new(Graph::new());
+
+        let mut v1 = Function::new("MyFunc", "com.example");
+        v1.overload = "v1".to_string();
+        let mut v2 = Function::new("MyFunc", "com.example");
+        v2.overload = "v2".to_string();
+
+        assert!(model.add_function(v1).is_none());
+        assert!(model.add_function(v2).is_none());
+        assert_eq!(model.functions.len(), 2);
+        assert!(model.get_function("com.example", "MyFunc").is_none());
+
+        let mut node = Node::new("MyFunc");
+        node.domain = "com.example".to_string();
+        node.overload = "v2".to_string();
+        assert_eq!(model.function_for(&node).unwrap().overload, "v2");
+
+        let id = FunctionId::new("com.example", "MyFunc", "v1");
+        assert!(model.remove_function(&id).is_some());
+        assert!(model.get_function_by_id(&id).is_none());
+    }
 }
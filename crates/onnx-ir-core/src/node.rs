@@ -3,14 +3,20 @@
 
 //! Node representation with attributes and input/output management.
 
-use crate::attribute::Attr;
+use crate::attribute::{Attr, AttrValue, AttributeError, NodeAttr, RefAttr};
+use crate::enums::AttributeType;
+use crate::function::FunctionId;
+use crate::graph::Graph;
 use crate::metadata::MetadataStore;
+use crate::schema::{validation, SchemaRegistry, ValidationError};
+use crate::tensor::Tensor;
 use crate::value::Value;
 use indexmap::IndexMap;
 use std::collections::HashMap;
+use std::rc::Rc;
 
 /// A node represents an invocation of an operation.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Node {
     pub name: Option<String>,
     pub domain: String,
@@ -18,7 +24,7 @@ pub struct Node {
     pub overload: String,
     pub inputs: Vec<Value>,
     pub outputs: Vec<Value>,
-    pub attributes: IndexMap<String, Attr>,
+    pub attributes: IndexMap<String, NodeAttr>,
     pub version: Option<i32>,
     pub doc_string: Option<String>,
     pub metadata_props: HashMap<String, String>,
@@ -42,6 +48,27 @@ impl Node {
         }
     }
 
+    /// Creates a node with the given inputs and outputs.
+    ///
+    /// Inputs and outputs may be given as [`Value`]s or as value names.
+    pub fn with_io<I, O>(op_type: impl Into<String>, inputs: I, outputs: O) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<Value>,
+        O: IntoIterator,
+        O::Item: Into<Value>,
+    {
+        let mut node = Self::new(op_type);
+        node.inputs = inputs.into_iter().map(Into::into).collect();
+        node.outputs = outputs.into_iter().map(Into::into).collect();
+        node
+    }
+
+    /// Returns the identifier of the function this node would call.
+    pub fn function_id(&self) -> FunctionId {
+        FunctionId::new(&self.domain, &self.op_type, &self.overload)
+    }
+
     /// Adds an input value to the node.
     pub fn add_input(&mut self, value: Value) {
         self.inputs.push(value);
@@ -53,20 +80,343 @@ impl Node {
     }
 
     /// Sets an attribute on the node.
-    pub fn set_attribute(&mut self, attr: Attr) {
-        self.attributes.insert(attr.name.clone(), attr);
+    ///
+    /// Accepts either a concrete [`Attr`] or a [`RefAttr`].
+    pub fn set_attribute(&mut self, attr: impl Into<NodeAttr>) {
+        let attr = attr.into();
+        self.attributes.insert(attr.name().to_string(), attr);
     }
 
-    /// Gets an attribute by name.
+    /// Gets a concrete attribute by name.
+    ///
+    /// Returns `None` if the attribute is missing or is a reference.
     pub fn get_attribute(&self, name: &str) -> Option<&Attr> {
-        self.attributes.get(name)
+        self.attributes.get(name)?.as_value()
+    }
+
+    /// Gets a reference attribute by name.
+    pub fn get_ref_attribute(&self, name: &str) -> Option<&RefAttr> {
+        self.attributes.get(name)?.as_ref_attr()
     }
 
     /// Removes an attribute by name.
-    pub fn remove_attribute(&mut self, name: &str) -> Option<Attr> {
+    pub fn remove_attribute(&mut self, name: &str) -> Option<NodeAttr> {
         self.attributes.shift_remove(name)
     }
 
+    /// Checks the node against the schema of its operator in `registry`, at
+    /// the version imported for its domain in `opset_imports`.
+    ///
+    /// Returns every problem found, or an empty list if the node is valid.
+    pub fn validate(
+        &self,
+        registry: &SchemaRegistry,
+        opset_imports: &HashMap<String, i32>,
+    ) -> Vec<ValidationError> {
+        validation::validate_node(self, registry, opset_imports)
+    }
+
+    /// Returns a short description of the node for messages, e.g. `Conv(conv_3)`.
+    pub fn label(&self) -> String {
+        match &self.name {
+            Some(name) => format!("{}({})", self.op_type, name),
+            None => self.op_type.clone(),
+        }
+    }
+
+    /// Looks up a concrete attribute and checks its type.
+    ///
+    /// Returns `Ok(None)` if the attribute is not set.
+    fn typed_attribute(
+        &self,
+        name: &str,
+        expected: AttributeType,
+    ) -> Result<Option<&AttrValue>, AttributeError> {
+        match self.attributes.get(name) {
+            None => Ok(None),
+            Some(NodeAttr::Ref(ref_attr)) => Err(AttributeError::UnresolvedRef {
+                node: self.label(),
+                name: name.to_string(),
+                ref_attr_name: ref_attr.ref_attr_name.clone(),
+            }),
+            Some(NodeAttr::Value(attr)) if attr.attr_type() != expected => {
+                Err(AttributeError::TypeMismatch {
+                    node: self.label(),
+                    name: name.to_string(),
+                    expected,
+                    actual: attr.attr_type(),
+                })
+            }
+            Some(NodeAttr::Value(attr)) => Ok(Some(&attr.value)),
+        }
+    }
+
+    /// Looks up a required attribute and checks its type.
+    fn required_attribute(
+        &self,
+        name: &str,
+        expected: AttributeType,
+    ) -> Result<&AttrValue, AttributeError> {
+        self.typed_attribute(name, expected)?
+            .ok_or_else(|| AttributeError::Missing {
+                node: self.label(),
+                name: name.to_string(),
+            })
+    }
+
+    /// Returns the value of an `INT` attribute.
+    pub fn attr_int(&self, name: &str) -> Result<i64, AttributeError> {
+        match self.required_attribute(name, AttributeType::Int)? {
+            AttrValue::Int(v) => Ok(*v),
+            _ => unreachable!("attribute type was checked"),
+        }
+    }
+
+    /// Returns the value of an `INT` attribute, or `default` if it is not set.
+    pub fn attr_int_or(&self, name: &str, default: i64) -> Result<i64, AttributeError> {
+        match self.typed_attribute(name, AttributeType::Int)? {
+            Some(AttrValue::Int(v)) => Ok(*v),
+            _ => Ok(default),
+        }
+    }
+
+    /// Returns the value of an `INTS` attribute.
+    pub fn attr_ints(&self, name: &str) -> Result<&[i64], AttributeError> {
+        match self.required_attribute(name, AttributeType::Ints)? {
+            AttrValue::Ints(v) => Ok(v),
+            _ => unreachable!("attribute type was checked"),
+        }
+    }
+
+    /// Returns the value of an `INTS` attribute, or `default` if it is not set.
+    pub fn attr_ints_or<'a>(
+        &'a self,
+        name: &str,
+        default: &'a [i64],
+    ) -> Result<&'a [i64], AttributeError> {
+        match self.typed_attribute(name, AttributeType::Ints)? {
+            Some(AttrValue::Ints(v)) => Ok(v),
+            _ => Ok(default),
+        }
+    }
+
+    /// Returns the value of a `FLOAT` attribute.
+    pub fn attr_float(&self, name: &str) -> Result<f32, AttributeError> {
+        match self.required_attribute(name, AttributeType::Float)? {
+            AttrValue::Float(v) => Ok(*v),
+            _ => unreachable!("attribute type was checked"),
+        }
+    }
+
+    /// Returns the value of a `FLOAT` attribute, or `default` if it is not set.
+    pub fn attr_float_or(&self, name: &str, default: f32) -> Result<f32, AttributeError> {
+        match self.typed_attribute(name, AttributeType::Float)? {
+            Some(AttrValue::Float(v)) => Ok(*v),
+            _ => Ok(default),
+        }
+    }
+
+    /// Returns the value of a `FLOATS` attribute.
+    pub fn attr_floats(&self, name: &str) -> Result<&[f32], AttributeError> {
+        match self.required_attribute(name, AttributeType::Floats)? {
+            AttrValue::Floats(v) => Ok(v),
+            _ => unreachable!("attribute type was checked"),
+        }
+    }
+
+    /// Returns the value of a `FLOATS` attribute, or `default` if it is not set.
+    pub fn attr_floats_or<'a>(
+        &'a self,
+        name: &str,
+        default: &'a [f32],
+    ) -> Result<&'a [f32], AttributeError> {
+        match self.typed_attribute(name, AttributeType::Floats)? {
+            Some(AttrValue::Floats(v)) => Ok(v),
+            _ => Ok(default),
+        }
+    }
+
+    /// Decodes a string attribute value as UTF-8.
+    fn utf8<'a>(&self, name: &str, bytes: &'a [u8]) -> Result<&'a str, AttributeError> {
+        std::str::from_utf8(bytes).map_err(|_| AttributeError::InvalidUtf8 {
+            node: self.label(),
+            name: name.to_string(),
+        })
+    }
+
+    /// Returns the value of a `STRING` attribute as raw bytes.
+    pub fn attr_bytes(&self, name: &str) -> Result<&[u8], AttributeError> {
+        match self.required_attribute(name, AttributeType::String)? {
+            AttrValue::String(v) => Ok(v),
+            _ => unreachable!("attribute type was checked"),
+        }
+    }
+
+    /// Returns the value of a `STRING` attribute as raw bytes, or `default`
+    /// if it is not set.
+    pub fn attr_bytes_or<'a>(
+        &'a self,
+        name: &str,
+        default: &'a [u8],
+    ) -> Result<&'a [u8], AttributeError> {
+        match self.typed_attribute(name, AttributeType::String)? {
+            Some(AttrValue::String(v)) => Ok(v),
+            _ => Ok(default),
+        }
+    }
+
+    /// Returns the value of a `STRING` attribute decoded as UTF-8.
+    pub fn attr_string(&self, name: &str) -> Result<&str, AttributeError> {
+        self.utf8(name, self.attr_bytes(name)?)
+    }
+
+    /// Returns the value of a `STRING` attribute decoded as UTF-8, or
+    /// `default` if it is not set.
+    pub fn attr_string_or<'a>(
+        &'a self,
+        name: &str,
+        default: &'a str,
+    ) -> Result<&'a str, AttributeError> {
+        match self.typed_attribute(name, AttributeType::String)? {
+            Some(AttrValue::String(v)) => self.utf8(name, v),
+            _ => Ok(default),
+        }
+    }
+
+    /// Returns the value of a `STRINGS` attribute decoded as UTF-8.
+    pub fn attr_strings(&self, name: &str) -> Result<Vec<&str>, AttributeError> {
+        match self.required_attribute(name, AttributeType::Strings)? {
+            AttrValue::Strings(v) => v.iter().map(|s| self.utf8(name, s)).collect(),
+            _ => unreachable!("attribute type was checked"),
+        }
+    }
+
+    /// Returns the value of a `STRINGS` attribute decoded as UTF-8, or
+    /// `default` if it is not set.
+    pub fn attr_strings_or<'a>(
+        &'a self,
+        name: &str,
+        default: &[&'a str],
+    ) -> Result<Vec<&'a str>, AttributeError> {
+        match self.typed_attribute(name, AttributeType::Strings)? {
+            Some(AttrValue::Strings(v)) => v.iter().map(|s| self.utf8(name, s)).collect(),
+            _ => Ok(default.to_vec()),
+        }
+    }
+
+    /// Returns the value of a `TENSOR` attribute.
+    pub fn attr_tensor(&self, name: &str) -> Result<&Tensor, AttributeError> {
+        match self.required_attribute(name, AttributeType::Tensor)? {
+            AttrValue::Tensor(v) => Ok(v),
+            _ => unreachable!("attribute type was checked"),
+        }
+    }
+
+    /// Returns the value of a `TENSOR` attribute, or `default` if it is not set.
+    pub fn attr_tensor_or<'a>(
+        &'a self,
+        name: &str,
+        default: &'a Tensor,
+    ) -> Result<&'a Tensor, AttributeError> {
+        match self.typed_attribute(name, AttributeType::Tensor)? {
+            Some(AttrValue::Tensor(v)) => Ok(v),
+            _ => Ok(default),
+        }
+    }
+
+    /// Returns the value of a `GRAPH` attribute.
+    pub fn attr_graph(&self, name: &str) -> Result<&Rc<Graph>, AttributeError> {
+        match self.required_attribute(name, AttributeType::Graph)? {
+            AttrValue::Graph(v) => Ok(v),
+            _ => unreachable!("attribute type was checked"),
+        }
+    }
+
+    /// Returns the value of a `GRAPH` attribute, or `default` if it is not set.
+    pub fn attr_graph_or<'a>(
+        &'a self,
+        name: &str,
+        default: &'a Rc<Graph>,
+    ) -> Result<&'a Rc<Graph>, AttributeError> {
+        match self.typed_attribute(name, AttributeType::Graph)? {
+            Some(AttrValue::Graph(v)) => Ok(v),
+            _ => Ok(default),
+        }
+    }
+
+    /// Returns true if any attribute of the node is a reference.
+    pub fn has_ref_attributes(&self) -> bool {
+        self.attributes.values().any(NodeAttr::is_ref)
+    }
+
+    /// Returns the reference attributes of the node.
+    pub fn ref_attributes(&self) -> impl Iterator<Item = &RefAttr> {
+        self.attributes.values().filter_map(NodeAttr::as_ref_attr)
+    }
+
+    /// Resolves reference attributes against a map of bound attribute values.
+    ///
+    /// Every reference, including those on nodes in subgraphs, is replaced by
+    /// the value bound to its `ref_attr_name`. Following ONNX semantics,
+    /// references without a binding are removed from the node. So are
+    /// references bound to a value of another type than their `type_`. Both
+    /// are returned so the caller can report them.
+    pub fn resolve_ref_attributes(&mut self, bindings: &HashMap<String, Attr>) -> Vec<RefAttr> {
+        let mut unresolved = Vec::new();
+        self.attributes.retain(|_, attr| {
+            if !attr.is_ref() {
+                return true;
+            }
+            match attr.resolve(bindings) {
+                Some(resolved) => {
+                    *attr = NodeAttr::Value(resolved);
+                    true
+                }
+                None => {
+                    unresolved.extend(attr.as_ref_attr().cloned());
+                    false
+                }
+            }
+        });
+
+        for attr in self.attributes.values_mut() {
+            let NodeAttr::Value(attr) = attr else {
+                continue;
+            };
+            let graphs: Vec<&mut Rc<Graph>> = match &mut attr.value {
+                AttrValue::Graph(graph) => vec![graph],
+                AttrValue::Graphs(graphs) => graphs.iter_mut().collect(),
+                _ => continue,
+            };
+            for graph in graphs {
+                if graph.iter_nodes().any(|n| n.has_ref_attributes_recursive()) {
+                    let graph = Rc::make_mut(graph);
+                    for node in graph.iter_nodes_mut() {
+                        unresolved.extend(node.resolve_ref_attributes(bindings));
+                    }
+                }
+            }
+        }
+        unresolved
+    }
+
+    /// Returns true if the node or a node in one of its subgraphs has a reference attribute.
+    fn has_ref_attributes_recursive(&self) -> bool {
+        self.attributes.values().any(|attr| match attr {
+            NodeAttr::Ref(_) => true,
+            NodeAttr::Value(attr) => match &attr.value {
+                AttrValue::Graph(graph) => {
+                    graph.iter_nodes().any(Node::has_ref_attributes_recursive)
+                }
+                AttrValue::Graphs(graphs) => graphs
+                    .iter()
+                    .flat_map(|g| g.iter_nodes())
+                    .any(Node::has_ref_attributes_recursive),
+                _ => false,
+            },
+        })
+    }
+
     /// Returns the number of inputs.
     pub fn num_inputs(&self) -> usize {
         self.inputs.len()
@@ -87,6 +437,9 @@ impl Node {
 mod tests {
     use super::*;
     use crate::attribute::Attr;
+    use crate::enums::DataType;
+    use crate::shape::Shape;
+    use crate::tensor::TensorProtocol;
 
     #[test]
     fn test_node_new() {
@@ -107,6 +460,18 @@ mod tests {
         assert_eq!(node.num_outputs(), 1);
     }
 
+    #[test]
+    fn test_node_with_io() {
+        let mut y = Value::new("y");
+        y.type_ = Some(crate::types::TensorType::new(DataType::Float));
+        let node = Node::with_io("Add", ["x", "x"], vec![y]);
+
+        assert_eq!(node.op_type, "Add");
+        assert_eq!(node.num_inputs(), 2);
+        assert_eq!(node.inputs[1].name, "x");
+        assert!(node.outputs[0].type_.is_some());
+    }
+
     #[test]
     fn test_node_attributes() {
         let mut node = Node::new("Conv");
@@ -126,4 +491,143 @@ mod tests {
         assert!(removed.is_some());
         assert_eq!(node.attributes.len(), 1);
     }
+
+    #[test]
+    fn test_node_ref_attributes() {
+        let mut node = Node::new("LeakyRelu");
+        node.set_attribute(RefAttr::new("alpha", "slope", AttributeType::Float));
+
+        assert!(node.get_attribute("alpha").is_none());
+        let ref_attr = node.get_ref_attribute("alpha").unwrap();
+        assert_eq!(ref_attr.ref_attr_name, "slope");
+        assert!(node.has_ref_attributes());
+        assert_eq!(node.ref_attributes().count(), 1);
+    }
+
+    #[test]
+    fn test_node_resolve_ref_attributes() {
+        let mut node = Node::new("Gemm");
+        node.set_attribute(RefAttr::new("alpha", "a", AttributeType::Float));
+        node.set_attribute(RefAttr::new("beta", "b", AttributeType::Float));
+        node.set_attribute(RefAttr::new("transB", "t", AttributeType::Int));
+        node.set_attribute(Attr::int("transA", 1));
+
+        let mut bindings = HashMap::new();
+        bindings.insert("a".to_string(), Attr::float("a", 2.0));
+        bindings.insert("t".to_string(), Attr::string("t", "yes"));
+
+        let unresolved = node.resolve_ref_attributes(&bindings);
+        let names: Vec<_> = unresolved.iter().map(|a| a.name.as_str()).collect();
+        assert_eq!(names, vec!["beta", "transB"]);
+        assert!(!node.has_ref_attributes());
+        assert!(node.get_attribute("alpha").is_some());
+        assert!(node.get_attribute("beta").is_none());
+        assert!(node.get_attribute("transB").is_none());
+        assert!(node.get_attribute("transA").is_some());
+    }
+
+    #[test]
+    fn test_node_resolve_ref_attributes_in_subgraph() {
+        let mut inner = Node::new("LeakyRelu");
+        inner.set_attribute(RefAttr::new("alpha", "slope", AttributeType::Float));
+        let mut branch = Graph::new();
+        branch.append(inner);
+
+        let mut node = Node::new("If");
+        node.set_attribute(Attr::new("then_branch", AttrValue::Graph(Rc::new(branch))));
+
+        let mut bindings = HashMap::new();
+        bindings.insert("slope".to_string(), Attr::float("slope", 0.1));
+        assert!(node.resolve_ref_attributes(&bindings).is_empty());
+
+        let branch = match &node.get_attribute("then_branch").unwrap().value {
+            AttrValue::Graph(g) => g.clone(),
+            _ => panic!("Expected graph value"),
+        };
+        assert!(branch
+            .iter_nodes()
+            .next()
+            .unwrap()
+            .get_attribute("alpha")
+            .is_some());
+    }
+
+    #[test]
+    fn test_node_typed_attributes() {
+        let mut node = Node::new("Conv");
+        node.set_attribute(Attr::int("group", 2));
+        node.set_attribute(Attr::ints("pads", vec![1, 1, 1, 1]));
+        node.set_attribute(Attr::float("alpha", 0.5));
+        node.set_attribute(Attr::string("auto_pad", "NOTSET".to_string()));
+        node.set_attribute(Attr::strings("names", vec!["x", "w"]));
+        let tensor = Tensor::new(DataType::Float, Shape::new(vec![2]));
+        node.set_attribute(Attr::new("value", AttrValue::Tensor(Box::new(tensor))));
+
+        assert_eq!(node.attr_int("group"), Ok(2));
+        assert_eq!(node.attr_ints("pads"), Ok(&[1, 1, 1, 1][..]));
+        assert_eq!(node.attr_float("alpha"), Ok(0.5));
+        assert_eq!(node.attr_string("auto_pad"), Ok("NOTSET"));
+        assert_eq!(node.attr_strings("names"), Ok(vec!["x", "w"]));
+        assert_eq!(node.attr_tensor("value").unwrap().size(), 2);
+
+        assert_eq!(node.attr_int_or("axis", -1), Ok(-1));
+        assert_eq!(node.attr_int_or("group", 1), Ok(2));
+        assert_eq!(node.attr_ints_or("strides", &[1, 1]), Ok(&[1, 1][..]));
+        assert_eq!(node.attr_float_or("beta", 1.0), Ok(1.0));
+        assert_eq!(node.attr_string_or("mode", "constant"), Ok("constant"));
+        assert_eq!(node.attr_bytes_or("auto_pad", b""), Ok(&b"NOTSET"[..]));
+        assert_eq!(
+            node.attr_bytes_or("mode", b"constant"),
+            Ok(&b"constant"[..])
+        );
+        assert_eq!(node.attr_strings_or("names", &[]), Ok(vec!["x", "w"]));
+        assert_eq!(node.attr_strings_or("labels", &["a"]), Ok(vec!["a"]));
+    }
+
+    #[test]
+    fn test_node_typed_attribute_errors() {
+        let mut node = Node::new("Conv");
+        node.name = Some("node_conv_3".to_string());
+        node.set_attribute(Attr::int("pads", 1));
+        node.set_attribute(RefAttr::new("group", "g", AttributeType::Int));
+
+        let err = node.attr_ints("pads").unwrap_err();
+        assert_eq!(
+            err,
+            AttributeError::TypeMismatch {
+                node: "Conv(node_conv_3)".to_string(),
+                name: "pads".to_string(),
+                expected: AttributeType::Ints,
+                actual: AttributeType::Int,
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "Conv(node_conv_3): attribute 'pads' expects INTS, got INT"
+        );
+
+        // A default does not hide a type mismatch
+        assert!(node.attr_ints_or("pads", &[0]).is_err());
+
+        assert!(matches!(
+            node.attr_float("alpha"),
+            Err(AttributeError::Missing { .. })
+        ));
+
+        node.set_attribute(Attr::string("mode", vec![0xff, 0xfe]));
+        assert_eq!(node.attr_bytes("mode"), Ok(&[0xff, 0xfe][..]));
+        assert!(matches!(
+            node.attr_string("mode"),
+            Err(AttributeError::InvalidUtf8 { .. })
+        ));
+        node.set_attribute(Attr::strings("labels", vec![vec![0xff]]));
+        assert!(matches!(
+            node.attr_strings_or("labels", &[]),
+            Err(AttributeError::InvalidUtf8 { .. })
+        ));
+        assert!(matches!(
+            node.attr_int_or("group", 1),
+            Err(AttributeError::UnresolvedRef { .. })
+        ));
+    }
 }
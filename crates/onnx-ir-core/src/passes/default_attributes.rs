@@ -0,0 +1,501 @@
+// Copyright (c) ONNX Project Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Filling in and stripping default attribute values.
+//!
+//! Two models can express the same node with or without attributes that
+//! equal their defaults, e.g. `Gemm` with or without `alpha=1.0`. These
+//! passes bring nodes to one of the two forms so that rewrites can match
+//! them without caring how the model was written.
+
+use super::{Pass, PassError, PassResult};
+use crate::attribute::{Attr, AttrValue};
+use crate::function::FunctionId;
+use crate::graph::{subgraphs, Graph};
+use crate::linked_list::DoublyLinkedList;
+use crate::model::Model;
+use crate::node::Node;
+use crate::schema::{OpSchema, SchemaRegistry};
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+/// Sets every attribute that a node omits to its default value.
+///
+/// Besides the defaults in the schemas, the per-axis attributes of
+/// convolution and pooling operators (`strides`, `dilations` and `pads`)
+/// are filled in once the number of spatial axes is known from
+/// `kernel_shape` or the weight shape. Nodes whose operator is not in the
+/// registry are left unchanged and listed by
+/// [`unknown_nodes`](Self::unknown_nodes).
+#[derive(Debug)]
+pub struct FillDefaultAttributesPass<'r> {
+    registry: &'r SchemaRegistry,
+    unknown_nodes: Vec<String>,
+}
+
+impl<'r> FillDefaultAttributesPass<'r> {
+    pub fn new(registry: &'r SchemaRegistry) -> Self {
+        Self {
+            registry,
+            unknown_nodes: Vec::new(),
+        }
+    }
+
+    /// Returns the labels of the nodes left unchanged by the last run
+    /// because their operator has no schema in the registry.
+    ///
+    /// Calls to functions of the model are not included.
+    pub fn unknown_nodes(&self) -> &[String] {
+        &self.unknown_nodes
+    }
+}
+
+impl Pass for FillDefaultAttributesPass<'_> {
+    fn run(&mut self, model: &mut Model) -> Result<PassResult, PassError> {
+        let mut rewriter = Rewriter::new(model, self.registry, missing_defaults);
+        let modified = rewriter.rewrite_model(model);
+        self.unknown_nodes = rewriter.unknown_nodes;
+        Ok(PassResult::new(modified))
+    }
+}
+
+/// Removes every attribute whose value equals its default.
+///
+/// This is the reverse of [`FillDefaultAttributesPass`]. Nodes whose
+/// operator is not in the registry are left unchanged and listed by
+/// [`unknown_nodes`](Self::unknown_nodes).
+#[derive(Debug)]
+pub struct StripDefaultAttributesPass<'r> {
+    registry: &'r SchemaRegistry,
+    unknown_nodes: Vec<String>,
+}
+
+impl<'r> StripDefaultAttributesPass<'r> {
+    pub fn new(registry: &'r SchemaRegistry) -> Self {
+        Self {
+            registry,
+            unknown_nodes: Vec::new(),
+        }
+    }
+
+    /// Returns the labels of the nodes left unchanged by the last run
+    /// because their operator has no schema in the registry.
+    ///
+    /// Calls to functions of the model are not included.
+    pub fn unknown_nodes(&self) -> &[String] {
+        &self.unknown_nodes
+    }
+}
+
+impl Pass for StripDefaultAttributesPass<'_> {
+    fn run(&mut self, model: &mut Model) -> Result<PassResult, PassError> {
+        let mut rewriter = Rewriter::new(model, self.registry, redundant_attributes);
+        let modified = rewriter.rewrite_model(model);
+        self.unknown_nodes = rewriter.unknown_nodes;
+        Ok(PassResult::new(modified))
+    }
+}
+
+/// Sets the attributes that `node` omits to their defaults in `schema`.
+///
+/// Returns true if any attribute was added.
+pub fn fill_default_attributes(node: &mut Node, schema: &OpSchema) -> bool {
+    missing_defaults(node, schema).apply(node)
+}
+
+/// Removes the attributes of `node` that equal their defaults in `schema`.
+///
+/// Returns true if any attribute was removed.
+pub fn strip_default_attributes(node: &mut Node, schema: &OpSchema) -> bool {
+    redundant_attributes(node, schema).apply(node)
+}
+
+/// Attributes to add to and remove from a node.
+#[derive(Debug, Default)]
+struct AttributeEdit {
+    set: Vec<Attr>,
+    remove: Vec<String>,
+}
+
+impl AttributeEdit {
+    fn is_empty(&self) -> bool {
+        self.set.is_empty() && self.remove.is_empty()
+    }
+
+    /// Applies the edit, returning true if it changed `node`.
+    fn apply(self, node: &mut Node) -> bool {
+        let modified = !self.is_empty();
+        for attr in self.set {
+            node.set_attribute(attr);
+        }
+        for name in &self.remove {
+            node.remove_attribute(name);
+        }
+        modified
+    }
+}
+
+/// Returns the defaults in `schema` of the attributes that `node` omits.
+fn missing_defaults(node: &Node, schema: &OpSchema) -> AttributeEdit {
+    let mut edit = AttributeEdit::default();
+    let defaults = schema
+        .attributes
+        .values()
+        .filter_map(|spec| spec.default.clone())
+        .chain(spatial_defaults(node, schema));
+    for default in defaults {
+        if !node.attributes.contains_key(&default.name)
+            && !edit.set.iter().any(|attr| attr.name == default.name)
+        {
+            edit.set.push(default);
+        }
+    }
+    edit
+}
+
+/// Returns the attributes of `node` that equal their defaults in `schema`.
+fn redundant_attributes(node: &Node, schema: &OpSchema) -> AttributeEdit {
+    let defaults: Vec<Attr> = schema
+        .attributes
+        .values()
+        .filter_map(|spec| spec.default.clone())
+        .chain(spatial_defaults(node, schema))
+        .collect();
+    let remove = node
+        .attributes
+        .iter()
+        .filter(|(name, attr)| {
+            let default = defaults.iter().find(|d| &d.name == *name);
+            match (attr.as_value(), default) {
+                (Some(attr), Some(default)) => same_value(&attr.value, &default.value),
+                _ => false,
+            }
+        })
+        .map(|(name, _)| name.clone())
+        .collect();
+    AttributeEdit {
+        set: Vec::new(),
+        remove,
+    }
+}
+
+/// Returns the defaults of the per-axis attributes of convolution and
+/// pooling operators, which depend on the number of spatial axes.
+fn spatial_defaults(node: &Node, schema: &OpSchema) -> Vec<Attr> {
+    if !schema.domain.is_empty()
+        || !matches!(
+            schema.name.as_str(),
+            "Conv" | "ConvTranspose" | "MaxPool" | "AveragePool" | "LpPool"
+        )
+    {
+        return Vec::new();
+    }
+    let rank = match node.attr_ints("kernel_shape") {
+        Ok(kernel_shape) => kernel_shape.len(),
+        // Convolutions may infer the kernel shape from the weights
+        Err(_) => match node.inputs.get(1).and_then(|w| w.shape.as_ref()) {
+            Some(shape) if shape.rank() >= 2 => shape.rank() - 2,
+            _ => return Vec::new(),
+        },
+    };
+
+    let mut defaults = Vec::new();
+    for name in ["strides", "dilations"] {
+        if schema.attribute(name).is_some() {
+            defaults.push(Attr::ints(name, vec![1; rank]));
+        }
+    }
+    let explicit_pads = match node.attr_string("auto_pad") {
+        Ok(auto_pad) => auto_pad == "NOTSET",
+        Err(_) => true,
+    };
+    if explicit_pads && schema.attribute("pads").is_some() {
+        defaults.push(Attr::ints("pads", vec![0; 2 * rank]));
+    }
+    defaults
+}
+
+/// Compares attribute values that can be schema defaults.
+fn same_value(a: &AttrValue, b: &AttrValue) -> bool {
+    match (a, b) {
+        (AttrValue::Float(a), AttrValue::Float(b)) => a == b,
+        (AttrValue::Int(a), AttrValue::Int(b)) => a == b,
+        (AttrValue::String(a), AttrValue::String(b)) => a == b,
+        (AttrValue::Floats(a), AttrValue::Floats(b)) => a == b,
+        (AttrValue::Ints(a), AttrValue::Ints(b)) => a == b,
+        (AttrValue::Strings(a), AttrValue::Strings(b)) => a == b,
+        _ => false,
+    }
+}
+
+/// Computes the edit of a node with a schema.
+type Rewrite = fn(&Node, &OpSchema) -> AttributeEdit;
+
+/// Applies a rewrite to every node of a model whose operator has a schema.
+struct Rewriter<'r> {
+    registry: &'r SchemaRegistry,
+    rewrite: Rewrite,
+    /// The functions of the model, which have no schema.
+    functions: HashSet<FunctionId>,
+    unknown_nodes: Vec<String>,
+}
+
+impl<'r> Rewriter<'r> {
+    fn new(model: &Model, registry: &'r SchemaRegistry, rewrite: Rewrite) -> Self {
+        Self {
+            registry,
+            rewrite,
+            functions: model.functions.keys().cloned().collect(),
+            unknown_nodes: Vec::new(),
+        }
+    }
+
+    fn rewrite_model(&mut self, model: &mut Model) -> bool {
+        let mut modified = self.rewrite_graph(&mut model.graph, &model.opset_imports);
+        for function in model.functions.values_mut() {
+            modified |= self.rewrite_nodes(&mut function.nodes, &function.opset_imports);
+        }
+        modified
+    }
+
+    fn rewrite_graph(&mut self, graph: &mut Graph, outer_imports: &HashMap<String, i32>) -> bool {
+        let opset_imports = scoped_imports(graph, outer_imports);
+        self.rewrite_nodes(&mut graph.nodes, &opset_imports)
+    }
+
+    fn rewrite_nodes(
+        &mut self,
+        nodes: &mut DoublyLinkedList<Node>,
+        opset_imports: &HashMap<String, i32>,
+    ) -> bool {
+        let mut modified = false;
+        for node in nodes.iter_mut() {
+            match self.registry.schema_for(node, opset_imports) {
+                Some(schema) => modified |= (self.rewrite)(node, schema).apply(node),
+                None if !self.functions.contains(&node.function_id()) => {
+                    self.unknown_nodes.push(node.label());
+                }
+                None => {}
+            }
+            for attr in node.attributes.values_mut() {
+                let graphs = match attr.as_value_mut() {
+                    Some(Attr {
+                        value: AttrValue::Graph(graph),
+                        ..
+                    }) => std::slice::from_mut(graph),
+                    Some(Attr {
+                        value: AttrValue::Graphs(graphs),
+                        ..
+                    }) => graphs.as_mut_slice(),
+                    _ => continue,
+                };
+                for graph in graphs {
+                    modified |= self.rewrite_subgraph(graph, opset_imports);
+                }
+            }
+        }
+        modified
+    }
+
+    fn rewrite_subgraph(
+        &mut self,
+        graph: &mut Rc<Graph>,
+        outer_imports: &HashMap<String, i32>,
+    ) -> bool {
+        // Subgraphs may be shared, so a shared one is only copied if it changes
+        if Rc::get_mut(graph).is_none() {
+            let mut unknown_nodes = Vec::new();
+            if !self.changes_graph(graph, outer_imports, &mut unknown_nodes) {
+                self.unknown_nodes.extend(unknown_nodes);
+                return false;
+            }
+        }
+        self.rewrite_graph(Rc::make_mut(graph), outer_imports)
+    }
+
+    /// Returns true if the rewrite would change `graph`, and collects the
+    /// labels of its nodes without a schema in `unknown_nodes`.
+    fn changes_graph(
+        &self,
+        graph: &Graph,
+        outer_imports: &HashMap<String, i32>,
+        unknown_nodes: &mut Vec<String>,
+    ) -> bool {
+        let opset_imports = scoped_imports(graph, outer_imports);
+        let mut changes = false;
+        for node in graph.iter_nodes() {
+            match self.registry.schema_for(node, &opset_imports) {
+                Some(schema) => changes |= !(self.rewrite)(node, schema).is_empty(),
+                None if !self.functions.contains(&node.function_id()) => {
+                    unknown_nodes.push(node.label());
+                }
+                None => {}
+            }
+            for graph in subgraphs(node) {
+                changes |= self.changes_graph(graph, &opset_imports, unknown_nodes);
+            }
+        }
+        changes
+    }
+}
+
+/// Returns the opset imports in scope in `graph`.
+fn scoped_imports(graph: &Graph, outer_imports: &HashMap<String, i32>) -> HashMap<String, i32> {
+    let mut opset_imports = outer_imports.clone();
+    opset_imports.extend(graph.opset_imports.iter().map(|(d, v)| (d.clone(), *v)));
+    opset_imports
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shape::Shape;
+    use crate::test_support::{graph, model};
+    use crate::value::Value;
+
+    fn attribute_names(model: &Model) -> Vec<Vec<String>> {
+        model
+            .graph
+            .iter_nodes()
+            .map(|n| n.attributes.keys().cloned().collect())
+            .collect()
+    }
+
+    #[test]
+    fn test_fill_defaults() {
+        let mut gemm = Node::new("Gemm");
+        gemm.set_attribute(Attr::float("alpha", 2.0));
+
+        let mut conv = Node::new("Conv");
+        conv.add_input(Value::new("x"));
+        let mut w = Value::new("w");
+        w.shape = Some(Shape::new(vec![8, 3, 3, 3]));
+        conv.add_input(w);
+
+        let registry = SchemaRegistry::builtin();
+        let mut model = model(graph(vec![gemm, conv, Node::new("Unknown")], &[], &[]), 18);
+        let mut pass = FillDefaultAttributesPass::new(&registry);
+        let result = pass.run(&mut model).unwrap();
+        assert!(result.modified);
+        assert_eq!(pass.unknown_nodes(), &["Unknown".to_string()]);
+
+        let gemm = model.graph.iter_nodes().next().unwrap();
+        assert_eq!(gemm.attr_float("alpha").unwrap(), 2.0);
+        assert_eq!(gemm.attr_float("beta").unwrap(), 1.0);
+        assert_eq!(gemm.attr_int("transB").unwrap(), 0);
+
+        let conv = model.graph.iter_nodes().nth(1).unwrap();
+        assert_eq!(conv.attr_int("group").unwrap(), 1);
+        assert_eq!(conv.attr_ints("strides").unwrap(), &[1, 1]);
+        assert_eq!(conv.attr_ints("dilations").unwrap(), &[1, 1]);
+        assert_eq!(conv.attr_ints("pads").unwrap(), &[0, 0, 0, 0]);
+        assert_eq!(conv.attr_string("auto_pad").unwrap(), "NOTSET");
+        assert!(conv.get_attribute("kernel_shape").is_none());
+        assert!(attribute_names(&model)[2].is_empty());
+
+        let result = FillDefaultAttributesPass::new(&registry)
+            .run(&mut model)
+            .unwrap();
+        assert!(!result.modified);
+    }
+
+    #[test]
+    fn test_strip_defaults() {
+        let mut gemm = Node::new("Gemm");
+        gemm.set_attribute(Attr::float("alpha", 1.0));
+        gemm.set_attribute(Attr::float("beta", 0.5));
+
+        let mut pool = Node::new("MaxPool");
+        pool.set_attribute(Attr::ints("kernel_shape", vec![2, 2]));
+        pool.set_attribute(Attr::ints("strides", vec![1, 1]));
+        pool.set_attribute(Attr::ints("pads", vec![0, 0, 1, 1]));
+
+        let registry = SchemaRegistry::builtin();
+        let mut model = model(graph(vec![gemm, pool], &[], &[]), 18);
+        let result = StripDefaultAttributesPass::new(&registry)
+            .run(&mut model)
+            .unwrap();
+        assert!(result.modified);
+        assert_eq!(
+            attribute_names(&model),
+            vec![vec!["beta"], vec!["kernel_shape", "pads"]]
+        );
+    }
+
+    #[test]
+    fn test_round_trip_in_subgraphs_and_functions() {
+        let mut branch = Graph::new();
+        branch.append(Node::new("LeakyRelu"));
+        let mut if_node = Node::new("If");
+        if_node.set_attribute(Attr::graph("then_branch", branch));
+
+        let mut function = crate::function::Function::new("F", "custom");
+        function.set_opset_import("", 18);
+        function.append(Node::new("Softmax"));
+
+        let registry = SchemaRegistry::builtin();
+        let mut model = model(graph(vec![if_node], &[], &[]), 18);
+        model.add_function(function);
+        let mut call = Node::new("F");
+        call.domain = "custom".to_string();
+        model.graph.append(call);
+
+        let if_node = model.graph.iter_nodes().next().unwrap();
+        let shared = if_node.attr_graph("then_branch").unwrap().clone();
+        let mut pass = FillDefaultAttributesPass::new(&registry);
+        pass.run(&mut model).unwrap();
+        assert!(pass.unknown_nodes().is_empty());
+        // Another owner of the subgraph still sees it unchanged
+        assert!(shared.iter_nodes().next().unwrap().attributes.is_empty());
+
+        // Subgraphs that need no change stay shared
+        let if_node = model.graph.iter_nodes().next().unwrap();
+        let shared = if_node.attr_graph("then_branch").unwrap().clone();
+        assert!(!pass.run(&mut model).unwrap().modified);
+        let if_node = model.graph.iter_nodes().next().unwrap();
+        assert!(Rc::ptr_eq(
+            if_node.attr_graph("then_branch").unwrap(),
+            &shared
+        ));
+
+        let if_node = model.graph.iter_nodes().next().unwrap();
+        let leaky_relu = if_node
+            .attr_graph("then_branch")
+            .unwrap()
+            .iter_nodes()
+            .next()
+            .unwrap();
+        assert_eq!(leaky_relu.attr_float("alpha").unwrap(), 0.01);
+        let softmax = model
+            .functions
+            .values()
+            .next()
+            .unwrap()
+            .iter_nodes()
+            .next()
+            .unwrap();
+        assert_eq!(softmax.attr_int("axis").unwrap(), -1);
+
+        // Subgraphs with no other owner are changed in place
+        drop(shared);
+        let if_node = model.graph.iter_nodes().next().unwrap();
+        let branch = Rc::as_ptr(if_node.attr_graph("then_branch").unwrap());
+        StripDefaultAttributesPass::new(&registry)
+            .run(&mut model)
+            .unwrap();
+        let if_node = model.graph.iter_nodes().next().unwrap();
+        let then_branch = if_node.attr_graph("then_branch").unwrap();
+        assert_eq!(Rc::as_ptr(then_branch), branch);
+        let leaky_relu = then_branch.iter_nodes().next().unwrap();
+        assert!(leaky_relu.attributes.is_empty());
+        let softmax = model
+            .functions
+            .values()
+            .next()
+            .unwrap()
+            .iter_nodes()
+            .next()
+            .unwrap();
+        assert!(softmax.attributes.is_empty());
+    }
+}
@@ -0,0 +1,910 @@
+// Copyright (c) ONNX Project Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Equality constraints between symbolic dimensions.
+//!
+//! Exporters often give different names to what is the same dimension,
+//! e.g. `batch` on the graph input and `unk__123` on an intermediate value.
+//! [`DimClasses`] collects the equalities that operators imply between
+//! dimensions across a graph and groups the dimensions into equivalence
+//! classes. [`UnifyDimsPass`] then renames every dimension to the canonical
+//! member of its class.
+
+use super::{Pass, PassError, PassResult};
+use crate::attribute::{Attr, AttrValue};
+use crate::graph::Graph;
+use crate::model::Model;
+use crate::node::Node;
+use crate::shape::{Shape, SymbolicDim};
+use crate::value::Value;
+use indexmap::IndexMap;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use thiserror::Error;
+
+/// Two constraints on a dimension contradict each other.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("dimension {dim} cannot be both {first} and {second}")]
+pub struct DimConflict {
+    pub dim: String,
+    pub first: i64,
+    pub second: i64,
+}
+
+/// Equivalence classes of symbolic dimensions.
+///
+/// Named symbols and expressions are the members of the classes. A class
+/// may also be bound to an integer, in which case all of its members equal
+/// that integer. Unknown dimensions carry no information and are ignored.
+#[derive(Debug, Clone, Default)]
+pub struct DimClasses {
+    /// The members in the order they were first seen.
+    members: IndexMap<String, SymbolicDim>,
+    /// The parent of each member in the union-find forest. Lookups shorten
+    /// the paths to the roots, hence the cell.
+    parent: RefCell<HashMap<String, String>>,
+    /// An upper bound on the height of each tree, keyed by its root.
+    ranks: HashMap<String, u32>,
+    /// The canonical member of each class, keyed by the root of the class.
+    canonical: HashMap<String, String>,
+    /// The integer each class is bound to, keyed by the root of the class.
+    values: HashMap<String, i64>,
+}
+
+impl DimClasses {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Collects the constraints implied by the nodes of `graph` and its
+    /// subgraphs.
+    ///
+    /// Every copy of a value must have the same shape, and the operators
+    /// below relate the dimensions of their inputs and outputs:
+    ///
+    /// - elementwise operators keep the shape of their input
+    /// - broadcasting operators such as `Add` or `Where` equate an output
+    ///   dimension with the input dimension it comes from, when all the
+    ///   other aligned input dimensions are 1, or with an integer other than
+    ///   1 among them
+    /// - `MatMul` and `Gemm` equate the inner dimensions, and broadcast the
+    ///   batch dimensions
+    /// - `Concat` equates the dimensions off the axis, and the output
+    ///   dimension on the axis with the sum of the input dimensions
+    pub fn from_graph(graph: &Graph) -> Result<Self, PassError> {
+        Self::from_graph_with(graph, false)
+    }
+
+    /// Collects the constraints like [`DimClasses::from_graph`], and if
+    /// `broadcast_equal` is true, also assumes that symbolic dimensions that
+    /// broadcast against each other are equal rather than 1.
+    ///
+    /// The assumption holds in most models and finds many more equalities,
+    /// but makes a graph that broadcasts a dimension of size 1 against
+    /// another one look inconsistent.
+    pub fn from_graph_with(graph: &Graph, broadcast_equal: bool) -> Result<Self, PassError> {
+        let mut collector = Collector {
+            classes: Self::new(),
+            shapes: HashMap::new(),
+            broadcast_equal,
+        };
+        collector.graph(graph)?;
+        Ok(collector.classes)
+    }
+
+    fn key(dim: &SymbolicDim) -> Option<String> {
+        match dim {
+            SymbolicDim::Symbol(Some(name)) => Some(name.clone()),
+            SymbolicDim::Expr(expr) => Some(expr.to_string()),
+            _ => None,
+        }
+    }
+
+    fn add(&mut self, key: &str, dim: &SymbolicDim) {
+        if !self.members.contains_key(key) {
+            self.members.insert(key.to_string(), dim.clone());
+            self.parent
+                .get_mut()
+                .insert(key.to_string(), key.to_string());
+            self.canonical.insert(key.to_string(), key.to_string());
+        }
+    }
+
+    /// Returns the root of the class of a member, pointing every member on
+    /// the way directly at the root.
+    fn find(&self, key: &str) -> Option<String> {
+        let mut parent = self.parent.borrow_mut();
+        let mut root = parent.get_key_value(key)?.0.clone();
+        while let Some(next) = parent.get(&root).filter(|p| **p != root) {
+            root = next.clone();
+        }
+        let mut key = key.to_string();
+        while key != root {
+            key = parent.insert(key, root.clone()).expect("member was added");
+        }
+        Some(root)
+    }
+
+    /// Merges the classes of two members, keeping the shallower tree under
+    /// the deeper one.
+    fn union(&mut self, x: &str, y: &str) -> Result<(), DimConflict> {
+        let (root_x, root_y) = (self.find(x).unwrap(), self.find(y).unwrap());
+        if root_x == root_y {
+            return Ok(());
+        }
+        let rank_x = self.ranks.get(&root_x).copied().unwrap_or(0);
+        let rank_y = self.ranks.get(&root_y).copied().unwrap_or(0);
+        let (root, child) = if rank_x < rank_y {
+            (root_y.clone(), root_x.clone())
+        } else {
+            (root_x.clone(), root_y.clone())
+        };
+        if rank_x == rank_y {
+            *self.ranks.entry(root.clone()).or_default() += 1;
+        }
+        self.ranks.remove(&child);
+        self.parent.get_mut().insert(child.clone(), root.clone());
+
+        let candidates = [&root, &child].map(|r| self.canonical.remove(r).expect("root has one"));
+        let canonical = candidates
+            .into_iter()
+            .min_by_key(|member| self.preference(member))
+            .expect("two candidates");
+        self.canonical.insert(root.clone(), canonical);
+
+        let value = match (self.values.remove(&root_x), self.values.remove(&root_y)) {
+            (Some(first), Some(second)) if first != second => {
+                self.values.insert(root, first);
+                return Err(DimConflict {
+                    dim: y.to_string(),
+                    first,
+                    second,
+                });
+            }
+            (first, second) => first.or(second),
+        };
+        if let Some(value) = value {
+            self.values.insert(root, value);
+        }
+        Ok(())
+    }
+
+    /// Orders the members of a class, the least being the canonical one:
+    /// names come before expressions, and expressions before names
+    /// generated by an exporter, then members seen first come first.
+    fn preference(&self, key: &str) -> (u8, usize) {
+        let (index, _, dim) = self.members.get_full(key).expect("member was added");
+        let rank = match dim {
+            SymbolicDim::Expr(_) => 1,
+            _ if is_generated(key) => 2,
+            _ => 0,
+        };
+        (rank, index)
+    }
+
+    fn bind(&mut self, key: &str, value: i64) -> Result<(), DimConflict> {
+        let root = self.find(key).expect("member was added");
+        match self.values.get(&root) {
+            Some(&first) if first != value => Err(DimConflict {
+                dim: key.to_string(),
+                first,
+                second: value,
+            }),
+            _ => {
+                self.values.insert(root, value);
+                Ok(())
+            }
+        }
+    }
+
+    /// Records that two dimensions are equal.
+    ///
+    /// Returns an error if that would make a dimension equal to two
+    /// different integers.
+    pub fn equate(&mut self, a: &SymbolicDim, b: &SymbolicDim) -> Result<(), DimConflict> {
+        match (a, b, Self::key(a), Self::key(b)) {
+            (SymbolicDim::Int(x), SymbolicDim::Int(y), _, _) if x != y => Err(DimConflict {
+                dim: x.to_string(),
+                first: *x,
+                second: *y,
+            }),
+            (SymbolicDim::Int(value), dim, _, Some(key))
+            | (dim, SymbolicDim::Int(value), Some(key), _) => {
+                self.add(&key, dim);
+                self.bind(&key, *value)
+            }
+            (_, _, Some(x), Some(y)) => {
+                self.add(&x, a);
+                self.add(&y, b);
+                self.union(&x, &y)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns true if the dimensions are known to be equal.
+    pub fn are_equal(&self, a: &SymbolicDim, b: &SymbolicDim) -> bool {
+        let resolved = self.resolve(a);
+        !resolved.is_unknown() && resolved == self.resolve(b)
+    }
+
+    /// Returns the classes with more than one member or a bound integer,
+    /// with their members in the order they were first seen.
+    pub fn classes(&self) -> Vec<(Vec<SymbolicDim>, Option<i64>)> {
+        let mut classes: IndexMap<String, Vec<SymbolicDim>> = IndexMap::new();
+        for (key, dim) in &self.members {
+            let root = self.find(key).expect("member was added");
+            classes.entry(root).or_default().push(dim.clone());
+        }
+        classes
+            .into_iter()
+            .map(|(root, members)| (members, self.values.get(&root).copied()))
+            .filter(|(members, value)| members.len() > 1 || value.is_some())
+            .collect()
+    }
+
+    /// Returns the canonical form of a dimension.
+    ///
+    /// That is the integer the class of the dimension is bound to, if any.
+    /// Otherwise it is the first member of the class, preferring names to
+    /// expressions and expressions to names generated by an exporter (such
+    /// as `unk__123`).
+    /// Symbols within expressions are replaced by their canonical form.
+    pub fn resolve(&self, dim: &SymbolicDim) -> SymbolicDim {
+        let dim = Self::key(dim)
+            .and_then(|key| self.canonical(&key))
+            .unwrap_or_else(|| dim.clone());
+        match &dim {
+            SymbolicDim::Expr(expr) => {
+                let replace = |name: &str| {
+                    self.canonical(name)
+                        .and_then(|canonical| canonical.to_expr())
+                };
+                SymbolicDim::from_expr(expr.replace_symbols(&replace))
+            }
+            _ => dim,
+        }
+    }
+
+    fn canonical(&self, key: &str) -> Option<SymbolicDim> {
+        let root = self.find(key)?;
+        if let Some(&value) = self.values.get(&root) {
+            return Some(SymbolicDim::Int(value));
+        }
+        let member = self.canonical.get(&root).expect("root has one");
+        Some(self.members[member.as_str()].clone())
+    }
+}
+
+/// Returns true if a dimension name looks generated by an exporter.
+fn is_generated(name: &str) -> bool {
+    name.strip_prefix("unk__")
+        .is_some_and(|n| n.chars().all(|c| c.is_ascii_digit()))
+}
+
+/// Walks a graph, collecting constraints.
+struct Collector {
+    classes: DimClasses,
+    /// The first shape seen for each value.
+    shapes: HashMap<String, Shape>,
+    /// Whether symbolic dimensions that broadcast are assumed equal.
+    broadcast_equal: bool,
+}
+
+/// Operators whose output has the shape of their first input.
+const SAME_SHAPE_OPS: &[&str] = &[
+    "Abs",
+    "BatchNormalization",
+    "Cast",
+    "CastLike",
+    "Ceil",
+    "Celu",
+    "Clip",
+    "Dropout",
+    "Elu",
+    "Erf",
+    "Exp",
+    "Floor",
+    "Gelu",
+    "HardSigmoid",
+    "HardSwish",
+    "Identity",
+    "InstanceNormalization",
+    "IsInf",
+    "IsNaN",
+    "LayerNormalization",
+    "LeakyRelu",
+    "Log",
+    "LogSoftmax",
+    "Mish",
+    "Neg",
+    "Not",
+    "Reciprocal",
+    "Relu",
+    "Round",
+    "Selu",
+    "Sigmoid",
+    "Sign",
+    "Softmax",
+    "Softplus",
+    "Sqrt",
+    "Tanh",
+];
+
+/// Operators that broadcast their inputs multidirectionally.
+const BROADCAST_OPS: &[&str] = &[
+    "Add",
+    "And",
+    "BitShift",
+    "BitwiseAnd",
+    "BitwiseOr",
+    "BitwiseXor",
+    "Div",
+    "Equal",
+    "Greater",
+    "GreaterOrEqual",
+    "Less",
+    "LessOrEqual",
+    "Max",
+    "Mean",
+    "Min",
+    "Mod",
+    "Mul",
+    "Or",
+    "Pow",
+    "Sub",
+    "Sum",
+    "Where",
+    "Xor",
+];
+
+impl Collector {
+    fn graph(&mut self, graph: &Graph) -> Result<(), PassError> {
+        let mut initializers: Vec<_> = graph.initializers.values().collect();
+        initializers.sort_by(|a, b| a.name.cmp(&b.name));
+        for value in graph.inputs.iter().chain(initializers) {
+            self.value(value, "graph")?;
+        }
+        for node in graph.iter_nodes() {
+            self.node(node)?;
+        }
+        for value in &graph.outputs {
+            self.value(value, "graph")?;
+        }
+        Ok(())
+    }
+
+    fn equate(&mut self, a: &SymbolicDim, b: &SymbolicDim, node: &str) -> Result<(), PassError> {
+        self.classes
+            .equate(a, b)
+            .map_err(|conflict| PassError::DimConflict {
+                node: node.to_string(),
+                conflict,
+            })
+    }
+
+    /// Equates the shape of a copy of a value with the first one seen.
+    fn value(&mut self, value: &Value, node: &str) -> Result<(), PassError> {
+        let Some(shape) = &value.shape else {
+            return Ok(());
+        };
+        if value.name.is_empty() {
+            return Ok(());
+        }
+        match self.shapes.get(&value.name).cloned() {
+            Some(first) if first.rank() == shape.rank() => {
+                for (a, b) in first.dims().iter().zip(shape.dims()) {
+                    self.equate(a, b, node)?;
+                }
+            }
+            Some(_) => {}
+            None => {
+                self.shapes.insert(value.name.clone(), shape.clone());
+            }
+        }
+        Ok(())
+    }
+
+    fn node(&mut self, node: &Node) -> Result<(), PassError> {
+        let label = node.label();
+        for value in node.inputs.iter().chain(&node.outputs) {
+            self.value(value, &label)?;
+        }
+        for attr in node.attributes.values() {
+            match attr.as_value() {
+                Some(Attr {
+                    value: AttrValue::Graph(graph),
+                    ..
+                }) => self.graph(graph)?,
+                Some(Attr {
+                    value: AttrValue::Graphs(graphs),
+                    ..
+                }) => {
+                    for graph in graphs {
+                        self.graph(graph)?;
+                    }
+                }
+                _ => {}
+            }
+        }
+        if !matches!(node.domain.as_str(), "" | "ai.onnx") {
+            return Ok(());
+        }
+
+        let shape = |values: &[Value], i: usize| values.get(i).and_then(|v| v.shape.clone());
+        let op_type = node.op_type.as_str();
+        if SAME_SHAPE_OPS.contains(&op_type) {
+            if let (Some(x), Some(y)) = (shape(&node.inputs, 0), shape(&node.outputs, 0)) {
+                self.same_shape(&x, &y, &label)?;
+            }
+        } else if BROADCAST_OPS.contains(&op_type) {
+            let inputs: Option<Vec<Shape>> = node.inputs.iter().map(|v| v.shape.clone()).collect();
+            if let Some(inputs) = inputs {
+                self.broadcast(&inputs, shape(&node.outputs, 0), &label)?;
+            }
+        } else if op_type == "MatMul" {
+            if let (Some(a), Some(b)) = (shape(&node.inputs, 0), shape(&node.inputs, 1)) {
+                self.matmul(&a, &b, shape(&node.outputs, 0), &label)?;
+            }
+        } else if op_type == "Gemm" {
+            if let (Some(a), Some(b)) = (shape(&node.inputs, 0), shape(&node.inputs, 1)) {
+                if a.rank() == 2 && b.rank() == 2 {
+                    let trans_a = node.attr_int_or("transA", 0)? != 0;
+                    let trans_b = node.attr_int_or("transB", 0)? != 0;
+                    let k = &a.dims()[usize::from(!trans_a)];
+                    self.equate(k, &b.dims()[usize::from(trans_b)], &label)?;
+                }
+            }
+        } else if op_type == "Concat" {
+            // Only Concat-1 may leave out the axis, and the opset is not
+            // known here
+            if let Some(axis) = node.get_attribute("axis").map(|_| node.attr_int("axis")) {
+                self.concat(node, axis?, &label)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn same_shape(&mut self, x: &Shape, y: &Shape, node: &str) -> Result<(), PassError> {
+        if x.rank() == y.rank() {
+            for (a, b) in x.dims().iter().zip(y.dims()) {
+                self.equate(a, b, node)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Relates the aligned dimensions of broadcast input shapes and their
+    /// output, leaving out the dimensions that are or are bound to 1.
+    fn broadcast(
+        &mut self,
+        inputs: &[Shape],
+        output: Option<Shape>,
+        node: &str,
+    ) -> Result<(), PassError> {
+        let rank = inputs.iter().map(Shape::rank).max().unwrap_or(0);
+        let output = output.filter(|y| y.rank() == rank);
+        for i in 0..rank {
+            let dims: Vec<SymbolicDim> = inputs
+                .iter()
+                .filter_map(|s| s.dims().get((i + s.rank()).checked_sub(rank)?))
+                .filter(|d| self.classes.resolve(d) != SymbolicDim::Int(1))
+                .cloned()
+                .collect();
+            let y = output.as_ref().map(|y| &y.dims()[i]);
+            if self.broadcast_equal {
+                for pair in dims.windows(2) {
+                    self.equate(&pair[0], &pair[1], node)?;
+                }
+                if let (Some(first), Some(y)) = (dims.first(), y) {
+                    self.equate(first, y, node)?;
+                }
+                continue;
+            }
+            // Any symbolic input dimension may be 1, so only integers are
+            // known to match the output
+            let ints: Vec<&SymbolicDim> = dims.iter().filter(|d| d.is_int()).collect();
+            for pair in ints.windows(2) {
+                self.equate(pair[0], pair[1], node)?;
+            }
+            let source = match (dims.as_slice(), ints.first()) {
+                ([dim], _) => Some(dim),
+                (_, Some(&int)) => Some(int),
+                _ => None,
+            };
+            if let (Some(source), Some(y)) = (source, y) {
+                self.equate(source, y, node)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn matmul(
+        &mut self,
+        a: &Shape,
+        b: &Shape,
+        y: Option<Shape>,
+        node: &str,
+    ) -> Result<(), PassError> {
+        let (Some(k), Some(other_k)) = (a.dims().last(), b.dims().get(b.rank().saturating_sub(2)))
+        else {
+            return Ok(());
+        };
+        self.equate(k, other_k, node)?;
+        if a.rank() < 2 || b.rank() < 2 {
+            return Ok(());
+        }
+        let batch = |s: &Shape| Shape::new(s.dims()[..s.rank() - 2].to_vec());
+        let y = y.filter(|y| y.rank() >= 2);
+        if let Some(y) = &y {
+            self.equate(&a.dims()[a.rank() - 2], &y.dims()[y.rank() - 2], node)?;
+            self.equate(&b.dims()[b.rank() - 1], &y.dims()[y.rank() - 1], node)?;
+        }
+        self.broadcast(&[batch(a), batch(b)], y.as_ref().map(batch), node)
+    }
+
+    fn concat(&mut self, node: &Node, axis: i64, label: &str) -> Result<(), PassError> {
+        let shapes: Option<Vec<Shape>> = node
+            .inputs
+            .iter()
+            .chain(node.outputs.first())
+            .map(|v| v.shape.clone())
+            .collect();
+        let Some((y, inputs)) = shapes.as_deref().and_then(|s| s.split_last()) else {
+            return Ok(());
+        };
+        let rank = y.rank();
+        if inputs.iter().any(|s| s.rank() != rank) {
+            return Ok(());
+        }
+        let Some(axis) = usize::try_from(if axis < 0 { axis + rank as i64 } else { axis })
+            .ok()
+            .filter(|&axis| axis < rank)
+        else {
+            return Ok(());
+        };
+        for x in inputs {
+            for (i, (a, b)) in x.dims().iter().zip(y.dims()).enumerate() {
+                if i != axis {
+                    self.equate(a, b, label)?;
+                }
+            }
+        }
+        let total = inputs
+            .iter()
+            .map(|x| x.dims()[axis].clone())
+            .fold(SymbolicDim::Int(0), |total, dim| total + dim);
+        self.equate(&total, &y.dims()[axis], label)
+    }
+}
+
+/// Unifies the symbolic dimensions of the main graph.
+///
+/// Collects the constraints between dimensions with
+/// [`DimClasses::from_graph_with`] and fails if they contradict each other.
+/// Unless renaming is turned off, every dimension is then replaced by its
+/// canonical form, so that equal dimensions have the same name everywhere
+/// and dimensions bound to an integer become that integer.
+///
+/// Function bodies are left alone, since their dimensions depend on the
+/// call site.
+#[derive(Debug)]
+pub struct UnifyDimsPass {
+    rename: bool,
+    broadcast_equal: bool,
+}
+
+impl UnifyDimsPass {
+    pub fn new() -> Self {
+        Self {
+            rename: true,
+            broadcast_equal: false,
+        }
+    }
+
+    /// Sets whether to assume that symbolic dimensions that broadcast
+    /// against each other are equal. Off by default.
+    pub fn broadcast_equal(mut self, broadcast_equal: bool) -> Self {
+        self.broadcast_equal = broadcast_equal;
+        self
+    }
+
+    /// Sets whether to rename the dimensions, or only check the
+    /// constraints.
+    pub fn rename(mut self, rename: bool) -> Self {
+        self.rename = rename;
+        self
+    }
+}
+
+impl Default for UnifyDimsPass {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Pass for UnifyDimsPass {
+    fn run(&mut self, model: &mut Model) -> Result<PassResult, PassError> {
+        let classes = DimClasses::from_graph_with(&model.graph, self.broadcast_equal)?;
+        if !self.rename {
+            return Ok(PassResult::new(false));
+        }
+        Ok(PassResult::new(rename_graph(&mut model.graph, &classes)))
+    }
+}
+
+fn rename_graph(graph: &mut Graph, classes: &DimClasses) -> bool {
+    let mut modified = false;
+    for value in graph
+        .inputs
+        .iter_mut()
+        .chain(graph.outputs.iter_mut())
+        .chain(graph.initializers.values_mut())
+    {
+        modified |= rename_value(value, classes);
+    }
+    for node in graph.iter_nodes_mut() {
+        for value in node.inputs.iter_mut().chain(node.outputs.iter_mut()) {
+            modified |= rename_value(value, classes);
+        }
+        for attr in node.attributes.values_mut() {
+            let graphs = match attr.as_value_mut() {
+                Some(Attr {
+                    value: AttrValue::Graph(graph),
+                    ..
+                }) => std::slice::from_mut(graph),
+                Some(Attr {
+                    value: AttrValue::Graphs(graphs),
+                    ..
+                }) => graphs.as_mut_slice(),
+                _ => continue,
+            };
+            for graph in graphs {
+                modified |= rename_graph(Rc::make_mut(graph), classes);
+            }
+        }
+    }
+    modified
+}
+
+fn rename_value(value: &mut Value, classes: &DimClasses) -> bool {
+    let Some(shape) = &value.shape else {
+        return false;
+    };
+    let dims: Vec<_> = shape.dims().iter().map(|d| classes.resolve(d)).collect();
+    if dims == shape.dims() {
+        return false;
+    }
+    let mut renamed = Shape::new(dims);
+    for i in 0..renamed.rank() {
+        renamed.set_denotation(i, shape.get_denotation(i).map(str::to_string));
+    }
+    if shape.is_frozen() {
+        renamed.freeze();
+    }
+    value.shape = Some(renamed);
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enums::DataType;
+    use crate::test_support::{sym, value};
+
+    fn transformer_graph() -> Graph {
+        let mut graph = Graph::new();
+        graph.inputs.push(value(
+            "x",
+            DataType::Float,
+            vec![sym("batch"), sym("seq"), 8.into()],
+        ));
+        let w = value("w", DataType::Float, vec![8.into(), 16.into()]);
+        let h = value(
+            "h",
+            DataType::Float,
+            vec![sym("unk__1"), sym("unk__2"), 16.into()],
+        );
+        graph.append(Node::with_io(
+            "MatMul",
+            vec![
+                value(
+                    "x",
+                    DataType::Float,
+                    vec![sym("batch"), sym("seq"), 8.into()],
+                ),
+                w,
+            ],
+            vec![h.clone()],
+        ));
+        let bias = value("bias", DataType::Float, vec![sym("N"), 1.into(), 16.into()]);
+        let y = value(
+            "y",
+            DataType::Float,
+            vec![sym("unk__3"), sym("unk__4"), 16.into()],
+        );
+        graph.append(Node::with_io("Add", vec![h, bias], vec![y.clone()]));
+        graph.outputs.push(y);
+        graph
+    }
+
+    #[test]
+    fn test_equivalence_classes() {
+        // Either of batch and N may be 1, so neither is known to equal the
+        // output dimension
+        let classes = DimClasses::from_graph(&transformer_graph()).unwrap();
+        assert!(classes.are_equal(&sym("batch"), &sym("unk__1")));
+        assert!(classes.are_equal(&sym("seq"), &sym("unk__4")));
+        assert!(!classes.are_equal(&sym("batch"), &sym("unk__3")));
+        assert!(!classes.are_equal(&sym("N"), &sym("unk__1")));
+
+        let classes = DimClasses::from_graph_with(&transformer_graph(), true).unwrap();
+        assert!(classes.are_equal(&sym("batch"), &sym("unk__3")));
+        assert!(classes.are_equal(&sym("N"), &sym("unk__1")));
+        assert!(classes.are_equal(&sym("seq"), &sym("unk__4")));
+        assert!(!classes.are_equal(&sym("batch"), &sym("seq")));
+        assert_eq!(classes.resolve(&sym("unk__3")), sym("batch"));
+        assert_eq!(
+            classes.classes(),
+            vec![
+                (
+                    vec![sym("batch"), sym("unk__1"), sym("N"), sym("unk__3")],
+                    None
+                ),
+                (vec![sym("seq"), sym("unk__2"), sym("unk__4")], None),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_long_chains() {
+        let mut classes = DimClasses::new();
+        let dims: Vec<_> = (0..1000).map(|i| sym(&format!("unk__{}", i))).collect();
+        for pair in dims.windows(2) {
+            classes.equate(&pair[0], &pair[1]).unwrap();
+        }
+        assert_eq!(classes.resolve(&dims[999]), dims[0]);
+        // A name wins over generated names even when its tree is smaller
+        classes.equate(&dims[500], &sym("batch")).unwrap();
+        assert_eq!(classes.resolve(&dims[0]), sym("batch"));
+        classes.equate(&sym("batch"), &SymbolicDim::Int(4)).unwrap();
+        assert_eq!(classes.resolve(&dims[999]), SymbolicDim::Int(4));
+        assert_eq!(classes.classes().len(), 1);
+    }
+
+    #[test]
+    fn test_integers_and_expressions() {
+        let mut classes = DimClasses::new();
+        classes.equate(&sym("unk__7"), &sym("batch")).unwrap();
+        classes.equate(&sym("batch"), &1.into()).unwrap();
+        assert_eq!(classes.resolve(&sym("unk__7")), SymbolicDim::Int(1));
+
+        let err = classes.equate(&sym("unk__7"), &2.into()).unwrap_err();
+        assert_eq!(err.to_string(), "dimension unk__7 cannot be both 1 and 2");
+        assert!(classes.equate(&3.into(), &4.into()).is_err());
+
+        let expr = SymbolicDim::parse_expr("seq + batch").unwrap();
+        assert_eq!(classes.resolve(&expr).to_string(), "seq + 1");
+        classes.equate(&expr, &sym("total")).unwrap();
+        assert_eq!(classes.resolve(&sym("total")), sym("total"));
+    }
+
+    #[test]
+    fn test_concat_and_conflicts() {
+        let mut graph = Graph::new();
+        let cls = value("cls", DataType::Float, vec![sym("B"), 1.into()]);
+        let x = value("x", DataType::Float, vec![sym("unk__1"), sym("seq")]);
+        let y = value("y", DataType::Float, vec![sym("unk__2"), sym("unk__3")]);
+        let mut concat = Node::with_io("Concat", vec![cls, x], vec![y]);
+        concat.set_attribute(Attr::int("axis", 1));
+        graph.append(concat);
+        let classes = DimClasses::from_graph(&graph).unwrap();
+        assert_eq!(classes.resolve(&sym("unk__1")), sym("B"));
+        assert_eq!(classes.resolve(&sym("unk__3")).to_string(), "seq + 1");
+
+        let mut legacy = Graph::new();
+        let a = value("a", DataType::Float, vec![sym("A"), 2.into()]);
+        let b = value("b", DataType::Float, vec![sym("unk__4"), 2.into()]);
+        let mut concat = Node::with_io("Concat", vec![a.clone(), b], vec![Value::new("ab")]);
+        concat.domain = "ai.onnx".to_string();
+        legacy.append(concat);
+        let mut relu = Node::with_io(
+            "Relu",
+            vec![a],
+            vec![value("r", DataType::Float, vec![sym("unk__5"), 2.into()])],
+        );
+        relu.domain = "ai.onnx".to_string();
+        legacy.append(relu);
+        let classes = DimClasses::from_graph(&legacy).unwrap();
+        assert!(!classes.are_equal(&sym("A"), &sym("unk__4")));
+        assert!(classes.are_equal(&sym("A"), &sym("unk__5")));
+
+        // N may be 1, and is only assumed equal to 3 and 2 on request
+        let a = value("a", DataType::Float, vec![sym("N"), 2.into()]);
+        let b = value("b", DataType::Float, vec![3.into(), sym("N")]);
+        graph.append(Node::with_io("Mul", vec![a, b], vec![Value::new("c")]));
+        assert!(DimClasses::from_graph(&graph).is_ok());
+        let err = DimClasses::from_graph_with(&graph, true).unwrap_err();
+        assert_eq!(err.to_string(), "Mul: dimension N cannot be both 3 and 2");
+
+        let d = value("d", DataType::Float, vec![sym("M"), 2.into()]);
+        let e = value("e", DataType::Float, vec![1.into(), 3.into()]);
+        graph.append(Node::with_io("Sub", vec![d, e], vec![Value::new("f")]));
+        let err = DimClasses::from_graph(&graph).unwrap_err();
+        assert_eq!(err.to_string(), "Sub: dimension 2 cannot be both 2 and 3");
+    }
+
+    #[test]
+    fn test_broadcast_of_dims_bound_to_one() {
+        // K is 1, so Add(k, b) broadcasts k rather than equating K with 5
+        let mut graph = Graph::new();
+        graph
+            .inputs
+            .push(value("k", DataType::Float, vec![sym("K")]));
+        graph.append(Node::with_io(
+            "Identity",
+            vec![value("k", DataType::Float, vec![1.into()])],
+            vec![value("k1", DataType::Float, vec![1.into()])],
+        ));
+        let b = value("b", DataType::Float, vec![5.into()]);
+        let y = value("y", DataType::Float, vec![sym("unk__1")]);
+        graph.append(Node::with_io(
+            "Add",
+            vec![value("k", DataType::Float, vec![sym("K")]), b],
+            vec![y],
+        ));
+        let m = value("m", DataType::Float, vec![sym("M")]);
+        let z = value("z", DataType::Float, vec![sym("unk__2")]);
+        graph.append(Node::with_io(
+            "Mul",
+            vec![value("k", DataType::Float, vec![sym("K")]), m],
+            vec![z],
+        ));
+
+        for broadcast_equal in [false, true] {
+            let classes = DimClasses::from_graph_with(&graph, broadcast_equal).unwrap();
+            assert_eq!(classes.resolve(&sym("K")), SymbolicDim::Int(1));
+            assert_eq!(classes.resolve(&sym("unk__1")), SymbolicDim::Int(5));
+            assert_eq!(classes.resolve(&sym("unk__2")), sym("M"));
+        }
+    }
+
+    #[test]
+    fn test_pass_renames_dims() {
+        let mut model = Model::new(transformer_graph());
+        let result = UnifyDimsPass::new().rename(false).run(&mut model).unwrap();
+        assert!(!result.modified);
+
+        let result = UnifyDimsPass::new().run(&mut model).unwrap();
+        assert!(result.modified);
+        let h = &model.graph.iter_nodes().next().unwrap().outputs[0];
+        assert_eq!(
+            h.shape,
+            Some(Shape::new(vec![sym("batch"), sym("seq"), 16.into()]))
+        );
+        let expected = Shape::new(vec![sym("unk__3"), sym("seq"), 16.into()]);
+        assert_eq!(model.graph.outputs[0].shape, Some(expected));
+
+        let result = UnifyDimsPass::new()
+            .broadcast_equal(true)
+            .run(&mut model)
+            .unwrap();
+        assert!(result.modified);
+        let expected = Shape::new(vec![sym("batch"), sym("seq"), 16.into()]);
+        assert_eq!(model.graph.outputs[0].shape, Some(expected.clone()));
+        let add = model.graph.iter_nodes().nth(1).unwrap();
+        assert_eq!(add.inputs[0].shape, Some(expected));
+        assert_eq!(
+            add.inputs[1].shape,
+            Some(Shape::new(vec![sym("batch"), 1.into(), 16.into()]))
+        );
+        assert!(
+            !UnifyDimsPass::new()
+                .broadcast_equal(true)
+                .run(&mut model)
+                .unwrap()
+                .modified
+        );
+    }
+}
@@ -0,0 +1,671 @@
+// Copyright (c) ONNX Project Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Function inlining.
+//!
+//! Replaces every node that calls a model-local function with a copy of the
+//! function body, so the model can run on backends that do not support
+//! local functions.
+
+use super::{Pass, PassError, PassResult};
+use crate::attribute::{Attr, AttrValue, NodeAttr};
+use crate::function::{Function, FunctionId};
+use crate::graph::Graph;
+use crate::linked_list::DoublyLinkedList;
+use crate::model::Model;
+use crate::name_authority::NameAuthority;
+use crate::node::Node;
+use crate::value::Value;
+use indexmap::IndexMap;
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+/// Inlines all calls to model-local functions.
+///
+/// For each call site, the function body is copied with:
+///
+/// - function inputs and outputs renamed to the values at the call site,
+///   with an `Identity` node for each output that is a function input or
+///   repeats an earlier output,
+/// - intermediate values and nodes given names that are unique in the model,
+/// - [`RefAttr`](crate::attribute::RefAttr) references replaced by the call site's attributes, or by the
+///   function's default when the call site omits them. A reference that has
+///   neither, or that is bound to a value of another type, is an error,
+///   since dropping it would make the node fall back to its own default.
+///
+/// Calls nested inside function bodies and inside subgraphs are inlined
+/// recursively. The opset imports of inlined functions are merged into the
+/// model. Inlined functions are removed from the model afterwards unless a
+/// remaining function still calls them; functions that are never called
+/// are kept.
+///
+/// If an error occurs, the model remains valid: calls processed before the
+/// error are inlined and the others are left in place.
+#[derive(Debug, Default)]
+pub struct InlinePass;
+
+impl InlinePass {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Pass for InlinePass {
+    fn run(&mut self, model: &mut Model) -> Result<PassResult, PassError> {
+        if model.functions.is_empty() {
+            return Ok(PassResult::new(false));
+        }
+
+        let mut names = NameAuthority::new();
+        register_graph_names(&model.graph, &mut names);
+
+        let mut inliner = Inliner {
+            functions: &model.functions,
+            names,
+            opset_imports: &mut model.opset_imports,
+            stack: Vec::new(),
+            inlined: HashSet::new(),
+        };
+        inliner.inline_list(&mut model.graph.nodes)?;
+
+        let mut removed = inliner.inlined;
+        if removed.is_empty() {
+            return Ok(PassResult::new(false));
+        }
+        // Functions called by a function that is kept must be kept too
+        loop {
+            let kept = model
+                .functions
+                .values()
+                .filter(|f| !removed.contains(&f.id()));
+            let called: HashSet<_> = kept
+                .flat_map(|f| f.iter_nodes())
+                .flat_map(|node| called_functions(node, &removed))
+                .collect();
+            if called.is_empty() {
+                break;
+            }
+            removed.retain(|id| !called.contains(id));
+        }
+        model.functions.retain(|id, _| !removed.contains(id));
+        Ok(PassResult::new(true))
+    }
+}
+
+/// Returns the functions in `ids` that `node` or its subgraphs call.
+fn called_functions(node: &Node, ids: &HashSet<FunctionId>) -> Vec<FunctionId> {
+    let mut called = Vec::new();
+    let id = node.function_id();
+    if ids.contains(&id) {
+        called.push(id);
+    }
+    for attr in node.attributes.values().filter_map(NodeAttr::as_value) {
+        let graphs = match &attr.value {
+            AttrValue::Graph(graph) => std::slice::from_ref(graph),
+            AttrValue::Graphs(graphs) => graphs.as_slice(),
+            _ => &[],
+        };
+        for graph in graphs {
+            called.extend(graph.iter_nodes().flat_map(|n| called_functions(n, ids)));
+        }
+    }
+    called
+}
+
+struct Inliner<'a> {
+    functions: &'a IndexMap<FunctionId, Function>,
+    names: NameAuthority,
+    opset_imports: &'a mut HashMap<String, i32>,
+    /// Functions currently being expanded, for cycle detection.
+    stack: Vec<FunctionId>,
+    /// Functions inlined at least once.
+    inlined: HashSet<FunctionId>,
+}
+
+impl<'a> Inliner<'a> {
+    /// Inlines every call in the list, including calls inside subgraphs.
+    fn inline_list(&mut self, list: &mut DoublyLinkedList<Node>) -> Result<(), PassError> {
+        let mut nodes = Vec::with_capacity(list.len());
+        while let Some(node) = list.pop_front() {
+            nodes.push(node);
+        }
+
+        let mut result = Ok(());
+        for mut node in nodes {
+            if result.is_ok() {
+                match self.expand(&mut node) {
+                    Ok(Some(replacement)) => {
+                        for new_node in replacement {
+                            list.push_back(new_node);
+                        }
+                        continue;
+                    }
+                    Ok(None) => {}
+                    Err(err) => result = Err(err),
+                }
+            }
+            list.push_back(node);
+        }
+        result
+    }
+
+    /// Returns the nodes replacing `node`, or `None` if it is not a call.
+    fn expand(&mut self, node: &mut Node) -> Result<Option<Vec<Node>>, PassError> {
+        self.inline_subgraphs(node)?;
+
+        let Some(function) = self.lookup(node) else {
+            return Ok(None);
+        };
+        let id = function.id();
+        if let Some(pos) = self.stack.iter().position(|k| k == &id) {
+            let mut cycle: Vec<_> = self.stack[pos..].iter().map(|k| k.to_string()).collect();
+            cycle.push(id.to_string());
+            return Err(PassError::RecursiveFunction(cycle));
+        }
+
+        let mut body = self.instantiate(function, node)?;
+        self.stack.push(id.clone());
+        let result = self.inline_list(&mut body);
+        self.stack.pop();
+        result?;
+
+        self.merge_opset_imports(function)?;
+        self.inlined.insert(id);
+
+        let mut nodes = Vec::with_capacity(body.len());
+        while let Some(new_node) = body.pop_front() {
+            nodes.push(new_node);
+        }
+        Ok(Some(nodes))
+    }
+
+    /// Inlines calls inside the graph attributes of a node.
+    ///
+    /// A subgraph is only replaced once it has been fully inlined, so an
+    /// error leaves the node unchanged.
+    fn inline_subgraphs(&mut self, node: &mut Node) -> Result<(), PassError> {
+        for attr in node.attributes.values_mut() {
+            let NodeAttr::Value(attr) = attr else {
+                continue;
+            };
+            match &mut attr.value {
+                AttrValue::Graph(graph) if self.contains_calls(graph) => {
+                    *graph = Rc::new(self.inline_graph(graph)?);
+                }
+                AttrValue::Graphs(graphs) => {
+                    for graph in graphs.iter_mut() {
+                        if self.contains_calls(graph) {
+                            *graph = Rc::new(self.inline_graph(graph)?);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn inline_graph(&mut self, graph: &Graph) -> Result<Graph, PassError> {
+        let mut graph = graph.clone();
+        self.inline_list(&mut graph.nodes)?;
+        Ok(graph)
+    }
+
+    /// Returns true if the graph or any of its subgraphs calls a function.
+    fn contains_calls(&self, graph: &Graph) -> bool {
+        graph.iter_nodes().any(|node| {
+            self.lookup(node).is_some()
+                || node.attributes.values().any(|attr| match attr.as_value() {
+                    Some(Attr {
+                        value: AttrValue::Graph(g),
+                        ..
+                    }) => self.contains_calls(g),
+                    Some(Attr {
+                        value: AttrValue::Graphs(gs),
+                        ..
+                    }) => gs.iter().any(|g| self.contains_calls(g)),
+                    _ => false,
+                })
+        })
+    }
+
+    fn lookup(&self, node: &Node) -> Option<&'a Function> {
+        self.functions.get(&node.function_id())
+    }
+
+    /// Creates a renamed copy of the function body for the given call site.
+    fn instantiate(
+        &mut self,
+        function: &Function,
+        call: &Node,
+    ) -> Result<DoublyLinkedList<Node>, PassError> {
+        if call.num_inputs() > function.num_inputs() {
+            return Err(PassError::TooManyInputs {
+                function: function.id().to_string(),
+                expected: function.num_inputs(),
+                actual: call.num_inputs(),
+            });
+        }
+        if call.num_outputs() > function.num_outputs() {
+            return Err(PassError::TooManyOutputs {
+                function: function.id().to_string(),
+                expected: function.num_outputs(),
+                actual: call.num_outputs(),
+            });
+        }
+
+        let mut renamer = Renamer {
+            names: &mut self.names,
+            prefix: call.name.clone().unwrap_or_else(|| call.op_type.clone()),
+            mapping: HashMap::new(),
+        };
+        // Omitted trailing inputs are missing optional inputs.
+        for (i, input) in function.inputs.iter().enumerate() {
+            let actual = call.inputs.get(i).map_or("", |v| v.name.as_str());
+            renamer
+                .mapping
+                .insert(input.name.clone(), actual.to_string());
+        }
+        // An output that is a function input, or the same value as an earlier
+        // output, cannot take the name at the call site, so it is copied
+        let mut copies = Vec::new();
+        for (output, actual) in function.outputs.iter().zip(&call.outputs) {
+            if renamer.mapping.contains_key(&output.name) {
+                copies.push((output.name.clone(), actual.clone()));
+            } else {
+                renamer
+                    .mapping
+                    .insert(output.name.clone(), actual.name.clone());
+            }
+        }
+
+        let bindings = function.bind_attributes(call);
+        let mut body = function.nodes.clone();
+        for node in body.iter_mut() {
+            renamer.rename_node(node);
+            if let Some(unresolved) = node.resolve_ref_attributes(&bindings).first() {
+                let attribute = unresolved.ref_attr_name.clone();
+                return Err(match bindings.get(&attribute) {
+                    Some(bound) => PassError::RefAttrTypeMismatch {
+                        function: function.id().to_string(),
+                        attribute,
+                        expected: unresolved.type_,
+                        actual: bound.attr_type(),
+                    },
+                    None => PassError::MissingAttribute {
+                        function: function.id().to_string(),
+                        attribute,
+                    },
+                });
+            }
+            for output in node.outputs.iter_mut() {
+                if let Some(actual) = call.outputs.iter().find(|v| v.name == output.name) {
+                    if output.type_.is_none() {
+                        output.type_ = actual.type_.clone();
+                    }
+                    if output.shape.is_none() {
+                        output.shape = actual.shape.clone();
+                    }
+                }
+            }
+        }
+        for (name, actual) in copies {
+            if actual.name.is_empty() {
+                continue;
+            }
+            let mut identity = Node::new("Identity");
+            identity.add_input(Value::new(renamer.rename_value(&name)));
+            identity.add_output(actual);
+            body.push_back(identity);
+        }
+        Ok(body)
+    }
+
+    fn merge_opset_imports(&mut self, function: &Function) -> Result<(), PassError> {
+        for (domain, &version) in &function.opset_imports {
+            match self.opset_imports.get(domain) {
+                Some(&existing) if existing != version => {
+                    return Err(PassError::OpsetConflict {
+                        domain: domain.clone(),
+                        existing,
+                        new: version,
+                    });
+                }
+                Some(_) => {}
+                None => {
+                    self.opset_imports.insert(domain.clone(), version);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Renames the values and nodes of a function body for one call site.
+struct Renamer<'n> {
+    names: &'n mut NameAuthority,
+    prefix: String,
+    mapping: HashMap<String, String>,
+}
+
+impl Renamer<'_> {
+    fn rename_value(&mut self, name: &str) -> String {
+        if name.is_empty() {
+            return String::new();
+        }
+        if let Some(new_name) = self.mapping.get(name) {
+            return new_name.clone();
+        }
+        let new_name = self
+            .names
+            .unique_value_name(Some(&format!("{}_{}", self.prefix, name)));
+        self.mapping.insert(name.to_string(), new_name.clone());
+        new_name
+    }
+
+    fn rename_node(&mut self, node: &mut Node) {
+        if let Some(name) = &node.name {
+            let preferred = format!("{}_{}", self.prefix, name);
+            node.name = Some(self.names.unique_node_name(&node.op_type, Some(&preferred)));
+        }
+        for value in node.inputs.iter_mut().chain(node.outputs.iter_mut()) {
+            value.name = self.rename_value(&value.name);
+        }
+        for attr in node.attributes.values_mut() {
+            let NodeAttr::Value(attr) = attr else {
+                continue;
+            };
+            match &mut attr.value {
+                AttrValue::Graph(graph) => *graph = Rc::new(self.rename_graph(graph)),
+                AttrValue::Graphs(graphs) => {
+                    for graph in graphs.iter_mut() {
+                        *graph = Rc::new(self.rename_graph(graph));
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn rename_graph(&mut self, graph: &Graph) -> Graph {
+        let mut graph = graph.clone();
+        for value in graph.inputs.iter_mut().chain(graph.outputs.iter_mut()) {
+            value.name = self.rename_value(&value.name);
+        }
+        graph.initializers = std::mem::take(&mut graph.initializers)
+            .into_values()
+            .map(|mut value| {
+                value.name = self.rename_value(&value.name);
+                (value.name.clone(), value)
+            })
+            .collect();
+        for node in graph.iter_nodes_mut() {
+            self.rename_node(node);
+        }
+        graph
+    }
+}
+
+/// Registers all value and node names used in a graph and its subgraphs.
+fn register_graph_names(graph: &Graph, names: &mut NameAuthority) {
+    for value in graph.inputs.iter().chain(&graph.outputs) {
+        names.register_value_name(value.name.clone());
+    }
+    for name in graph.initializers.keys() {
+        names.register_value_name(name.clone());
+    }
+    for node in graph.iter_nodes() {
+        if let Some(name) = &node.name {
+            names.register_node_name(name.clone());
+        }
+        for value in node.inputs.iter().chain(&node.outputs) {
+            names.register_value_name(value.name.clone());
+        }
+        for attr in node.attributes.values().filter_map(NodeAttr::as_value) {
+            match &attr.value {
+                AttrValue::Graph(g) => register_graph_names(g, names),
+                AttrValue::Graphs(gs) => gs.iter().for_each(|g| register_graph_names(g, names)),
+                _ => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::attribute::RefAttr;
+    use crate::enums::AttributeType;
+    use crate::test_support::node;
+
+    /// `y = LeakyRelu(x + x, alpha=@slope)`
+    fn leaky_double() -> Function {
+        let mut func = Function::new("LeakyDouble", "custom");
+        func.add_input(Value::new("x"));
+        func.add_output(Value::new("y"));
+        func.set_opset_import("", 18);
+        func.set_attribute_default(Attr::float("slope", 0.1));
+        func.append(node("Add", &["x", "x"], &["t"]));
+        let mut relu = node("LeakyRelu", &["t"], &["y"]);
+        relu.set_attribute(RefAttr::new("alpha", "slope", AttributeType::Float));
+        func.append(relu);
+        func
+    }
+
+    fn call(op_type: &str, input: &str, output: &str) -> Node {
+        let mut call = node(op_type, &[input], &[output]);
+        call.domain = "custom".to_string();
+        call
+    }
+
+    fn alpha(node: &Node) -> f32 {
+        match node.get_attribute("alpha").unwrap().value {
+            AttrValue::Float(v) => v,
+            _ => panic!("Expected float value"),
+        }
+    }
+
+    #[test]
+    fn test_inline_single_call() {
+        let mut graph = Graph::new();
+        let mut call_node = call("LeakyDouble", "a", "b");
+        call_node.set_attribute(Attr::float("slope", 0.3));
+        graph.append(call_node);
+
+        let mut model = Model::new(graph);
+        model.add_function(leaky_double());
+
+        let result = InlinePass::new().run(&mut model).unwrap();
+        assert!(result.modified);
+        assert!(model.functions.is_empty());
+        assert_eq!(model.get_opset_version(""), Some(18));
+
+        let nodes: Vec<_> = model.graph.iter_nodes().collect();
+        assert_eq!(nodes.len(), 2);
+        assert_eq!(nodes[0].inputs[0].name, "a");
+        assert_eq!(nodes[1].outputs[0].name, "b");
+        // The intermediate value is renamed and connects both nodes
+        assert_eq!(nodes[0].outputs[0].name, nodes[1].inputs[0].name);
+        assert_ne!(nodes[0].outputs[0].name, "t");
+        assert_eq!(alpha(nodes[1]), 0.3);
+    }
+
+    #[test]
+    fn test_inline_uses_defaults_and_unique_names() {
+        let mut graph = Graph::new();
+        graph.append(call("LeakyDouble", "a", "b"));
+        graph.append(call("LeakyDouble", "b", "c"));
+
+        let mut model = Model::new(graph);
+        model.add_function(leaky_double());
+        InlinePass::new().run(&mut model).unwrap();
+
+        let nodes: Vec<_> = model.graph.iter_nodes().collect();
+        assert_eq!(nodes.len(), 4);
+        assert_eq!(alpha(nodes[1]), 0.1);
+        assert_ne!(nodes[0].outputs[0].name, nodes[2].outputs[0].name);
+    }
+
+    #[test]
+    fn test_inline_nested_calls() {
+        let mut outer = Function::new("Outer", "custom");
+        outer.add_input(Value::new("x"));
+        outer.add_output(Value::new("y"));
+        outer.add_attribute("s");
+        let mut inner_call = call("LeakyDouble", "x", "y");
+        inner_call.set_attribute(RefAttr::new("slope", "s", AttributeType::Float));
+        outer.append(inner_call);
+
+        let mut graph = Graph::new();
+        let mut call_node = call("Outer", "a", "b");
+        call_node.set_attribute(Attr::float("s", 0.7));
+        graph.append(call_node);
+
+        let mut model = Model::new(graph);
+        model.add_function(leaky_double());
+        model.add_function(outer);
+        InlinePass::new().run(&mut model).unwrap();
+
+        let op_types: Vec<_> = model
+            .graph
+            .iter_nodes()
+            .map(|n| n.op_type.as_str())
+            .collect();
+        assert_eq!(op_types, vec!["Add", "LeakyRelu"]);
+        assert_eq!(alpha(model.graph.iter_nodes().nth(1).unwrap()), 0.7);
+    }
+
+    #[test]
+    fn test_inline_rejects_unresolved_refs() {
+        let mut graph = Graph::new();
+        let mut call_node = call("LeakyDouble", "a", "b");
+        call_node.set_attribute(Attr::int("slope", 1));
+        graph.append(call_node);
+        let mut model = Model::new(graph);
+        model.add_function(leaky_double());
+        let err = InlinePass::new().run(&mut model).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "attribute 'slope' of function 'custom::LeakyDouble' is INT, but is referenced as FLOAT"
+        );
+        assert_eq!(model.graph.iter_nodes().count(), 1);
+
+        let mut func = leaky_double();
+        func.attribute_defaults.clear();
+        func.add_attribute("slope");
+        let mut model = Model::new(Graph::new());
+        model.graph.append(call("LeakyDouble", "a", "b"));
+        model.add_function(func);
+        let err = InlinePass::new().run(&mut model).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "call to function 'custom::LeakyDouble' does not set attribute 'slope', which has no default"
+        );
+    }
+
+    #[test]
+    fn test_inline_detects_recursion() {
+        let mut ping = Function::new("Ping", "custom");
+        ping.add_input(Value::new("x"));
+        ping.add_output(Value::new("y"));
+        ping.append(call("Pong", "x", "y"));
+
+        let mut pong = Function::new("Pong", "custom");
+        pong.add_input(Value::new("x"));
+        pong.add_output(Value::new("y"));
+        pong.append(call("Ping", "x", "y"));
+
+        let mut graph = Graph::new();
+        graph.append(call("Ping", "a", "b"));
+        let mut model = Model::new(graph);
+        model.add_function(ping);
+        model.add_function(pong);
+
+        let err = InlinePass::new().run(&mut model).unwrap_err();
+        assert!(matches!(err, PassError::RecursiveFunction(ref cycle) if cycle.len() == 3));
+        // The original call is left in place
+        assert_eq!(model.graph.num_nodes(), 1);
+        assert_eq!(model.graph.iter_nodes().next().unwrap().op_type, "Ping");
+    }
+
+    #[test]
+    fn test_inline_inside_subgraph() {
+        let mut branch = Graph::new();
+        branch.append(call("LeakyDouble", "a", "b"));
+
+        let mut if_node = node("If", &["cond"], &["out"]);
+        if_node.set_attribute(Attr::new("then_branch", AttrValue::Graph(Rc::new(branch))));
+
+        let mut graph = Graph::new();
+        graph.append(if_node);
+        let mut model = Model::new(graph);
+        model.add_function(leaky_double());
+        InlinePass::new().run(&mut model).unwrap();
+
+        let if_node = model.graph.iter_nodes().next().unwrap();
+        let branch = match &if_node.get_attribute("then_branch").unwrap().value {
+            AttrValue::Graph(g) => g.clone(),
+            _ => panic!("Expected graph value"),
+        };
+        assert_eq!(branch.num_nodes(), 2);
+    }
+
+    #[test]
+    fn test_inline_passthrough_outputs() {
+        // (y, z) = Pass(x) with y = x and z = y
+        let mut func = Function::new("Pass", "custom");
+        func.add_input(Value::new("x"));
+        func.add_output(Value::new("x"));
+        func.add_output(Value::new("x"));
+
+        let mut graph = Graph::new();
+        graph.append(node("Relu", &["in"], &["a"]));
+        let mut call_node = node("Pass", &["a"], &["b", "c"]);
+        call_node.domain = "custom".to_string();
+        graph.append(call_node);
+
+        let mut model = Model::new(graph);
+        model.add_function(func);
+        InlinePass::new().run(&mut model).unwrap();
+
+        let nodes: Vec<_> = model.graph.iter_nodes().collect();
+        assert_eq!(nodes.len(), 3);
+        assert_eq!(nodes[1].op_type, "Identity");
+        assert_eq!(
+            (
+                nodes[1].inputs[0].name.as_str(),
+                nodes[1].outputs[0].name.as_str()
+            ),
+            ("a", "b")
+        );
+        assert_eq!(
+            (
+                nodes[2].inputs[0].name.as_str(),
+                nodes[2].outputs[0].name.as_str()
+            ),
+            ("a", "c")
+        );
+    }
+
+    #[test]
+    fn test_inline_keeps_uncalled_functions() {
+        let mut unused = Function::new("Unused", "custom");
+        unused.add_input(Value::new("x"));
+        unused.add_output(Value::new("y"));
+        unused.append(call("LeakyDouble", "x", "y"));
+
+        let mut model = Model::new(Graph::new());
+        model.add_function(leaky_double());
+        model.add_function(unused);
+        let result = InlinePass::new().run(&mut model).unwrap();
+        assert!(!result.modified);
+        assert_eq!(model.functions.len(), 2);
+
+        // LeakyDouble is still called by the function that is kept
+        model.graph.append(call("LeakyDouble", "a", "b"));
+        let result = InlinePass::new().run(&mut model).unwrap();
+        assert!(result.modified);
+        assert_eq!(model.graph.num_nodes(), 2);
+        assert_eq!(model.functions.len(), 2);
+    }
+}
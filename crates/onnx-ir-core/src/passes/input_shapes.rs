@@ -0,0 +1,585 @@
+// Copyright (c) ONNX Project Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Overriding the shapes of graph inputs.
+//!
+//! Deploying a model often means fixing its dynamic dimensions, e.g. a
+//! batch size of 1, or the reverse, making a dimension the exporter baked
+//! in dynamic again. [`SetInputShapesPass`] changes the input shapes and
+//! brings the rest of the graph in line.
+
+use super::shape_inference::ShapeInferencePass;
+use super::{Pass, PassError, PassResult};
+use crate::attribute::{Attr, AttrValue};
+use crate::graph::Graph;
+use crate::model::Model;
+use crate::schema::onnx_inference::{int64_tensor, tensor_ints};
+use crate::schema::SchemaRegistry;
+use crate::shape::{Shape, SymbolicDim};
+use crate::value::Value;
+use indexmap::IndexMap;
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+/// Sets the shapes of graph inputs and propagates the change.
+///
+/// Changes that replace a named dimension, such as `N` becoming `1` or
+/// `batch`, are applied to every shape in the graph that mentions the
+/// name, including expressions and other inputs. Any other change, such
+/// as making a static dimension dynamic, clears the shapes of the values
+/// computed from the changed inputs.
+///
+/// Shape inference is then run to fill in the shapes again. When a
+/// static dimension becomes dynamic, the constant targets of `Reshape`
+/// nodes that repeated its old value at the same position are changed to
+/// copy the dimension (`0`) instead, as long as no other node uses the
+/// target.
+#[derive(Debug)]
+pub struct SetInputShapesPass<'r> {
+    registry: &'r SchemaRegistry,
+    shapes: IndexMap<String, Shape>,
+    dims: HashMap<String, SymbolicDim>,
+}
+
+impl<'r> SetInputShapesPass<'r> {
+    pub fn new(registry: &'r SchemaRegistry) -> Self {
+        Self {
+            registry,
+            shapes: IndexMap::new(),
+            dims: HashMap::new(),
+        }
+    }
+
+    /// Sets the shape of the graph input `name`.
+    pub fn input_shape(mut self, name: impl Into<String>, shape: Shape) -> Self {
+        self.shapes.insert(name.into(), shape);
+        self
+    }
+
+    /// Replaces the named dimension `name` throughout the graph, e.g. to
+    /// fix `batch` to 1 or rename it.
+    pub fn dim(mut self, name: impl Into<String>, dim: impl Into<SymbolicDim>) -> Self {
+        self.dims.insert(name.into(), dim.into());
+        self
+    }
+}
+
+impl Pass for SetInputShapesPass<'_> {
+    fn run(&mut self, model: &mut Model) -> Result<PassResult, PassError> {
+        let graph = &mut model.graph;
+        let mut bindings = self.dims.clone();
+        let mut changed = HashSet::new();
+        for (name, shape) in &self.shapes {
+            let input = graph
+                .inputs
+                .iter()
+                .find(|v| &v.name == name)
+                .ok_or_else(|| PassError::UnknownInput(name.clone()))?;
+            let old = input.shape.as_ref().map(|s| s.dims().to_vec());
+            let renames = old.as_ref().and_then(|old| renamed_dims(old, shape.dims()));
+            match renames {
+                Some(renames) => bindings.extend(renames),
+                None => {
+                    changed.insert(name.clone());
+                }
+            }
+        }
+
+        let mut modified = substitute_graph(graph, &bindings);
+        for input in graph.inputs.iter_mut() {
+            if let Some(shape) = self.shapes.get(&input.name) {
+                if input.shape.as_ref() != Some(shape) {
+                    input.shape = Some(shape.clone());
+                    modified = true;
+                }
+            }
+        }
+        if !changed.is_empty() {
+            let inputs: HashMap<String, Value> = graph
+                .inputs
+                .iter()
+                .filter(|v| changed.contains(&v.name))
+                .map(|v| (v.name.clone(), v.clone()))
+                .collect();
+            // Reshape targets are checked against the shapes the data had
+            // before the change
+            let reshapes = reshape_inputs(graph);
+            taint_graph(graph, &mut changed);
+            clear_graph(graph, &changed, &inputs);
+            modified = true;
+
+            // Fixing a target can make the data of a later Reshape dynamic
+            // too, so the targets are fixed until none changes
+            loop {
+                ShapeInferencePass::new(self.registry).run(model)?;
+                let indices: Vec<usize> = model
+                    .graph
+                    .iter_nodes()
+                    .enumerate()
+                    .filter(|(_, node)| node.op_type == "Reshape" && node.domain.is_empty())
+                    .map(|(index, _)| index)
+                    .collect();
+                let mut rewritten = HashSet::new();
+                for index in indices {
+                    let node = model.graph.iter_nodes().nth(index).expect("node exists");
+                    let Some(old) = node.inputs.first().and_then(|x| reshapes.get(&x.name)) else {
+                        continue;
+                    };
+                    if let Some(output) = fix_reshape_target(&mut model.graph, index, old) {
+                        rewritten.insert(output);
+                    }
+                }
+                if rewritten.is_empty() {
+                    break;
+                }
+                taint_graph(&model.graph, &mut rewritten);
+                clear_graph(&mut model.graph, &rewritten, &HashMap::new());
+            }
+        }
+        let inferred = ShapeInferencePass::new(self.registry).run(model)?;
+        Ok(PassResult::new(modified || inferred.modified))
+    }
+}
+
+/// Returns the named dimensions that `old` renames to get `new`, or `None`
+/// if the change is not just renaming.
+fn renamed_dims(old: &[SymbolicDim], new: &[SymbolicDim]) -> Option<HashMap<String, SymbolicDim>> {
+    if old.len() != new.len() {
+        return None;
+    }
+    let mut renames: HashMap<String, SymbolicDim> = HashMap::new();
+    for (old, new) in old.iter().zip(new) {
+        match old {
+            _ if old == new => {}
+            SymbolicDim::Symbol(Some(name)) if !new.is_unknown() => {
+                if renames
+                    .insert(name.clone(), new.clone())
+                    .is_some_and(|d| d != *new)
+                {
+                    return None;
+                }
+            }
+            _ => return None,
+        }
+    }
+    Some(renames)
+}
+
+fn substitute_dim(dim: &SymbolicDim, bindings: &HashMap<String, SymbolicDim>) -> SymbolicDim {
+    match dim {
+        SymbolicDim::Symbol(Some(name)) => {
+            bindings.get(name).cloned().unwrap_or_else(|| dim.clone())
+        }
+        SymbolicDim::Expr(expr) => {
+            SymbolicDim::from_expr(expr.replace_symbols(&|name| bindings.get(name)?.to_expr()))
+        }
+        _ => dim.clone(),
+    }
+}
+
+/// Applies `f` to every value of the graph and its subgraphs, returning
+/// whether any call returned true.
+fn for_each_value(graph: &mut Graph, f: &mut dyn FnMut(&mut Value) -> bool) -> bool {
+    let mut modified = false;
+    for value in graph
+        .inputs
+        .iter_mut()
+        .chain(graph.outputs.iter_mut())
+        .chain(graph.initializers.values_mut())
+    {
+        modified |= f(value);
+    }
+    for node in graph.iter_nodes_mut() {
+        for value in node.inputs.iter_mut().chain(node.outputs.iter_mut()) {
+            modified |= f(value);
+        }
+        for attr in node.attributes.values_mut() {
+            let graphs = match attr.as_value_mut() {
+                Some(Attr {
+                    value: AttrValue::Graph(graph),
+                    ..
+                }) => std::slice::from_mut(graph),
+                Some(Attr {
+                    value: AttrValue::Graphs(graphs),
+                    ..
+                }) => graphs.as_mut_slice(),
+                _ => continue,
+            };
+            for graph in graphs {
+                modified |= for_each_value(Rc::make_mut(graph), f);
+            }
+        }
+    }
+    modified
+}
+
+/// Replaces named dimensions in every shape of the graph.
+fn substitute_graph(graph: &mut Graph, bindings: &HashMap<String, SymbolicDim>) -> bool {
+    if bindings.is_empty() {
+        return false;
+    }
+    for_each_value(graph, &mut |value| {
+        let Some(shape) = &value.shape else {
+            return false;
+        };
+        let dims: Vec<_> = shape
+            .dims()
+            .iter()
+            .map(|d| substitute_dim(d, bindings))
+            .collect();
+        if dims == shape.dims() {
+            return false;
+        }
+        let mut substituted = Shape::new(dims);
+        for i in 0..substituted.rank() {
+            substituted.set_denotation(i, shape.get_denotation(i).map(str::to_string));
+        }
+        value.shape = Some(substituted);
+        true
+    })
+}
+
+/// Adds the names of the values computed from `tainted` values to the set.
+fn taint_graph(graph: &Graph, tainted: &mut HashSet<String>) {
+    for node in graph.iter_nodes() {
+        let mut reads_tainted = node.inputs.iter().any(|v| tainted.contains(&v.name));
+        for attr in node.attributes.values() {
+            let graphs = match attr.as_value() {
+                Some(Attr {
+                    value: AttrValue::Graph(graph),
+                    ..
+                }) => std::slice::from_ref(graph),
+                Some(Attr {
+                    value: AttrValue::Graphs(graphs),
+                    ..
+                }) => graphs.as_slice(),
+                _ => continue,
+            };
+            for graph in graphs {
+                taint_graph(graph, tainted);
+                reads_tainted |= graph.outputs.iter().any(|v| tainted.contains(&v.name));
+            }
+        }
+        if reads_tainted {
+            tainted.extend(node.outputs.iter().map(|v| v.name.clone()));
+        }
+    }
+}
+
+/// Clears the shapes of tainted values, except for the copies of the
+/// changed inputs, which are given their new shape.
+fn clear_graph(graph: &mut Graph, tainted: &HashSet<String>, inputs: &HashMap<String, Value>) {
+    let graph_inputs: HashSet<String> = graph.inputs.iter().map(|v| v.name.clone()).collect();
+    for_each_value(graph, &mut |value| {
+        if !tainted.contains(&value.name) {
+            return false;
+        }
+        let shape = match inputs.get(&value.name) {
+            Some(input) => input.shape.clone(),
+            None if graph_inputs.contains(&value.name) => return false,
+            None => None,
+        };
+        value.shape = shape;
+        true
+    });
+}
+
+/// Returns the shapes of the data inputs of the `Reshape` nodes of the
+/// graph, keyed by name.
+fn reshape_inputs(graph: &Graph) -> HashMap<String, Shape> {
+    graph
+        .iter_nodes()
+        .filter(|node| node.op_type == "Reshape" && node.domain.is_empty())
+        .filter_map(|node| {
+            let data = node.inputs.first()?;
+            Some((data.name.clone(), data.shape.clone()?))
+        })
+        .collect()
+}
+
+/// Rewrites the constant target of the `Reshape` node at `index` for the
+/// dimensions of the data that have changed since it had shape `old`; see
+/// [`rewrite_target`].
+///
+/// Returns the name of the output, whose shape is now stale, or `None` if
+/// the target was not rewritten.
+fn fix_reshape_target(graph: &mut Graph, index: usize, old: &Shape) -> Option<String> {
+    let node = graph.iter_nodes().nth(index)?;
+    if node.attr_int_or("allowzero", 0).ok()? != 0 {
+        return None;
+    }
+    let new = node.inputs.first()?.shape.clone()?;
+    let target_name = node.inputs.get(1)?.name.clone();
+    let output = node.outputs.first()?.name.clone();
+
+    // The target is either an initializer or the output of a Constant node
+    let producer = graph
+        .iter_nodes()
+        .position(|n| n.outputs.iter().any(|v| v.name == target_name));
+    let constant = producer.and_then(|i| graph.iter_nodes().nth(i));
+    if constant.is_some_and(|n| n.op_type != "Constant" || !n.domain.is_empty()) {
+        return None;
+    }
+    let tensor = match producer {
+        Some(i) => graph
+            .iter_nodes()
+            .nth(i)?
+            .attr_tensor("value")
+            .ok()?
+            .clone(),
+        None => graph.initializers.get(&target_name)?.const_value.clone()?,
+    };
+    let target = rewrite_target(&tensor_ints(&tensor)?, old.dims(), new.dims())?;
+    // Other users of the target would be changed too
+    let uses = graph
+        .iter_nodes()
+        .flat_map(|n| &n.inputs)
+        .filter(|v| v.name == target_name)
+        .count();
+    if uses != 1 || graph.outputs.iter().any(|v| v.name == target_name) {
+        return None;
+    }
+
+    let tensor = int64_tensor(&target, tensor.shape.clone());
+    match producer {
+        Some(i) => {
+            let constant = graph.iter_nodes_mut().nth(i)?;
+            constant.set_attribute(Attr::tensor("value", tensor.clone()));
+        }
+        None => graph.initializers.get_mut(&target_name)?.const_value = Some(tensor.clone()),
+    }
+    let node = graph.iter_nodes_mut().nth(index)?;
+    if node.inputs[1].const_value.is_some() {
+        node.inputs[1].const_value = Some(tensor);
+    }
+    Some(output)
+}
+
+/// Rewrites a reshape target for data whose shape changed from `old` to
+/// `new`, or returns `None` if it need not or cannot be rewritten.
+///
+/// The target and the old shape are split into runs of dimensions with the
+/// same number of elements. A target dimension that repeats a changed
+/// dimension at the same position becomes 0, which copies the new
+/// dimension, and any other single target dimension covering changed
+/// dimensions becomes -1, which infers it.
+fn rewrite_target(target: &[i64], old: &[SymbolicDim], new: &[SymbolicDim]) -> Option<Vec<i64>> {
+    if old.len() != new.len() {
+        return None;
+    }
+    let unknown = || SymbolicDim::Symbol(None);
+    let prefixes = |dims: Vec<SymbolicDim>| {
+        let mut products = vec![SymbolicDim::Int(1)];
+        for dim in dims {
+            products.push(products.last().cloned().unwrap() * dim);
+        }
+        products
+    };
+    let target_dims = target.iter().enumerate().map(|(i, &value)| match value {
+        0 => old.get(i).cloned().unwrap_or_else(unknown),
+        value if value > 0 => SymbolicDim::Int(value),
+        _ => unknown(),
+    });
+    let target_prefixes = prefixes(target_dims.collect());
+    let old_prefixes = prefixes(old.to_vec());
+
+    let mut rewritten = target.to_vec();
+    let mut inferred = target.contains(&-1);
+    let (mut start, mut old_start) = (0, 0);
+    for (end, product) in target_prefixes.iter().enumerate().skip(1) {
+        let Some(old_end) =
+            (old_start + 1..=old.len()).find(|&j| product.is_same_as(&old_prefixes[j]))
+        else {
+            continue;
+        };
+        let changed = old[old_start..old_end] != new[old_start..old_end];
+        match (end - start, old_end - old_start) {
+            _ if !changed => {}
+            (1, 1) if start == old_start => rewritten[start] = 0,
+            (1, _) if !inferred => {
+                rewritten[start] = -1;
+                inferred = true;
+            }
+            _ => return None,
+        }
+        (start, old_start) = (end, old_end);
+    }
+    // The dimensions left over must not have changed
+    if old[old_start..] != new[old_start..] {
+        return None;
+    }
+    (rewritten != target).then_some(rewritten)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enums::DataType;
+    use crate::node::Node;
+    use crate::test_support::{self, sym, value};
+
+    /// Builds `y = Reshape(Relu(x), target)` and infers its shapes.
+    fn model(x: &str, target: &[i64], registry: &SchemaRegistry) -> Model {
+        let x = x.parse::<Shape>().unwrap().dims().to_vec();
+        let mut graph = Graph::new();
+        graph.inputs.push(value("x", DataType::Float, x.clone()));
+        let mut shape = Value::new("target");
+        shape.const_value = Some(int64_tensor(target, Shape::new(vec![target.len() as i64])));
+        graph
+            .initializers
+            .insert("target".to_string(), shape.clone());
+        graph.append(Node::with_io(
+            "Relu",
+            vec![value("x", DataType::Float, x)],
+            ["r"],
+        ));
+        graph.append(Node::with_io(
+            "Reshape",
+            vec![Value::new("r"), shape],
+            ["y"],
+        ));
+        graph.outputs.push(Value::new("y"));
+        let mut model = test_support::model(graph, 18);
+        ShapeInferencePass::new(registry).run(&mut model).unwrap();
+        model
+    }
+
+    fn output_shape(model: &Model) -> String {
+        model.graph.outputs[0].shape.as_ref().unwrap().to_string()
+    }
+
+    #[test]
+    fn test_fix_dynamic_dim() {
+        let registry = SchemaRegistry::builtin();
+        let mut model = model("[N,4,8]", &[0, 32], &registry);
+        assert_eq!(output_shape(&model), "[N,32]");
+
+        let result = SetInputShapesPass::new(&registry)
+            .dim("N", 1)
+            .run(&mut model)
+            .unwrap();
+        assert!(result.modified);
+        assert_eq!(
+            model.graph.inputs[0].shape,
+            Some("[1,4,8]".parse().unwrap())
+        );
+        assert_eq!(output_shape(&model), "[1,32]");
+        let relu = model.graph.iter_nodes().next().unwrap();
+        assert_eq!(relu.outputs[0].shape, Some("[1,4,8]".parse().unwrap()));
+    }
+
+    #[test]
+    fn test_rename_dims_in_expressions() {
+        let registry = SchemaRegistry::builtin();
+        let mut model = model("[N,seq,8]", &[0, -1], &registry);
+        assert_eq!(output_shape(&model), "[N,8*seq]");
+
+        SetInputShapesPass::new(&registry)
+            .input_shape("x", "[batch,128,8]".parse().unwrap())
+            .run(&mut model)
+            .unwrap();
+        assert_eq!(output_shape(&model), "[batch,1024]");
+    }
+
+    #[test]
+    fn test_make_static_dim_dynamic() {
+        let registry = SchemaRegistry::builtin();
+        let mut model = model("[1,4,8]", &[1, 32], &registry);
+        assert_eq!(output_shape(&model), "[1,32]");
+
+        SetInputShapesPass::new(&registry)
+            .input_shape("x", Shape::new(vec![sym("N"), 4.into(), 8.into()]))
+            .run(&mut model)
+            .unwrap();
+        assert_eq!(output_shape(&model), "[N,32]");
+        let target = &model.graph.initializers["target"];
+        assert_eq!(
+            tensor_ints(target.const_value.as_ref().unwrap()),
+            Some(vec![0, 32])
+        );
+        let relu = model.graph.iter_nodes().next().unwrap();
+        assert_eq!(relu.inputs[0].shape, Some("[N,4,8]".parse().unwrap()));
+    }
+
+    #[test]
+    fn test_infer_merged_dims() {
+        let registry = SchemaRegistry::builtin();
+        let mut model = model("[2,2,2,3]", &[4, 2, 3], &registry);
+        assert_eq!(output_shape(&model), "[4,2,3]");
+
+        // The 2 at position 1 of the target is not the dimension at position
+        // 1 of the data, which is merged into the 4
+        SetInputShapesPass::new(&registry)
+            .input_shape("x", "[2,N,2,3]".parse().unwrap())
+            .run(&mut model)
+            .unwrap();
+        assert_eq!(output_shape(&model), "[2*N,2,3]");
+        let target = &model.graph.initializers["target"];
+        assert_eq!(
+            tensor_ints(target.const_value.as_ref().unwrap()),
+            Some(vec![-1, 2, 3])
+        );
+    }
+
+    #[test]
+    fn test_rewrite_target() {
+        let dims = |shape: &str| shape.parse::<Shape>().unwrap().dims().to_vec();
+        let rewrite =
+            |target: &[i64], old: &str, new: &str| rewrite_target(target, &dims(old), &dims(new));
+        assert_eq!(rewrite(&[1, 32], "[1,4,8]", "[N,4,8]"), Some(vec![0, 32]));
+        assert_eq!(rewrite(&[6, 4], "[2,3,4]", "[2,N,4]"), Some(vec![-1, 4]));
+        assert_eq!(rewrite(&[0, 12], "[N,3,4]", "[M,3,4]"), None);
+        // Only one dimension can be inferred
+        assert_eq!(rewrite(&[6, 20], "[2,3,4,5]", "[2,N,4,M]"), None);
+        assert_eq!(rewrite(&[-1, 4], "[2,3,4]", "[2,3,N]"), None);
+    }
+
+    #[test]
+    fn test_target_from_other_op() {
+        // The value of ConstantOfShape is not the target itself
+        let registry = SchemaRegistry::builtin();
+        let mut graph = Graph::new();
+        graph
+            .inputs
+            .push(value("x", DataType::Float, vec![2.into(), 2.into()]));
+        let mut target = Node::with_io("ConstantOfShape", vec![Value::new("n")], ["target"]);
+        target.set_attribute(Attr::tensor(
+            "value",
+            int64_tensor(&[2], Shape::new(vec![1])),
+        ));
+        let mut n = Value::new("n");
+        n.const_value = Some(int64_tensor(&[2], Shape::new(vec![1])));
+        graph.initializers.insert("n".to_string(), n);
+        graph.append(target);
+        graph.append(Node::with_io(
+            "Reshape",
+            vec![
+                value("x", DataType::Float, vec![2.into(), 2.into()]),
+                Value::new("target"),
+            ],
+            ["y"],
+        ));
+        graph.outputs.push(Value::new("y"));
+        let mut model = Model::new(graph);
+        model.set_opset_import("", 18);
+
+        SetInputShapesPass::new(&registry)
+            .input_shape("x", "[N,2]".parse().unwrap())
+            .run(&mut model)
+            .unwrap();
+        let constant = model.graph.iter_nodes().next().unwrap();
+        let value = constant.attr_tensor("value").unwrap();
+        assert_eq!(tensor_ints(value), Some(vec![2]));
+    }
+
+    #[test]
+    fn test_unknown_input() {
+        let registry = SchemaRegistry::builtin();
+        let mut model = model("[N,4,8]", &[0, 32], &registry);
+        let err = SetInputShapesPass::new(&registry)
+            .input_shape("z", Shape::new(vec![1]))
+            .run(&mut model)
+            .unwrap_err();
+        assert_eq!(err.to_string(), "graph has no input named 'z'");
+    }
+}
@@ -0,0 +1,116 @@
+// Copyright (c) ONNX Project Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Transformation passes over models.
+//!
+//! A pass takes a [`Model`] and modifies it in place, reporting whether
+//! anything was changed.
+
+pub mod default_attributes;
+pub mod dim_constraints;
+pub mod inliner;
+pub mod input_shapes;
+pub mod shape_inference;
+
+use crate::attribute::AttributeError;
+use crate::enums::AttributeType;
+use crate::model::Model;
+use crate::schema::InferenceError;
+use dim_constraints::DimConflict;
+use thiserror::Error;
+
+pub use default_attributes::{FillDefaultAttributesPass, StripDefaultAttributesPass};
+pub use dim_constraints::{DimClasses, UnifyDimsPass};
+pub use inliner::InlinePass;
+pub use input_shapes::SetInputShapesPass;
+pub use shape_inference::ShapeInferencePass;
+
+/// The result of running a pass.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PassResult {
+    /// Whether the pass modified the model.
+    pub modified: bool,
+}
+
+impl PassResult {
+    pub fn new(modified: bool) -> Self {
+        Self { modified }
+    }
+}
+
+/// An error raised while running a pass.
+#[derive(Debug, Error)]
+pub enum PassError {
+    /// A function calls itself, directly or indirectly.
+    #[error("recursive function call: {}", .0.join(" -> "))]
+    RecursiveFunction(Vec<String>),
+
+    /// A call site passes more inputs than the function declares.
+    #[error(
+        "call to function '{function}' has {actual} inputs, but the function declares {expected}"
+    )]
+    TooManyInputs {
+        function: String,
+        expected: usize,
+        actual: usize,
+    },
+
+    /// A call site expects more outputs than the function produces.
+    #[error(
+        "call to function '{function}' has {actual} outputs, but the function declares {expected}"
+    )]
+    TooManyOutputs {
+        function: String,
+        expected: usize,
+        actual: usize,
+    },
+
+    /// A reference attribute in a function body is bound to a value of
+    /// another type.
+    #[error(
+        "attribute '{attribute}' of function '{function}' is {actual}, but is referenced as {expected}"
+    )]
+    RefAttrTypeMismatch {
+        function: String,
+        attribute: String,
+        expected: AttributeType,
+        actual: AttributeType,
+    },
+
+    /// A reference attribute in a function body is bound neither at the
+    /// call site nor by a default.
+    #[error(
+        "call to function '{function}' does not set attribute '{attribute}', which has no default"
+    )]
+    MissingAttribute { function: String, attribute: String },
+
+    /// Two opset imports for the same domain disagree on the version.
+    #[error("conflicting opset versions for domain '{domain}': {existing} and {new}")]
+    OpsetConflict {
+        domain: String,
+        existing: i32,
+        new: i32,
+    },
+
+    /// Type and shape inference failed or conflicts with an annotation.
+    #[error(transparent)]
+    Inference(#[from] InferenceError),
+
+    /// Constraints on symbolic dimensions contradict each other.
+    #[error("{node}: {conflict}")]
+    DimConflict { node: String, conflict: DimConflict },
+
+    /// A pass was asked to change a graph input that does not exist.
+    #[error("graph has no input named '{0}'")]
+    UnknownInput(String),
+
+    /// An attribute a pass relies on could not be read.
+    #[error(transparent)]
+    Attribute(#[from] AttributeError),
+}
+
+/// A transformation over a model.
+pub trait Pass {
+    /// Runs the pass, modifying the model in place.
+    fn run(&mut self, model: &mut Model) -> Result<PassResult, PassError>;
+}
@@ -0,0 +1,943 @@
+// Copyright (c) ONNX Project Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Type and shape inference.
+//!
+//! [`ShapeInferencePass`] walks the graph in order and runs the inference
+//! function of each node's schema, filling in the element types and shapes
+//! of the values it produces. Inferred information is merged with the
+//! annotations already on the values: an integer dimension refines a
+//! symbolic one, named symbols are kept over unknown dimensions, and any
+//! disagreement between two known facts is reported as an error.
+//!
+//! Control flow operators are inferred by the pass itself, since their
+//! outputs depend on their subgraphs: the branches of `If` are inferred
+//! and joined, and the bodies of `Loop`, `Scan` and `SequenceMap` are
+//! inferred with the types of the loop-carried, scanned and mapped values. Calls to functions of the
+//! model are inferred by inferring the function body for the call site.
+
+use super::{Pass, PassError, PassResult};
+use crate::attribute::{Attr, AttrValue};
+use crate::enums::DataType;
+use crate::function::{Function, FunctionId};
+use crate::graph::Graph;
+use crate::model::Model;
+use crate::node::Node;
+use crate::schema::onnx_inference::{generalize, int64_tensor, tensor_ints, unknown};
+use crate::schema::{
+    opset_version, InferenceContext, InferenceError, InferredOutput, SchemaRegistry,
+};
+use crate::shape::{Shape, SymbolicDim};
+use crate::tensor::Tensor;
+use crate::types::TensorType;
+use crate::value::Value;
+use indexmap::IndexMap;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// Infers the element types and shapes of the values in a model.
+///
+/// By default the values of initializers and `Constant` nodes are used to
+/// infer data-dependent operators such as `Reshape` and `Slice`. Values whose
+/// type or shape cannot be inferred are left as they are.
+#[derive(Debug)]
+pub struct ShapeInferencePass<'r> {
+    registry: &'r SchemaRegistry,
+    use_constants: bool,
+}
+
+impl<'r> ShapeInferencePass<'r> {
+    pub fn new(registry: &'r SchemaRegistry) -> Self {
+        Self {
+            registry,
+            use_constants: true,
+        }
+    }
+
+    /// Sets whether constant values are used to infer data-dependent
+    /// operators.
+    pub fn use_constants(mut self, use_constants: bool) -> Self {
+        self.use_constants = use_constants;
+        self
+    }
+}
+
+impl Pass for ShapeInferencePass<'_> {
+    fn run(&mut self, model: &mut Model) -> Result<PassResult, PassError> {
+        let mut engine = Engine {
+            registry: self.registry,
+            functions: &model.functions,
+            use_constants: self.use_constants,
+            scopes: Vec::new(),
+            call_stack: Vec::new(),
+            modified: false,
+        };
+        engine.infer_graph(&mut model.graph, &model.opset_imports)?;
+        Ok(PassResult::new(engine.modified))
+    }
+}
+
+/// The values visible in a graph.
+#[derive(Default)]
+struct Scope {
+    values: HashMap<String, InferredOutput>,
+    constants: HashMap<String, Tensor>,
+}
+
+struct Engine<'a> {
+    registry: &'a SchemaRegistry,
+    functions: &'a IndexMap<FunctionId, Function>,
+    use_constants: bool,
+    scopes: Vec<Scope>,
+    /// The functions being inferred, to stop at recursive calls.
+    call_stack: Vec<FunctionId>,
+    modified: bool,
+}
+
+impl Engine<'_> {
+    fn lookup(&self, name: &str) -> Option<&InferredOutput> {
+        self.scopes.iter().rev().find_map(|s| s.values.get(name))
+    }
+
+    fn constant(&self, name: &str) -> Option<&Tensor> {
+        self.scopes.iter().rev().find_map(|s| s.constants.get(name))
+    }
+
+    /// Makes a value visible in the innermost scope.
+    fn define(&mut self, value: &Value, constant: Option<Tensor>) {
+        if value.name.is_empty() {
+            return;
+        }
+        let scope = self.scopes.last_mut().expect("inside a scope");
+        scope.values.insert(value.name.clone(), known(value));
+        if let Some(tensor) = constant.filter(|_| self.use_constants) {
+            scope.constants.insert(value.name.clone(), tensor);
+        }
+    }
+
+    fn infer_graph(
+        &mut self,
+        graph: &mut Graph,
+        outer_imports: &HashMap<String, i32>,
+    ) -> Result<(), PassError> {
+        let mut opset_imports = outer_imports.clone();
+        opset_imports.extend(graph.opset_imports.iter().map(|(d, v)| (d.clone(), *v)));
+
+        self.scopes.push(Scope::default());
+        for input in &graph.inputs {
+            self.define(input, None);
+        }
+        for initializer in graph.initializers.values() {
+            // An initializer that is also an input is only a default value
+            let is_input = graph.inputs.iter().any(|v| v.name == initializer.name);
+            let constant = initializer.const_value.clone().filter(|_| !is_input);
+            self.define(initializer, constant);
+        }
+        let result = self.infer_nodes(graph, &opset_imports);
+        self.scopes.pop();
+        result
+    }
+
+    fn infer_nodes(
+        &mut self,
+        graph: &mut Graph,
+        opset_imports: &HashMap<String, i32>,
+    ) -> Result<(), PassError> {
+        for node in graph.nodes.iter_mut() {
+            self.infer_node(node, opset_imports)?;
+        }
+        let label = match &graph.name {
+            Some(name) => format!("graph '{}'", name),
+            None => "graph".to_string(),
+        };
+        for output in graph.outputs.iter_mut() {
+            self.refresh(output, &label, "output")?;
+        }
+        Ok(())
+    }
+
+    /// Merges what is known about a value into one of its copies.
+    fn refresh(&mut self, value: &mut Value, label: &str, role: &str) -> Result<(), PassError> {
+        let Some(inferred) = self.lookup(&value.name).cloned() else {
+            return Ok(());
+        };
+        self.merge(value, &inferred, label, role)
+    }
+
+    fn merge(
+        &mut self,
+        value: &mut Value,
+        inferred: &InferredOutput,
+        label: &str,
+        role: &str,
+    ) -> Result<(), PassError> {
+        match merge_into(value, inferred) {
+            Ok(modified) => {
+                self.modified |= modified;
+                Ok(())
+            }
+            Err(message) => Err(InferenceError {
+                node: label.to_string(),
+                message: format!("{} {}", role, message),
+            }
+            .into()),
+        }
+    }
+
+    fn infer_node(
+        &mut self,
+        node: &mut Node,
+        opset_imports: &HashMap<String, i32>,
+    ) -> Result<(), PassError> {
+        let label = node.label();
+        for input in node.inputs.iter_mut() {
+            self.refresh(input, &label, "input")?;
+        }
+
+        let outputs = match (node.domain.as_str(), node.op_type.as_str()) {
+            ("" | "ai.onnx", "If") => self.infer_if(node, opset_imports)?,
+            ("" | "ai.onnx", "Loop") => self.infer_loop(node, opset_imports)?,
+            ("" | "ai.onnx", "Scan") => self.infer_scan(node, opset_imports)?,
+            ("" | "ai.onnx", "SequenceMap") => self.infer_sequence_map(node, opset_imports)?,
+            _ => {
+                for graph in subgraphs_mut(node) {
+                    self.infer_graph(graph, opset_imports)?;
+                }
+                match self.functions.get(&node.function_id()) {
+                    Some(function) => self.infer_call(function, node)?,
+                    None => self.infer_with_schema(node, opset_imports)?,
+                }
+            }
+        };
+
+        for (value, inferred) in node.outputs.iter_mut().zip(&outputs) {
+            if !value.name.is_empty() {
+                self.merge(value, inferred, &label, "output")?;
+            }
+        }
+        let constants = if self.use_constants {
+            self.fold_constants(node)
+        } else {
+            Vec::new()
+        };
+        let mut constants = constants.into_iter();
+        for value in &node.outputs {
+            self.define(value, constants.next().flatten());
+        }
+        Ok(())
+    }
+
+    fn infer_with_schema(
+        &self,
+        node: &Node,
+        opset_imports: &HashMap<String, i32>,
+    ) -> Result<Vec<InferredOutput>, PassError> {
+        let Some(schema) = self.registry.schema_for(node, opset_imports) else {
+            return Ok(Vec::new());
+        };
+        let version = opset_version(&node.domain, opset_imports).unwrap_or_default();
+        let mut ctx = InferenceContext::new(node, version);
+        if self.use_constants {
+            for (i, input) in node.inputs.iter().enumerate() {
+                if let Some(tensor) = self.constant(&input.name) {
+                    ctx.set_input_constant(i, tensor);
+                }
+            }
+        }
+        schema.infer(&mut ctx)?;
+        Ok(ctx.into_outputs())
+    }
+
+    /// Infers a call to a model function by inferring its body with the
+    /// types of the call's inputs.
+    fn infer_call(
+        &mut self,
+        function: &Function,
+        call: &Node,
+    ) -> Result<Vec<InferredOutput>, PassError> {
+        let id = function.id();
+        if self.call_stack.contains(&id) {
+            return Ok(Vec::new());
+        }
+
+        let mut scope = Scope::default();
+        for (param, actual) in function.inputs.iter().zip(&call.inputs) {
+            if actual.name.is_empty() {
+                continue;
+            }
+            scope.values.insert(param.name.clone(), known(actual));
+            if let Some(tensor) = self.constant(&actual.name) {
+                scope.constants.insert(param.name.clone(), tensor.clone());
+            }
+        }
+        let bindings = function.bind_attributes(call);
+        let mut body = function.nodes.clone();
+        for node in body.iter_mut() {
+            node.resolve_ref_attributes(&bindings);
+        }
+
+        // The body only sees its parameters, and inferring it does not
+        // modify the model
+        let outer_scopes = std::mem::replace(&mut self.scopes, vec![scope]);
+        let modified = self.modified;
+        self.call_stack.push(id);
+        let result = body
+            .iter_mut()
+            .try_for_each(|node| self.infer_node(node, &function.opset_imports));
+        let outputs = function
+            .outputs
+            .iter()
+            .map(|output| self.lookup(&output.name).cloned().unwrap_or_default())
+            .collect();
+        self.call_stack.pop();
+        self.modified = modified;
+        self.scopes = outer_scopes;
+        result.map(|()| outputs)
+    }
+
+    /// Infers a subgraph whose inputs take the given types and shapes, and
+    /// returns what is known about its outputs.
+    fn infer_subgraph(
+        &mut self,
+        graph: &mut Graph,
+        inputs: &[InferredOutput],
+        opset_imports: &HashMap<String, i32>,
+    ) -> Result<Vec<InferredOutput>, PassError> {
+        let label = match &graph.name {
+            Some(name) => format!("graph '{}'", name),
+            None => "graph".to_string(),
+        };
+        for (value, inferred) in graph.inputs.iter_mut().zip(inputs) {
+            self.merge(value, inferred, &label, "input")?;
+        }
+        self.infer_graph(graph, opset_imports)?;
+        Ok(graph.outputs.iter().map(known).collect())
+    }
+
+    fn infer_if(
+        &mut self,
+        node: &mut Node,
+        opset_imports: &HashMap<String, i32>,
+    ) -> Result<Vec<InferredOutput>, PassError> {
+        let mut branches = Vec::new();
+        for name in ["then_branch", "else_branch"] {
+            if let Some(graph) = graph_attr_mut(node, name) {
+                branches.push(self.infer_subgraph(graph, &[], opset_imports)?);
+            }
+        }
+        let [then_outputs, else_outputs] = branches.as_slice() else {
+            return Ok(branches.into_iter().next().unwrap_or_default());
+        };
+        let mut outputs = Vec::new();
+        for (i, (a, b)) in then_outputs.iter().zip(else_outputs).enumerate() {
+            let dtype = match (a.dtype, b.dtype) {
+                (Some(x), Some(y)) if x != y => {
+                    return Err(InferenceError {
+                        node: node.label(),
+                        message: format!("branches produce {} and {} for output {}", x, y, i),
+                    }
+                    .into());
+                }
+                (x, y) => x.or(y),
+            };
+            let shape = match (&a.shape, &b.shape) {
+                (Some(x), Some(y)) => generalize(x, y),
+                _ => None,
+            };
+            outputs.push(InferredOutput { dtype, shape });
+        }
+        Ok(outputs)
+    }
+
+    fn infer_loop(
+        &mut self,
+        node: &mut Node,
+        opset_imports: &HashMap<String, i32>,
+    ) -> Result<Vec<InferredOutput>, PassError> {
+        let carried: Vec<InferredOutput> = node.inputs.iter().skip(2).map(known).collect();
+        // The loop runs exactly the trip count only without a condition,
+        // which could end it earlier
+        let has_cond = node.inputs.get(1).is_some_and(|c| !c.name.is_empty());
+        let trip_count = node
+            .inputs
+            .first()
+            .filter(|_| !has_cond)
+            .and_then(|m| tensor_ints(self.constant(&m.name)?)?.first().copied());
+        let mut inputs = vec![scalar(DataType::Int64), scalar(DataType::Bool)];
+        inputs.extend(carried.iter().cloned());
+        let Some(body) = graph_attr_mut(node, "body") else {
+            return Ok(Vec::new());
+        };
+        let body_outputs = self.infer_subgraph(body, &inputs, opset_imports)?;
+
+        // The body outputs the condition, then the carried values, then the
+        // values to concatenate across iterations
+        let mut outputs = Vec::new();
+        for (i, output) in body_outputs.iter().skip(1).enumerate() {
+            outputs.push(match carried.get(i) {
+                Some(initial) => join_iterations(initial, output),
+                None => stack(output, trip_count.map_or_else(unknown, SymbolicDim::Int), 0),
+            });
+        }
+        Ok(outputs)
+    }
+
+    fn infer_scan(
+        &mut self,
+        node: &mut Node,
+        opset_imports: &HashMap<String, i32>,
+    ) -> Result<Vec<InferredOutput>, PassError> {
+        let num_scan_inputs = node
+            .attr_int("num_scan_inputs")
+            .map_err(InferenceError::from)?;
+        let num_scan_inputs = usize::try_from(num_scan_inputs).unwrap_or_default();
+        let input_axes = node
+            .attr_ints_or("scan_input_axes", &[])
+            .map_err(InferenceError::from)?
+            .to_vec();
+        let output_axes = node
+            .attr_ints_or("scan_output_axes", &[])
+            .map_err(InferenceError::from)?
+            .to_vec();
+        // Scan-8 takes the sequence lengths first, and its inputs and
+        // outputs have a leading batch axis the body does not see
+        let version = opset_version(&node.domain, opset_imports).unwrap_or_default();
+        let batched = version < 9;
+        let operands = node.inputs.get(usize::from(batched)..).unwrap_or_default();
+        let num_state = operands.len().saturating_sub(num_scan_inputs);
+
+        let mut batch = None;
+        let mut operands: Vec<InferredOutput> = operands.iter().map(known).collect();
+        if batched {
+            for operand in &mut operands {
+                operand.shape = operand.shape.take().and_then(|shape| {
+                    let (first, rest) = shape.dims().split_first()?;
+                    batch.get_or_insert_with(|| first.clone());
+                    Some(Shape::new(rest.to_vec()))
+                });
+            }
+        }
+        let state = operands[..num_state].to_vec();
+        let mut inputs = state.clone();
+        let mut sequence_len = None;
+        for (i, mut slice) in operands.into_iter().skip(num_state).enumerate() {
+            if let Some(shape) = &slice.shape {
+                let axis = input_axes.get(i).copied().unwrap_or(0);
+                slice.shape = resolve_axis(axis, shape.rank()).map(|axis| {
+                    sequence_len.get_or_insert_with(|| shape.dims()[axis].clone());
+                    let mut dims = shape.dims().to_vec();
+                    dims.remove(axis);
+                    Shape::new(dims)
+                });
+            }
+            inputs.push(slice);
+        }
+        let Some(body) = graph_attr_mut(node, "body") else {
+            return Ok(Vec::new());
+        };
+        let body_outputs = self.infer_subgraph(body, &inputs, opset_imports)?;
+
+        let sequence_len = sequence_len.unwrap_or_else(unknown);
+        let batch = batch.unwrap_or_else(unknown);
+        let mut outputs = Vec::new();
+        for (i, output) in body_outputs.iter().enumerate() {
+            let output = match state.get(i) {
+                Some(initial) => join_iterations(initial, output),
+                None => {
+                    let axis = output_axes.get(i - num_state).copied().unwrap_or(0);
+                    stack(output, sequence_len.clone(), axis)
+                }
+            };
+            outputs.push(if batched {
+                stack(&output, batch.clone(), 0)
+            } else {
+                output
+            });
+        }
+        Ok(outputs)
+    }
+
+    /// Infers `SequenceMap`, whose body maps the elements of the input
+    /// sequences to the elements of the output sequences.
+    fn infer_sequence_map(
+        &mut self,
+        node: &mut Node,
+        opset_imports: &HashMap<String, i32>,
+    ) -> Result<Vec<InferredOutput>, PassError> {
+        // Sequences are annotated with the type of their elements
+        let inputs: Vec<InferredOutput> = node.inputs.iter().map(known).collect();
+        let Some(body) = graph_attr_mut(node, "body") else {
+            return Ok(Vec::new());
+        };
+        self.infer_subgraph(body, &inputs, opset_imports)
+    }
+
+    /// Returns the constant values of the node's outputs that can be
+    /// computed without evaluating the node.
+    fn fold_constants(&self, node: &Node) -> Vec<Option<Tensor>> {
+        if !matches!(node.domain.as_str(), "" | "ai.onnx") {
+            return Vec::new();
+        }
+        let constant = match node.op_type.as_str() {
+            "Constant" => node
+                .attributes
+                .keys()
+                .find_map(|name| constant_attribute(node.get_attribute(name)?)),
+            "Identity" => node
+                .inputs
+                .first()
+                .and_then(|input| self.constant(&input.name))
+                .cloned(),
+            "Shape" => {
+                let shape = node.inputs.first().and_then(|x| x.shape.as_ref());
+                let dims = shape.and_then(Shape::to_vec);
+                let rank = dims.as_ref().map_or(0, Vec::len) as i64;
+                let clamp =
+                    |index: i64| (if index < 0 { index + rank } else { index }).clamp(0, rank);
+                let start = clamp(node.attr_int_or("start", 0).unwrap_or(0));
+                let end = clamp(node.attr_int_or("end", rank).unwrap_or(rank));
+                dims.map(|dims| {
+                    let dims = &dims[start as usize..end.max(start) as usize];
+                    int64_tensor(dims, Shape::new(vec![dims.len() as i64]))
+                })
+            }
+            _ => None,
+        };
+        vec![constant]
+    }
+}
+
+/// Returns what is annotated on a value.
+fn known(value: &Value) -> InferredOutput {
+    InferredOutput {
+        dtype: value.type_.as_ref().map(|t| t.elem_type),
+        shape: value.shape.clone(),
+    }
+}
+
+fn scalar(dtype: DataType) -> InferredOutput {
+    InferredOutput {
+        dtype: Some(dtype),
+        shape: Some(Shape::scalar()),
+    }
+}
+
+/// Returns the tensor held by a `Constant` attribute.
+fn constant_attribute(attr: &Attr) -> Option<Tensor> {
+    match (attr.name.as_str(), &attr.value) {
+        ("value", AttrValue::Tensor(tensor)) => Some(tensor.as_ref().clone()),
+        ("value_int", AttrValue::Int(value)) => Some(int64_tensor(&[*value], Shape::scalar())),
+        ("value_ints", AttrValue::Ints(values)) => {
+            Some(int64_tensor(values, Shape::new(vec![values.len() as i64])))
+        }
+        _ => None,
+    }
+}
+
+fn resolve_axis(axis: i64, rank: usize) -> Option<usize> {
+    let index = if axis < 0 { axis + rank as i64 } else { axis };
+    usize::try_from(index).ok().filter(|&i| i < rank)
+}
+
+/// Returns the type of a value carried across loop iterations, whose shape
+/// may change from one iteration to the next.
+fn join_iterations(initial: &InferredOutput, output: &InferredOutput) -> InferredOutput {
+    InferredOutput {
+        dtype: output.dtype.or(initial.dtype),
+        shape: match (&initial.shape, &output.shape) {
+            (Some(a), Some(b)) => generalize(a, b),
+            _ => None,
+        },
+    }
+}
+
+/// Returns the type of the values produced in every iteration, stacked
+/// along `axis`.
+fn stack(output: &InferredOutput, len: SymbolicDim, axis: i64) -> InferredOutput {
+    let shape = output.shape.as_ref().and_then(|shape| {
+        let axis = resolve_axis(axis, shape.rank() + 1)?;
+        let mut dims = shape.dims().to_vec();
+        dims.insert(axis, len);
+        Some(Shape::new(dims))
+    });
+    InferredOutput {
+        dtype: output.dtype,
+        shape,
+    }
+}
+
+/// Merges an inferred type and shape into the annotations of a value.
+///
+/// Returns whether the value changed, or a description of the conflict.
+fn merge_into(value: &mut Value, inferred: &InferredOutput) -> Result<bool, String> {
+    let mut modified = false;
+    if let Some(dtype) = inferred.dtype {
+        match &value.type_ {
+            Some(existing) if existing.elem_type != dtype => {
+                return Err(format!(
+                    "'{}' has type {}, but {} was inferred",
+                    value.name, existing.elem_type, dtype
+                ));
+            }
+            Some(_) => {}
+            None => {
+                value.type_ = Some(TensorType::new(dtype));
+                modified = true;
+            }
+        }
+    }
+    if let Some(shape) = &inferred.shape {
+        let merged = match &value.shape {
+            Some(existing) => existing.merge(shape).map_err(|_| {
+                format!(
+                    "'{}' has shape {}, but {} was inferred",
+                    value.name, existing, shape
+                )
+            })?,
+            None => shape.clone(),
+        };
+        if value.shape.as_ref() != Some(&merged) {
+            value.shape = Some(merged);
+            modified = true;
+        }
+    }
+    Ok(modified)
+}
+
+fn graph_attr_mut<'n>(node: &'n mut Node, name: &str) -> Option<&'n mut Graph> {
+    match node.attributes.get_mut(name)?.as_value_mut()? {
+        Attr {
+            value: AttrValue::Graph(graph),
+            ..
+        } => Some(Rc::make_mut(graph)),
+        _ => None,
+    }
+}
+
+fn subgraphs_mut(node: &mut Node) -> impl Iterator<Item = &mut Graph> {
+    node.attributes
+        .values_mut()
+        .filter_map(|attr| match attr.as_value_mut() {
+            Some(Attr {
+                value: AttrValue::Graph(graph),
+                ..
+            }) => Some(std::slice::from_mut(graph)),
+            Some(Attr {
+                value: AttrValue::Graphs(graphs),
+                ..
+            }) => Some(graphs.as_mut_slice()),
+            _ => None,
+        })
+        .flatten()
+        .map(Rc::make_mut)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::onnx_inference::unknown_shape;
+    use crate::test_support::{model, node, sym, value};
+
+    fn infer(model: &mut Model) -> Result<PassResult, PassError> {
+        let registry = SchemaRegistry::builtin();
+        ShapeInferencePass::new(&registry).run(model)
+    }
+
+    fn output_of<'m>(model: &'m Model, name: &str) -> &'m Value {
+        model
+            .graph
+            .iter_nodes()
+            .flat_map(|n| &n.outputs)
+            .find(|v| v.name == name)
+            .expect("value is produced")
+    }
+
+    #[test]
+    fn test_infers_through_graph() {
+        let mut graph = Graph::new();
+        graph.inputs.push(value(
+            "x",
+            DataType::Float,
+            vec![sym("batch"), 3.into(), 4.into()],
+        ));
+        let mut shape = Value::new("shape");
+        shape.const_value = Some(int64_tensor(&[0, -1], Shape::new(vec![2])));
+        graph.initializers.insert("shape".to_string(), shape);
+        graph.append(node("Relu", &["x"], &["r"]));
+        graph.append(node("Reshape", &["r", "shape"], &["flat"]));
+        let mut cast = node("Cast", &["flat"], &["y"]);
+        cast.set_attribute(Attr::int("to", DataType::Int64 as i64));
+        graph.append(cast);
+        graph.outputs.push(Value::new("y"));
+
+        let mut model = model(graph, 21);
+        assert!(infer(&mut model).unwrap().modified);
+        let flat = output_of(&model, "flat");
+        assert_eq!(flat.shape, Some(Shape::new(vec![sym("batch"), 12.into()])));
+        let y = &model.graph.outputs[0];
+        assert_eq!(y.type_.as_ref().unwrap().elem_type, DataType::Int64);
+        assert_eq!(y.shape, flat.shape);
+        // Inputs of later nodes are annotated too
+        let cast = model.graph.iter_nodes().nth(2).unwrap();
+        assert_eq!(cast.inputs[0].shape, flat.shape);
+
+        assert!(!infer(&mut model).unwrap().modified);
+    }
+
+    #[test]
+    fn test_constants_are_optional() {
+        let mut graph = Graph::new();
+        graph
+            .inputs
+            .push(value("x", DataType::Float, vec![2.into(), 6.into()]));
+        let mut constant = node("Constant", &[], &["shape"]);
+        constant.set_attribute(Attr::ints("value_ints", vec![3, 4]));
+        graph.append(constant);
+        graph.append(node("Reshape", &["x", "shape"], &["y"]));
+
+        let registry = SchemaRegistry::builtin();
+        let mut without = model(graph.clone(), 21);
+        ShapeInferencePass::new(&registry)
+            .use_constants(false)
+            .run(&mut without)
+            .unwrap();
+        assert_eq!(output_of(&without, "y").shape, Some(unknown_shape(2)));
+
+        let mut with = model(graph, 21);
+        ShapeInferencePass::new(&registry).run(&mut with).unwrap();
+        assert_eq!(output_of(&with, "y").shape, Some(Shape::new(vec![3, 4])));
+    }
+
+    #[test]
+    fn test_merges_with_existing_annotations() {
+        let mut graph = Graph::new();
+        graph
+            .inputs
+            .push(value("x", DataType::Float, vec![sym("N"), 4.into()]));
+        let mut relu = node("Relu", &["x"], &["y"]);
+        relu.outputs[0].shape = Some(Shape::new(vec![8.into(), unknown()]));
+        graph.append(relu);
+
+        let mut merged = model(graph.clone(), 21);
+        infer(&mut merged).unwrap();
+        assert_eq!(output_of(&merged, "y").shape, Some(Shape::new(vec![8, 4])));
+
+        let mut conflicting = graph;
+        let relu = conflicting.iter_nodes_mut().next().unwrap();
+        relu.outputs[0].type_ = Some(TensorType::new(DataType::Int64));
+        let err = infer(&mut model(conflicting, 21)).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Relu: output 'y' has type Int64, but Float was inferred"
+        );
+    }
+
+    #[test]
+    fn test_control_flow() {
+        let mut then_branch = Graph::new();
+        then_branch.append(node("Neg", &["x"], &["a"]));
+        then_branch.outputs.push(Value::new("a"));
+        let mut else_branch = Graph::new();
+        else_branch.append(node("Transpose", &["x"], &["b"]));
+        else_branch.outputs.push(Value::new("b"));
+        let mut if_node = node("If", &["cond"], &["z"]);
+        if_node.set_attribute(Attr::graph("then_branch", then_branch));
+        if_node.set_attribute(Attr::graph("else_branch", else_branch));
+
+        let mut body = Graph::new();
+        body.inputs.push(Value::new("i"));
+        body.inputs.push(Value::new("c"));
+        body.inputs.push(Value::new("acc"));
+        body.append(node("Add", &["acc", "z"], &["next"]));
+        body.append(node("Cast", &["i"], &["i_float"]));
+        body.nodes
+            .iter_mut()
+            .last()
+            .unwrap()
+            .set_attribute(Attr::int("to", 1));
+        body.outputs.push(Value::new("c"));
+        body.outputs.push(Value::new("next"));
+        body.outputs.push(Value::new("i_float"));
+        let mut loop_node = node("Loop", &["", "cond", "z"], &["total", "steps"]);
+        loop_node.set_attribute(Attr::graph("body", body));
+
+        let mut graph = Graph::new();
+        graph
+            .inputs
+            .push(value("x", DataType::Float, vec![3.into(), 3.into()]));
+        graph.inputs.push(value("cond", DataType::Bool, vec![]));
+        graph.append(if_node);
+        graph.append(loop_node);
+        let mut model = model(graph, 21);
+        infer(&mut model).unwrap();
+
+        assert_eq!(output_of(&model, "z").shape, Some(Shape::new(vec![3, 3])));
+        assert_eq!(
+            output_of(&model, "total").shape,
+            Some(Shape::new(vec![3, 3]))
+        );
+        let steps = output_of(&model, "steps");
+        assert_eq!(steps.type_.as_ref().unwrap().elem_type, DataType::Float);
+        assert_eq!(steps.shape, Some(Shape::new(vec![unknown()])));
+        let body = model
+            .graph
+            .iter_nodes()
+            .nth(1)
+            .unwrap()
+            .attr_graph("body")
+            .unwrap();
+        assert_eq!(body.inputs[0].shape, Some(Shape::scalar()));
+        assert_eq!(
+            body.iter_nodes().next().unwrap().outputs[0].shape,
+            Some(Shape::new(vec![3, 3]))
+        );
+    }
+
+    #[test]
+    fn test_loop_trip_count() {
+        let mut body = Graph::new();
+        body.inputs.push(Value::new("i"));
+        body.inputs.push(Value::new("c"));
+        body.outputs.push(Value::new("c"));
+        body.outputs.push(Value::new("i"));
+
+        let mut graph = Graph::new();
+        graph.inputs.push(value("cond", DataType::Bool, vec![]));
+        let mut constant = node("Constant", &[], &["n"]);
+        constant.set_attribute(Attr::int("value_int", 5));
+        graph.append(constant);
+        let mut loop_node = node("Loop", &["n", ""], &["steps"]);
+        loop_node.set_attribute(Attr::graph("body", body.clone()));
+        graph.append(loop_node);
+        // The condition may end the loop before the trip count
+        let mut loop_node = node("Loop", &["n", "cond"], &["steps_until"]);
+        loop_node.set_attribute(Attr::graph("body", body));
+        graph.append(loop_node);
+
+        let mut model = model(graph, 21);
+        infer(&mut model).unwrap();
+        assert_eq!(output_of(&model, "steps").shape, Some(Shape::new(vec![5])));
+        assert_eq!(
+            output_of(&model, "steps_until").shape,
+            Some(Shape::new(vec![unknown()]))
+        );
+    }
+
+    #[test]
+    fn test_scan() {
+        let mut body = Graph::new();
+        body.inputs.push(Value::new("sum"));
+        body.inputs.push(Value::new("row"));
+        body.append(node("Add", &["sum", "row"], &["next"]));
+        body.append(node("Relu", &["row"], &["out"]));
+        body.outputs.push(Value::new("next"));
+        body.outputs.push(Value::new("out"));
+
+        let mut graph = Graph::new();
+        graph
+            .inputs
+            .push(value("init", DataType::Float, vec![4.into()]));
+        graph
+            .inputs
+            .push(value("rows", DataType::Float, vec![4.into(), sym("T")]));
+        let mut scan = node("Scan", &["init", "rows"], &["total", "outs"]);
+        scan.set_attribute(Attr::graph("body", body.clone()));
+        scan.set_attribute(Attr::int("num_scan_inputs", 1));
+        scan.set_attribute(Attr::ints("scan_input_axes", vec![1]));
+        graph.append(scan);
+        let mut current = model(graph, 21);
+        infer(&mut current).unwrap();
+        assert_eq!(
+            output_of(&current, "total").shape,
+            Some(Shape::new(vec![4]))
+        );
+        assert_eq!(
+            output_of(&current, "outs").shape,
+            Some(Shape::new(vec![sym("T"), 4.into()]))
+        );
+
+        // Scan-8 takes the sequence lengths first and scans along the axis
+        // after the batch axis
+        let mut graph = Graph::new();
+        graph
+            .inputs
+            .push(value("init", DataType::Float, vec![sym("B"), 4.into()]));
+        graph.inputs.push(value(
+            "rows",
+            DataType::Float,
+            vec![sym("B"), sym("T"), 4.into()],
+        ));
+        let mut scan = node("Scan", &["", "init", "rows"], &["total", "outs"]);
+        scan.set_attribute(Attr::graph("body", body));
+        scan.set_attribute(Attr::int("num_scan_inputs", 1));
+        graph.append(scan);
+        let mut legacy = model(graph, 8);
+        infer(&mut legacy).unwrap();
+        assert_eq!(
+            output_of(&legacy, "total").shape,
+            Some(Shape::new(vec![sym("B"), 4.into()]))
+        );
+        assert_eq!(
+            output_of(&legacy, "outs").shape,
+            Some(Shape::new(vec![sym("B"), sym("T"), 4.into()]))
+        );
+        let body = legacy
+            .graph
+            .iter_nodes()
+            .next()
+            .unwrap()
+            .attr_graph("body")
+            .unwrap();
+        assert_eq!(body.inputs[1].shape, Some(Shape::new(vec![4])));
+    }
+
+    #[test]
+    fn test_sequence_map() {
+        let mut body = Graph::new();
+        body.inputs.push(Value::new("element"));
+        body.append(node("Transpose", &["element"], &["t"]));
+        body.outputs.push(Value::new("t"));
+
+        let mut graph = Graph::new();
+        // Sequences are annotated with the type of their elements
+        graph
+            .inputs
+            .push(value("seq", DataType::Float, vec![sym("N"), 3.into()]));
+        let mut map = node("SequenceMap", &["seq"], &["mapped"]);
+        map.set_attribute(Attr::graph("body", body));
+        graph.append(map);
+        let mut model = model(graph, 21);
+        infer(&mut model).unwrap();
+        let mapped = output_of(&model, "mapped");
+        assert_eq!(mapped.type_.as_ref().unwrap().elem_type, DataType::Float);
+        assert_eq!(mapped.shape, Some(Shape::new(vec![3.into(), sym("N")])));
+    }
+
+    #[test]
+    fn test_function_calls() {
+        let mut function = Function::new("Double", "custom");
+        function.set_opset_import("", 21);
+        function.add_input(Value::new("a"));
+        function.add_output(Value::new("b"));
+        function.append(node("Add", &["a", "a"], &["b"]));
+
+        let mut graph = Graph::new();
+        graph
+            .inputs
+            .push(value("x", DataType::Float, vec![sym("N")]));
+        let mut call = node("Double", &["x"], &["y"]);
+        call.domain = "custom".to_string();
+        graph.append(call);
+        let mut model = model(graph, 21);
+        model.set_opset_import("custom", 1);
+        model.add_function(function);
+        infer(&mut model).unwrap();
+
+        let y = output_of(&model, "y");
+        assert_eq!(y.type_.as_ref().unwrap().elem_type, DataType::Float);
+        assert_eq!(y.shape, Some(Shape::new(vec![sym("N")])));
+        // The body is shared by all call sites and stays unannotated
+        let body = model.functions.values().next().unwrap();
+        assert!(body.iter_nodes().next().unwrap().outputs[0].shape.is_none());
+    }
+}
@@ -0,0 +1,271 @@
+// Copyright (c) ONNX Project Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Type and shape inference functions of operator schemas.
+//!
+//! An [`InferenceFunction`] attached to an [`OpSchema`](super::OpSchema)
+//! computes the element types and shapes of a node's outputs from those of
+//! its inputs, its attributes and any constant inputs. The functions see the
+//! node through an [`InferenceContext`].
+
+use crate::attribute::AttributeError;
+use crate::enums::DataType;
+use crate::node::Node;
+use crate::shape::Shape;
+use crate::tensor::Tensor;
+use std::fmt;
+use std::rc::Rc;
+use thiserror::Error;
+
+/// An error raised by an inference function.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("{node}: {message}")]
+pub struct InferenceError {
+    pub node: String,
+    pub message: String,
+}
+
+impl From<AttributeError> for InferenceError {
+    fn from(err: AttributeError) -> Self {
+        let node = match &err {
+            AttributeError::Missing { node, .. }
+            | AttributeError::TypeMismatch { node, .. }
+            | AttributeError::InvalidUtf8 { node, .. }
+            | AttributeError::UnresolvedRef { node, .. } => node.clone(),
+        };
+        let message = err.to_string();
+        let message = message
+            .strip_prefix(&format!("{}: ", node))
+            .unwrap_or(&message)
+            .to_string();
+        Self { node, message }
+    }
+}
+
+/// The type and shape inferred for an output.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct InferredOutput {
+    pub dtype: Option<DataType>,
+    pub shape: Option<Shape>,
+}
+
+/// The signature of an inference function.
+type InferFn = dyn Fn(&mut InferenceContext<'_>) -> Result<(), InferenceError>;
+
+/// A type and shape inference function.
+///
+/// Wraps a closure so that schemas holding one can still be cloned and
+/// printed.
+#[derive(Clone)]
+pub struct InferenceFunction(Rc<InferFn>);
+
+impl InferenceFunction {
+    pub fn new(
+        function: impl Fn(&mut InferenceContext<'_>) -> Result<(), InferenceError> + 'static,
+    ) -> Self {
+        Self(Rc::new(function))
+    }
+
+    /// Runs the function on a context.
+    pub fn call(&self, ctx: &mut InferenceContext<'_>) -> Result<(), InferenceError> {
+        (self.0)(ctx)
+    }
+}
+
+impl fmt::Debug for InferenceFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("InferenceFunction")
+    }
+}
+
+/// The view of a node given to an inference function.
+///
+/// Input types and shapes are read from the node's input values, unless
+/// overridden by the caller with more precise information. Outputs start
+/// out unknown and are filled in by the inference function.
+#[derive(Debug)]
+pub struct InferenceContext<'a> {
+    node: &'a Node,
+    opset_version: i32,
+    inputs: Vec<InferredOutput>,
+    constants: Vec<Option<&'a Tensor>>,
+    outputs: Vec<InferredOutput>,
+}
+
+impl<'a> InferenceContext<'a> {
+    /// Creates a context for `node`, whose operator is imported at
+    /// `opset_version`.
+    pub fn new(node: &'a Node, opset_version: i32) -> Self {
+        let inputs = node
+            .inputs
+            .iter()
+            .map(|value| InferredOutput {
+                dtype: value.type_.as_ref().map(|t| t.elem_type),
+                shape: value.shape.clone(),
+            })
+            .collect();
+        let constants = node
+            .inputs
+            .iter()
+            .map(|value| value.const_value.as_ref())
+            .collect();
+        Self {
+            node,
+            opset_version,
+            inputs,
+            constants,
+            outputs: vec![InferredOutput::default(); node.outputs.len()],
+        }
+    }
+
+    /// Overrides the type and shape known for input `index`.
+    pub fn set_input(&mut self, index: usize, input: InferredOutput) {
+        if let Some(slot) = self.inputs.get_mut(index) {
+            *slot = input;
+        }
+    }
+
+    /// Sets the constant value known for input `index`.
+    pub fn set_input_constant(&mut self, index: usize, tensor: &'a Tensor) {
+        if let Some(slot) = self.constants.get_mut(index) {
+            *slot = Some(tensor);
+        }
+    }
+
+    pub fn node(&self) -> &'a Node {
+        self.node
+    }
+
+    /// Returns the opset version the operator is imported at.
+    pub fn opset_version(&self) -> i32 {
+        self.opset_version
+    }
+
+    pub fn num_inputs(&self) -> usize {
+        self.inputs.len()
+    }
+
+    pub fn num_outputs(&self) -> usize {
+        self.outputs.len()
+    }
+
+    /// Returns true if input `index` is given and not left empty.
+    pub fn has_input(&self, index: usize) -> bool {
+        self.node
+            .inputs
+            .get(index)
+            .is_some_and(|value| !value.name.is_empty())
+    }
+
+    pub fn input_dtype(&self, index: usize) -> Option<DataType> {
+        self.inputs.get(index)?.dtype
+    }
+
+    pub fn input_shape(&self, index: usize) -> Option<&Shape> {
+        self.inputs.get(index)?.shape.as_ref()
+    }
+
+    /// Returns the constant value of input `index`, if known.
+    pub fn input_constant(&self, index: usize) -> Option<&'a Tensor> {
+        self.constants.get(index).copied().flatten()
+    }
+
+    pub fn output_dtype(&self, index: usize) -> Option<DataType> {
+        self.outputs.get(index)?.dtype
+    }
+
+    pub fn output_shape(&self, index: usize) -> Option<&Shape> {
+        self.outputs.get(index)?.shape.as_ref()
+    }
+
+    /// Sets the element type of output `index`.
+    pub fn set_output_dtype(&mut self, index: usize, dtype: DataType) {
+        if let Some(output) = self.outputs.get_mut(index) {
+            output.dtype = Some(dtype);
+        }
+    }
+
+    /// Sets the shape of output `index`.
+    pub fn set_output_shape(&mut self, index: usize, shape: Shape) {
+        if let Some(output) = self.outputs.get_mut(index) {
+            output.shape = Some(shape);
+        }
+    }
+
+    /// Gives output `index` the type and shape of input `input`.
+    pub fn propagate(&mut self, input: usize, output: usize) {
+        if let (Some(inferred), Some(slot)) = (
+            self.inputs.get(input).cloned(),
+            self.outputs.get_mut(output),
+        ) {
+            *slot = inferred;
+        }
+    }
+
+    /// Creates an error about this node.
+    pub fn error(&self, message: impl Into<String>) -> InferenceError {
+        InferenceError {
+            node: self.node.label(),
+            message: message.into(),
+        }
+    }
+
+    /// Returns the inferred outputs.
+    pub fn into_outputs(self) -> Vec<InferredOutput> {
+        self.outputs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::value;
+    use crate::value::Value;
+
+    fn node() -> Node {
+        let x = value("x", DataType::Float, vec![2.into(), 3.into()]);
+        Node::with_io("Relu", vec![x, Value::new("")], ["y"])
+    }
+
+    #[test]
+    fn test_context_inputs() {
+        let node = node();
+        let mut ctx = InferenceContext::new(&node, 14);
+        assert_eq!(ctx.opset_version(), 14);
+        assert_eq!(ctx.input_dtype(0), Some(DataType::Float));
+        assert_eq!(ctx.input_shape(0), Some(&Shape::new(vec![2, 3])));
+        assert!(ctx.has_input(0));
+        assert!(!ctx.has_input(1));
+        assert!(ctx.input_constant(0).is_none());
+
+        let tensor = Tensor::new(DataType::Int64, Shape::new(vec![2]));
+        ctx.set_input_constant(1, &tensor);
+        assert_eq!(ctx.input_constant(1).unwrap().dtype, DataType::Int64);
+    }
+
+    #[test]
+    fn test_inference_function() {
+        let function = InferenceFunction::new(|ctx| {
+            if ctx.input_dtype(0).is_none() {
+                return Err(ctx.error("input type is unknown"));
+            }
+            ctx.propagate(0, 0);
+            Ok(())
+        });
+
+        let node = node();
+        let mut ctx = InferenceContext::new(&node, 14);
+        function.call(&mut ctx).unwrap();
+        let outputs = ctx.into_outputs();
+        assert_eq!(outputs[0].dtype, Some(DataType::Float));
+        assert_eq!(outputs[0].shape, Some(Shape::new(vec![2, 3])));
+
+        let mut untyped = node.clone();
+        untyped.inputs[0].type_ = None;
+        let mut ctx = InferenceContext::new(&untyped, 14);
+        assert_eq!(
+            function.call(&mut ctx).unwrap_err().to_string(),
+            "Relu: input type is unknown"
+        );
+    }
+}
@@ -0,0 +1,180 @@
+// Copyright (c) ONNX Project Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Schemas of the operators in the `ai.onnx.ml` domain.
+//!
+//! Every version of every operator is read from `ml.txt`, which is
+//! generated from the ONNX operator definitions by
+//! `scripts/gen_schema_tables.py`.
+
+use super::inference::{InferenceContext, InferenceError};
+use super::onnx_inference::unknown;
+use super::{table, SchemaRegistry, ML_DOMAIN};
+use crate::attribute::AttrValue;
+use crate::enums::DataType;
+use crate::shape::{Shape, SymbolicDim};
+
+/// The operator table of the `ai.onnx.ml` domain.
+const TABLE: &str = include_str!("ml.txt");
+
+/// Registers the schemas of the `ai.onnx.ml` domain.
+pub fn register_schemas(registry: &mut SchemaRegistry) {
+    let schemas = table::parse(ML_DOMAIN, TABLE).expect("the built-in operator table is valid");
+    for schema in schemas {
+        let schema = match inference(&schema.name) {
+            Some(function) => schema.inference_fn(function),
+            None => schema,
+        };
+        registry.register(schema);
+    }
+}
+
+type InferFn = fn(&mut InferenceContext<'_>) -> Result<(), InferenceError>;
+
+/// Returns the inference function of an operator in the `ai.onnx.ml` domain.
+fn inference(op_type: &str) -> Option<InferFn> {
+    let function: InferFn = match op_type {
+        "Binarizer" | "Imputer" => |ctx| {
+            ctx.propagate(0, 0);
+            Ok(())
+        },
+        "Normalizer" | "Scaler" => |ctx| {
+            ctx.set_output_dtype(0, DataType::Float);
+            copy_shape(ctx, 0, 0);
+            Ok(())
+        },
+        "LabelEncoder" => label_encoder,
+        "ArrayFeatureExtractor" => array_feature_extractor,
+        "OneHotEncoder" => one_hot_encoder,
+        "LinearClassifier" | "TreeEnsembleClassifier" => classifier,
+        "LinearRegressor" | "TreeEnsembleRegressor" => regressor,
+        "FeatureVectorizer" => feature_vectorizer,
+        _ => return None,
+    };
+    Some(function)
+}
+
+fn copy_shape(ctx: &mut InferenceContext<'_>, input: usize, output: usize) {
+    if let Some(shape) = ctx.input_shape(input).cloned() {
+        ctx.set_output_shape(output, shape);
+    }
+}
+
+/// Returns the first dimension of input 0, which is the batch size of
+/// most ML operators.
+fn batch(ctx: &InferenceContext<'_>) -> SymbolicDim {
+    match ctx.input_shape(0) {
+        Some(x) if x.rank() > 1 => x.dims()[0].clone(),
+        Some(_) => SymbolicDim::Int(1),
+        None => unknown(),
+    }
+}
+
+/// Returns the number of values of the first list attribute the node has.
+fn attr_len(ctx: &InferenceContext<'_>, names: &[&str]) -> Option<usize> {
+    names
+        .iter()
+        .find_map(|name| match &ctx.node().get_attribute(name)?.value {
+            AttrValue::Ints(values) => Some(values.len()),
+            AttrValue::Strings(values) => Some(values.len()),
+            _ => None,
+        })
+}
+
+fn label_encoder(ctx: &mut InferenceContext<'_>) -> Result<(), InferenceError> {
+    let node = ctx.node();
+    let dtype = if node.get_attribute("values_strings").is_some() {
+        Some(DataType::String)
+    } else if node.get_attribute("values_int64s").is_some() {
+        Some(DataType::Int64)
+    } else if node.get_attribute("values_floats").is_some() {
+        Some(DataType::Float)
+    } else {
+        None
+    };
+    if let Some(dtype) = dtype {
+        ctx.set_output_dtype(0, dtype);
+    }
+    copy_shape(ctx, 0, 0);
+    Ok(())
+}
+
+fn array_feature_extractor(ctx: &mut InferenceContext<'_>) -> Result<(), InferenceError> {
+    if let Some(dtype) = ctx.input_dtype(0) {
+        ctx.set_output_dtype(0, dtype);
+    }
+    let (Some(x), Some(indices)) = (ctx.input_shape(0), ctx.input_shape(1)) else {
+        return Ok(());
+    };
+    let mut dims = x.dims().to_vec();
+    if let Some(last) = dims.last_mut() {
+        *last = indices
+            .size()
+            .map_or_else(unknown, |n| SymbolicDim::Int(n as i64));
+    }
+    ctx.set_output_shape(0, Shape::new(dims));
+    Ok(())
+}
+
+fn one_hot_encoder(ctx: &mut InferenceContext<'_>) -> Result<(), InferenceError> {
+    ctx.set_output_dtype(0, DataType::Float);
+    let categories = attr_len(ctx, &["cats_int64s", "cats_strings"]);
+    if let Some(x) = ctx.input_shape(0) {
+        let mut dims = x.dims().to_vec();
+        dims.push(categories.map_or_else(unknown, |n| SymbolicDim::Int(n as i64)));
+        ctx.set_output_shape(0, Shape::new(dims));
+    }
+    Ok(())
+}
+
+fn classifier(ctx: &mut InferenceContext<'_>) -> Result<(), InferenceError> {
+    let n = batch(ctx);
+    let labels = [
+        "classlabels_ints",
+        "classlabels_int64s",
+        "classlabels_strings",
+    ];
+    let dtype = if labels[..2]
+        .iter()
+        .any(|l| ctx.node().get_attribute(l).is_some())
+    {
+        Some(DataType::Int64)
+    } else if ctx.node().get_attribute(labels[2]).is_some() {
+        Some(DataType::String)
+    } else {
+        None
+    };
+    if let Some(dtype) = dtype {
+        ctx.set_output_dtype(0, dtype);
+    }
+    ctx.set_output_shape(0, Shape::new(vec![n.clone()]));
+    ctx.set_output_dtype(1, DataType::Float);
+    let classes = attr_len(ctx, &labels).map_or_else(unknown, |c| SymbolicDim::Int(c as i64));
+    ctx.set_output_shape(1, Shape::new(vec![n, classes]));
+    Ok(())
+}
+
+fn regressor(ctx: &mut InferenceContext<'_>) -> Result<(), InferenceError> {
+    let n = batch(ctx);
+    let targets = ["targets", "n_targets"]
+        .iter()
+        .find(|name| ctx.node().get_attribute(name).is_some());
+    let targets = match targets {
+        Some(name) => SymbolicDim::Int(ctx.node().attr_int(name)?),
+        None if ctx.node().op_type == "LinearRegressor" => SymbolicDim::Int(1),
+        None => unknown(),
+    };
+    ctx.set_output_dtype(0, DataType::Float);
+    ctx.set_output_shape(0, Shape::new(vec![n, targets]));
+    Ok(())
+}
+
+fn feature_vectorizer(ctx: &mut InferenceContext<'_>) -> Result<(), InferenceError> {
+    ctx.set_output_dtype(0, DataType::Float);
+    let features = match ctx.node().attr_ints_or("inputdimensions", &[])? {
+        [] => unknown(),
+        dims => SymbolicDim::Int(dims.iter().sum()),
+    };
+    ctx.set_output_shape(0, Shape::new(vec![batch(ctx), features]));
+    Ok(())
+}
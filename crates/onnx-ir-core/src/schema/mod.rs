@@ -0,0 +1,708 @@
+// Copyright (c) ONNX Project Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Operator schemas.
+//!
+//! An [`OpSchema`] describes one version of an operator: its inputs and
+//! outputs, its attributes with their types and defaults, and the types
+//! allowed for each type parameter. A [`SchemaRegistry`] holds the schemas
+//! of all versions of many operators and resolves the version in effect for
+//! a given opset.
+//!
+//! The built-in schemas cover every version of every operator in the default
+//! domain and `ai.onnx.ml`. They are read from operator tables generated
+//! from the ONNX operator definitions and embedded in the [`onnx`] and
+//! [`ml`] modules, so no Python or C++ ONNX installation is needed at run
+//! time.
+
+pub mod inference;
+pub mod ml;
+pub mod onnx;
+pub(crate) mod onnx_inference;
+mod table;
+pub mod validation;
+
+use crate::attribute::Attr;
+use crate::enums::{AttributeType, DataType};
+use crate::node::Node;
+use crate::types::{TypeKind, TypeProto};
+use indexmap::IndexMap;
+use std::collections::HashMap;
+
+pub use inference::{InferenceContext, InferenceError, InferenceFunction, InferredOutput};
+pub use validation::ValidationError;
+
+/// The domain of the ONNX-ML operators.
+pub const ML_DOMAIN: &str = "ai.onnx.ml";
+
+/// Returns the canonical name of a domain, mapping `ai.onnx` to the
+/// default domain `""`.
+pub fn normalize_domain(domain: &str) -> &str {
+    if domain == "ai.onnx" {
+        ""
+    } else {
+        domain
+    }
+}
+
+/// Returns the opset version imported for `domain`.
+///
+/// The default domain may be imported as either `""` or `ai.onnx`.
+pub fn opset_version(domain: &str, opset_imports: &HashMap<String, i32>) -> Option<i32> {
+    let domain = normalize_domain(domain);
+    opset_imports
+        .get(domain)
+        .or_else(|| {
+            domain
+                .is_empty()
+                .then(|| opset_imports.get("ai.onnx"))
+                .flatten()
+        })
+        .copied()
+}
+
+/// How many values a formal parameter accepts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParameterOption {
+    /// Exactly one value.
+    Single,
+    /// Zero or one value.
+    Optional,
+    /// Any number of values, at least the minimum arity.
+    Variadic,
+}
+
+/// A formal input or output of an operator.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FormalParameter {
+    pub name: String,
+    /// The type parameter (e.g. `T`) or a fixed type (e.g. `tensor(int64)`).
+    pub type_str: String,
+    pub option: ParameterOption,
+    /// Whether all values of a variadic parameter must have the same type.
+    pub is_homogeneous: bool,
+    /// The minimum number of values of a variadic parameter.
+    pub min_arity: usize,
+}
+
+impl FormalParameter {
+    pub fn single(name: impl Into<String>, type_str: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            type_str: type_str.into(),
+            option: ParameterOption::Single,
+            is_homogeneous: true,
+            min_arity: 1,
+        }
+    }
+
+    pub fn optional(name: impl Into<String>, type_str: impl Into<String>) -> Self {
+        Self {
+            option: ParameterOption::Optional,
+            min_arity: 0,
+            ..Self::single(name, type_str)
+        }
+    }
+
+    pub fn variadic(
+        name: impl Into<String>,
+        type_str: impl Into<String>,
+        min_arity: usize,
+    ) -> Self {
+        Self {
+            option: ParameterOption::Variadic,
+            min_arity,
+            ..Self::single(name, type_str)
+        }
+    }
+
+    /// Allows the values of a variadic parameter to have different types.
+    pub fn heterogeneous(mut self) -> Self {
+        self.is_homogeneous = false;
+        self
+    }
+
+    pub fn is_optional(&self) -> bool {
+        self.option == ParameterOption::Optional
+    }
+
+    pub fn is_variadic(&self) -> bool {
+        self.option == ParameterOption::Variadic
+    }
+}
+
+/// An attribute of an operator.
+#[derive(Debug, Clone)]
+pub struct AttributeSpec {
+    pub name: String,
+    pub type_: AttributeType,
+    pub required: bool,
+    /// The value used when the attribute is not set.
+    pub default: Option<Attr>,
+}
+
+/// The types allowed for a type parameter.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypeConstraint {
+    pub type_param: String,
+    pub allowed_types: Vec<TypeProto>,
+    pub description: String,
+}
+
+impl TypeConstraint {
+    /// Returns true if `dtype` is allowed as the element type of a tensor.
+    pub fn allows_tensor(&self, dtype: DataType) -> bool {
+        self.allowed_types
+            .iter()
+            .any(|t| matches!(&t.kind, TypeKind::Tensor { elem_type, .. } if *elem_type == dtype))
+    }
+}
+
+/// The schema of one version of an operator.
+///
+/// Schemas are built with chained methods:
+///
+/// ```
+/// use onnx_ir_core::schema::OpSchema;
+/// use onnx_ir_core::{Attr, AttributeType, DataType};
+///
+/// let schema = OpSchema::new("Scale", "com.example", 1)
+///     .input("X", "T")
+///     .optional_input("bias", "T")
+///     .output("Y", "T")
+///     .required_attr("factor", AttributeType::Float)
+///     .attr_default(Attr::int("axis", -1))
+///     .tensor_constraint("T", &[DataType::Float, DataType::Double]);
+///
+/// assert_eq!(schema.min_inputs(), 1);
+/// assert_eq!(schema.max_inputs(), Some(2));
+/// assert!(schema.attribute("factor").unwrap().required);
+/// ```
+#[derive(Debug, Clone)]
+pub struct OpSchema {
+    pub name: String,
+    pub domain: String,
+    pub since_version: i32,
+    pub doc: String,
+    pub inputs: Vec<FormalParameter>,
+    pub outputs: Vec<FormalParameter>,
+    pub attributes: IndexMap<String, AttributeSpec>,
+    pub type_constraints: Vec<TypeConstraint>,
+    pub deprecated: bool,
+    /// Computes the types and shapes of the outputs, if the operator
+    /// supports inference.
+    pub inference: Option<InferenceFunction>,
+}
+
+impl OpSchema {
+    pub fn new(name: impl Into<String>, domain: impl Into<String>, since_version: i32) -> Self {
+        Self {
+            name: name.into(),
+            domain: normalize_domain(&domain.into()).to_string(),
+            since_version,
+            doc: String::new(),
+            inputs: Vec::new(),
+            outputs: Vec::new(),
+            attributes: IndexMap::new(),
+            type_constraints: Vec::new(),
+            deprecated: false,
+            inference: None,
+        }
+    }
+
+    pub fn doc(mut self, doc: impl Into<String>) -> Self {
+        self.doc = doc.into();
+        self
+    }
+
+    /// Marks the operator as deprecated from this version on.
+    pub fn deprecate(mut self) -> Self {
+        self.deprecated = true;
+        self
+    }
+
+    pub fn input(self, name: &str, type_str: &str) -> Self {
+        self.input_param(FormalParameter::single(name, type_str))
+    }
+
+    pub fn optional_input(self, name: &str, type_str: &str) -> Self {
+        self.input_param(FormalParameter::optional(name, type_str))
+    }
+
+    pub fn variadic_input(self, name: &str, type_str: &str, min_arity: usize) -> Self {
+        self.input_param(FormalParameter::variadic(name, type_str, min_arity))
+    }
+
+    pub fn input_param(mut self, param: FormalParameter) -> Self {
+        self.inputs.push(param);
+        self
+    }
+
+    pub fn output(self, name: &str, type_str: &str) -> Self {
+        self.output_param(FormalParameter::single(name, type_str))
+    }
+
+    pub fn optional_output(self, name: &str, type_str: &str) -> Self {
+        self.output_param(FormalParameter::optional(name, type_str))
+    }
+
+    pub fn variadic_output(self, name: &str, type_str: &str, min_arity: usize) -> Self {
+        self.output_param(FormalParameter::variadic(name, type_str, min_arity))
+    }
+
+    pub fn output_param(mut self, param: FormalParameter) -> Self {
+        self.outputs.push(param);
+        self
+    }
+
+    /// Adds an optional attribute without a default value.
+    pub fn attr(self, name: &str, type_: AttributeType) -> Self {
+        self.attr_spec(AttributeSpec {
+            name: name.to_string(),
+            type_,
+            required: false,
+            default: None,
+        })
+    }
+
+    /// Adds a required attribute.
+    pub fn required_attr(self, name: &str, type_: AttributeType) -> Self {
+        self.attr_spec(AttributeSpec {
+            name: name.to_string(),
+            type_,
+            required: true,
+            default: None,
+        })
+    }
+
+    /// Adds an optional attribute whose default is `default`.
+    pub fn attr_default(self, default: Attr) -> Self {
+        self.attr_spec(AttributeSpec {
+            name: default.name.clone(),
+            type_: default.attr_type(),
+            required: false,
+            default: Some(default),
+        })
+    }
+
+    pub fn attr_spec(mut self, spec: AttributeSpec) -> Self {
+        self.attributes.insert(spec.name.clone(), spec);
+        self
+    }
+
+    pub fn type_constraint(
+        mut self,
+        type_param: &str,
+        allowed_types: impl IntoIterator<Item = TypeProto>,
+    ) -> Self {
+        self.type_constraints.push(TypeConstraint {
+            type_param: type_param.to_string(),
+            allowed_types: allowed_types.into_iter().collect(),
+            description: String::new(),
+        });
+        self
+    }
+
+    /// Constrains a type parameter to tensors of the given element types.
+    pub fn tensor_constraint(self, type_param: &str, dtypes: &[DataType]) -> Self {
+        self.type_constraint(type_param, tensor_types(dtypes))
+    }
+
+    /// Sets the type and shape inference function.
+    pub fn inference_fn(
+        mut self,
+        function: impl Fn(&mut InferenceContext<'_>) -> Result<(), InferenceError> + 'static,
+    ) -> Self {
+        self.inference = Some(InferenceFunction::new(function));
+        self
+    }
+
+    /// Runs the inference function on a context.
+    ///
+    /// Returns `Ok(false)` if the operator has no inference function.
+    pub fn infer(&self, ctx: &mut InferenceContext<'_>) -> Result<bool, InferenceError> {
+        match &self.inference {
+            Some(function) => function.call(ctx).map(|()| true),
+            None => Ok(false),
+        }
+    }
+
+    pub fn attribute(&self, name: &str) -> Option<&AttributeSpec> {
+        self.attributes.get(name)
+    }
+
+    pub fn type_constraint_for(&self, type_param: &str) -> Option<&TypeConstraint> {
+        self.type_constraints
+            .iter()
+            .find(|c| c.type_param == type_param)
+    }
+
+    /// Returns the minimum number of inputs.
+    pub fn min_inputs(&self) -> usize {
+        min_arity(&self.inputs)
+    }
+
+    /// Returns the maximum number of inputs, or `None` if unbounded.
+    pub fn max_inputs(&self) -> Option<usize> {
+        max_arity(&self.inputs)
+    }
+
+    /// Returns the minimum number of outputs.
+    pub fn min_outputs(&self) -> usize {
+        min_arity(&self.outputs)
+    }
+
+    /// Returns the maximum number of outputs, or `None` if unbounded.
+    pub fn max_outputs(&self) -> Option<usize> {
+        max_arity(&self.outputs)
+    }
+
+    /// Returns the formal parameter that the input at `index` binds to.
+    pub fn input_at(&self, index: usize) -> Option<&FormalParameter> {
+        param_at(&self.inputs, index)
+    }
+
+    /// Returns the formal parameter that the output at `index` binds to.
+    pub fn output_at(&self, index: usize) -> Option<&FormalParameter> {
+        param_at(&self.outputs, index)
+    }
+}
+
+/// Returns tensor types with the given element types.
+pub fn tensor_types(dtypes: &[DataType]) -> Vec<TypeProto> {
+    dtypes
+        .iter()
+        .map(|dtype| TypeProto::tensor(*dtype, None))
+        .collect()
+}
+
+/// Returns sequence-of-tensor types with the given element types.
+pub fn sequence_types(dtypes: &[DataType]) -> Vec<TypeProto> {
+    dtypes
+        .iter()
+        .map(|dtype| TypeProto::sequence(TypeProto::tensor(*dtype, None)))
+        .collect()
+}
+
+fn min_arity(params: &[FormalParameter]) -> usize {
+    // Trailing optional parameters may be omitted, but an optional
+    // parameter followed by a required one must be given (possibly empty).
+    let last_required = params
+        .iter()
+        .rposition(|p| !p.is_optional())
+        .map_or(0, |i| i + 1);
+    params[..last_required]
+        .iter()
+        .map(|p| match p.option {
+            ParameterOption::Variadic => p.min_arity,
+            _ => 1,
+        })
+        .sum()
+}
+
+fn max_arity(params: &[FormalParameter]) -> Option<usize> {
+    if params.iter().any(FormalParameter::is_variadic) {
+        None
+    } else {
+        Some(params.len())
+    }
+}
+
+fn param_at(params: &[FormalParameter], index: usize) -> Option<&FormalParameter> {
+    match params.get(index) {
+        Some(param) => Some(param),
+        None => params.last().filter(|p| p.is_variadic()),
+    }
+}
+
+/// A collection of operator schemas, indexed by domain and operator name.
+///
+/// Besides the built-in schemas, schemas for operators in custom domains
+/// can be registered at any time with [`register`](Self::register). They
+/// then take part in validation and inference like built-in operators.
+/// Registering several versions of an operator makes each one apply from
+/// its `since_version` on.
+///
+/// # Examples
+///
+/// ```
+/// use onnx_ir_core::schema::SchemaRegistry;
+///
+/// let registry = SchemaRegistry::builtin();
+/// let reshape = registry.get("", "Reshape", 12).unwrap();
+/// assert_eq!(reshape.since_version, 5);
+/// assert!(reshape.attribute("allowzero").is_none());
+///
+/// let reshape = registry.get("ai.onnx", "Reshape", 18).unwrap();
+/// assert_eq!(reshape.since_version, 14);
+/// assert!(reshape.attribute("allowzero").is_some());
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct SchemaRegistry {
+    /// Schemas by (domain, name), sorted by `since_version`.
+    schemas: HashMap<(String, String), Vec<OpSchema>>,
+}
+
+impl SchemaRegistry {
+    /// Creates an empty registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a registry with the built-in schemas of the default domain
+    /// and `ai.onnx.ml`.
+    ///
+    /// Building the registry allocates every schema, so create it once and
+    /// reuse it.
+    pub fn builtin() -> Self {
+        let mut registry = Self::new();
+        onnx::register_schemas(&mut registry);
+        ml::register_schemas(&mut registry);
+        registry
+    }
+
+    /// Adds a schema, replacing the schema of the same operator and version
+    /// if there is one.
+    ///
+    /// Returns the replaced schema.
+    pub fn register(&mut self, schema: OpSchema) -> Option<OpSchema> {
+        let versions = self
+            .schemas
+            .entry((schema.domain.clone(), schema.name.clone()))
+            .or_default();
+        match versions.binary_search_by_key(&schema.since_version, |s| s.since_version) {
+            Ok(index) => Some(std::mem::replace(&mut versions[index], schema)),
+            Err(index) => {
+                versions.insert(index, schema);
+                None
+            }
+        }
+    }
+
+    /// Returns the schema of `op_type` in effect for opset version
+    /// `opset_version` of `domain`: the latest version not newer than the
+    /// opset.
+    pub fn get(&self, domain: &str, op_type: &str, opset_version: i32) -> Option<&OpSchema> {
+        self.versions(domain, op_type)
+            .iter()
+            .rev()
+            .find(|s| s.since_version <= opset_version)
+    }
+
+    /// Returns the schema of the operator a node calls, at the version
+    /// imported for its domain.
+    pub fn schema_for(
+        &self,
+        node: &Node,
+        opset_imports: &HashMap<String, i32>,
+    ) -> Option<&OpSchema> {
+        let version = opset_version(&node.domain, opset_imports)?;
+        self.get(&node.domain, &node.op_type, version)
+    }
+
+    /// Runs the inference function of a node's operator.
+    ///
+    /// Returns `Ok(None)` if the operator is unknown or has no inference
+    /// function.
+    pub fn infer_node(
+        &self,
+        node: &Node,
+        opset_imports: &HashMap<String, i32>,
+    ) -> Result<Option<Vec<InferredOutput>>, InferenceError> {
+        let Some(schema) = self.schema_for(node, opset_imports) else {
+            return Ok(None);
+        };
+        let version = opset_version(&node.domain, opset_imports).unwrap_or_default();
+        let mut ctx = InferenceContext::new(node, version);
+        Ok(schema.infer(&mut ctx)?.then(|| ctx.into_outputs()))
+    }
+
+    /// Removes one version of an operator and returns it.
+    pub fn unregister(
+        &mut self,
+        domain: &str,
+        op_type: &str,
+        since_version: i32,
+    ) -> Option<OpSchema> {
+        let key = (normalize_domain(domain).to_string(), op_type.to_string());
+        let versions = self.schemas.get_mut(&key)?;
+        let index = versions
+            .iter()
+            .position(|s| s.since_version == since_version)?;
+        let schema = versions.remove(index);
+        if versions.is_empty() {
+            self.schemas.remove(&key);
+        }
+        Some(schema)
+    }
+
+    /// Returns the domains with at least one registered operator.
+    pub fn domains(&self) -> impl Iterator<Item = &str> {
+        let mut domains: Vec<_> = self.schemas.keys().map(|(d, _)| d.as_str()).collect();
+        domains.sort_unstable();
+        domains.dedup();
+        domains.into_iter()
+    }
+
+    /// Returns all versions of an operator, oldest first.
+    pub fn versions(&self, domain: &str, op_type: &str) -> &[OpSchema] {
+        self.schemas
+            .get(&(normalize_domain(domain).to_string(), op_type.to_string()))
+            .map_or(&[], Vec::as_slice)
+    }
+
+    /// Returns true if any version of the operator is registered.
+    pub fn contains(&self, domain: &str, op_type: &str) -> bool {
+        !self.versions(domain, op_type).is_empty()
+    }
+
+    /// Returns the number of schemas, counting each version separately.
+    pub fn len(&self) -> usize {
+        self.schemas.values().map(Vec::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.schemas.is_empty()
+    }
+
+    /// Returns an iterator over all schemas.
+    pub fn iter(&self) -> impl Iterator<Item = &OpSchema> {
+        self.schemas.values().flatten()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::attribute::AttrValue;
+
+    #[test]
+    fn test_arity() {
+        let schema = OpSchema::new("Op", "", 1)
+            .input("A", "T")
+            .optional_input("B", "T")
+            .input("C", "T")
+            .optional_input("D", "T")
+            .variadic_output("Y", "T", 2);
+
+        assert_eq!(schema.min_inputs(), 3);
+        assert_eq!(schema.max_inputs(), Some(4));
+        assert_eq!(schema.min_outputs(), 2);
+        assert_eq!(schema.max_outputs(), None);
+        assert_eq!(schema.output_at(5).unwrap().name, "Y");
+        assert!(schema.input_at(4).is_none());
+    }
+
+    #[test]
+    fn test_version_resolution() {
+        let mut registry = SchemaRegistry::new();
+        registry.register(OpSchema::new("Op", "com.example", 1));
+        registry.register(OpSchema::new("Op", "com.example", 5).doc("v5"));
+        registry.register(OpSchema::new("Op", "com.example", 3));
+
+        assert!(registry.get("com.example", "Op", 0).is_none());
+        assert_eq!(
+            registry.get("com.example", "Op", 4).unwrap().since_version,
+            3
+        );
+        assert_eq!(registry.get("com.example", "Op", 9).unwrap().doc, "v5");
+        assert_eq!(registry.len(), 3);
+
+        let replaced = registry.register(OpSchema::new("Op", "com.example", 5));
+        assert_eq!(replaced.unwrap().doc, "v5");
+        assert_eq!(registry.len(), 3);
+    }
+
+    #[test]
+    fn test_type_constraint() {
+        let schema = OpSchema::new("Op", "ai.onnx", 1).tensor_constraint("T", &[DataType::Float]);
+        assert_eq!(schema.domain, "");
+
+        let constraint = schema.type_constraint_for("T").unwrap();
+        assert!(constraint.allows_tensor(DataType::Float));
+        assert!(!constraint.allows_tensor(DataType::Int64));
+    }
+
+    #[test]
+    fn test_builtin_schemas() {
+        let registry = SchemaRegistry::builtin();
+
+        let conv = registry.get("", "Conv", 17).unwrap();
+        assert_eq!(conv.since_version, 11);
+        assert_eq!(conv.attribute("pads").unwrap().type_, AttributeType::Ints);
+        let group = conv.attribute("group").unwrap().default.as_ref().unwrap();
+        assert!(matches!(group.value, AttrValue::Int(1)));
+
+        let squeeze = registry.get("", "Squeeze", 11).unwrap();
+        assert!(squeeze.attribute("axes").is_some());
+        let squeeze = registry.get("", "Squeeze", 13).unwrap();
+        assert_eq!(squeeze.inputs[1].name, "axes");
+
+        let label_encoder = registry.get(ML_DOMAIN, "LabelEncoder", 3).unwrap();
+        assert_eq!(label_encoder.since_version, 2);
+        assert!(registry.get(ML_DOMAIN, "Conv", 3).is_none());
+    }
+
+    #[test]
+    fn test_custom_schemas() {
+        let mut registry = SchemaRegistry::builtin();
+        registry.register(
+            OpSchema::new("Repeat", "com.ourcompany", 1)
+                .input("X", "T")
+                .output("Y", "T")
+                .required_attr("times", AttributeType::Int)
+                .tensor_constraint("T", &[DataType::Float])
+                .inference_fn(|ctx| {
+                    let times = ctx
+                        .node()
+                        .attr_int("times")
+                        .map_err(|e| ctx.error(e.to_string()))?;
+                    let Some(shape) = ctx.input_shape(0) else {
+                        return Ok(());
+                    };
+                    let mut dims: Vec<_> = shape.dims().to_vec();
+                    if let Some(first) = dims.first_mut().and_then(|d| d.as_int()) {
+                        dims[0] = (first * times).into();
+                    }
+                    if let Some(dtype) = ctx.input_dtype(0) {
+                        ctx.set_output_dtype(0, dtype);
+                    }
+                    ctx.set_output_shape(0, crate::shape::Shape::new(dims));
+                    Ok(())
+                }),
+        );
+        registry.register(
+            OpSchema::new("Repeat", "com.ourcompany", 2)
+                .input("X", "T")
+                .input("times", "tensor(int64)")
+                .output("Y", "T")
+                .tensor_constraint("T", &[DataType::Float, DataType::Double]),
+        );
+        assert_eq!(
+            registry.domains().collect::<Vec<_>>(),
+            vec!["", "ai.onnx.ml", "com.ourcompany"]
+        );
+
+        let mut node = Node::new("Repeat");
+        node.domain = "com.ourcompany".to_string();
+        let mut x = crate::value::Value::new("x");
+        x.type_ = Some(crate::types::TensorType::new(DataType::Float));
+        x.shape = Some(crate::shape::Shape::new(vec![2, 3]));
+        node.add_input(x);
+        node.add_output(crate::value::Value::new("y"));
+        node.set_attribute(Attr::int("times", 3));
+
+        let v1 = HashMap::from([("com.ourcompany".to_string(), 1)]);
+        let v2 = HashMap::from([("com.ourcompany".to_string(), 2)]);
+        assert!(node.validate(&registry, &v1).is_empty());
+        assert_eq!(node.validate(&registry, &v2).len(), 2);
+
+        let outputs = registry.infer_node(&node, &v1).unwrap().unwrap();
+        assert_eq!(outputs[0].dtype, Some(DataType::Float));
+        assert_eq!(outputs[0].shape, Some(crate::shape::Shape::new(vec![6, 3])));
+        assert!(registry.infer_node(&node, &v2).unwrap().is_none());
+
+        assert!(registry.unregister("com.ourcompany", "Repeat", 2).is_some());
+        assert_eq!(registry.schema_for(&node, &v2).unwrap().since_version, 1);
+    }
+}
@@ -0,0 +1,113 @@
+// Copyright (c) ONNX Project Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Schemas of the operators in the default ONNX domain.
+//!
+//! Every version of every operator up to opset 24 is read from `onnx.txt`,
+//! which is generated from the ONNX operator definitions by
+//! `scripts/gen_schema_tables.py`.
+
+use super::{onnx_inference, table, SchemaRegistry};
+
+/// The operator table of the default domain.
+const TABLE: &str = include_str!("onnx.txt");
+
+/// Registers the schemas of the default domain.
+pub fn register_schemas(registry: &mut SchemaRegistry) {
+    let schemas = table::parse("", TABLE).expect("the built-in operator table is valid");
+    for schema in schemas {
+        let schema = match onnx_inference::builtin(&schema.name) {
+            Some(function) => schema.inference_fn(function),
+            None => schema,
+        };
+        registry.register(schema);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enums::{AttributeType, DataType};
+    use crate::types::TypeProto;
+
+    /// Versions in effect at a given opset, from the ONNX operator changelog.
+    const VERSIONS: &[(&str, i32, i32)] = &[
+        ("Relu", 12, 6),
+        ("Relu", 14, 14),
+        ("Conv", 10, 1),
+        ("Conv", 11, 11),
+        ("Shape", 13, 13),
+        ("Shape", 15, 15),
+        ("Concat", 12, 11),
+        ("Resize", 13, 13),
+        ("Resize", 19, 19),
+        ("Gemm", 11, 11),
+        ("MatMul", 12, 9),
+        ("Softmax", 10, 1),
+        ("Softmax", 13, 13),
+        ("Where", 13, 9),
+        ("Where", 16, 16),
+        ("Transpose", 12, 1),
+        ("Unsqueeze", 10, 1),
+        ("CastLike", 19, 19),
+        ("Reshape", 12, 5),
+        ("Reshape", 19, 19),
+        ("QuantizeLinear", 21, 21),
+        ("LSTM", 13, 7),
+        ("GRU", 14, 14),
+        ("RNN", 22, 22),
+        ("QLinearConv", 13, 10),
+        ("MatMulInteger", 20, 10),
+        ("DynamicQuantizeLinear", 11, 11),
+        ("Compress", 12, 11),
+        ("NonMaxSuppression", 13, 11),
+        ("SequenceAt", 17, 11),
+        ("OptionalHasElement", 18, 18),
+        ("BitShift", 11, 11),
+        ("BitwiseAnd", 18, 18),
+        ("Sinh", 21, 9),
+        ("LRN", 12, 1),
+        ("EyeLike", 20, 9),
+        ("Upsample", 10, 10),
+    ];
+
+    #[test]
+    fn test_schema_versions() {
+        let registry = SchemaRegistry::builtin();
+        for &(op, opset, since_version) in VERSIONS {
+            let schema = registry
+                .get("", op, opset)
+                .unwrap_or_else(|| panic!("no schema for {} at opset {}", op, opset));
+            assert_eq!(schema.since_version, since_version, "{}-{}", op, opset);
+        }
+        assert!(registry.get("", "CastLike", 14).is_none());
+        assert!(registry.get("", "Upsample", 10).unwrap().deprecated);
+    }
+
+    #[test]
+    fn test_schema_signatures() {
+        let registry = SchemaRegistry::builtin();
+        let cast = registry.get("", "Cast", 18).unwrap();
+        assert!(cast.attribute("saturate").is_none());
+        let cast = registry.get("", "Cast", 19).unwrap();
+        assert_eq!(
+            cast.attribute("saturate").unwrap().type_,
+            AttributeType::Int
+        );
+
+        let quantize = registry.get("", "QuantizeLinear", 21).unwrap();
+        assert!(quantize.attribute("block_size").is_some());
+        assert!(quantize.attribute("output_dtype").is_some());
+        let allowed = &quantize.type_constraint_for("T3").unwrap().allowed_types;
+        assert!(allowed.contains(&TypeProto::tensor(DataType::Int4, None)));
+
+        let lstm = registry.get("", "LSTM", 14).unwrap();
+        assert_eq!((lstm.min_inputs(), lstm.max_inputs()), (3, Some(8)));
+        assert!(lstm.attribute("layout").is_some());
+
+        let sequence_at = registry.get("", "SequenceAt", 11).unwrap();
+        let allowed = &sequence_at.type_constraint_for("S").unwrap().allowed_types;
+        let float_sequence = TypeProto::sequence(TypeProto::tensor(DataType::Float, None));
+        assert!(allowed.contains(&float_sequence));
+    }
+}
@@ -0,0 +1,3043 @@
+// Copyright (c) ONNX Project Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Inference functions of the built-in operators.
+//!
+//! The functions follow the ONNX specification of each operator. They leave
+//! an output unknown when the information needed to compute it is missing,
+//! and fail only when the inputs and attributes are inconsistent, e.g. when
+//! two shapes cannot be broadcast together. Data-dependent operators such as
+//! `Reshape` and `Slice` compute their output shapes from constant inputs
+//! when the caller provides them.
+//!
+//! The same function serves every version of an operator, and checks the
+//! opset version where an older version takes its arguments differently,
+//! e.g. the `shape` attribute of `Reshape-1`.
+//!
+//! Since values only carry tensor types, a sequence or optional value is
+//! described by its elements: it has their element type and, where all
+//! elements have the same shape, their shape. `SequenceAt` thus returns
+//! the type of its sequence input, and `SequenceConstruct` the shape its
+//! inputs agree on.
+//!
+//! `If`, `Loop`, `Scan` and `SequenceMap`, whose outputs come from their
+//! subgraphs, have no built-in function and are inferred by
+//! [`ShapeInferencePass`](crate::passes::ShapeInferencePass).
+
+use super::inference::{InferenceContext, InferenceError};
+use crate::attribute::AttrValue;
+use crate::element::Element;
+use crate::enums::DataType;
+use crate::shape::{Shape, SymbolicDim};
+use crate::tensor::Tensor;
+use crate::types::{TypeKind, TypeProto};
+
+type Result<T = ()> = std::result::Result<T, InferenceError>;
+
+/// A built-in inference function.
+pub(super) type BuiltinFn = fn(&mut InferenceContext<'_>) -> Result;
+
+/// Returns the inference function of an operator in the default domain.
+pub(super) fn builtin(op_type: &str) -> Option<BuiltinFn> {
+    let function: BuiltinFn = match op_type {
+        "Add" | "Sub" | "Mul" | "Div" | "Pow" | "Mod" | "Max" | "Min" | "Sum" | "Mean"
+        | "BitShift" | "BitwiseAnd" | "BitwiseOr" | "BitwiseXor" | "StringConcat" => elementwise,
+        "And" | "Or" | "Xor" | "Equal" | "Greater" | "Less" | "GreaterOrEqual" | "LessOrEqual" => {
+            comparison
+        }
+        "Not"
+        | "Abs"
+        | "Neg"
+        | "Sign"
+        | "Sqrt"
+        | "Reciprocal"
+        | "Floor"
+        | "Ceil"
+        | "Exp"
+        | "Log"
+        | "Erf"
+        | "Round"
+        | "Sin"
+        | "Cos"
+        | "Sinh"
+        | "Cosh"
+        | "Asinh"
+        | "Acosh"
+        | "Atanh"
+        | "BitwiseNot"
+        | "Tan"
+        | "Asin"
+        | "Acos"
+        | "Atan"
+        | "Relu"
+        | "Sigmoid"
+        | "Tanh"
+        | "Softplus"
+        | "Softsign"
+        | "HardSwish"
+        | "Mish"
+        | "Elu"
+        | "Celu"
+        | "Selu"
+        | "LeakyRelu"
+        | "HardSigmoid"
+        | "Gelu"
+        | "PRelu"
+        | "Softmax"
+        | "LogSoftmax"
+        | "Hardmax"
+        | "InstanceNormalization"
+        | "GroupNormalization"
+        | "Shrink"
+        | "ThresholdedRelu"
+        | "Swish"
+        | "LRN"
+        | "LpNormalization"
+        | "MeanVarianceNormalization"
+        | "RMSNormalization"
+        | "RotaryEmbedding"
+        | "ReverseSequence"
+        | "TensorScatter"
+        | "CumSum"
+        | "Identity"
+        | "Clip"
+        | "Trilu"
+        | "ScatterElements"
+        | "Scatter"
+        | "ScatterND" => same_as_input,
+        "IsNaN" | "IsInf" | "RegexFullMatch" => is_nan,
+        "Where" => where_,
+        "Cast" => cast,
+        "CastLike" => cast_like,
+        "MatMul" => matmul,
+        "MatMulInteger" => matmul_integer,
+        "QLinearMatMul" => qlinear_matmul,
+        "Gemm" => gemm,
+        "Conv" | "ConvTranspose" | "DeformConv" | "MaxPool" | "AveragePool" | "LpPool" => conv,
+        "ConvInteger" => conv_integer,
+        "QLinearConv" => qlinear_conv,
+        "RoiAlign" | "MaxRoiPool" => roi_pool,
+        "GridSample" => grid_sample,
+        "AffineGrid" => affine_grid,
+        "Col2Im" => col2im,
+        "MaxUnpool" => max_unpool,
+        "Attention" => attention,
+        "RNN" | "GRU" | "LSTM" => recurrent,
+        "GlobalAveragePool" | "GlobalMaxPool" | "GlobalLpPool" => global_pool,
+        "BatchNormalization" => batch_normalization,
+        "LayerNormalization" => layer_normalization,
+        "Dropout" => dropout,
+        "QuantizeLinear" => quantize_linear,
+        "DynamicQuantizeLinear" => dynamic_quantize_linear,
+        "DequantizeLinear" => dequantize_linear,
+        "Einsum" => einsum,
+        "ReduceSum" | "ReduceMean" | "ReduceMax" | "ReduceMin" | "ReduceProd" | "ReduceL1"
+        | "ReduceL2" | "ReduceLogSum" | "ReduceLogSumExp" | "ReduceSumSquare" => reduce,
+        "Det" => det,
+        "ArgMax" | "ArgMin" => arg_reduce,
+        "TopK" => top_k,
+        "Reshape" => reshape,
+        "Shape" => shape,
+        "Size" => size,
+        "Flatten" => flatten,
+        "Transpose" => transpose,
+        "Concat" => concat,
+        "Split" => split,
+        "Slice" => slice,
+        "Squeeze" => squeeze,
+        "Unsqueeze" => unsqueeze,
+        "Expand" => expand,
+        "Tile" => tile,
+        "Gather" => gather,
+        "GatherElements" => gather_elements,
+        "GatherND" => gather_nd,
+        "Pad" => pad,
+        "Constant" => constant,
+        "ConstantOfShape" => constant_of_shape,
+        "Range" => range,
+        "NonZero" => non_zero,
+        "OneHot" => one_hot,
+        "DepthToSpace" => depth_to_space,
+        "SpaceToDepth" => space_to_depth,
+        "Resize" => resize,
+        "Upsample" => upsample,
+        "CenterCropPad" => center_crop_pad,
+        "Compress" => compress,
+        "NonMaxSuppression" => non_max_suppression,
+        "Unique" => unique,
+        "EyeLike" | "RandomNormalLike" | "RandomUniformLike" | "Bernoulli" => like_input,
+        "RandomNormal" | "RandomUniform" => random,
+        "Multinomial" => multinomial,
+        "NegativeLogLikelihoodLoss" | "SoftmaxCrossEntropyLoss" => loss,
+        "DFT" => dft,
+        "STFT" => stft,
+        "MelWeightMatrix" => mel_weight_matrix,
+        "BlackmanWindow" | "HammingWindow" | "HannWindow" => window,
+        "ImageDecoder" => image_decoder,
+        "StringNormalizer" => string_normalizer,
+        "StringSplit" => string_split,
+        "TfIdfVectorizer" => tf_idf_vectorizer,
+        "SequenceEmpty" => sequence_empty,
+        "SequenceConstruct" => sequence_construct,
+        "SequenceInsert" => sequence_insert,
+        "SequenceAt" | "SequenceErase" | "OptionalGetElement" => same_as_input,
+        "SplitToSequence" => split_to_sequence,
+        "ConcatFromSequence" => concat_from_sequence,
+        "SequenceLength" => sequence_length,
+        "Optional" => optional,
+        "OptionalHasElement" => optional_has_element,
+        _ => return None,
+    };
+    Some(function)
+}
+
+/// Returns an unknown dimension.
+pub(crate) fn unknown() -> SymbolicDim {
+    SymbolicDim::Symbol(None)
+}
+
+/// Returns a shape of the given rank whose dimensions are all unknown.
+pub(crate) fn unknown_shape(rank: usize) -> Shape {
+    Shape::new(vec![unknown(); rank])
+}
+
+/// Broadcasts shapes following the multidirectional broadcasting rules.
+fn broadcast(ctx: &InferenceContext<'_>, shapes: &[&Shape]) -> Result<Shape> {
+    let mut result = Shape::scalar();
+    for shape in shapes {
+        result = result.broadcast(shape).map_err(|_| {
+            let shapes: Vec<_> = shapes.iter().map(|s| s.to_string()).collect();
+            ctx.error(format!("cannot broadcast shapes {}", shapes.join(" and ")))
+        })?;
+    }
+    Ok(result)
+}
+
+/// Returns the product of dimensions, which is unknown if any dimension
+/// is unknown.
+fn product(dims: &[SymbolicDim]) -> SymbolicDim {
+    dims.iter()
+        .cloned()
+        .fold(SymbolicDim::Int(1), |total, dim| total * dim)
+}
+
+/// Returns a shape that fits both shapes, keeping the dimensions they
+/// agree on. Returns `None` if the ranks differ.
+pub(crate) fn generalize(a: &Shape, b: &Shape) -> Option<Shape> {
+    if a.rank() != b.rank() {
+        return None;
+    }
+    let dims = a
+        .dims()
+        .iter()
+        .zip(b.dims())
+        .map(|(x, y)| if x == y { x.clone() } else { unknown() });
+    Some(Shape::new(dims))
+}
+
+/// Converts a possibly negative axis into an index below `rank`.
+fn normalize_axis(ctx: &InferenceContext<'_>, axis: i64, rank: usize) -> Result<usize> {
+    let rank = rank as i64;
+    let index = if axis < 0 { axis + rank } else { axis };
+    if (0..rank).contains(&index) {
+        Ok(index as usize)
+    } else {
+        Err(ctx.error(format!("axis {} is out of range for rank {}", axis, rank)))
+    }
+}
+
+fn normalize_axes(ctx: &InferenceContext<'_>, axes: &[i64], rank: usize) -> Result<Vec<usize>> {
+    axes.iter()
+        .map(|&axis| normalize_axis(ctx, axis, rank))
+        .collect()
+}
+
+/// Reads the values of an integer tensor.
+pub(crate) fn tensor_ints(tensor: &Tensor) -> Option<Vec<i64>> {
+    match tensor.dtype {
+        DataType::Int64 => tensor.to_vec().ok(),
+        DataType::Int32 => Some(widen::<i32, i64>(tensor)),
+        _ => None,
+    }
+}
+
+/// Reads the values of a numeric tensor as `f64`.
+fn tensor_numbers(tensor: &Tensor) -> Option<Vec<f64>> {
+    match tensor.dtype {
+        DataType::Float => Some(widen::<f32, f64>(tensor)),
+        DataType::Double => tensor.to_vec().ok(),
+        DataType::Int16 => Some(widen::<i16, f64>(tensor)),
+        _ => Some(tensor_ints(tensor)?.into_iter().map(|v| v as f64).collect()),
+    }
+}
+
+/// Converts the elements of a tensor holding `T` to a wider type.
+fn widen<T: Element, U: From<T>>(tensor: &Tensor) -> Vec<U> {
+    let values = tensor.as_slice::<T>().expect("dtype was matched");
+    values.iter().map(|&v| U::from(v)).collect()
+}
+
+/// Creates an `int64` tensor.
+pub(crate) fn int64_tensor(values: &[i64], shape: Shape) -> Tensor {
+    let data = values.iter().flat_map(|v| v.to_le_bytes()).collect();
+    Tensor::from_bytes(DataType::Int64, shape, data)
+}
+
+/// Returns the values of a constant integer input.
+fn constant_ints(ctx: &InferenceContext<'_>, index: usize) -> Option<Vec<i64>> {
+    tensor_ints(ctx.input_constant(index)?)
+}
+
+/// Returns the values of a constant numeric input.
+fn constant_numbers(ctx: &InferenceContext<'_>, index: usize) -> Option<Vec<f64>> {
+    tensor_numbers(ctx.input_constant(index)?)
+}
+
+/// Returns the value of a constant integer input holding a single element.
+fn constant_int(ctx: &InferenceContext<'_>, index: usize) -> Option<i64> {
+    match constant_ints(ctx, index).as_deref() {
+        Some(&[value]) => Some(value),
+        _ => None,
+    }
+}
+
+/// A list of integers given by either an attribute or an input.
+enum Ints {
+    /// Neither the attribute nor the input is given.
+    Absent,
+    /// The input is given but its value is not known.
+    Unknown,
+    Known(Vec<i64>),
+}
+
+/// Reads a list of integers from attribute `attr` or, if the node does not
+/// have the attribute, from input `input`.
+fn ints_from(ctx: &InferenceContext<'_>, attr: &str, input: usize) -> Result<Ints> {
+    if ctx.node().get_attribute(attr).is_some() {
+        return Ok(Ints::Known(ctx.node().attr_ints(attr)?.to_vec()));
+    }
+    if !ctx.has_input(input) {
+        return Ok(Ints::Absent);
+    }
+    Ok(constant_ints(ctx, input).map_or(Ints::Unknown, Ints::Known))
+}
+
+fn copy_dtype(ctx: &mut InferenceContext<'_>, input: usize, output: usize) {
+    if let Some(dtype) = ctx.input_dtype(input) {
+        ctx.set_output_dtype(output, dtype);
+    }
+}
+
+fn copy_shape(ctx: &mut InferenceContext<'_>, input: usize, output: usize) {
+    if let Some(shape) = ctx.input_shape(input).cloned() {
+        ctx.set_output_shape(output, shape);
+    }
+}
+
+/// Sets output 0 to the broadcast shape of the given inputs.
+fn broadcast_inputs(ctx: &mut InferenceContext<'_>, inputs: std::ops::Range<usize>) -> Result {
+    let shapes: Option<Vec<Shape>> = inputs.map(|i| ctx.input_shape(i).cloned()).collect();
+    if let Some(shapes) = shapes {
+        let shapes: Vec<&Shape> = shapes.iter().collect();
+        let shape = broadcast(ctx, &shapes)?;
+        ctx.set_output_shape(0, shape);
+    }
+    Ok(())
+}
+
+fn same_as_input(ctx: &mut InferenceContext<'_>) -> Result {
+    ctx.propagate(0, 0);
+    Ok(())
+}
+
+/// Sets output 0 to the broadcast shape of all inputs.
+///
+/// Before opset 7, inputs either have the same shape or the second one is
+/// broadcast to the first with the `broadcast` attribute, so the output has
+/// the shape of the first input.
+fn broadcast_all(ctx: &mut InferenceContext<'_>) -> Result {
+    if ctx.opset_version() < 7 {
+        copy_shape(ctx, 0, 0);
+        return Ok(());
+    }
+    broadcast_inputs(ctx, 0..ctx.num_inputs())
+}
+
+fn elementwise(ctx: &mut InferenceContext<'_>) -> Result {
+    copy_dtype(ctx, 0, 0);
+    broadcast_all(ctx)
+}
+
+fn comparison(ctx: &mut InferenceContext<'_>) -> Result {
+    ctx.set_output_dtype(0, DataType::Bool);
+    broadcast_all(ctx)
+}
+
+fn is_nan(ctx: &mut InferenceContext<'_>) -> Result {
+    ctx.set_output_dtype(0, DataType::Bool);
+    copy_shape(ctx, 0, 0);
+    Ok(())
+}
+
+fn where_(ctx: &mut InferenceContext<'_>) -> Result {
+    copy_dtype(ctx, 1, 0);
+    broadcast_inputs(ctx, 0..3)
+}
+
+fn cast(ctx: &mut InferenceContext<'_>) -> Result {
+    let dtype = if ctx.opset_version() < 6 {
+        // Cast-1 names the type, e.g. "FLOAT"
+        let to = ctx.node().attr_string("to")?;
+        DataType::from_onnx_name(&to.to_lowercase())
+            .ok_or_else(|| ctx.error(format!("attribute 'to' is not a data type: {}", to)))?
+    } else {
+        let to = ctx.node().attr_int("to")?;
+        i32::try_from(to)
+            .ok()
+            .and_then(DataType::from_i32)
+            .ok_or_else(|| ctx.error(format!("attribute 'to' is not a data type: {}", to)))?
+    };
+    ctx.set_output_dtype(0, dtype);
+    copy_shape(ctx, 0, 0);
+    Ok(())
+}
+
+fn cast_like(ctx: &mut InferenceContext<'_>) -> Result {
+    copy_dtype(ctx, 1, 0);
+    copy_shape(ctx, 0, 0);
+    Ok(())
+}
+
+fn matmul(ctx: &mut InferenceContext<'_>) -> Result {
+    copy_dtype(ctx, 0, 0);
+    matmul_shape(ctx, 1)
+}
+
+fn matmul_integer(ctx: &mut InferenceContext<'_>) -> Result {
+    ctx.set_output_dtype(0, DataType::Int32);
+    matmul_shape(ctx, 1)
+}
+
+fn qlinear_matmul(ctx: &mut InferenceContext<'_>) -> Result {
+    // The output has the type of its zero point
+    copy_dtype(ctx, 7, 0);
+    matmul_shape(ctx, 3)
+}
+
+/// Sets output 0 to the shape of the matrix product of input 0 and input
+/// `b`.
+fn matmul_shape(ctx: &mut InferenceContext<'_>, b: usize) -> Result {
+    let (Some(a), Some(b)) = (ctx.input_shape(0), ctx.input_shape(b)) else {
+        return Ok(());
+    };
+    if a.is_scalar() || b.is_scalar() {
+        return Err(ctx.error("inputs must not be scalars"));
+    }
+    // 1-D operands are promoted to matrices, and the added axis removed
+    let mut a_dims = a.dims().to_vec();
+    let mut b_dims = b.dims().to_vec();
+    let a_vector = a_dims.len() == 1;
+    let b_vector = b_dims.len() == 1;
+    if a_vector {
+        a_dims.insert(0, SymbolicDim::Int(1));
+    }
+    if b_vector {
+        b_dims.push(SymbolicDim::Int(1));
+    }
+    let (a_batch, a_matrix) = a_dims.split_at(a_dims.len() - 2);
+    let (b_batch, b_matrix) = b_dims.split_at(b_dims.len() - 2);
+    if !a_matrix[1].is_compatible_with(&b_matrix[0]) {
+        return Err(ctx.error(format!("cannot multiply shapes {} and {}", a, b)));
+    }
+    let batch = broadcast(
+        ctx,
+        &[&Shape::new(a_batch.to_vec()), &Shape::new(b_batch.to_vec())],
+    )?;
+    let mut dims = batch.dims().to_vec();
+    if !a_vector {
+        dims.push(a_matrix[0].clone());
+    }
+    if !b_vector {
+        dims.push(b_matrix[1].clone());
+    }
+    ctx.set_output_shape(0, Shape::new(dims));
+    Ok(())
+}
+
+fn gemm(ctx: &mut InferenceContext<'_>) -> Result {
+    copy_dtype(ctx, 0, 0);
+    let trans_a = ctx.node().attr_int_or("transA", 0)? != 0;
+    let trans_b = ctx.node().attr_int_or("transB", 0)? != 0;
+    let (Some(a), Some(b)) = (ctx.input_shape(0), ctx.input_shape(1)) else {
+        return Ok(());
+    };
+    if a.rank() != 2 || b.rank() != 2 {
+        return Err(ctx.error(format!(
+            "inputs must be matrices, got shapes {} and {}",
+            a, b
+        )));
+    }
+    let m = a.dims()[usize::from(trans_a)].clone();
+    let n = b.dims()[usize::from(!trans_b)].clone();
+    let mut shape = Shape::new(vec![m, n]);
+    // C is broadcast unidirectionally and may pin down unknown dimensions
+    if let Some(c) = ctx.input_shape(2).filter(|_| ctx.has_input(2)) {
+        shape = c
+            .broadcast_to(&shape)
+            .map_err(|_| ctx.error(format!("C of shape {} cannot be broadcast to {}", c, shape)))?;
+    }
+    ctx.set_output_shape(0, shape);
+    Ok(())
+}
+
+/// Reads a per-axis attribute of a convolution, checking its length.
+fn spatial_attr(
+    ctx: &InferenceContext<'_>,
+    name: &str,
+    default: i64,
+    len: usize,
+) -> Result<Vec<i64>> {
+    let values = ctx.node().attr_ints_or(name, &[])?;
+    if values.is_empty() {
+        return Ok(vec![default; len]);
+    }
+    if values.len() != len {
+        return Err(ctx.error(format!(
+            "attribute '{}' has {} values, expected {}",
+            name,
+            values.len(),
+            len
+        )));
+    }
+    Ok(values.to_vec())
+}
+
+/// Infers convolution and pooling operators.
+fn conv(ctx: &mut InferenceContext<'_>) -> Result {
+    copy_dtype(ctx, 0, 0);
+    let op_type = ctx.node().op_type.as_str();
+    let transpose = op_type == "ConvTranspose";
+    let is_conv = transpose
+        || matches!(
+            op_type,
+            "Conv" | "ConvInteger" | "QLinearConv" | "DeformConv"
+        );
+    let weights_input = if op_type == "QLinearConv" { 3 } else { 1 };
+    if op_type == "MaxPool" {
+        ctx.set_output_dtype(1, DataType::Int64);
+    }
+    let Some(x) = ctx.input_shape(0).cloned() else {
+        return Ok(());
+    };
+    if x.rank() < 2 {
+        return Err(ctx.error(format!("input has no channel axis: {}", x)));
+    }
+    let n = x.rank() - 2;
+    let weights = ctx.input_shape(weights_input).filter(|_| is_conv).cloned();
+    let kernel = match ctx.node().attr_ints_or("kernel_shape", &[])? {
+        [] => weights
+            .as_ref()
+            .and_then(|w| w.dims().get(2..)?.iter().map(|d| d.as_int()).collect()),
+        kernel => Some(kernel.to_vec()),
+    };
+
+    let channels = match (&weights, transpose) {
+        (Some(w), false) if w.rank() > 0 => w.dims()[0].clone(),
+        (Some(w), true) if w.rank() > 1 => {
+            let group = ctx.node().attr_int_or("group", 1)?;
+            w.dims()[1].clone() * SymbolicDim::Int(group)
+        }
+        _ if is_conv => unknown(),
+        _ => x.dims()[1].clone(),
+    };
+    let mut dims = vec![x.dims()[0].clone(), channels];
+
+    let Some(kernel) = kernel else {
+        dims.extend(vec![unknown(); n]);
+        ctx.set_output_shape(0, Shape::new(dims.clone()));
+        ctx.set_output_shape(1, Shape::new(dims));
+        return Ok(());
+    };
+    if kernel.len() != n {
+        return Err(ctx.error(format!(
+            "kernel has {} spatial axes, but the input has {}",
+            kernel.len(),
+            n
+        )));
+    }
+    let strides = spatial_attr(ctx, "strides", 1, n)?;
+    let dilations = spatial_attr(ctx, "dilations", 1, n)?;
+    let pads = spatial_attr(ctx, "pads", 0, 2 * n)?;
+    let output_padding = spatial_attr(ctx, "output_padding", 0, n)?;
+    let output_shape = ctx.node().attr_ints_or("output_shape", &[])?;
+    let auto_pad = ctx.node().attr_string_or("auto_pad", "NOTSET")?;
+    let ceil_mode = ctx.node().attr_int_or("ceil_mode", 0)? != 0;
+    if let Some(stride) = strides.iter().find(|&&s| s <= 0) {
+        return Err(ctx.error(format!("strides must be positive, got {}", stride)));
+    }
+    if let Some(dilation) = dilations.iter().find(|&&d| d <= 0) {
+        return Err(ctx.error(format!("dilations must be positive, got {}", dilation)));
+    }
+    if let Some(size) = kernel.iter().find(|&&k| k <= 0) {
+        return Err(ctx.error(format!("kernel sizes must be positive, got {}", size)));
+    }
+    if transpose && !output_shape.is_empty() && output_shape.len() < n {
+        return Err(ctx.error(format!(
+            "attribute 'output_shape' has {} values, expected at least {}",
+            output_shape.len(),
+            n
+        )));
+    }
+    let overflow = |ctx: &InferenceContext<'_>, axis: usize| {
+        ctx.error(format!("output size of spatial axis {} overflows", axis))
+    };
+
+    for i in 0..n {
+        if transpose && !output_shape.is_empty() {
+            let offset = output_shape.len() - n;
+            dims.push(output_shape[offset + i].into());
+            continue;
+        }
+        let stride = strides[i];
+        let extent = (kernel[i] - 1)
+            .checked_mul(dilations[i])
+            .and_then(|e| e.checked_add(1))
+            .ok_or_else(|| overflow(ctx, i))?;
+        let padding = match auto_pad {
+            "VALID" => 0,
+            _ => pads[i]
+                .checked_add(pads[i + n])
+                .ok_or_else(|| overflow(ctx, i))?,
+        };
+        let Some(input) = x.dims()[i + 2].as_int() else {
+            let input = x.dims()[i + 2].clone();
+            let stride = SymbolicDim::Int(stride);
+            dims.push(match (auto_pad, transpose) {
+                ("SAME_UPPER" | "SAME_LOWER", true) => input * stride,
+                ("SAME_UPPER" | "SAME_LOWER", false) => input.ceil_div(&stride),
+                (_, true) => {
+                    let offset = output_padding[i]
+                        .checked_add(extent)
+                        .and_then(|o| o.checked_sub(padding))
+                        .ok_or_else(|| overflow(ctx, i))?;
+                    stride * (input - SymbolicDim::Int(1)) + SymbolicDim::Int(offset)
+                }
+                // With ceil_mode, whether the last window is dropped depends
+                // on the input size
+                (_, false) if !ceil_mode => {
+                    let offset = padding
+                        .checked_sub(extent)
+                        .ok_or_else(|| overflow(ctx, i))?;
+                    let span = input + SymbolicDim::Int(offset);
+                    span.floor_div(&stride) + SymbolicDim::Int(1)
+                }
+                _ => unknown(),
+            });
+            continue;
+        };
+        let output = match (auto_pad, transpose) {
+            ("SAME_UPPER" | "SAME_LOWER", true) => input.checked_mul(stride),
+            ("SAME_UPPER" | "SAME_LOWER", false) => {
+                input.checked_add(stride - 1).map(|n| n / stride)
+            }
+            (_, true) => (input - 1)
+                .checked_mul(stride)
+                .and_then(|o| o.checked_add(output_padding[i]))
+                .and_then(|o| o.checked_add(extent))
+                .and_then(|o| o.checked_sub(padding)),
+            (_, false) => {
+                let padded = input.checked_add(padding).ok_or_else(|| overflow(ctx, i))?;
+                let span = padded - extent;
+                if span < 0 {
+                    return Err(ctx.error(format!(
+                        "kernel of size {} does not fit in input of size {}",
+                        extent, padded
+                    )));
+                }
+                if ceil_mode {
+                    let output = span / stride + i64::from(span % stride != 0) + 1;
+                    // The last window must start inside the input or the left padding
+                    match input.checked_add(pads[i]) {
+                        Some(limit) if (output - 1) * stride >= limit => Some(output - 1),
+                        _ => Some(output),
+                    }
+                } else {
+                    Some(span / stride + 1)
+                }
+            }
+        };
+        let output = output.ok_or_else(|| overflow(ctx, i))?;
+        dims.push(output.into());
+    }
+    ctx.set_output_shape(0, Shape::new(dims.clone()));
+    ctx.set_output_shape(1, Shape::new(dims));
+    Ok(())
+}
+
+fn global_pool(ctx: &mut InferenceContext<'_>) -> Result {
+    copy_dtype(ctx, 0, 0);
+    let Some(x) = ctx.input_shape(0) else {
+        return Ok(());
+    };
+    if x.rank() < 2 {
+        return Err(ctx.error(format!("input has no channel axis: {}", x)));
+    }
+    let mut dims = x.dims()[..2].to_vec();
+    dims.resize(x.rank(), SymbolicDim::Int(1));
+    ctx.set_output_shape(0, Shape::new(dims));
+    Ok(())
+}
+
+fn conv_integer(ctx: &mut InferenceContext<'_>) -> Result {
+    conv(ctx)?;
+    ctx.set_output_dtype(0, DataType::Int32);
+    Ok(())
+}
+
+fn qlinear_conv(ctx: &mut InferenceContext<'_>) -> Result {
+    conv(ctx)?;
+    // The output has the type of its zero point
+    copy_dtype(ctx, 7, 0);
+    Ok(())
+}
+
+/// Infers `RoiAlign` and `MaxRoiPool`, which pool each region of interest
+/// to a fixed size.
+fn roi_pool(ctx: &mut InferenceContext<'_>) -> Result {
+    copy_dtype(ctx, 0, 0);
+    let pooled = match ctx.node().op_type.as_str() {
+        "MaxRoiPool" => ctx.node().attr_ints("pooled_shape")?.to_vec(),
+        _ => vec![
+            ctx.node().attr_int_or("output_height", 1)?,
+            ctx.node().attr_int_or("output_width", 1)?,
+        ],
+    };
+    let channels = ctx
+        .input_shape(0)
+        .and_then(|x| x.dims().get(1).cloned())
+        .unwrap_or_else(unknown);
+    let rois = ctx
+        .input_shape(1)
+        .and_then(|r| r.dims().first().cloned())
+        .unwrap_or_else(unknown);
+    let mut dims = vec![rois, channels];
+    dims.extend(pooled.into_iter().map(SymbolicDim::Int));
+    ctx.set_output_shape(0, Shape::new(dims));
+    Ok(())
+}
+
+fn grid_sample(ctx: &mut InferenceContext<'_>) -> Result {
+    copy_dtype(ctx, 0, 0);
+    let (Some(x), Some(grid)) = (ctx.input_shape(0), ctx.input_shape(1)) else {
+        return Ok(());
+    };
+    if x.rank() < 2 || grid.rank() != x.rank() {
+        return Err(ctx.error(format!("cannot sample {} with grid {}", x, grid)));
+    }
+    // (N, C) from the input, then the spatial axes of the grid
+    let mut dims = x.dims()[..2].to_vec();
+    dims.extend_from_slice(&grid.dims()[1..grid.rank() - 1]);
+    ctx.set_output_shape(0, Shape::new(dims));
+    Ok(())
+}
+
+fn affine_grid(ctx: &mut InferenceContext<'_>) -> Result {
+    copy_dtype(ctx, 0, 0);
+    // The size is (N, C, H, W) for 2-D grids and (N, C, D, H, W) for 3-D
+    // grids, whose points have 2 or 3 coordinates
+    let size: Vec<SymbolicDim> = match constant_ints(ctx, 1) {
+        Some(size) => size.into_iter().map(SymbolicDim::Int).collect(),
+        None => match ctx.input_shape(1).and_then(|s| s.dims().first()?.as_int()) {
+            Some(len) => {
+                let batch = ctx
+                    .input_shape(0)
+                    .and_then(|theta| theta.dims().first().cloned())
+                    .unwrap_or_else(unknown);
+                let mut size = vec![batch];
+                size.resize(usize::try_from(len).unwrap_or_default(), unknown());
+                size
+            }
+            None => return Ok(()),
+        },
+    };
+    if !matches!(size.len(), 4 | 5) {
+        return Err(ctx.error(format!("size must have 4 or 5 values, got {}", size.len())));
+    }
+    let mut dims = vec![size[0].clone()];
+    dims.extend_from_slice(&size[2..]);
+    dims.push(SymbolicDim::Int(size.len() as i64 - 2));
+    ctx.set_output_shape(0, Shape::new(dims));
+    Ok(())
+}
+
+fn col2im(ctx: &mut InferenceContext<'_>) -> Result {
+    copy_dtype(ctx, 0, 0);
+    let Some(x) = ctx.input_shape(0).cloned() else {
+        return Ok(());
+    };
+    if x.rank() != 3 {
+        return Err(ctx.error(format!("input must have rank 3, got {}", x)));
+    }
+    let image: Vec<SymbolicDim> = match constant_ints(ctx, 1) {
+        Some(image) => image.into_iter().map(SymbolicDim::Int).collect(),
+        None => match ctx.input_shape(1).and_then(|s| s.dims().first()?.as_int()) {
+            Some(len) => vec![unknown(); usize::try_from(len).unwrap_or_default()],
+            None => return Ok(()),
+        },
+    };
+    // Each column holds one block of every channel
+    let channels = match constant_ints(ctx, 2) {
+        Some(block) if block.iter().any(|&b| b <= 0) => {
+            return Err(ctx.error(format!("block sizes must be positive, got {:?}", block)));
+        }
+        Some(block) => x.dims()[1].floor_div(&product(
+            &block.into_iter().map(SymbolicDim::Int).collect::<Vec<_>>(),
+        )),
+        None => unknown(),
+    };
+    let mut dims = vec![x.dims()[0].clone(), channels];
+    dims.extend(image);
+    ctx.set_output_shape(0, Shape::new(dims));
+    Ok(())
+}
+
+fn max_unpool(ctx: &mut InferenceContext<'_>) -> Result {
+    copy_dtype(ctx, 0, 0);
+    if ctx.has_input(2) {
+        if let Some(shape) = constant_ints(ctx, 2) {
+            ctx.set_output_shape(0, Shape::new(shape));
+        } else if let Some(rank) = ctx.input_shape(2).and_then(|s| s.dims().first()?.as_int()) {
+            ctx.set_output_shape(0, unknown_shape(usize::try_from(rank).unwrap_or_default()));
+        }
+        return Ok(());
+    }
+    let Some(x) = ctx.input_shape(0).cloned() else {
+        return Ok(());
+    };
+    if x.rank() < 2 {
+        return Err(ctx.error(format!("input has no channel axis: {}", x)));
+    }
+    let n = x.rank() - 2;
+    let kernel = ctx.node().attr_ints("kernel_shape")?.to_vec();
+    if kernel.len() != n {
+        return Err(ctx.error(format!(
+            "kernel has {} spatial axes, but the input has {}",
+            kernel.len(),
+            n
+        )));
+    }
+    let strides = spatial_attr(ctx, "strides", 1, n)?;
+    let pads = spatial_attr(ctx, "pads", 0, 2 * n)?;
+    if let Some(stride) = strides.iter().find(|&&s| s <= 0) {
+        return Err(ctx.error(format!("strides must be positive, got {}", stride)));
+    }
+    let mut dims = x.dims()[..2].to_vec();
+    for i in 0..n {
+        // The size of the input to the MaxPool this inverts
+        let offset = kernel[i]
+            .checked_sub(pads[i])
+            .and_then(|o| o.checked_sub(pads[i + n]))
+            .ok_or_else(|| ctx.error(format!("output size of spatial axis {} overflows", i)))?;
+        let input = x.dims()[i + 2].clone() - SymbolicDim::Int(1);
+        dims.push(SymbolicDim::Int(strides[i]) * input + SymbolicDim::Int(offset));
+    }
+    ctx.set_output_shape(0, Shape::new(dims));
+    Ok(())
+}
+
+/// Splits the query, key or value of `Attention` into its batch, heads,
+/// sequence and head size. A 3-D input holds the heads one after the other
+/// on its last axis, and their number is given by attribute `heads`.
+fn attention_heads(
+    ctx: &InferenceContext<'_>,
+    index: usize,
+    heads: &str,
+) -> Result<Option<[SymbolicDim; 4]>> {
+    let Some(x) = ctx.input_shape(index) else {
+        return Ok(None);
+    };
+    match x.dims() {
+        [batch, num_heads, seq, size] => Ok(Some([
+            batch.clone(),
+            num_heads.clone(),
+            seq.clone(),
+            size.clone(),
+        ])),
+        [batch, seq, hidden] => {
+            let num_heads = ctx.node().attr_int(heads)?;
+            if num_heads <= 0 {
+                return Err(ctx.error(format!(
+                    "attribute '{}' must be positive, got {}",
+                    heads, num_heads
+                )));
+            }
+            let num_heads = SymbolicDim::Int(num_heads);
+            let size = hidden.floor_div(&num_heads);
+            Ok(Some([batch.clone(), num_heads, seq.clone(), size]))
+        }
+        _ => Err(ctx.error(format!("input {} must have rank 3 or 4, got {}", index, x))),
+    }
+}
+
+fn attention(ctx: &mut InferenceContext<'_>) -> Result {
+    copy_dtype(ctx, 0, 0);
+    copy_dtype(ctx, 1, 1);
+    copy_dtype(ctx, 2, 2);
+    copy_dtype(ctx, 0, 3);
+    let q = attention_heads(ctx, 0, "q_num_heads")?;
+    let k = attention_heads(ctx, 1, "kv_num_heads")?;
+    let v = attention_heads(ctx, 2, "kv_num_heads")?;
+    // The present key and value append the new sequence to the past one
+    let past = ctx
+        .input_shape(4)
+        .filter(|_| ctx.has_input(4))
+        .map(|past| past.dims().get(2).cloned().unwrap_or_else(unknown));
+    let total = |seq: &SymbolicDim| match &past {
+        Some(past) => past.clone() + seq.clone(),
+        None => seq.clone(),
+    };
+    for (output, kv) in [(1, &k), (2, &v)] {
+        if let Some([batch, heads, seq, size]) = kv {
+            let dims = vec![batch.clone(), heads.clone(), total(seq), size.clone()];
+            ctx.set_output_shape(output, Shape::new(dims));
+        }
+    }
+
+    let Some([batch, heads, seq, _]) = q else {
+        return Ok(());
+    };
+    let kv_seq = k.map_or_else(unknown, |[_, _, seq, _]| total(&seq));
+    let qk = vec![batch.clone(), heads.clone(), seq.clone(), kv_seq];
+    ctx.set_output_shape(3, Shape::new(qk));
+    let v_size = v.map_or_else(unknown, |[_, _, _, size]| size);
+    let y = match ctx.input_shape(0).map(Shape::rank) {
+        Some(3) => vec![batch, seq, heads * v_size],
+        _ => vec![batch, heads, seq, v_size],
+    };
+    ctx.set_output_shape(0, Shape::new(y));
+    Ok(())
+}
+
+/// Infers `RNN`, `GRU` and `LSTM`.
+fn recurrent(ctx: &mut InferenceContext<'_>) -> Result {
+    for i in 0..ctx.num_outputs() {
+        copy_dtype(ctx, 0, i);
+    }
+    let Some(x) = ctx.input_shape(0).cloned() else {
+        return Ok(());
+    };
+    if x.rank() != 3 {
+        return Err(ctx.error(format!("input must have rank 3, got shape {}", x)));
+    }
+    let batch_first = ctx.node().attr_int_or("layout", 0)? != 0;
+    let directions = match ctx.node().attr_string_or("direction", "forward")? {
+        "bidirectional" => SymbolicDim::Int(2),
+        _ => SymbolicDim::Int(1),
+    };
+    let hidden = match ctx.node().attr_int_or("hidden_size", 0)? {
+        0 => unknown(),
+        size => SymbolicDim::Int(size),
+    };
+    let (seq, batch) = match batch_first {
+        false => (x.dims()[0].clone(), x.dims()[1].clone()),
+        true => (x.dims()[1].clone(), x.dims()[0].clone()),
+    };
+    let (y, state) = match batch_first {
+        false => (
+            vec![seq, directions.clone(), batch.clone(), hidden.clone()],
+            vec![directions, batch, hidden],
+        ),
+        true => (
+            vec![batch.clone(), seq, directions.clone(), hidden.clone()],
+            vec![batch, directions, hidden],
+        ),
+    };
+    ctx.set_output_shape(0, Shape::new(y));
+    // Y_h, and Y_c for LSTM
+    for i in 1..ctx.num_outputs() {
+        ctx.set_output_shape(i, Shape::new(state.clone()));
+    }
+    Ok(())
+}
+
+fn batch_normalization(ctx: &mut InferenceContext<'_>) -> Result {
+    ctx.propagate(0, 0);
+    // Running mean and variance in training mode
+    ctx.propagate(3, 1);
+    ctx.propagate(4, 2);
+    Ok(())
+}
+
+fn layer_normalization(ctx: &mut InferenceContext<'_>) -> Result {
+    ctx.propagate(0, 0);
+    let stash_type = ctx.node().attr_int_or("stash_type", 1)?;
+    if let Some(dtype) = i32::try_from(stash_type).ok().and_then(DataType::from_i32) {
+        ctx.set_output_dtype(1, dtype);
+        ctx.set_output_dtype(2, dtype);
+    }
+    let Some(x) = ctx.input_shape(0).cloned() else {
+        return Ok(());
+    };
+    let axis = normalize_axis(ctx, ctx.node().attr_int_or("axis", -1)?, x.rank())?;
+    let mut dims = x.dims().to_vec();
+    dims[axis..].fill(SymbolicDim::Int(1));
+    ctx.set_output_shape(1, Shape::new(dims.clone()));
+    ctx.set_output_shape(2, Shape::new(dims));
+    Ok(())
+}
+
+fn dropout(ctx: &mut InferenceContext<'_>) -> Result {
+    ctx.propagate(0, 0);
+    if ctx.opset_version() < 10 {
+        copy_dtype(ctx, 0, 1);
+    } else {
+        ctx.set_output_dtype(1, DataType::Bool);
+    }
+    copy_shape(ctx, 0, 1);
+    Ok(())
+}
+
+fn quantize_linear(ctx: &mut InferenceContext<'_>) -> Result {
+    let output_dtype = ctx.node().attr_int_or("output_dtype", 0)?;
+    match ctx.input_dtype(2) {
+        None if output_dtype != 0 => {
+            let dtype = i32::try_from(output_dtype)
+                .ok()
+                .and_then(DataType::from_i32)
+                .ok_or_else(|| ctx.error(format!("invalid output_dtype {}", output_dtype)))?;
+            ctx.set_output_dtype(0, dtype);
+        }
+        Some(dtype) => ctx.set_output_dtype(0, dtype),
+        None if !ctx.has_input(2) => ctx.set_output_dtype(0, DataType::Uint8),
+        None => {}
+    }
+    copy_shape(ctx, 0, 0);
+    Ok(())
+}
+
+fn dynamic_quantize_linear(ctx: &mut InferenceContext<'_>) -> Result {
+    ctx.set_output_dtype(0, DataType::Uint8);
+    copy_shape(ctx, 0, 0);
+    ctx.set_output_dtype(1, DataType::Float);
+    ctx.set_output_shape(1, Shape::scalar());
+    ctx.set_output_dtype(2, DataType::Uint8);
+    ctx.set_output_shape(2, Shape::scalar());
+    Ok(())
+}
+
+fn dequantize_linear(ctx: &mut InferenceContext<'_>) -> Result {
+    copy_dtype(ctx, 1, 0);
+    copy_shape(ctx, 0, 0);
+    Ok(())
+}
+
+fn einsum(ctx: &mut InferenceContext<'_>) -> Result {
+    copy_dtype(ctx, 0, 0);
+    let equation: String = ctx
+        .node()
+        .attr_string("equation")?
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect();
+    let (lhs, rhs) = match equation.split_once("->") {
+        Some((lhs, rhs)) => (lhs, Some(rhs)),
+        None => (equation.as_str(), None),
+    };
+    let terms: Vec<&str> = lhs.split(',').collect();
+    if terms.len() != ctx.num_inputs() {
+        return Err(ctx.error(format!(
+            "equation has {} operands, but the node has {} inputs",
+            terms.len(),
+            ctx.num_inputs()
+        )));
+    }
+    // Broadcast dimensions under an ellipsis are not tracked
+    if equation.contains("...") {
+        return Ok(());
+    }
+
+    let mut labels: Vec<(char, SymbolicDim, usize)> = Vec::new();
+    for (i, term) in terms.iter().enumerate() {
+        let shape = ctx.input_shape(i);
+        if let Some(shape) = shape {
+            if shape.rank() != term.len() {
+                return Err(ctx.error(format!(
+                    "operand '{}' does not match input shape {}",
+                    term, shape
+                )));
+            }
+        }
+        for (j, label) in term.chars().enumerate() {
+            let dim = shape.map_or_else(unknown, |s| s.dims()[j].clone());
+            match labels.iter_mut().find(|(l, _, _)| *l == label) {
+                Some((_, known, count)) => {
+                    *count += 1;
+                    if known.as_int().is_none() {
+                        *known = dim;
+                    }
+                }
+                None => labels.push((label, dim, 1)),
+            }
+        }
+    }
+    if terms
+        .iter()
+        .enumerate()
+        .any(|(i, _)| ctx.input_shape(i).is_none())
+        && rhs.is_none()
+    {
+        return Ok(());
+    }
+    let output: Vec<char> = match rhs {
+        Some(rhs) => rhs.chars().collect(),
+        None => {
+            // Implicit mode outputs the labels used once, in alphabetical order
+            let mut once: Vec<char> = labels
+                .iter()
+                .filter(|(_, _, count)| *count == 1)
+                .map(|(l, _, _)| *l)
+                .collect();
+            once.sort_unstable();
+            once
+        }
+    };
+    let mut dims = Vec::with_capacity(output.len());
+    for label in output {
+        let dim = labels
+            .iter()
+            .find(|(l, _, _)| *l == label)
+            .map(|(_, dim, _)| dim.clone())
+            .ok_or_else(|| ctx.error(format!("output label '{}' is not an input label", label)))?;
+        dims.push(dim);
+    }
+    ctx.set_output_shape(0, Shape::new(dims));
+    Ok(())
+}
+
+/// Returns the shape reduced over `axes`.
+fn reduced_shape(input: &Shape, axes: &[usize], keepdims: bool) -> Shape {
+    let mut dims = Vec::with_capacity(input.rank());
+    for (i, dim) in input.dims().iter().enumerate() {
+        if !axes.contains(&i) {
+            dims.push(dim.clone());
+        } else if keepdims {
+            dims.push(SymbolicDim::Int(1));
+        }
+    }
+    Shape::new(dims)
+}
+
+fn reduce(ctx: &mut InferenceContext<'_>) -> Result {
+    copy_dtype(ctx, 0, 0);
+    let keepdims = ctx.node().attr_int_or("keepdims", 1)? != 0;
+    let noop_with_empty_axes = ctx.node().attr_int_or("noop_with_empty_axes", 0)? != 0;
+    let axes = ints_from(ctx, "axes", 1)?;
+    let Some(x) = ctx.input_shape(0).cloned() else {
+        return Ok(());
+    };
+    let axes = match axes {
+        Ints::Unknown if keepdims => {
+            ctx.set_output_shape(0, unknown_shape(x.rank()));
+            return Ok(());
+        }
+        Ints::Unknown => return Ok(()),
+        Ints::Known(axes) if !axes.is_empty() => normalize_axes(ctx, &axes, x.rank())?,
+        _ if noop_with_empty_axes => {
+            ctx.set_output_shape(0, x);
+            return Ok(());
+        }
+        _ => (0..x.rank()).collect(),
+    };
+    ctx.set_output_shape(0, reduced_shape(&x, &axes, keepdims));
+    Ok(())
+}
+
+fn det(ctx: &mut InferenceContext<'_>) -> Result {
+    copy_dtype(ctx, 0, 0);
+    let Some(x) = ctx.input_shape(0) else {
+        return Ok(());
+    };
+    if x.rank() < 2 {
+        return Err(ctx.error(format!("input must be a batch of matrices, got {}", x)));
+    }
+    let dims = x.dims()[..x.rank() - 2].to_vec();
+    ctx.set_output_shape(0, Shape::new(dims));
+    Ok(())
+}
+
+fn arg_reduce(ctx: &mut InferenceContext<'_>) -> Result {
+    ctx.set_output_dtype(0, DataType::Int64);
+    let keepdims = ctx.node().attr_int_or("keepdims", 1)? != 0;
+    let Some(x) = ctx.input_shape(0).cloned() else {
+        return Ok(());
+    };
+    let axis = normalize_axis(ctx, ctx.node().attr_int_or("axis", 0)?, x.rank())?;
+    ctx.set_output_shape(0, reduced_shape(&x, &[axis], keepdims));
+    Ok(())
+}
+
+fn top_k(ctx: &mut InferenceContext<'_>) -> Result {
+    copy_dtype(ctx, 0, 0);
+    ctx.set_output_dtype(1, DataType::Int64);
+    let Some(x) = ctx.input_shape(0).cloned() else {
+        return Ok(());
+    };
+    let axis = normalize_axis(ctx, ctx.node().attr_int_or("axis", -1)?, x.rank())?;
+    let mut dims = x.dims().to_vec();
+    dims[axis] = if ctx.opset_version() < 10 {
+        SymbolicDim::Int(ctx.node().attr_int("k")?)
+    } else {
+        match constant_ints(ctx, 1).as_deref() {
+            Some([k]) => SymbolicDim::Int(*k),
+            _ => unknown(),
+        }
+    };
+    ctx.set_output_shape(0, Shape::new(dims.clone()));
+    ctx.set_output_shape(1, Shape::new(dims));
+    Ok(())
+}
+
+fn reshape(ctx: &mut InferenceContext<'_>) -> Result {
+    copy_dtype(ctx, 0, 0);
+    let target = if ctx.opset_version() < 5 {
+        Some(ctx.node().attr_ints("shape")?.to_vec())
+    } else {
+        constant_ints(ctx, 1)
+    };
+    let Some(target) = target else {
+        // The rank is still known from the length of the shape input
+        if let Some(rank) = ctx.input_shape(1).and_then(|s| s.dims().first()?.as_int()) {
+            ctx.set_output_shape(0, unknown_shape(rank as usize));
+        }
+        return Ok(());
+    };
+    let allowzero = ctx.node().attr_int_or("allowzero", 0)? != 0;
+    let input = ctx.input_shape(0).cloned();
+
+    let mut dims: Vec<SymbolicDim> = Vec::with_capacity(target.len());
+    let mut inferred_axis = None;
+    for (i, &dim) in target.iter().enumerate() {
+        match dim {
+            -1 => {
+                if inferred_axis.replace(i).is_some() {
+                    return Err(ctx.error("shape has more than one -1"));
+                }
+                dims.push(unknown());
+            }
+            0 if !allowzero => match &input {
+                Some(input) => dims.push(input.dims().get(i).cloned().ok_or_else(|| {
+                    ctx.error(format!(
+                        "shape copies axis {} of an input of rank {}",
+                        i,
+                        input.rank()
+                    ))
+                })?),
+                None => dims.push(unknown()),
+            },
+            dim if dim < 0 => {
+                return Err(ctx.error(format!("shape has invalid dimension {}", dim)));
+            }
+            dim => dims.push(dim.into()),
+        }
+    }
+    if let (Some(axis), Some(input)) = (inferred_axis, input) {
+        let total = product(input.dims());
+        let known: Vec<_> = dims
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| *i != axis)
+            .map(|(_, d)| d.clone())
+            .collect();
+        let known = product(&known);
+        if let (Some(total), Some(known)) = (total.as_int(), known.as_int()) {
+            if known == 0 || total % known != 0 {
+                return Err(ctx.error(format!("cannot reshape {} elements to {:?}", total, target)));
+            }
+        }
+        // Symbolic sizes are divided only if the division is exact, e.g.
+        // 12*N elements reshaped to [N, -1]
+        if let Some(dim) = total.exact_div(&known) {
+            dims[axis] = dim;
+        }
+    }
+    ctx.set_output_shape(0, Shape::new(dims));
+    Ok(())
+}
+
+fn shape(ctx: &mut InferenceContext<'_>) -> Result {
+    ctx.set_output_dtype(0, DataType::Int64);
+    let Some(x) = ctx.input_shape(0) else {
+        return Ok(());
+    };
+    let rank = x.rank() as i64;
+    let clamp = |index: i64| (if index < 0 { index + rank } else { index }).clamp(0, rank);
+    let start = clamp(ctx.node().attr_int_or("start", 0)?);
+    let end = clamp(ctx.node().attr_int_or("end", rank)?);
+    ctx.set_output_shape(0, Shape::new(vec![(end - start).max(0)]));
+    Ok(())
+}
+
+fn size(ctx: &mut InferenceContext<'_>) -> Result {
+    ctx.set_output_dtype(0, DataType::Int64);
+    ctx.set_output_shape(0, Shape::scalar());
+    Ok(())
+}
+
+fn flatten(ctx: &mut InferenceContext<'_>) -> Result {
+    copy_dtype(ctx, 0, 0);
+    let Some(x) = ctx.input_shape(0).cloned() else {
+        return Ok(());
+    };
+    let rank = x.rank() as i64;
+    let axis = ctx.node().attr_int_or("axis", 1)?;
+    let axis = if axis < 0 { axis + rank } else { axis };
+    if !(0..=rank).contains(&axis) {
+        return Err(ctx.error(format!("axis {} is out of range for rank {}", axis, rank)));
+    }
+    let (outer, inner) = x.dims().split_at(axis as usize);
+    ctx.set_output_shape(0, Shape::new(vec![product(outer), product(inner)]));
+    Ok(())
+}
+
+fn transpose(ctx: &mut InferenceContext<'_>) -> Result {
+    copy_dtype(ctx, 0, 0);
+    let Some(x) = ctx.input_shape(0).cloned() else {
+        return Ok(());
+    };
+    let perm = match ctx.node().attr_ints_or("perm", &[])? {
+        [] => (0..x.rank()).rev().collect(),
+        perm => {
+            let perm = normalize_axes(ctx, perm, x.rank())?;
+            let mut sorted = perm.clone();
+            sorted.sort_unstable();
+            if sorted != (0..x.rank()).collect::<Vec<_>>() {
+                return Err(ctx.error(format!(
+                    "perm {:?} is not a permutation of the axes of {}",
+                    perm, x
+                )));
+            }
+            perm
+        }
+    };
+    let dims: Vec<_> = perm.iter().map(|&i| x.dims()[i].clone()).collect();
+    ctx.set_output_shape(0, Shape::new(dims));
+    Ok(())
+}
+
+fn concat(ctx: &mut InferenceContext<'_>) -> Result {
+    if let Some(dtype) = (0..ctx.num_inputs()).find_map(|i| ctx.input_dtype(i)) {
+        ctx.set_output_dtype(0, dtype);
+    }
+    let shapes: Option<Vec<Shape>> = (0..ctx.num_inputs())
+        .map(|i| ctx.input_shape(i).cloned())
+        .collect();
+    let Some(shapes) = shapes.filter(|s| !s.is_empty()) else {
+        return Ok(());
+    };
+    let rank = shapes[0].rank();
+    let axis = if ctx.opset_version() < 4 {
+        ctx.node().attr_int_or("axis", 1)?
+    } else {
+        ctx.node().attr_int("axis")?
+    };
+    let axis = normalize_axis(ctx, axis, rank)?;
+    let mut dims = shapes[0].dims().to_vec();
+    for shape in &shapes[1..] {
+        if shape.rank() != rank {
+            return Err(ctx.error(format!(
+                "cannot concatenate shapes {} and {}",
+                shapes[0], shape
+            )));
+        }
+        for (i, (dim, other)) in dims.iter_mut().zip(shape.dims()).enumerate() {
+            if i == axis {
+                *dim = dim.clone() + other.clone();
+                continue;
+            }
+            match (dim.as_int(), other.as_int()) {
+                (Some(a), Some(b)) if a != b => {
+                    return Err(ctx.error(format!(
+                        "cannot concatenate shapes {} and {} along axis {}",
+                        shapes[0], shape, axis
+                    )));
+                }
+                (None, Some(_)) => *dim = other.clone(),
+                _ => {}
+            }
+        }
+    }
+    ctx.set_output_shape(0, Shape::new(dims));
+    Ok(())
+}
+
+fn split(ctx: &mut InferenceContext<'_>) -> Result {
+    let outputs = ctx.num_outputs();
+    for i in 0..outputs {
+        copy_dtype(ctx, 0, i);
+    }
+    let Some(x) = ctx.input_shape(0).cloned() else {
+        return Ok(());
+    };
+    let axis = normalize_axis(ctx, ctx.node().attr_int_or("axis", 0)?, x.rank())?;
+    let sizes = match ints_from(ctx, "split", 1)? {
+        Ints::Known(sizes) => Some(sizes),
+        Ints::Unknown => None,
+        // Without sizes, the axis is split evenly, with a smaller last chunk
+        Ints::Absent if outputs == 0 => {
+            return Err(ctx.error("cannot split evenly into zero outputs"));
+        }
+        Ints::Absent => x.dims()[axis].as_int().map(|dim| {
+            let parts = outputs as i64;
+            let chunk = dim / parts + i64::from(dim % parts != 0);
+            (0..outputs as i64)
+                .map(|i| (dim - i * chunk).clamp(0, chunk))
+                .collect()
+        }),
+    };
+    if let Some(sizes) = &sizes {
+        if sizes.len() != outputs {
+            return Err(ctx.error(format!(
+                "split has {} sizes for {} outputs",
+                sizes.len(),
+                outputs
+            )));
+        }
+        if let Some(dim) = x.dims()[axis].as_int() {
+            let total = sizes
+                .iter()
+                .try_fold(0i64, |sum, &size| sum.checked_add(size));
+            if sizes.iter().any(|&size| size < 0) || total != Some(dim) {
+                return Err(ctx.error(format!(
+                    "split sizes {:?} do not add up to dimension {}",
+                    sizes, dim
+                )));
+            }
+        }
+    }
+    for i in 0..outputs {
+        let mut dims = x.dims().to_vec();
+        dims[axis] = sizes
+            .as_ref()
+            .map_or_else(unknown, |sizes| SymbolicDim::Int(sizes[i]));
+        ctx.set_output_shape(i, Shape::new(dims));
+    }
+    Ok(())
+}
+
+/// Returns the length of `dim` sliced from `start` to `end` by `step`.
+fn slice_len(dim: i64, start: i64, end: i64, step: i64) -> i64 {
+    let resolve = |index: i64| if index < 0 { index + dim } else { index };
+    let (start, end) = (resolve(start), resolve(end));
+    if step > 0 {
+        let (start, end) = (start.clamp(0, dim), end.clamp(0, dim));
+        ((end - start).max(0) + step - 1) / step
+    } else {
+        let (start, end) = (start.clamp(0, dim - 1), end.clamp(-1, dim - 1));
+        ((start - end).max(0) - step - 1) / -step
+    }
+}
+
+fn slice(ctx: &mut InferenceContext<'_>) -> Result {
+    copy_dtype(ctx, 0, 0);
+    let Some(x) = ctx.input_shape(0).cloned() else {
+        return Ok(());
+    };
+    let starts = ints_from(ctx, "starts", 1)?;
+    let ends = ints_from(ctx, "ends", 2)?;
+    let (Ints::Known(starts), Ints::Known(ends)) = (starts, ends) else {
+        // The axes that are sliced may still be known
+        let mut dims = x.dims().to_vec();
+        match ints_from(ctx, "axes", 3)? {
+            Ints::Known(axes) => {
+                for axis in normalize_axes(ctx, &axes, x.rank())? {
+                    dims[axis] = unknown();
+                }
+            }
+            _ => dims.fill(unknown()),
+        }
+        ctx.set_output_shape(0, Shape::new(dims));
+        return Ok(());
+    };
+    let axes = match ints_from(ctx, "axes", 3)? {
+        Ints::Known(axes) => Some(axes),
+        Ints::Absent => Some((0..starts.len() as i64).collect()),
+        Ints::Unknown => None,
+    };
+    let steps = match ints_from(ctx, "steps", 4)? {
+        Ints::Known(steps) => Some(steps),
+        Ints::Absent => Some(vec![1; starts.len()]),
+        Ints::Unknown => None,
+    };
+    let (Some(axes), Some(steps)) = (axes, steps) else {
+        ctx.set_output_shape(0, unknown_shape(x.rank()));
+        return Ok(());
+    };
+    if ends.len() != starts.len() || axes.len() != starts.len() || steps.len() != starts.len() {
+        return Err(ctx.error("starts, ends, axes and steps have different lengths"));
+    }
+
+    let mut dims = x.dims().to_vec();
+    for (i, axis) in normalize_axes(ctx, &axes, x.rank())?
+        .into_iter()
+        .enumerate()
+    {
+        let (start, end, step) = (starts[i], ends[i], steps[i]);
+        if step == 0 {
+            return Err(ctx.error("slice step cannot be 0"));
+        }
+        dims[axis] = match dims[axis].as_int() {
+            Some(dim) => slice_len(dim, start, end, step).into(),
+            // Symbolic dimensions are assumed to be long enough for the
+            // slice, so that x[a:] has length dim - a and x[a:-b] has
+            // length dim - a - b
+            None if start >= 0 && step == 1 && end >= i32::MAX as i64 => {
+                dims[axis].clone() - SymbolicDim::Int(start)
+            }
+            None if start >= 0 && step == 1 && end < 0 && end > i32::MIN as i64 => {
+                dims[axis].clone() - SymbolicDim::Int(start - end)
+            }
+            None => unknown(),
+        };
+    }
+    ctx.set_output_shape(0, Shape::new(dims));
+    Ok(())
+}
+
+fn squeeze(ctx: &mut InferenceContext<'_>) -> Result {
+    copy_dtype(ctx, 0, 0);
+    let axes = ints_from(ctx, "axes", 1)?;
+    let Some(x) = ctx.input_shape(0).cloned() else {
+        return Ok(());
+    };
+    let axes = match axes {
+        Ints::Known(axes) => {
+            let axes = normalize_axes(ctx, &axes, x.rank())?;
+            for &axis in &axes {
+                if let Some(dim) = x.dims()[axis].as_int().filter(|&d| d != 1) {
+                    return Err(ctx.error(format!("cannot squeeze axis {} of size {}", axis, dim)));
+                }
+            }
+            axes
+        }
+        // All axes of size 1 are removed, which requires every size to be known
+        Ints::Absent if x.to_vec().is_some() => (0..x.rank())
+            .filter(|&i| x.dims()[i] == SymbolicDim::Int(1))
+            .collect(),
+        _ => return Ok(()),
+    };
+    ctx.set_output_shape(0, reduced_shape(&x, &axes, false));
+    Ok(())
+}
+
+fn unsqueeze(ctx: &mut InferenceContext<'_>) -> Result {
+    copy_dtype(ctx, 0, 0);
+    let Ints::Known(axes) = ints_from(ctx, "axes", 1)? else {
+        return Ok(());
+    };
+    let Some(x) = ctx.input_shape(0).cloned() else {
+        return Ok(());
+    };
+    let rank = x.rank() + axes.len();
+    let mut axes = normalize_axes(ctx, &axes, rank)?;
+    axes.sort_unstable();
+    let mut dims = x.dims().to_vec();
+    for axis in axes {
+        dims.insert(axis, SymbolicDim::Int(1));
+    }
+    ctx.set_output_shape(0, Shape::new(dims));
+    Ok(())
+}
+
+fn expand(ctx: &mut InferenceContext<'_>) -> Result {
+    copy_dtype(ctx, 0, 0);
+    let (Some(x), Some(target)) = (ctx.input_shape(0).cloned(), constant_ints(ctx, 1)) else {
+        return Ok(());
+    };
+    let shape = broadcast(ctx, &[&x, &Shape::new(target)])?;
+    ctx.set_output_shape(0, shape);
+    Ok(())
+}
+
+fn tile(ctx: &mut InferenceContext<'_>) -> Result {
+    copy_dtype(ctx, 0, 0);
+    let Some(x) = ctx.input_shape(0).cloned() else {
+        return Ok(());
+    };
+    // Tile-1 repeats a single axis given by the `axis` input
+    let repeats = constant_ints(ctx, 1).filter(|_| ctx.opset_version() >= 6);
+    let Some(repeats) = repeats else {
+        ctx.set_output_shape(0, unknown_shape(x.rank()));
+        return Ok(());
+    };
+    if repeats.len() != x.rank() {
+        return Err(ctx.error(format!(
+            "repeats has {} values for an input of rank {}",
+            repeats.len(),
+            x.rank()
+        )));
+    }
+    let dims: Vec<_> = x
+        .dims()
+        .iter()
+        .zip(&repeats)
+        .map(|(dim, &repeat)| dim.clone() * SymbolicDim::Int(repeat))
+        .collect();
+    ctx.set_output_shape(0, Shape::new(dims));
+    Ok(())
+}
+
+fn gather(ctx: &mut InferenceContext<'_>) -> Result {
+    copy_dtype(ctx, 0, 0);
+    let (Some(data), Some(indices)) = (ctx.input_shape(0).cloned(), ctx.input_shape(1).cloned())
+    else {
+        return Ok(());
+    };
+    let axis = normalize_axis(ctx, ctx.node().attr_int_or("axis", 0)?, data.rank())?;
+    let mut dims = data.dims()[..axis].to_vec();
+    dims.extend_from_slice(indices.dims());
+    dims.extend_from_slice(&data.dims()[axis + 1..]);
+    ctx.set_output_shape(0, Shape::new(dims));
+    Ok(())
+}
+
+fn gather_elements(ctx: &mut InferenceContext<'_>) -> Result {
+    copy_dtype(ctx, 0, 0);
+    copy_shape(ctx, 1, 0);
+    Ok(())
+}
+
+fn gather_nd(ctx: &mut InferenceContext<'_>) -> Result {
+    copy_dtype(ctx, 0, 0);
+    let (Some(data), Some(indices)) = (ctx.input_shape(0).cloned(), ctx.input_shape(1).cloned())
+    else {
+        return Ok(());
+    };
+    let batch_dims = ctx.node().attr_int_or("batch_dims", 0)?;
+    let Ok(batch_dims) = usize::try_from(batch_dims) else {
+        return Err(ctx.error(format!(
+            "batch_dims must not be negative, got {}",
+            batch_dims
+        )));
+    };
+    let Some((last, outer)) = indices.dims().split_last() else {
+        return Err(ctx.error("indices must not be a scalar"));
+    };
+    let Some(last) = last.as_int() else {
+        return Ok(());
+    };
+    let Ok(index_len) = usize::try_from(last) else {
+        return Err(ctx.error(format!("indices have a negative last dimension {}", last)));
+    };
+    let start = batch_dims.checked_add(index_len);
+    let Some(start) = start.filter(|&start| start <= data.rank()) else {
+        return Err(ctx.error(format!(
+            "indices of length {} index past the rank of data {}",
+            last, data
+        )));
+    };
+    let mut dims = outer.to_vec();
+    dims.extend_from_slice(&data.dims()[start..]);
+    ctx.set_output_shape(0, Shape::new(dims));
+    Ok(())
+}
+
+fn pad(ctx: &mut InferenceContext<'_>) -> Result {
+    copy_dtype(ctx, 0, 0);
+    let Some(x) = ctx.input_shape(0).cloned() else {
+        return Ok(());
+    };
+    let axes = match ints_from(ctx, "axes", 3)? {
+        Ints::Known(axes) => normalize_axes(ctx, &axes, x.rank())?,
+        Ints::Absent => (0..x.rank()).collect(),
+        Ints::Unknown => {
+            ctx.set_output_shape(0, unknown_shape(x.rank()));
+            return Ok(());
+        }
+    };
+    let mut dims = x.dims().to_vec();
+    let pads = if ctx.opset_version() < 2 {
+        "paddings"
+    } else {
+        "pads"
+    };
+    let Ints::Known(pads) = ints_from(ctx, pads, 1)? else {
+        for &axis in &axes {
+            dims[axis] = unknown();
+        }
+        ctx.set_output_shape(0, Shape::new(dims));
+        return Ok(());
+    };
+    if pads.len() != 2 * axes.len() {
+        return Err(ctx.error(format!(
+            "pads has {} values for {} axes",
+            pads.len(),
+            axes.len()
+        )));
+    }
+    for (i, &axis) in axes.iter().enumerate() {
+        let padding = pads[i] + pads[i + axes.len()];
+        dims[axis] = dims[axis].clone() + SymbolicDim::Int(padding);
+    }
+    ctx.set_output_shape(0, Shape::new(dims));
+    Ok(())
+}
+
+fn constant(ctx: &mut InferenceContext<'_>) -> Result {
+    let node = ctx.node();
+    let Some((dtype, shape)) = node.attributes.keys().find_map(|name| {
+        let value = &node.get_attribute(name)?.value;
+        Some(match (name.as_str(), value) {
+            ("value", AttrValue::Tensor(tensor)) => {
+                (tensor.dtype, Shape::new(tensor.shape.dims().to_vec()))
+            }
+            ("value_int", _) => (DataType::Int64, Shape::scalar()),
+            ("value_ints", AttrValue::Ints(v)) => {
+                (DataType::Int64, Shape::new(vec![v.len() as i64]))
+            }
+            ("value_float", _) => (DataType::Float, Shape::scalar()),
+            ("value_floats", AttrValue::Floats(v)) => {
+                (DataType::Float, Shape::new(vec![v.len() as i64]))
+            }
+            ("value_string", _) => (DataType::String, Shape::scalar()),
+            ("value_strings", AttrValue::Strings(v)) => {
+                (DataType::String, Shape::new(vec![v.len() as i64]))
+            }
+            _ => return None,
+        })
+    }) else {
+        return Ok(());
+    };
+    ctx.set_output_dtype(0, dtype);
+    ctx.set_output_shape(0, shape);
+    Ok(())
+}
+
+fn constant_of_shape(ctx: &mut InferenceContext<'_>) -> Result {
+    let dtype = match ctx.node().get_attribute("value") {
+        Some(_) => ctx.node().attr_tensor("value")?.dtype,
+        None => DataType::Float,
+    };
+    ctx.set_output_dtype(0, dtype);
+    if let Some(dims) = constant_ints(ctx, 0) {
+        ctx.set_output_shape(0, Shape::new(dims));
+    } else if let Some(rank) = ctx.input_shape(0).and_then(|s| s.dims().first()?.as_int()) {
+        ctx.set_output_shape(0, unknown_shape(rank as usize));
+    }
+    Ok(())
+}
+
+fn range(ctx: &mut InferenceContext<'_>) -> Result {
+    copy_dtype(ctx, 0, 0);
+    let scalar = |index| match constant_numbers(ctx, index).as_deref() {
+        Some(&[value]) => Some(value),
+        _ => None,
+    };
+    let len = match (scalar(0), scalar(1), scalar(2)) {
+        (Some(_), Some(_), Some(0.0)) => {
+            return Err(ctx.error("delta cannot be 0"));
+        }
+        (Some(start), Some(limit), Some(delta)) => {
+            SymbolicDim::Int(((limit - start) / delta).ceil().max(0.0) as i64)
+        }
+        _ => unknown(),
+    };
+    ctx.set_output_shape(0, Shape::new(vec![len]));
+    Ok(())
+}
+
+fn non_zero(ctx: &mut InferenceContext<'_>) -> Result {
+    ctx.set_output_dtype(0, DataType::Int64);
+    let rank = ctx
+        .input_shape(0)
+        .map_or_else(unknown, |x| SymbolicDim::Int(x.rank() as i64));
+    ctx.set_output_shape(0, Shape::new(vec![rank, unknown()]));
+    Ok(())
+}
+
+fn one_hot(ctx: &mut InferenceContext<'_>) -> Result {
+    copy_dtype(ctx, 2, 0);
+    let Some(indices) = ctx.input_shape(0).cloned() else {
+        return Ok(());
+    };
+    let axis = normalize_axis(ctx, ctx.node().attr_int_or("axis", -1)?, indices.rank() + 1)?;
+    let depth = match constant_numbers(ctx, 1).as_deref() {
+        Some(&[depth]) => SymbolicDim::Int(depth as i64),
+        _ => unknown(),
+    };
+    let mut dims = indices.dims().to_vec();
+    dims.insert(axis, depth);
+    ctx.set_output_shape(0, Shape::new(dims));
+    Ok(())
+}
+
+/// Reads the `blocksize` of `DepthToSpace` and `SpaceToDepth` and the
+/// shape of their 4-D input.
+fn blocks(ctx: &InferenceContext<'_>) -> Result<Option<(i64, Shape)>> {
+    let blocksize = ctx.node().attr_int("blocksize")?;
+    let Some(x) = ctx.input_shape(0).cloned() else {
+        return Ok(None);
+    };
+    if x.rank() != 4 {
+        return Err(ctx.error(format!("input must have rank 4, got {}", x)));
+    }
+    Ok(Some((blocksize, x)))
+}
+
+fn depth_to_space(ctx: &mut InferenceContext<'_>) -> Result {
+    copy_dtype(ctx, 0, 0);
+    let Some((b, x)) = blocks(ctx)? else {
+        return Ok(());
+    };
+    let [c, h, w] = [1, 2, 3].map(|i| x.dims()[i].clone());
+    let b = SymbolicDim::Int(b);
+    let channels = c.floor_div(&(b.clone() * b.clone()));
+    let shape = Shape::new(vec![x.dims()[0].clone(), channels, h * b.clone(), w * b]);
+    ctx.set_output_shape(0, shape);
+    Ok(())
+}
+
+fn space_to_depth(ctx: &mut InferenceContext<'_>) -> Result {
+    copy_dtype(ctx, 0, 0);
+    let Some((b, x)) = blocks(ctx)? else {
+        return Ok(());
+    };
+    let [c, h, w] = [1, 2, 3].map(|i| x.dims()[i].clone());
+    let b = SymbolicDim::Int(b);
+    let channels = c * b.clone() * b.clone();
+    let shape = Shape::new(vec![
+        x.dims()[0].clone(),
+        channels,
+        h.floor_div(&b),
+        w.floor_div(&b),
+    ]);
+    ctx.set_output_shape(0, shape);
+    Ok(())
+}
+
+fn resize(ctx: &mut InferenceContext<'_>) -> Result {
+    copy_dtype(ctx, 0, 0);
+    let Some(x) = ctx.input_shape(0).cloned() else {
+        return Ok(());
+    };
+    let axes = match ctx.node().attr_ints_or("axes", &[])? {
+        [] => (0..x.rank()).collect(),
+        axes => normalize_axes(ctx, axes, x.rank())?,
+    };
+    let mut dims = x.dims().to_vec();
+    // Resize-10 takes only the scales, as its second input
+    let scales_input = if ctx.opset_version() < 11 { 1 } else { 2 };
+    let sizes = constant_ints(ctx, 3).filter(|_| ctx.has_input(3) && scales_input == 2);
+    let scales = constant_numbers(ctx, scales_input).filter(|_| ctx.has_input(scales_input));
+    let count = match (&sizes, &scales) {
+        (Some(sizes), _) => Some(sizes.len()),
+        (None, Some(scales)) => Some(scales.len()),
+        (None, None) => None,
+    };
+    if let Some(count) = count.filter(|&c| c != axes.len()) {
+        return Err(ctx.error(format!(
+            "{} sizes or scales given for {} axes",
+            count,
+            axes.len()
+        )));
+    }
+
+    if let Some(sizes) = sizes {
+        let policy = ctx
+            .node()
+            .attr_string_or("keep_aspect_ratio_policy", "stretch")?;
+        let inputs: Option<Vec<i64>> = axes.iter().map(|&a| dims[a].as_int()).collect();
+        match (policy, inputs) {
+            ("stretch", _) => {
+                for (&axis, &size) in axes.iter().zip(&sizes) {
+                    dims[axis] = size.into();
+                }
+            }
+            (policy, Some(inputs)) => {
+                let ratios = sizes
+                    .iter()
+                    .zip(&inputs)
+                    .map(|(&s, &d)| s as f64 / d as f64);
+                let scale = match policy {
+                    "not_larger" => ratios.fold(f64::INFINITY, f64::min),
+                    _ => ratios.fold(0.0, f64::max),
+                };
+                for (&axis, &dim) in axes.iter().zip(&inputs) {
+                    dims[axis] = ((dim as f64 * scale).round() as i64).into();
+                }
+            }
+            (_, None) => {
+                for &axis in &axes {
+                    dims[axis] = unknown();
+                }
+            }
+        }
+    } else if let Some(scales) = scales {
+        for (&axis, &scale) in axes.iter().zip(&scales) {
+            dims[axis] = match dims[axis].as_int() {
+                Some(dim) => ((dim as f64 * scale).floor() as i64).into(),
+                None if scale == 1.0 => continue,
+                None => unknown(),
+            };
+        }
+    } else {
+        for &axis in &axes {
+            dims[axis] = unknown();
+        }
+    }
+    ctx.set_output_shape(0, Shape::new(dims));
+    Ok(())
+}
+
+fn upsample(ctx: &mut InferenceContext<'_>) -> Result {
+    copy_dtype(ctx, 0, 0);
+    let Some(x) = ctx.input_shape(0).cloned() else {
+        return Ok(());
+    };
+    // Upsample-7 takes the scales as an attribute
+    let scales = if ctx.opset_version() < 9 {
+        let scales = ctx.node().attr_floats("scales")?;
+        Some(scales.iter().map(|&s| f64::from(s)).collect())
+    } else {
+        constant_numbers(ctx, 1)
+    };
+    let Some(scales) = scales else {
+        ctx.set_output_shape(0, unknown_shape(x.rank()));
+        return Ok(());
+    };
+    if scales.len() != x.rank() {
+        return Err(ctx.error(format!(
+            "{} scales given for input of rank {}",
+            scales.len(),
+            x.rank()
+        )));
+    }
+    let dims = x
+        .dims()
+        .iter()
+        .zip(&scales)
+        .map(|(dim, &scale)| match dim.as_int() {
+            Some(dim) => SymbolicDim::Int((dim as f64 * scale).floor() as i64),
+            None if scale == 1.0 => dim.clone(),
+            None => unknown(),
+        });
+    ctx.set_output_shape(0, Shape::new(dims));
+    Ok(())
+}
+
+fn center_crop_pad(ctx: &mut InferenceContext<'_>) -> Result {
+    copy_dtype(ctx, 0, 0);
+    let Some(x) = ctx.input_shape(0).cloned() else {
+        return Ok(());
+    };
+    let axes = match ctx.node().attr_ints_or("axes", &[])? {
+        [] => (0..x.rank()).collect(),
+        axes => normalize_axes(ctx, axes, x.rank())?,
+    };
+    let mut dims = x.dims().to_vec();
+    match constant_ints(ctx, 1) {
+        Some(sizes) if sizes.len() != axes.len() => {
+            return Err(ctx.error(format!(
+                "{} sizes given for {} axes",
+                sizes.len(),
+                axes.len()
+            )));
+        }
+        Some(sizes) => {
+            for (&axis, &size) in axes.iter().zip(&sizes) {
+                dims[axis] = size.into();
+            }
+        }
+        None => {
+            for &axis in &axes {
+                dims[axis] = unknown();
+            }
+        }
+    }
+    ctx.set_output_shape(0, Shape::new(dims));
+    Ok(())
+}
+
+fn compress(ctx: &mut InferenceContext<'_>) -> Result {
+    copy_dtype(ctx, 0, 0);
+    let axis = ctx.node().attr_int_or("axis", i64::MIN)?;
+    let Some(x) = ctx.input_shape(0).cloned() else {
+        return Ok(());
+    };
+    // Without an axis, the flattened input is compressed
+    if axis == i64::MIN {
+        ctx.set_output_shape(0, unknown_shape(1));
+        return Ok(());
+    }
+    let axis = normalize_axis(ctx, axis, x.rank())?;
+    let mut dims = x.dims().to_vec();
+    dims[axis] = unknown();
+    ctx.set_output_shape(0, Shape::new(dims));
+    Ok(())
+}
+
+fn non_max_suppression(ctx: &mut InferenceContext<'_>) -> Result {
+    ctx.set_output_dtype(0, DataType::Int64);
+    // One (batch, class, box) triple per selected box
+    ctx.set_output_shape(0, Shape::new(vec![unknown(), SymbolicDim::Int(3)]));
+    Ok(())
+}
+
+fn unique(ctx: &mut InferenceContext<'_>) -> Result {
+    copy_dtype(ctx, 0, 0);
+    for i in 1..4 {
+        ctx.set_output_dtype(i, DataType::Int64);
+    }
+    let axis = ctx.node().attr_int_or("axis", i64::MIN)?;
+    let count = unknown_shape(1);
+    for i in 1..4 {
+        ctx.set_output_shape(i, count.clone());
+    }
+    let Some(x) = ctx.input_shape(0).cloned() else {
+        return Ok(());
+    };
+    // Without an axis, the unique elements of the flattened input are returned
+    if axis == i64::MIN {
+        ctx.set_output_shape(0, count);
+        return Ok(());
+    }
+    let axis = normalize_axis(ctx, axis, x.rank())?;
+    let mut dims = x.dims().to_vec();
+    dims[axis] = unknown();
+    ctx.set_output_shape(0, Shape::new(dims));
+    Ok(())
+}
+
+/// Returns the data type given by attribute `name`, if set.
+fn dtype_attr(ctx: &InferenceContext<'_>, name: &str) -> Result<Option<DataType>> {
+    let Some(dtype) = ctx
+        .node()
+        .get_attribute(name)
+        .map(|_| ctx.node().attr_int(name))
+    else {
+        return Ok(None);
+    };
+    let dtype = dtype?;
+    i32::try_from(dtype)
+        .ok()
+        .and_then(DataType::from_i32)
+        .map(Some)
+        .ok_or_else(|| {
+            ctx.error(format!(
+                "attribute '{}' is not a data type: {}",
+                name, dtype
+            ))
+        })
+}
+
+/// Infers operators whose output has the shape of the input, and the type
+/// given by attribute `dtype` or else the type of the input.
+fn like_input(ctx: &mut InferenceContext<'_>) -> Result {
+    match dtype_attr(ctx, "dtype")? {
+        Some(dtype) => ctx.set_output_dtype(0, dtype),
+        None => copy_dtype(ctx, 0, 0),
+    }
+    copy_shape(ctx, 0, 0);
+    Ok(())
+}
+
+fn random(ctx: &mut InferenceContext<'_>) -> Result {
+    ctx.set_output_dtype(0, dtype_attr(ctx, "dtype")?.unwrap_or(DataType::Float));
+    let shape = ctx.node().attr_ints("shape")?.to_vec();
+    ctx.set_output_shape(0, Shape::new(shape));
+    Ok(())
+}
+
+fn multinomial(ctx: &mut InferenceContext<'_>) -> Result {
+    ctx.set_output_dtype(0, dtype_attr(ctx, "dtype")?.unwrap_or(DataType::Int32));
+    let batch = ctx
+        .input_shape(0)
+        .and_then(|x| x.dims().first().cloned())
+        .unwrap_or_else(unknown);
+    let samples = ctx.node().attr_int_or("sample_size", 1)?;
+    ctx.set_output_shape(0, Shape::new(vec![batch, SymbolicDim::Int(samples)]));
+    Ok(())
+}
+
+/// Infers `NegativeLogLikelihoodLoss` and `SoftmaxCrossEntropyLoss`.
+fn loss(ctx: &mut InferenceContext<'_>) -> Result {
+    copy_dtype(ctx, 0, 0);
+    let reduction = ctx.node().attr_string_or("reduction", "mean")?;
+    // The loss has the shape of the target unless it is reduced to a scalar
+    if reduction == "none" {
+        copy_shape(ctx, 1, 0);
+    } else {
+        ctx.set_output_shape(0, Shape::scalar());
+    }
+    // The log probabilities of SoftmaxCrossEntropyLoss
+    copy_dtype(ctx, 0, 1);
+    copy_shape(ctx, 0, 1);
+    Ok(())
+}
+
+fn dft(ctx: &mut InferenceContext<'_>) -> Result {
+    copy_dtype(ctx, 0, 0);
+    let Some(x) = ctx.input_shape(0).cloned() else {
+        return Ok(());
+    };
+    // The last axis holds the real and imaginary parts, of which the input
+    // may only have the real part
+    if x.rank() < 2 {
+        return Err(ctx.error(format!("input must have rank 2 or more, got {}", x)));
+    }
+    let mut dims = x.dims().to_vec();
+    dims[x.rank() - 1] = SymbolicDim::Int(2);
+    // DFT-20 takes the axis as an optional input
+    let axis = if ctx.opset_version() < 20 {
+        Some(ctx.node().attr_int_or("axis", 1)?)
+    } else if ctx.has_input(2) {
+        constant_int(ctx, 2)
+    } else {
+        Some(-2)
+    };
+    let Some(axis) = axis else {
+        let mut dims = vec![unknown(); x.rank() - 1];
+        dims.push(SymbolicDim::Int(2));
+        ctx.set_output_shape(0, Shape::new(dims));
+        return Ok(());
+    };
+    let axis = normalize_axis(ctx, axis, x.rank())?;
+    if axis == x.rank() - 1 {
+        return Err(ctx.error("cannot transform along the axis of the complex parts"));
+    }
+    let len = if ctx.has_input(1) {
+        constant_int(ctx, 1).map_or_else(unknown, SymbolicDim::Int)
+    } else {
+        dims[axis].clone()
+    };
+    // A one-sided transform keeps only the frequencies up to the Nyquist
+    // frequency, since the others are their conjugates
+    dims[axis] = match ctx.node().attr_int_or("onesided", 0)? {
+        0 => len,
+        _ => len.floor_div(&SymbolicDim::Int(2)) + SymbolicDim::Int(1),
+    };
+    ctx.set_output_shape(0, Shape::new(dims));
+    Ok(())
+}
+
+fn stft(ctx: &mut InferenceContext<'_>) -> Result {
+    copy_dtype(ctx, 0, 0);
+    let Some(signal) = ctx.input_shape(0).cloned() else {
+        return Ok(());
+    };
+    if signal.rank() != 3 {
+        return Err(ctx.error(format!("signal must have rank 3, got {}", signal)));
+    }
+    let step = constant_int(ctx, 1);
+    if let Some(step) = step.filter(|&step| step <= 0) {
+        return Err(ctx.error(format!("frame_step must be positive, got {}", step)));
+    }
+    // The frame length is given, or else is the length of the window
+    let frame_length = if ctx.has_input(3) {
+        constant_int(ctx, 3).map(SymbolicDim::Int)
+    } else if ctx.has_input(2) {
+        ctx.input_shape(2).and_then(|w| w.dims().first().cloned())
+    } else {
+        None
+    };
+    let frames = match (&frame_length, step) {
+        (Some(len), Some(step)) => {
+            let span = signal.dims()[1].clone() - len.clone();
+            span.floor_div(&SymbolicDim::Int(step)) + SymbolicDim::Int(1)
+        }
+        _ => unknown(),
+    };
+    let bins = match (frame_length, ctx.node().attr_int_or("onesided", 1)?) {
+        (Some(len), 0) => len,
+        (Some(len), _) => len.floor_div(&SymbolicDim::Int(2)) + SymbolicDim::Int(1),
+        (None, _) => unknown(),
+    };
+    let dims = vec![signal.dims()[0].clone(), frames, bins, SymbolicDim::Int(2)];
+    ctx.set_output_shape(0, Shape::new(dims));
+    Ok(())
+}
+
+fn mel_weight_matrix(ctx: &mut InferenceContext<'_>) -> Result {
+    let dtype = dtype_attr(ctx, "output_datatype")?;
+    ctx.set_output_dtype(0, dtype.unwrap_or(DataType::Float));
+    // One row per frequency of a one-sided DFT
+    let frequencies = constant_int(ctx, 1).map_or_else(unknown, |len| (len / 2 + 1).into());
+    let bins = constant_int(ctx, 0).map_or_else(unknown, SymbolicDim::Int);
+    ctx.set_output_shape(0, Shape::new(vec![frequencies, bins]));
+    Ok(())
+}
+
+/// Infers `BlackmanWindow`, `HammingWindow` and `HannWindow`.
+fn window(ctx: &mut InferenceContext<'_>) -> Result {
+    let dtype = dtype_attr(ctx, "output_datatype")?;
+    ctx.set_output_dtype(0, dtype.unwrap_or(DataType::Float));
+    let size = constant_int(ctx, 0).map_or_else(unknown, SymbolicDim::Int);
+    ctx.set_output_shape(0, Shape::new(vec![size]));
+    Ok(())
+}
+
+fn image_decoder(ctx: &mut InferenceContext<'_>) -> Result {
+    ctx.set_output_dtype(0, DataType::Uint8);
+    let channels = match ctx.node().attr_string_or("pixel_format", "RGB")? {
+        "Grayscale" => 1,
+        _ => 3,
+    };
+    let dims = vec![unknown(), unknown(), SymbolicDim::Int(channels)];
+    ctx.set_output_shape(0, Shape::new(dims));
+    Ok(())
+}
+
+fn string_normalizer(ctx: &mut InferenceContext<'_>) -> Result {
+    ctx.set_output_dtype(0, DataType::String);
+    let Some(x) = ctx.input_shape(0).cloned() else {
+        return Ok(());
+    };
+    if !matches!(x.rank(), 1 | 2) {
+        return Err(ctx.error(format!("input must have rank 1 or 2, got {}", x)));
+    }
+    let mut dims = x.dims().to_vec();
+    // Removing stopwords shortens the last axis
+    if !ctx.node().attr_strings_or("stopwords", &[])?.is_empty() {
+        dims[x.rank() - 1] = unknown();
+    }
+    ctx.set_output_shape(0, Shape::new(dims));
+    Ok(())
+}
+
+fn string_split(ctx: &mut InferenceContext<'_>) -> Result {
+    ctx.set_output_dtype(0, DataType::String);
+    ctx.set_output_dtype(1, DataType::Int64);
+    let Some(x) = ctx.input_shape(0).cloned() else {
+        return Ok(());
+    };
+    // The number of substrings of each string, and the substrings padded
+    // to the longest split
+    ctx.set_output_shape(1, x.clone());
+    let mut dims = x.dims().to_vec();
+    dims.push(unknown());
+    ctx.set_output_shape(0, Shape::new(dims));
+    Ok(())
+}
+
+fn tf_idf_vectorizer(ctx: &mut InferenceContext<'_>) -> Result {
+    ctx.set_output_dtype(0, DataType::Float);
+    let indexes = ctx.node().attr_ints("ngram_indexes")?;
+    let size = match indexes.iter().max() {
+        Some(&max) => max
+            .checked_add(1)
+            .ok_or_else(|| ctx.error(format!("n-gram index {} is too large", max)))?,
+        None => 0,
+    };
+    let Some(x) = ctx.input_shape(0).cloned() else {
+        return Ok(());
+    };
+    let dims = match x.dims() {
+        [_] => vec![size.into()],
+        [batch, _] => vec![batch.clone(), size.into()],
+        _ => return Err(ctx.error(format!("input must have rank 1 or 2, got {}", x))),
+    };
+    ctx.set_output_shape(0, Shape::new(dims));
+    Ok(())
+}
+
+fn sequence_empty(ctx: &mut InferenceContext<'_>) -> Result {
+    let dtype = dtype_attr(ctx, "dtype")?;
+    ctx.set_output_dtype(0, dtype.unwrap_or(DataType::Float));
+    Ok(())
+}
+
+fn sequence_construct(ctx: &mut InferenceContext<'_>) -> Result {
+    copy_dtype(ctx, 0, 0);
+    let shapes: Option<Vec<&Shape>> = (0..ctx.num_inputs()).map(|i| ctx.input_shape(i)).collect();
+    let shape = shapes.and_then(|shapes| {
+        let (first, rest) = shapes.split_first()?;
+        rest.iter()
+            .try_fold((*first).clone(), |shape, other| generalize(&shape, other))
+    });
+    if let Some(shape) = shape {
+        ctx.set_output_shape(0, shape);
+    }
+    Ok(())
+}
+
+fn sequence_insert(ctx: &mut InferenceContext<'_>) -> Result {
+    copy_dtype(ctx, 1, 0);
+    copy_dtype(ctx, 0, 0);
+    let shape = match (ctx.input_shape(0), ctx.input_shape(1)) {
+        (Some(elements), Some(tensor)) => generalize(elements, tensor),
+        _ => None,
+    };
+    if let Some(shape) = shape {
+        ctx.set_output_shape(0, shape);
+    }
+    Ok(())
+}
+
+fn split_to_sequence(ctx: &mut InferenceContext<'_>) -> Result {
+    copy_dtype(ctx, 0, 0);
+    let Some(x) = ctx.input_shape(0).cloned() else {
+        return Ok(());
+    };
+    let axis = normalize_axis(ctx, ctx.node().attr_int_or("axis", 0)?, x.rank())?;
+    let mut dims = x.dims().to_vec();
+    let split = ctx
+        .input_constant(1)
+        .filter(|_| ctx.has_input(1))
+        .and_then(|split| Some((split.shape.rank(), tensor_ints(split)?)));
+    match split {
+        // Without split, the input is split into elements of size 1, whose
+        // axis is removed unless `keepdims` is set
+        _ if !ctx.has_input(1) => {
+            if ctx.node().attr_int_or("keepdims", 1)? == 0 {
+                dims.remove(axis);
+            } else {
+                dims[axis] = SymbolicDim::Int(1);
+            }
+        }
+        // A scalar split gives chunks of that size, and a smaller last chunk
+        Some((0, sizes)) => {
+            let size = sizes[0];
+            if size <= 0 {
+                return Err(ctx.error(format!("split must be positive, got {}", size)));
+            }
+            dims[axis] = match dims[axis].as_int() {
+                Some(dim) if dim % size == 0 => SymbolicDim::Int(size),
+                _ => unknown(),
+            };
+        }
+        Some((_, sizes)) => {
+            dims[axis] = match sizes.split_first() {
+                Some((&first, rest)) if rest.iter().all(|&size| size == first) => first.into(),
+                _ => unknown(),
+            };
+        }
+        None => dims[axis] = unknown(),
+    }
+    ctx.set_output_shape(0, Shape::new(dims));
+    Ok(())
+}
+
+fn concat_from_sequence(ctx: &mut InferenceContext<'_>) -> Result {
+    copy_dtype(ctx, 0, 0);
+    let axis = ctx.node().attr_int("axis")?;
+    let Some(x) = ctx.input_shape(0).cloned() else {
+        return Ok(());
+    };
+    // The length of the sequence, and so of the concatenated axis, is not
+    // known
+    let mut dims = x.dims().to_vec();
+    if ctx.node().attr_int_or("new_axis", 0)? != 0 {
+        let axis = normalize_axis(ctx, axis, x.rank() + 1)?;
+        dims.insert(axis, unknown());
+    } else {
+        let axis = normalize_axis(ctx, axis, x.rank())?;
+        dims[axis] = unknown();
+    }
+    ctx.set_output_shape(0, Shape::new(dims));
+    Ok(())
+}
+
+fn sequence_length(ctx: &mut InferenceContext<'_>) -> Result {
+    ctx.set_output_dtype(0, DataType::Int64);
+    ctx.set_output_shape(0, Shape::scalar());
+    Ok(())
+}
+
+fn optional(ctx: &mut InferenceContext<'_>) -> Result {
+    if ctx.has_input(0) {
+        ctx.propagate(0, 0);
+        return Ok(());
+    }
+    // An empty optional has the element type given by attribute `type`
+    let type_ = ctx.node().get_attribute("type").map(|attr| &attr.value);
+    if let Some(AttrValue::TypeProto(TypeProto {
+        kind: TypeKind::Tensor { elem_type, shape },
+        ..
+    })) = type_
+    {
+        ctx.set_output_dtype(0, *elem_type);
+        if let Some(shape) = shape {
+            ctx.set_output_shape(0, shape.clone());
+        }
+    }
+    Ok(())
+}
+
+fn optional_has_element(ctx: &mut InferenceContext<'_>) -> Result {
+    ctx.set_output_dtype(0, DataType::Bool);
+    ctx.set_output_shape(0, Shape::scalar());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::attribute::Attr;
+    use crate::node::Node;
+    use crate::test_support::{sym, value};
+    use crate::value::Value;
+
+    fn infer(node: &Node, constants: &[(usize, &Tensor)]) -> Result<Vec<Option<Shape>>> {
+        let mut ctx = InferenceContext::new(node, 21);
+        for &(index, tensor) in constants {
+            ctx.set_input_constant(index, tensor);
+        }
+        builtin(&node.op_type).expect("built-in")(&mut ctx)?;
+        Ok(ctx.into_outputs().into_iter().map(|o| o.shape).collect())
+    }
+
+    #[test]
+    fn test_broadcast_keeps_symbols() {
+        let a = value("a", DataType::Float, vec![sym("N"), 1.into(), 4.into()]);
+        let b = value("b", DataType::Float, vec![3.into(), sym("M")]);
+        let add = Node::with_io("Add", vec![a, b], ["y0"]);
+        let shape = infer(&add, &[]).unwrap()[0].clone().unwrap();
+        assert_eq!(shape, Shape::new(vec![sym("N"), 3.into(), 4.into()]));
+
+        let c = value("c", DataType::Float, vec![2.into()]);
+        let d = value("d", DataType::Float, vec![3.into()]);
+        let err = infer(&Node::with_io("Mul", vec![c, d], ["y0"]), &[]).unwrap_err();
+        assert_eq!(err.to_string(), "Mul: cannot broadcast shapes [2] and [3]");
+
+        let a = value("a", DataType::Float, vec![sym("M"), 4.into()]);
+        let b = value("b", DataType::Float, vec![4.into(), unknown()]);
+        let c = value("c", DataType::Float, vec![5.into()]);
+        let gemm = Node::with_io("Gemm", vec![a.clone(), b.clone(), c], ["y0"]);
+        assert_eq!(
+            infer(&gemm, &[]).unwrap()[0],
+            Some(Shape::new(vec![sym("M"), 5.into()]))
+        );
+        let c = value("c", DataType::Float, vec![2.into(), 5.into()]);
+        assert_eq!(
+            infer(
+                &Node::with_io("Gemm", vec![a.clone(), b, c.clone()], ["y0"]),
+                &[]
+            )
+            .unwrap()[0],
+            Some(Shape::new(vec![2, 5]))
+        );
+        let b = value("b", DataType::Float, vec![4.into(), 6.into()]);
+        let err = infer(&Node::with_io("Gemm", vec![a, b, c], ["y0"]), &[]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Gemm: C of shape [2,5] cannot be broadcast to [M,6]"
+        );
+    }
+
+    #[test]
+    fn test_conv_and_pool() {
+        let x = value(
+            "x",
+            DataType::Float,
+            vec![sym("N"), 3.into(), 32.into(), 31.into()],
+        );
+        let w = value(
+            "w",
+            DataType::Float,
+            vec![8.into(), 3.into(), 3.into(), 3.into()],
+        );
+        let mut conv = Node::with_io("Conv", vec![x.clone(), w], ["y0"]);
+        conv.set_attribute(Attr::ints("strides", vec![2, 2]));
+        conv.set_attribute(Attr::ints("pads", vec![1, 1, 1, 1]));
+        assert_eq!(
+            infer(&conv, &[]).unwrap()[0],
+            Some(Shape::new(vec![sym("N"), 8.into(), 16.into(), 16.into()]))
+        );
+
+        let mut pool = Node::with_io("MaxPool", vec![x], ["y0", "y1"]);
+        pool.set_attribute(Attr::ints("kernel_shape", vec![2, 2]));
+        pool.set_attribute(Attr::ints("strides", vec![2, 2]));
+        pool.set_attribute(Attr::int("ceil_mode", 1));
+        let shapes = infer(&pool, &[]).unwrap();
+        let expected = Shape::new(vec![sym("N"), 3.into(), 16.into(), 16.into()]);
+        assert_eq!(shapes, vec![Some(expected.clone()), Some(expected)]);
+    }
+
+    #[test]
+    fn test_invalid_attributes_are_errors() {
+        let x = value("x", DataType::Float, vec![1.into(), 3.into(), 8.into()]);
+        let w = value("w", DataType::Float, vec![4.into(), 3.into(), 3.into()]);
+        let mut conv = Node::with_io("Conv", vec![x.clone(), w.clone()], ["y0"]);
+        conv.set_attribute(Attr::ints("strides", vec![0]));
+        let err = infer(&conv, &[]).unwrap_err();
+        assert_eq!(err.to_string(), "Conv: strides must be positive, got 0");
+        conv.set_attribute(Attr::ints("strides", vec![1]));
+        conv.set_attribute(Attr::ints("dilations", vec![i64::MAX]));
+        let err = infer(&conv, &[]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Conv: output size of spatial axis 0 overflows"
+        );
+
+        let mut transpose = Node::with_io("ConvTranspose", vec![x.clone(), w], ["y0"]);
+        transpose.set_attribute(Attr::ints("output_shape", vec![]));
+        assert!(infer(&transpose, &[]).is_ok());
+        let y = value(
+            "y",
+            DataType::Float,
+            vec![1.into(), 3.into(), 8.into(), 8.into()],
+        );
+        let w = value(
+            "w",
+            DataType::Float,
+            vec![3.into(), 4.into(), 3.into(), 3.into()],
+        );
+        let mut transpose = Node::with_io("ConvTranspose", vec![y, w], ["y0"]);
+        transpose.set_attribute(Attr::ints("output_shape", vec![16]));
+        let err = infer(&transpose, &[]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "ConvTranspose: attribute 'output_shape' has 1 values, expected at least 2"
+        );
+
+        let indices = value("i", DataType::Int64, vec![1.into(), 2.into()]);
+        let mut gather = Node::with_io("GatherND", vec![x.clone(), indices], ["y0"]);
+        gather.set_attribute(Attr::int("batch_dims", -1));
+        let err = infer(&gather, &[]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "GatherND: batch_dims must not be negative, got -1"
+        );
+
+        let split = Node::with_io("Split", vec![x], Vec::<Value>::new());
+        let err = infer(&split, &[]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Split: cannot split evenly into zero outputs"
+        );
+    }
+
+    #[test]
+    fn test_reshape_with_constant_shape() {
+        let x = value(
+            "x",
+            DataType::Float,
+            vec![sym("N"), 2.into(), 3.into(), 4.into()],
+        );
+        let shape = value("shape", DataType::Int64, vec![3.into()]);
+        let reshape = Node::with_io("Reshape", vec![x.clone(), shape.clone()], ["y0"]);
+        assert_eq!(infer(&reshape, &[]).unwrap()[0], Some(unknown_shape(3)));
+
+        let target = int64_tensor(&[0, -1, 4], Shape::new(vec![3]));
+        assert_eq!(
+            infer(&reshape, &[(1, &target)]).unwrap()[0],
+            Some(Shape::new(vec![sym("N"), 6.into(), 4.into()]))
+        );
+
+        let static_x = value("x", DataType::Float, vec![2.into(), 3.into(), 4.into()]);
+        let reshape = Node::with_io("Reshape", vec![static_x, shape], ["y0"]);
+        let target = int64_tensor(&[4, -1, 2], Shape::new(vec![3]));
+        assert_eq!(
+            infer(&reshape, &[(1, &target)]).unwrap()[0],
+            Some(Shape::new(vec![4, 3, 2]))
+        );
+    }
+
+    #[test]
+    fn test_symbolic_expressions() {
+        let x = value("x", DataType::Float, vec![sym("B"), sym("seq"), 8.into()]);
+        let cls = value("cls", DataType::Float, vec![sym("B"), 1.into(), 8.into()]);
+        let mut concat = Node::with_io("Concat", vec![cls, x.clone()], ["y0"]);
+        concat.set_attribute(Attr::int("axis", 1));
+        let shape = infer(&concat, &[]).unwrap()[0].clone().unwrap();
+        assert_eq!(shape.to_string(), "[B,seq + 1,8]");
+
+        let mut slice = Node::with_io(
+            "Slice",
+            vec![value("x", DataType::Float, shape.dims().to_vec())],
+            ["y0"],
+        );
+        slice.set_attribute(Attr::ints("starts", vec![1]));
+        slice.set_attribute(Attr::ints("ends", vec![i64::MAX]));
+        slice.set_attribute(Attr::ints("axes", vec![1]));
+        assert_eq!(infer(&slice, &[]).unwrap()[0], x.shape);
+
+        let shape = value("shape", DataType::Int64, vec![2.into()]);
+        let reshape = Node::with_io("Reshape", vec![x, shape], ["y0"]);
+        let target = int64_tensor(&[-1, 8], Shape::new(vec![2]));
+        let shape = infer(&reshape, &[(1, &target)]).unwrap()[0]
+            .clone()
+            .unwrap();
+        assert_eq!(shape.to_string(), "[B*seq,8]");
+    }
+
+    #[test]
+    fn test_slice_and_reduce_with_constants() {
+        let x = value("x", DataType::Float, vec![sym("N"), 10.into(), 6.into()]);
+        let ints = |name: &str| value(name, DataType::Int64, vec![2.into()]);
+        let slice = Node::with_io(
+            "Slice",
+            vec![
+                x.clone(),
+                ints("starts"),
+                ints("ends"),
+                ints("axes"),
+                ints("steps"),
+            ],
+            ["y0"],
+        );
+        let starts = int64_tensor(&[1, -1], Shape::new(vec![2]));
+        let ends = int64_tensor(&[i64::MAX, 0], Shape::new(vec![2]));
+        let axes = int64_tensor(&[1, 2], Shape::new(vec![2]));
+        let steps = int64_tensor(&[2, -2], Shape::new(vec![2]));
+        let constants = [(1, &starts), (2, &ends), (3, &axes), (4, &steps)];
+        assert_eq!(
+            infer(&slice, &constants).unwrap()[0],
+            Some(Shape::new(vec![sym("N"), 5.into(), 3.into()]))
+        );
+        assert_eq!(
+            infer(&slice, &[(1, &starts), (2, &ends)]).unwrap()[0],
+            Some(unknown_shape(3))
+        );
+
+        let mut reduce = Node::with_io("ReduceMean", vec![x, ints("axes")], ["y0"]);
+        reduce.set_attribute(Attr::int("keepdims", 0));
+        let axes = int64_tensor(&[-1], Shape::new(vec![1]));
+        assert_eq!(
+            infer(&reduce, &[(1, &axes)]).unwrap()[0],
+            Some(Shape::new(vec![sym("N"), 10.into()]))
+        );
+    }
+
+    #[test]
+    fn test_matmul_gather_and_einsum() {
+        let a = value("a", DataType::Float, vec![sym("B"), 4.into(), 5.into()]);
+        let b = value("b", DataType::Float, vec![5.into()]);
+        let matmul = Node::with_io("MatMul", vec![a.clone(), b], ["y0"]);
+        assert_eq!(
+            infer(&matmul, &[]).unwrap()[0],
+            Some(Shape::new(vec![sym("B"), 4.into()]))
+        );
+
+        let indices = value("i", DataType::Int64, vec![2.into(), 3.into()]);
+        let mut gather = Node::with_io("Gather", vec![a.clone(), indices], ["y0"]);
+        gather.set_attribute(Attr::int("axis", 1));
+        assert_eq!(
+            infer(&gather, &[]).unwrap()[0],
+            Some(Shape::new(vec![sym("B"), 2.into(), 3.into(), 5.into()]))
+        );
+
+        let c = value("c", DataType::Float, vec![5.into(), 6.into()]);
+        let mut einsum = Node::with_io("Einsum", vec![a, c], ["y0"]);
+        einsum.set_attribute(Attr::string("equation", "bij,jk"));
+        assert_eq!(
+            infer(&einsum, &[]).unwrap()[0],
+            Some(Shape::new(vec![sym("B"), 4.into(), 6.into()]))
+        );
+    }
+
+    #[test]
+    fn test_cast_and_constant() {
+        let x = value("x", DataType::Float, vec![2.into()]);
+        let mut cast = Node::with_io("Cast", vec![x], ["y0"]);
+        cast.set_attribute(Attr::int("to", 7));
+        let mut ctx = InferenceContext::new(&cast, 21);
+        builtin("Cast").unwrap()(&mut ctx).unwrap();
+        assert_eq!(ctx.output_dtype(0), Some(DataType::Int64));
+
+        let mut constant = Node::with_io("Constant", Vec::<Value>::new(), ["y0"]);
+        constant.set_attribute(Attr::ints("value_ints", vec![1, 2, 3]));
+        let mut ctx = InferenceContext::new(&constant, 21);
+        builtin("Constant").unwrap()(&mut ctx).unwrap();
+        assert_eq!(ctx.output_dtype(0), Some(DataType::Int64));
+        assert_eq!(ctx.output_shape(0), Some(&Shape::new(vec![3])));
+    }
+
+    #[test]
+    fn test_legacy_versions() {
+        let infer_at = |node: &Node, opset: i32| {
+            let mut ctx = InferenceContext::new(node, opset);
+            builtin(&node.op_type).unwrap()(&mut ctx).unwrap();
+            (ctx.output_dtype(0), ctx.output_shape(0).cloned())
+        };
+        let x = || value("x", DataType::Float, vec![sym("N"), 6.into()]);
+
+        let mut cast = Node::with_io("Cast", vec![x()], ["y0"]);
+        cast.set_attribute(Attr::string("to", "INT64"));
+        assert_eq!(infer_at(&cast, 1).0, Some(DataType::Int64));
+
+        let mut add = Node::with_io(
+            "Add",
+            vec![x(), value("b", DataType::Float, vec![6.into()])],
+            ["y0"],
+        );
+        add.set_attribute(Attr::int("broadcast", 1));
+        assert_eq!(infer_at(&add, 6).1, x().shape);
+
+        let mut reshape = Node::with_io("Reshape", vec![x()], ["y0"]);
+        reshape.set_attribute(Attr::ints("shape", vec![0, 2, 3]));
+        let expected = Shape::new(vec![sym("N"), 2.into(), 3.into()]);
+        assert_eq!(infer_at(&reshape, 1).1, Some(expected));
+
+        let mut top_k = Node::with_io("TopK", vec![x()], ["y0", "y1"]);
+        top_k.set_attribute(Attr::int("k", 2));
+        assert_eq!(
+            infer_at(&top_k, 1).1,
+            Some(Shape::new(vec![sym("N"), 2.into()]))
+        );
+
+        let concat = Node::with_io("Concat", vec![x(), x()], ["y0"]);
+        assert_eq!(
+            infer_at(&concat, 1).1,
+            Some(Shape::new(vec![sym("N"), 12.into()]))
+        );
+
+        let mut dropout = Node::with_io("Dropout", vec![x()], ["y0", "y1"]);
+        let mut ctx = InferenceContext::new(&dropout, 7);
+        builtin("Dropout").unwrap()(&mut ctx).unwrap();
+        assert_eq!(ctx.output_dtype(1), Some(DataType::Float));
+        dropout.set_attribute(Attr::float("ratio", 0.5));
+        let mut ctx = InferenceContext::new(&dropout, 12);
+        builtin("Dropout").unwrap()(&mut ctx).unwrap();
+        assert_eq!(ctx.output_dtype(1), Some(DataType::Bool));
+    }
+
+    #[test]
+    fn test_operator_coverage() {
+        let schemas = super::super::table::parse("", include_str!("onnx.txt")).unwrap();
+        let mut missing: Vec<_> = schemas
+            .iter()
+            .map(|schema| schema.name.as_str())
+            .filter(|&name| builtin(name).is_none())
+            .collect();
+        missing.dedup();
+        assert_eq!(missing, ["If", "Loop", "Scan", "SequenceMap"]);
+    }
+
+    #[test]
+    fn test_attention() {
+        let q = value(
+            "q",
+            DataType::Float,
+            vec![sym("B"), 8.into(), sym("S"), 64.into()],
+        );
+        let k = value(
+            "k",
+            DataType::Float,
+            vec![sym("B"), 2.into(), sym("L"), 64.into()],
+        );
+        let v = value(
+            "v",
+            DataType::Float,
+            vec![sym("B"), 2.into(), sym("L"), 32.into()],
+        );
+        let mask = Value::new("");
+        let past = value(
+            "pk",
+            DataType::Float,
+            vec![sym("B"), 2.into(), sym("P"), 64.into()],
+        );
+        let past_v = value(
+            "pv",
+            DataType::Float,
+            vec![sym("B"), 2.into(), sym("P"), 32.into()],
+        );
+        let attention = Node::with_io(
+            "Attention",
+            vec![q, k, v, mask, past, past_v],
+            ["y0", "y1", "y2", "y3"],
+        );
+        let shapes = infer(&attention, &[]).unwrap();
+        let shapes: Vec<_> = shapes
+            .iter()
+            .map(|s| s.as_ref().unwrap().to_string())
+            .collect();
+        assert_eq!(
+            shapes,
+            [
+                "[B,8,S,32]",
+                "[B,2,L + P,64]",
+                "[B,2,L + P,32]",
+                "[B,8,S,L + P]"
+            ]
+        );
+
+        let q = value("q", DataType::Float, vec![sym("B"), sym("S"), 512.into()]);
+        let k = value("k", DataType::Float, vec![sym("B"), sym("S"), 128.into()]);
+        let mut attention = Node::with_io("Attention", vec![q, k.clone(), k], ["y0"]);
+        attention.set_attribute(Attr::int("q_num_heads", 8));
+        attention.set_attribute(Attr::int("kv_num_heads", 2));
+        assert_eq!(
+            infer(&attention, &[]).unwrap()[0],
+            Some(Shape::new(vec![sym("B"), sym("S"), 512.into()]))
+        );
+    }
+
+    #[test]
+    fn test_image_operators() {
+        let x = value(
+            "x",
+            DataType::Float,
+            vec![sym("N"), 3.into(), 4.into(), 4.into()],
+        );
+        let indices = value(
+            "i",
+            DataType::Int64,
+            vec![sym("N"), 3.into(), 4.into(), 4.into()],
+        );
+        let mut unpool = Node::with_io("MaxUnpool", vec![x.clone(), indices], ["y0"]);
+        unpool.set_attribute(Attr::ints("kernel_shape", vec![2, 2]));
+        unpool.set_attribute(Attr::ints("strides", vec![2, 2]));
+        assert_eq!(
+            infer(&unpool, &[]).unwrap()[0],
+            Some(Shape::new(vec![sym("N"), 3.into(), 8.into(), 8.into()]))
+        );
+
+        let columns = value("c", DataType::Float, vec![sym("N"), 12.into(), sym("L")]);
+        let image = value("image_shape", DataType::Int64, vec![2.into()]);
+        let block = value("block_shape", DataType::Int64, vec![2.into()]);
+        let col2im = Node::with_io("Col2Im", vec![columns, image, block], ["y0"]);
+        let image = int64_tensor(&[5, 5], Shape::new(vec![2]));
+        let block = int64_tensor(&[2, 2], Shape::new(vec![2]));
+        assert_eq!(
+            infer(&col2im, &[(1, &image), (2, &block)]).unwrap()[0],
+            Some(Shape::new(vec![sym("N"), 3.into(), 5.into(), 5.into()]))
+        );
+
+        let theta = value("theta", DataType::Float, vec![sym("N"), 2.into(), 3.into()]);
+        let size = value("size", DataType::Int64, vec![4.into()]);
+        let grid = Node::with_io("AffineGrid", vec![theta, size], ["y0"]);
+        assert_eq!(
+            infer(&grid, &[]).unwrap()[0],
+            Some(Shape::new(vec![sym("N"), unknown(), unknown(), 2.into()]))
+        );
+        let size = int64_tensor(&[1, 3, 16, 8], Shape::new(vec![4]));
+        assert_eq!(
+            infer(&grid, &[(1, &size)]).unwrap()[0],
+            Some(Shape::new(vec![1, 16, 8, 2]))
+        );
+
+        let shape = value("shape", DataType::Int64, vec![2.into()]);
+        let mut crop = Node::with_io("CenterCropPad", vec![x.clone(), shape], ["y0"]);
+        crop.set_attribute(Attr::ints("axes", vec![-2, -1]));
+        let sizes = int64_tensor(&[6, 2], Shape::new(vec![2]));
+        assert_eq!(
+            infer(&crop, &[(1, &sizes)]).unwrap()[0],
+            Some(Shape::new(vec![sym("N"), 3.into(), 6.into(), 2.into()]))
+        );
+
+        let mut upsample = Node::with_io("Upsample", vec![x.clone()], ["y0"]);
+        upsample.set_attribute(Attr::floats("scales", vec![1.0, 1.0, 2.0, 1.5]));
+        let mut ctx = InferenceContext::new(&upsample, 7);
+        builtin("Upsample").unwrap()(&mut ctx).unwrap();
+        assert_eq!(
+            ctx.output_shape(0),
+            Some(&Shape::new(vec![sym("N"), 3.into(), 8.into(), 6.into()]))
+        );
+
+        let w = value(
+            "w",
+            DataType::Float,
+            vec![5.into(), 3.into(), 3.into(), 3.into()],
+        );
+        let offset = value(
+            "offset",
+            DataType::Float,
+            vec![sym("N"), 18.into(), 2.into(), 2.into()],
+        );
+        let deform = Node::with_io("DeformConv", vec![x, w, offset], ["y0"]);
+        assert_eq!(
+            infer(&deform, &[]).unwrap()[0],
+            Some(Shape::new(vec![sym("N"), 5.into(), 2.into(), 2.into()]))
+        );
+    }
+
+    #[test]
+    fn test_signal_and_text_operators() {
+        let signal = value(
+            "signal",
+            DataType::Float,
+            vec![sym("B"), 16.into(), 1.into()],
+        );
+        let mut dft = Node::with_io("DFT", vec![signal.clone()], ["y0"]);
+        dft.set_attribute(Attr::int("onesided", 1));
+        assert_eq!(
+            infer(&dft, &[]).unwrap()[0],
+            Some(Shape::new(vec![sym("B"), 9.into(), 2.into()]))
+        );
+
+        let step = value("step", DataType::Int64, vec![]);
+        let window = value("window", DataType::Float, vec![8.into()]);
+        let stft = Node::with_io("STFT", vec![signal, step, window], ["y0"]);
+        let four = int64_tensor(&[4], Shape::scalar());
+        assert_eq!(
+            infer(&stft, &[(1, &four)]).unwrap()[0],
+            Some(Shape::new(vec![sym("B"), 3.into(), 5.into(), 2.into()]))
+        );
+
+        let size = value("size", DataType::Int64, vec![]);
+        let mut hann = Node::with_io("HannWindow", vec![size], ["y0"]);
+        hann.set_attribute(Attr::int("output_datatype", DataType::Double as i64));
+        let mut ctx = InferenceContext::new(&hann, 17);
+        ctx.set_input_constant(0, &four);
+        builtin("HannWindow").unwrap()(&mut ctx).unwrap();
+        assert_eq!(ctx.output_dtype(0), Some(DataType::Double));
+        assert_eq!(ctx.output_shape(0), Some(&Shape::new(vec![4])));
+
+        let text = value("text", DataType::String, vec![sym("N")]);
+        let split = Node::with_io("StringSplit", vec![text.clone()], ["y0", "y1"]);
+        assert_eq!(
+            infer(&split, &[]).unwrap(),
+            vec![
+                Some(Shape::new(vec![sym("N"), unknown()])),
+                Some(Shape::new(vec![sym("N")]))
+            ]
+        );
+        let mut normalizer = Node::with_io("StringNormalizer", vec![text], ["y0"]);
+        normalizer.set_attribute(Attr::strings("stopwords", vec!["a".to_string()]));
+        assert_eq!(infer(&normalizer, &[]).unwrap()[0], Some(unknown_shape(1)));
+
+        let tokens = value("tokens", DataType::Int64, vec![sym("N"), 6.into()]);
+        let mut tf_idf = Node::with_io("TfIdfVectorizer", vec![tokens], ["y0"]);
+        tf_idf.set_attribute(Attr::ints("ngram_indexes", vec![0, 1, 2, 3]));
+        assert_eq!(
+            infer(&tf_idf, &[]).unwrap()[0],
+            Some(Shape::new(vec![sym("N"), 4.into()]))
+        );
+    }
+
+    #[test]
+    fn test_sequences_and_optionals() {
+        let a = value("a", DataType::Float, vec![sym("N"), 3.into()]);
+        let b = value("b", DataType::Float, vec![sym("N"), 4.into()]);
+        let construct = Node::with_io("SequenceConstruct", vec![a.clone(), b], ["y0"]);
+        assert_eq!(
+            infer(&construct, &[]).unwrap()[0],
+            Some(Shape::new(vec![sym("N"), unknown()]))
+        );
+        let at = Node::with_io("SequenceAt", vec![a.clone(), Value::new("i")], ["y0"]);
+        assert_eq!(infer(&at, &[]).unwrap()[0], a.shape);
+
+        let mut split = Node::with_io("SplitToSequence", vec![a.clone()], ["y0"]);
+        split.set_attribute(Attr::int("keepdims", 0));
+        assert_eq!(infer(&split, &[]).unwrap()[0], Some(Shape::new(vec![3])));
+        let mut split = Node::with_io(
+            "SplitToSequence",
+            vec![a.clone(), Value::new("split")],
+            ["y0"],
+        );
+        split.set_attribute(Attr::int("axis", 1));
+        let size = int64_tensor(&[3], Shape::scalar());
+        assert_eq!(infer(&split, &[(1, &size)]).unwrap()[0], a.shape);
+
+        let mut concat = Node::with_io("ConcatFromSequence", vec![a.clone()], ["y0"]);
+        concat.set_attribute(Attr::int("axis", -1));
+        concat.set_attribute(Attr::int("new_axis", 1));
+        assert_eq!(
+            infer(&concat, &[]).unwrap()[0],
+            Some(Shape::new(vec![sym("N"), 3.into(), unknown()]))
+        );
+
+        let mut optional = Node::with_io("Optional", Vec::<Value>::new(), ["y0"]);
+        let shape = Shape::new(vec![2, 2]);
+        optional.set_attribute(Attr::type_proto(
+            "type",
+            TypeProto::tensor(DataType::Int64, Some(shape.clone())),
+        ));
+        assert_eq!(infer(&optional, &[]).unwrap()[0], Some(shape));
+        let optional = Node::with_io("Optional", vec![a.clone()], ["y0"]);
+        let element = Node::with_io("OptionalGetElement", vec![a.clone()], ["y0"]);
+        assert_eq!(infer(&optional, &[]).unwrap()[0], a.shape);
+        assert_eq!(infer(&element, &[]).unwrap()[0], a.shape);
+    }
+
+    #[test]
+    fn test_recurrent() {
+        let x = value("x", DataType::Float, vec![sym("T"), sym("N"), 8.into()]);
+        let mut lstm = Node::with_io("LSTM", vec![x], ["y0", "y1", "y2"]);
+        lstm.set_attribute(Attr::int("hidden_size", 16));
+        lstm.set_attribute(Attr::string("direction", "bidirectional"));
+        let shapes = infer(&lstm, &[]).unwrap();
+        let state = Shape::new(vec![2.into(), sym("N"), 16.into()]);
+        assert_eq!(
+            shapes[0],
+            Some(Shape::new(vec![sym("T"), 2.into(), sym("N"), 16.into()]))
+        );
+        assert_eq!(shapes[1], Some(state.clone()));
+        assert_eq!(shapes[2], Some(state));
+
+        let x = value("x", DataType::Float, vec![sym("N"), sym("T"), 8.into()]);
+        let mut gru = Node::with_io("GRU", vec![x], ["y0", "y1"]);
+        gru.set_attribute(Attr::int("layout", 1));
+        let shapes = infer(&gru, &[]).unwrap();
+        assert_eq!(
+            shapes[1],
+            Some(Shape::new(vec![sym("N"), 1.into(), unknown()]))
+        );
+    }
+
+    #[test]
+    fn test_quantized_and_detection() {
+        let int8 = |name: &str, dims: Vec<SymbolicDim>| value(name, DataType::Int8, dims);
+        let scalar = |name: &str, dtype| value(name, dtype, vec![]);
+        let x = int8("x", vec![sym("N"), 3.into(), 8.into(), 8.into()]);
+        let w = int8("w", vec![16.into(), 3.into(), 3.into(), 3.into()]);
+        let conv = Node::with_io(
+            "QLinearConv",
+            vec![
+                x,
+                scalar("xs", DataType::Float),
+                scalar("xz", DataType::Int8),
+                w,
+                scalar("ws", DataType::Float),
+                scalar("wz", DataType::Int8),
+                scalar("ys", DataType::Float),
+                scalar("yz", DataType::Uint8),
+            ],
+            ["y0"],
+        );
+        let mut ctx = InferenceContext::new(&conv, 21);
+        builtin("QLinearConv").unwrap()(&mut ctx).unwrap();
+        assert_eq!(ctx.output_dtype(0), Some(DataType::Uint8));
+        assert_eq!(
+            ctx.output_shape(0),
+            Some(&Shape::new(vec![sym("N"), 16.into(), 6.into(), 6.into()]))
+        );
+
+        let a = value("a", DataType::Uint8, vec![sym("M"), 4.into()]);
+        let b = value("b", DataType::Uint8, vec![4.into(), 5.into()]);
+        let matmul = Node::with_io("MatMulInteger", vec![a, b], ["y0"]);
+        let mut ctx = InferenceContext::new(&matmul, 21);
+        builtin("MatMulInteger").unwrap()(&mut ctx).unwrap();
+        assert_eq!(ctx.output_dtype(0), Some(DataType::Int32));
+        assert_eq!(
+            ctx.output_shape(0),
+            Some(&Shape::new(vec![sym("M"), 5.into()]))
+        );
+
+        let x = value(
+            "x",
+            DataType::Float,
+            vec![1.into(), 3.into(), 8.into(), 8.into()],
+        );
+        let rois = value("rois", DataType::Float, vec![sym("R"), 4.into()]);
+        let indices = value("i", DataType::Int64, vec![sym("R")]);
+        let mut roi_align = Node::with_io("RoiAlign", vec![x, rois, indices], ["y0"]);
+        roi_align.set_attribute(Attr::int("output_height", 7));
+        roi_align.set_attribute(Attr::int("output_width", 7));
+        assert_eq!(
+            infer(&roi_align, &[]).unwrap()[0],
+            Some(Shape::new(vec![sym("R"), 3.into(), 7.into(), 7.into()]))
+        );
+
+        let boxes = value("boxes", DataType::Float, vec![1.into(), sym("B"), 4.into()]);
+        let scores = value(
+            "scores",
+            DataType::Float,
+            vec![1.into(), 2.into(), sym("B")],
+        );
+        let nms = Node::with_io("NonMaxSuppression", vec![boxes, scores], ["y0"]);
+        assert_eq!(
+            infer(&nms, &[]).unwrap()[0],
+            Some(Shape::new(vec![unknown(), 3.into()]))
+        );
+
+        let x = value("x", DataType::Float, vec![sym("N"), 4.into()]);
+        let condition = value("c", DataType::Bool, vec![sym("C")]);
+        let mut compress = Node::with_io("Compress", vec![x, condition], ["y0"]);
+        compress.set_attribute(Attr::int("axis", 1));
+        assert_eq!(
+            infer(&compress, &[]).unwrap()[0],
+            Some(Shape::new(vec![sym("N"), unknown()]))
+        );
+    }
+}
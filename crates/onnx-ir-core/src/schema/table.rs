@@ -0,0 +1,305 @@
+// Copyright (c) ONNX Project Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Parser of the operator tables the built-in schemas are read from.
+//!
+//! The tables `onnx.txt` and `ml.txt` are generated from the ONNX operator
+//! definitions by `scripts/gen_schema_tables.py` and list every version of
+//! every operator. Each schema is a block of lines:
+//!
+//! ```text
+//! op Clip 11
+//!   input input T
+//!   input min T optional
+//!   input max T optional
+//!   output output T
+//!   type T float16 float double
+//! ```
+//!
+//! - `op <name> <since_version> [deprecated]` starts a schema.
+//! - `input` and `output` give the name and type string of a formal
+//!   parameter, followed by `optional`, `variadic` or `variadic(<min arity>)`
+//!   and `heterogeneous` where they apply.
+//! - `attr <name> <TYPE> [required | = <default>]` declares an attribute.
+//!   Defaults are written as `1`, `0.5`, `"NOTSET"` or `[1, 2]`.
+//! - `type <param> <types>...` lists the types allowed for a type
+//!   parameter. A bare element type such as `float` stands for
+//!   `tensor(float)`.
+//!
+//! A line `@NAME = <types>...` names a set of types, which can then be used
+//! in place of a type as `@NAME` or inside another type as in `seq(@NAME)`.
+//! Lines starting with `#` are comments.
+
+use super::{FormalParameter, OpSchema};
+use crate::attribute::Attr;
+use crate::enums::{AttributeType, DataType};
+use crate::error::{IrError, Result};
+use crate::types::TypeProto;
+use std::collections::HashMap;
+
+/// Parses the schemas of a table for operators in `domain`.
+pub(crate) fn parse(domain: &str, table: &str) -> Result<Vec<OpSchema>> {
+    let mut schemas = Vec::new();
+    let mut macros: HashMap<&str, Vec<String>> = HashMap::new();
+    for (number, line) in table.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let error = |message: String| IrError::Parse {
+            input: line.to_string(),
+            message: format!("line {}: {}", number + 1, message),
+        };
+        let (keyword, rest) = line.split_once(' ').unwrap_or((line, ""));
+        if let Some(name) = keyword.strip_prefix('@') {
+            let types = rest
+                .strip_prefix("= ")
+                .ok_or_else(|| error("expected '='".into()))?;
+            let types = expand_types(types, &macros).map_err(error)?;
+            macros.insert(name, types);
+            continue;
+        }
+        if keyword == "op" {
+            schemas.push(parse_op(domain, rest).ok_or_else(|| error("invalid op".into()))?);
+            continue;
+        }
+        let schema = schemas
+            .pop()
+            .ok_or_else(|| error("expected 'op' first".into()))?;
+        let schema = match keyword {
+            "input" => parse_param(rest).map(|param| schema.input_param(param)),
+            "output" => parse_param(rest).map(|param| schema.output_param(param)),
+            "attr" => parse_attr(rest).map(|attr| schema.attr_spec(attr)),
+            "type" => {
+                let (param, types) = rest.split_once(' ').unwrap_or((rest, ""));
+                let types = expand_types(types, &macros)
+                    .map_err(error)?
+                    .iter()
+                    .map(|t| parse_type(t).ok_or_else(|| error(format!("invalid type {}", t))))
+                    .collect::<Result<Vec<_>>>()?;
+                Some(schema.type_constraint(param, types))
+            }
+            _ => return Err(error(format!("unknown keyword '{}'", keyword))),
+        };
+        schemas.push(schema.ok_or_else(|| error(format!("invalid {}", keyword)))?);
+    }
+    Ok(schemas)
+}
+
+fn parse_op(domain: &str, text: &str) -> Option<OpSchema> {
+    let mut words = text.split_whitespace();
+    let name = words.next()?;
+    let since_version = words.next()?.parse().ok()?;
+    let schema = OpSchema::new(name, domain, since_version);
+    match words.next() {
+        None => Some(schema),
+        Some("deprecated") => Some(schema.deprecate()),
+        Some(_) => None,
+    }
+}
+
+fn parse_param(text: &str) -> Option<FormalParameter> {
+    let mut words = text.split_whitespace();
+    let (name, type_str) = (words.next()?, words.next()?);
+    let mut param = FormalParameter::single(name, type_str);
+    for word in words {
+        param = match word {
+            "optional" => FormalParameter::optional(name, type_str),
+            "variadic" => FormalParameter::variadic(name, type_str, 1),
+            "heterogeneous" => param.heterogeneous(),
+            _ => {
+                let min_arity = word.strip_prefix("variadic(")?.strip_suffix(')')?;
+                FormalParameter::variadic(name, type_str, min_arity.parse().ok()?)
+            }
+        };
+    }
+    Some(param)
+}
+
+fn parse_attr(text: &str) -> Option<super::AttributeSpec> {
+    let mut words = text.splitn(3, ' ');
+    let (name, type_) = (words.next()?, words.next()?);
+    let type_ = ATTRIBUTE_TYPES
+        .into_iter()
+        .find(|t| t.onnx_name() == type_)?;
+    let (required, default) = match words.next() {
+        None => (false, None),
+        Some("required") => (true, None),
+        Some(default) => {
+            let default = default.strip_prefix("= ")?;
+            (false, Some(parse_default(name, type_, default)?))
+        }
+    };
+    Some(super::AttributeSpec {
+        name: name.to_string(),
+        type_,
+        required,
+        default,
+    })
+}
+
+const ATTRIBUTE_TYPES: [AttributeType; 14] = [
+    AttributeType::Float,
+    AttributeType::Int,
+    AttributeType::String,
+    AttributeType::Tensor,
+    AttributeType::Graph,
+    AttributeType::Floats,
+    AttributeType::Ints,
+    AttributeType::Strings,
+    AttributeType::Tensors,
+    AttributeType::Graphs,
+    AttributeType::SparseTensor,
+    AttributeType::SparseTensors,
+    AttributeType::TypeProto,
+    AttributeType::TypeProtos,
+];
+
+fn parse_default(name: &str, type_: AttributeType, text: &str) -> Option<Attr> {
+    let list = || -> Option<Vec<&str>> {
+        let items = text.strip_prefix('[')?.strip_suffix(']')?;
+        Some(items.split(',').map(str::trim).collect())
+    };
+    Some(match type_ {
+        AttributeType::Int => Attr::int(name, text.parse().ok()?),
+        AttributeType::Float => Attr::float(name, text.parse().ok()?),
+        AttributeType::String => Attr::string(name, unquote(text)?),
+        AttributeType::Ints => {
+            let values = list()?.into_iter().map(|v| v.parse().ok());
+            Attr::ints(name, values.collect::<Option<_>>()?)
+        }
+        AttributeType::Floats => {
+            let values = list()?.into_iter().map(|v| v.parse().ok());
+            Attr::floats(name, values.collect::<Option<_>>()?)
+        }
+        AttributeType::Strings => {
+            let values = list()?.into_iter().map(unquote);
+            Attr::strings(name, values.collect::<Option<Vec<_>>>()?)
+        }
+        _ => return None,
+    })
+}
+
+/// Removes the quotes around a string, which contains no quotes itself.
+fn unquote(text: &str) -> Option<&str> {
+    text.strip_prefix('"')?
+        .strip_suffix('"')
+        .filter(|s| !s.contains('"'))
+}
+
+/// Expands the named type sets in a list of types.
+fn expand_types(
+    text: &str,
+    macros: &HashMap<&str, Vec<String>>,
+) -> std::result::Result<Vec<String>, String> {
+    let mut types = Vec::new();
+    for word in text.split_whitespace() {
+        let Some(start) = word.find('@') else {
+            types.push(word.to_string());
+            continue;
+        };
+        let end = word[start + 1..]
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .map_or(word.len(), |i| start + 1 + i);
+        let name = &word[start + 1..end];
+        let set = macros
+            .get(name)
+            .ok_or_else(|| format!("unknown type set @{}", name))?;
+        for t in set {
+            types.push(format!("{}{}{}", &word[..start], t, &word[end..]));
+        }
+    }
+    Ok(types)
+}
+
+/// Parses an ONNX type string such as `seq(tensor(float))`.
+pub(crate) fn parse_type(text: &str) -> Option<TypeProto> {
+    let Some((kind, rest)) = text.split_once('(') else {
+        return DataType::from_onnx_name(text).map(|dtype| TypeProto::tensor(dtype, None));
+    };
+    let inner = rest.strip_suffix(')')?;
+    match kind {
+        "tensor" => Some(TypeProto::tensor(DataType::from_onnx_name(inner)?, None)),
+        "sparse_tensor" => Some(TypeProto::sparse_tensor(
+            DataType::from_onnx_name(inner)?,
+            None,
+        )),
+        "seq" => Some(TypeProto::sequence(parse_type(inner)?)),
+        "optional" => Some(TypeProto::optional(parse_type(inner)?)),
+        "map" => {
+            let (key, value) = inner.split_once(',')?;
+            Some(TypeProto::map(
+                DataType::from_onnx_name(key.trim())?,
+                parse_type(value.trim())?,
+            ))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::attribute::AttrValue;
+
+    const TABLE: &str = r#"
+# Types
+@FLOAT = float16 float double
+@ALL = @FLOAT int64
+
+op Op 1
+  input X T
+  input B T optional
+  output Y V variadic(0) heterogeneous
+  attr mode STRING = "NOTSET"
+  attr axes INTS = [0, 2]
+  attr axis INT required
+  type T @FLOAT
+  type V @ALL seq(@FLOAT) map(int64,string)
+
+op Op 3 deprecated
+  output Y T
+"#;
+
+    #[test]
+    fn test_parse_table() {
+        let schemas = parse("com.example", TABLE).unwrap();
+        assert_eq!(schemas.len(), 2);
+        let op = &schemas[0];
+        assert_eq!(op.domain, "com.example");
+        assert_eq!(op.max_inputs(), Some(2));
+        assert_eq!(op.min_outputs(), 0);
+        assert!(!op.outputs[0].is_homogeneous);
+        assert!(op.attribute("axis").unwrap().required);
+        let mode = op.attribute("mode").unwrap().default.as_ref().unwrap();
+        assert!(matches!(&mode.value, AttrValue::String(s) if s == b"NOTSET"));
+        let axes = op.attribute("axes").unwrap().default.as_ref().unwrap();
+        assert!(matches!(&axes.value, AttrValue::Ints(v) if v == &[0, 2]));
+
+        let types = &op.type_constraint_for("V").unwrap().allowed_types;
+        assert_eq!(types.len(), 8);
+        assert_eq!(
+            types[4],
+            TypeProto::sequence(TypeProto::tensor(DataType::Float16, None))
+        );
+        assert_eq!(
+            types[7],
+            TypeProto::map(DataType::Int64, TypeProto::tensor(DataType::String, None))
+        );
+        assert!(schemas[1].deprecated);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let err = parse("", "input X T").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Cannot parse 'input X T': line 1: expected 'op' first"
+        );
+        let err = parse("", "op Op 1\n  type T @ALL").unwrap_err();
+        assert!(err.to_string().contains("line 2: unknown type set @ALL"));
+        let err = parse("", "op Op 1\n  attr axis INT = one").unwrap_err();
+        assert!(err.to_string().contains("line 2: invalid attr"));
+        assert!(parse_type("tensor(float32)").is_none());
+    }
+}
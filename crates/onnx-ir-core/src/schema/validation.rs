@@ -0,0 +1,577 @@
+// Copyright (c) ONNX Project Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Validation of nodes against operator schemas.
+
+use super::{opset_version, param_at, FormalParameter, OpSchema, ParameterOption, SchemaRegistry};
+use crate::attribute::{AttrValue, AttributeError};
+use crate::enums::DataType;
+use crate::function::{Function, FunctionId};
+use crate::graph::Graph;
+use crate::model::Model;
+use crate::node::Node;
+use crate::value::Value;
+use indexmap::IndexMap;
+use std::collections::HashMap;
+use thiserror::Error;
+
+/// A way in which a node does not conform to its operator schema.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum ValidationError {
+    /// The node's domain is not imported.
+    #[error("{node}: no opset import for domain '{domain}'")]
+    MissingOpsetImport { node: String, domain: String },
+
+    /// No schema is registered for the operator at the imported version.
+    #[error("{node}: operator '{op_type}' is not defined in domain '{domain}' at opset {version}")]
+    UnknownOperator {
+        node: String,
+        op_type: String,
+        domain: String,
+        version: i32,
+    },
+
+    /// The node has fewer inputs or outputs than the schema requires.
+    #[error("{node}: expects at least {expected} {kind}s, got {actual}")]
+    TooFew {
+        node: String,
+        kind: &'static str,
+        expected: usize,
+        actual: usize,
+    },
+
+    /// The node has more inputs or outputs than the schema allows.
+    #[error("{node}: expects at most {expected} {kind}s, got {actual}")]
+    TooMany {
+        node: String,
+        kind: &'static str,
+        expected: usize,
+        actual: usize,
+    },
+
+    /// A required input or output is left empty.
+    #[error("{node}: required {kind} '{param}' is missing")]
+    MissingValue {
+        node: String,
+        kind: &'static str,
+        param: String,
+    },
+
+    /// An attribute is missing or has the wrong type.
+    #[error(transparent)]
+    Attribute(#[from] AttributeError),
+
+    /// The node sets an attribute the schema does not define.
+    #[error("{node}: unknown attribute '{name}'")]
+    UnknownAttribute { node: String, name: String },
+
+    /// A value has an element type that its formal parameter does not allow.
+    #[error(
+        "{node}: {kind} '{param}' has element type {actual}, which is not allowed for {type_str}"
+    )]
+    DisallowedType {
+        node: String,
+        kind: &'static str,
+        param: String,
+        type_str: String,
+        actual: DataType,
+    },
+
+    /// Values bound to the same type parameter have different element types.
+    #[error("{node}: type parameter {type_param} is bound to both {first} and {second}")]
+    ConflictingTypes {
+        node: String,
+        type_param: String,
+        first: DataType,
+        second: DataType,
+    },
+}
+
+/// Checks a node against the schema of its operator.
+///
+/// The schema version is the one in effect for the opset imported for the
+/// node's domain in `opset_imports`.
+pub fn validate_node(
+    node: &Node,
+    registry: &SchemaRegistry,
+    opset_imports: &HashMap<String, i32>,
+) -> Vec<ValidationError> {
+    let Some(version) = opset_version(&node.domain, opset_imports) else {
+        return vec![ValidationError::MissingOpsetImport {
+            node: node.label(),
+            domain: node.domain.clone(),
+        }];
+    };
+    match registry.get(&node.domain, &node.op_type, version) {
+        Some(schema) => validate_node_with_schema(node, schema),
+        None => vec![ValidationError::UnknownOperator {
+            node: node.label(),
+            op_type: node.op_type.clone(),
+            domain: node.domain.clone(),
+            version,
+        }],
+    }
+}
+
+/// Checks a node against a given operator schema.
+pub fn validate_node_with_schema(node: &Node, schema: &OpSchema) -> Vec<ValidationError> {
+    let mut validator = Validator {
+        node,
+        schema,
+        label: node.label(),
+        errors: Vec::new(),
+        bindings: HashMap::new(),
+    };
+    validator.check_values("input", &node.inputs, &schema.inputs);
+    validator.check_values("output", &node.outputs, &schema.outputs);
+    validator.check_attributes();
+    validator.errors
+}
+
+/// Checks a call to a model-local function against the function's
+/// signature.
+///
+/// Trailing inputs and outputs may be omitted, and the call may only set
+/// attributes that are parameters of the function.
+pub fn validate_call(node: &Node, function: &Function) -> Vec<ValidationError> {
+    let mut errors = Vec::new();
+    let counts = [
+        ("input", node.num_inputs(), function.num_inputs()),
+        ("output", node.num_outputs(), function.num_outputs()),
+    ];
+    for (kind, actual, expected) in counts {
+        if actual > expected {
+            errors.push(ValidationError::TooMany {
+                node: node.label(),
+                kind,
+                expected,
+                actual,
+            });
+        }
+    }
+    for name in node.attributes.keys() {
+        if !function.has_attribute(name) {
+            errors.push(ValidationError::UnknownAttribute {
+                node: node.label(),
+                name: name.clone(),
+            });
+        }
+    }
+    errors
+}
+
+/// Checks every node of a graph and its subgraphs.
+///
+/// Nodes are resolved against the graph's opset imports; subgraphs also
+/// see the imports of the graphs enclosing them.
+pub fn validate_graph(graph: &Graph, registry: &SchemaRegistry) -> Vec<ValidationError> {
+    let mut validator = GraphValidator {
+        registry,
+        functions: &IndexMap::new(),
+        errors: Vec::new(),
+    };
+    validator.validate_graph(graph, &HashMap::new());
+    validator.errors
+}
+
+/// Checks every node of a model, including its subgraphs and the bodies of
+/// its functions.
+///
+/// Nodes of the main graph are resolved against the model's opset imports,
+/// and nodes of function bodies against the function's. Calls to functions
+/// of the model are checked with [`validate_call`].
+pub fn validate_model(model: &Model, registry: &SchemaRegistry) -> Vec<ValidationError> {
+    let mut validator = GraphValidator {
+        registry,
+        functions: &model.functions,
+        errors: Vec::new(),
+    };
+    validator.validate_graph(&model.graph, &model.opset_imports);
+    for function in model.functions.values() {
+        validator.validate_nodes(function.iter_nodes(), &function.opset_imports);
+    }
+    validator.errors
+}
+
+struct GraphValidator<'a> {
+    registry: &'a SchemaRegistry,
+    functions: &'a IndexMap<FunctionId, Function>,
+    errors: Vec<ValidationError>,
+}
+
+impl<'a> GraphValidator<'a> {
+    fn validate_graph(&mut self, graph: &Graph, outer_imports: &HashMap<String, i32>) {
+        let mut opset_imports = outer_imports.clone();
+        opset_imports.extend(graph.opset_imports.iter().map(|(d, v)| (d.clone(), *v)));
+        self.validate_nodes(graph.iter_nodes(), &opset_imports);
+    }
+
+    fn validate_nodes<'n>(
+        &mut self,
+        nodes: impl Iterator<Item = &'n Node>,
+        opset_imports: &HashMap<String, i32>,
+    ) {
+        for node in nodes {
+            match self.functions.get(&node.function_id()) {
+                Some(function) => self.errors.extend(validate_call(node, function)),
+                None => {
+                    let errors = validate_node(node, self.registry, opset_imports);
+                    self.errors.extend(errors);
+                }
+            }
+            for attr in node.attributes.values().filter_map(|attr| attr.as_value()) {
+                match &attr.value {
+                    AttrValue::Graph(subgraph) => self.validate_graph(subgraph, opset_imports),
+                    AttrValue::Graphs(subgraphs) => {
+                        for subgraph in subgraphs {
+                            self.validate_graph(subgraph, opset_imports);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+}
+
+struct Validator<'a> {
+    node: &'a Node,
+    schema: &'a OpSchema,
+    label: String,
+    errors: Vec<ValidationError>,
+    /// Element types bound to each homogeneous type parameter.
+    bindings: HashMap<&'a str, DataType>,
+}
+
+impl<'a> Validator<'a> {
+    fn check_values(
+        &mut self,
+        kind: &'static str,
+        values: &'a [Value],
+        params: &'a [FormalParameter],
+    ) {
+        let (min, max) = match kind {
+            "input" => (self.schema.min_inputs(), self.schema.max_inputs()),
+            _ => (self.schema.min_outputs(), self.schema.max_outputs()),
+        };
+        if values.len() < min {
+            self.errors.push(ValidationError::TooFew {
+                node: self.label.clone(),
+                kind,
+                expected: min,
+                actual: values.len(),
+            });
+        }
+        if let Some(max) = max.filter(|max| values.len() > *max) {
+            self.errors.push(ValidationError::TooMany {
+                node: self.label.clone(),
+                kind,
+                expected: max,
+                actual: values.len(),
+            });
+        }
+
+        for (index, value) in values.iter().enumerate() {
+            let Some(param) = param_at(params, index) else {
+                break;
+            };
+            if value.name.is_empty() {
+                if param.option == ParameterOption::Single {
+                    self.errors.push(ValidationError::MissingValue {
+                        node: self.label.clone(),
+                        kind,
+                        param: param.name.clone(),
+                    });
+                }
+                continue;
+            }
+            if let Some(type_) = &value.type_ {
+                self.check_type(kind, param, type_.elem_type);
+            }
+        }
+    }
+
+    fn check_type(&mut self, kind: &'static str, param: &'a FormalParameter, dtype: DataType) {
+        let allowed = match self.schema.type_constraint_for(&param.type_str) {
+            Some(constraint) => constraint.allows_tensor(dtype),
+            None => match fixed_tensor_type(&param.type_str) {
+                Some(fixed) => fixed == dtype,
+                // Not a tensor type, such as a sequence; nothing to check
+                None => true,
+            },
+        };
+        if !allowed {
+            self.errors.push(ValidationError::DisallowedType {
+                node: self.label.clone(),
+                kind,
+                param: param.name.clone(),
+                type_str: param.type_str.clone(),
+                actual: dtype,
+            });
+            return;
+        }
+        if !param.is_homogeneous || self.schema.type_constraint_for(&param.type_str).is_none() {
+            return;
+        }
+        match self.bindings.get(param.type_str.as_str()) {
+            Some(&bound) if bound != dtype => {
+                self.errors.push(ValidationError::ConflictingTypes {
+                    node: self.label.clone(),
+                    type_param: param.type_str.clone(),
+                    first: bound,
+                    second: dtype,
+                });
+            }
+            Some(_) => {}
+            None => {
+                self.bindings.insert(&param.type_str, dtype);
+            }
+        }
+    }
+
+    fn check_attributes(&mut self) {
+        for spec in self.schema.attributes.values() {
+            match self.node.attributes.get(&spec.name) {
+                None if spec.required => {
+                    self.errors.push(
+                        AttributeError::Missing {
+                            node: self.label.clone(),
+                            name: spec.name.clone(),
+                        }
+                        .into(),
+                    );
+                }
+                Some(attr) if attr.attr_type() != spec.type_ => {
+                    self.errors.push(
+                        AttributeError::TypeMismatch {
+                            node: self.label.clone(),
+                            name: spec.name.clone(),
+                            expected: spec.type_,
+                            actual: attr.attr_type(),
+                        }
+                        .into(),
+                    );
+                }
+                _ => {}
+            }
+        }
+        for name in self.node.attributes.keys() {
+            if self.schema.attribute(name).is_none() {
+                self.errors.push(ValidationError::UnknownAttribute {
+                    node: self.label.clone(),
+                    name: name.clone(),
+                });
+            }
+        }
+    }
+}
+
+/// Parses a fixed tensor type such as `tensor(int64)`.
+fn fixed_tensor_type(type_str: &str) -> Option<DataType> {
+    let name = type_str.strip_prefix("tensor(")?.strip_suffix(')')?;
+    DataType::from_onnx_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::attribute::Attr;
+    use crate::types::TensorType;
+
+    fn typed(name: &str, dtype: DataType) -> Value {
+        let mut value = Value::new(name);
+        value.type_ = Some(TensorType::new(dtype));
+        value
+    }
+
+    fn imports(version: i32) -> HashMap<String, i32> {
+        HashMap::from([("".to_string(), version)])
+    }
+
+    fn conv() -> Node {
+        let mut node = Node::new("Conv");
+        node.name = Some("node_conv_3".to_string());
+        node.add_input(typed("x", DataType::Float));
+        node.add_input(typed("w", DataType::Float));
+        node.add_output(Value::new("y"));
+        node
+    }
+
+    fn messages(node: &Node, version: i32) -> Vec<String> {
+        let registry = SchemaRegistry::builtin();
+        validate_node(node, &registry, &imports(version))
+            .iter()
+            .map(ToString::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn test_valid_node() {
+        let mut node = conv();
+        node.set_attribute(Attr::ints("pads", vec![1, 1, 1, 1]));
+        assert!(messages(&node, 17).is_empty());
+    }
+
+    #[test]
+    fn test_attributes() {
+        let mut node = conv();
+        node.set_attribute(Attr::int("pads", 1));
+        node.set_attribute(Attr::int("padding", 1));
+        assert_eq!(
+            messages(&node, 17),
+            vec![
+                "Conv(node_conv_3): attribute 'pads' expects INTS, got INT",
+                "Conv(node_conv_3): unknown attribute 'padding'",
+            ]
+        );
+
+        let mut pool = Node::new("MaxPool");
+        pool.add_input(Value::new("x"));
+        pool.add_output(Value::new("y"));
+        assert_eq!(
+            messages(&pool, 17),
+            vec!["MaxPool: missing attribute 'kernel_shape'"]
+        );
+    }
+
+    #[test]
+    fn test_arity() {
+        let mut node = conv();
+        node.inputs[1] = Value::new("");
+        node.add_input(Value::new("b"));
+        node.add_input(Value::new("extra"));
+        node.outputs.clear();
+        assert_eq!(
+            messages(&node, 17),
+            vec![
+                "Conv(node_conv_3): expects at most 3 inputs, got 4",
+                "Conv(node_conv_3): required input 'W' is missing",
+                "Conv(node_conv_3): expects at least 1 outputs, got 0",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_type_constraints() {
+        let mut node = Node::new("Add");
+        node.add_input(typed("a", DataType::Float));
+        node.add_input(typed("b", DataType::Int64));
+        node.add_output(typed("c", DataType::Bool));
+        assert_eq!(
+            messages(&node, 14),
+            vec![
+                "Add: type parameter T is bound to both Float and Int64",
+                "Add: output 'C' has element type Bool, which is not allowed for T",
+            ]
+        );
+
+        let mut reshape = Node::new("Reshape");
+        reshape.add_input(typed("data", DataType::Float));
+        reshape.add_input(typed("shape", DataType::Int32));
+        reshape.add_output(Value::new("reshaped"));
+        assert_eq!(
+            messages(&reshape, 14),
+            vec!["Reshape: input 'shape' has element type Int32, which is not allowed for tensor(int64)"]
+        );
+    }
+
+    #[test]
+    fn test_opset_resolution() {
+        let mut node = Node::new("Squeeze");
+        node.add_input(Value::new("x"));
+        node.add_output(Value::new("y"));
+        node.set_attribute(Attr::ints("axes", vec![0]));
+        assert!(messages(&node, 11).is_empty());
+        assert_eq!(
+            messages(&node, 13),
+            vec!["Squeeze: unknown attribute 'axes'"]
+        );
+        assert_eq!(
+            messages(&node, 0),
+            vec!["Squeeze: operator 'Squeeze' is not defined in domain '' at opset 0"]
+        );
+
+        node.domain = "com.example".to_string();
+        assert_eq!(
+            messages(&node, 13),
+            vec!["Squeeze: no opset import for domain 'com.example'"]
+        );
+    }
+
+    #[test]
+    fn test_validate_graph_subgraphs() {
+        let mut branch = Graph::new();
+        branch.append(Node::new("Relu"));
+
+        let mut if_node = Node::new("If");
+        if_node.add_input(Value::new("cond"));
+        if_node.add_output(Value::new("y"));
+        if_node.set_attribute(Attr::graph("then_branch", branch.clone()));
+        if_node.set_attribute(Attr::graph("else_branch", branch));
+
+        let mut graph = Graph::new();
+        graph.opset_imports.insert("".to_string(), 18);
+        graph.append(if_node);
+
+        let errors = validate_graph(&graph, &SchemaRegistry::builtin());
+        assert_eq!(errors.len(), 4);
+        assert!(errors
+            .iter()
+            .all(|e| e.to_string().starts_with("Relu: expects at least 1")));
+    }
+
+    #[test]
+    fn test_validate_model_function_calls() {
+        let mut function = Function::new("Double", "com.example");
+        function.add_input(Value::new("x"));
+        function.add_output(Value::new("y"));
+        function.set_opset_import("", 18);
+        let mut loop_body = Graph::new();
+        loop_body.append(Node::new("Relu"));
+        let mut loop_node = Node::new("Loop");
+        loop_node.add_output(Value::new("y"));
+        loop_node.set_attribute(Attr::graph("body", loop_body));
+        function.append(loop_node);
+
+        let mut call = Node::new("Double");
+        call.domain = "com.example".to_string();
+        call.add_input(Value::new("a"));
+        call.add_output(Value::new("b"));
+        let mut bad_call = call.clone();
+        bad_call.add_input(Value::new("extra"));
+        bad_call.set_attribute(Attr::int("scale", 2));
+
+        let mut graph = Graph::new();
+        graph.append(call);
+        graph.append(bad_call);
+        let mut model = Model::new(graph);
+        model.set_opset_import("", 18);
+        model.add_function(function);
+
+        let errors: Vec<_> = model
+            .validate_nodes(&SchemaRegistry::builtin())
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            errors,
+            vec![
+                "Double: expects at most 1 inputs, got 2",
+                "Double: unknown attribute 'scale'",
+                "Loop: expects at least 2 inputs, got 0",
+                "Relu: expects at least 1 inputs, got 0",
+                "Relu: expects at least 1 outputs, got 0",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_fixed_tensor_types() {
+        assert_eq!(
+            fixed_tensor_type("tensor(float8e4m3fn)"),
+            Some(DataType::Float8E4M3Fn)
+        );
+        assert_eq!(fixed_tensor_type("tensor(int4)"), Some(DataType::Int4));
+        assert_eq!(fixed_tensor_type("seq(tensor(float))"), None);
+    }
+}
@@ -6,18 +6,29 @@
 //! This module provides the shape representation for ONNX tensors,
 //! including support for symbolic/dynamic dimensions.
 
+use crate::dim_expr::DimExpr;
+use crate::error::{IrError, Result};
+use std::collections::HashMap;
 use std::fmt;
+use std::ops::{Add, Mul, Sub};
+use std::str::FromStr;
 
 /// A symbolic or dynamic dimension in a shape.
 ///
-/// A dimension can be either a concrete integer value or a symbolic
-/// parameter represented by a string.
+/// A dimension can be either a concrete integer value, a symbolic
+/// parameter represented by a string, or an expression over symbolic
+/// parameters such as `seq_len + 1`.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum SymbolicDim {
     /// A concrete integer dimension.
     Int(i64),
     /// A symbolic dimension with an optional parameter name.
     Symbol(Option<String>),
+    /// An expression that is neither a constant nor a single symbol.
+    ///
+    /// Use [`SymbolicDim::from_expr`] to build dimensions from expressions,
+    /// so that equal dimensions always use the same variant.
+    Expr(DimExpr),
 }
 
 impl SymbolicDim {
@@ -31,14 +42,157 @@ impl SymbolicDim {
         Self::Symbol(name)
     }
 
+    /// Creates a dimension from an expression, using the simplest variant
+    /// that represents it.
+    pub fn from_expr(expr: DimExpr) -> Self {
+        if let Some(value) = expr.as_int() {
+            Self::Int(value)
+        } else if let Some(name) = expr.as_symbol() {
+            Self::Symbol(Some(name.to_string()))
+        } else {
+            Self::Expr(expr)
+        }
+    }
+
+    /// Creates a dimension from a `dim_param` string.
+    ///
+    /// The string is kept as an opaque symbol name, even if it reads as an
+    /// expression, so that [`SymbolicDim::to_dim_param`] gives it back
+    /// unchanged. Use [`SymbolicDim::parse_expr`] to interpret it.
+    pub fn from_dim_param(param: &str) -> Self {
+        Self::Symbol(Some(param.to_string()))
+    }
+
+    /// Parses an expression such as `2*N` or `seq + 1`, using the simplest
+    /// variant that represents it.
+    ///
+    /// Returns an error if the string is not an expression in the syntax
+    /// of [`DimExpr`].
+    pub fn parse_expr(s: &str) -> Result<Self> {
+        Ok(Self::from_expr(s.parse()?))
+    }
+
+    /// Returns the `dim_param` string of a named or expression dimension.
+    pub fn to_dim_param(&self) -> Option<String> {
+        match self {
+            Self::Symbol(Some(name)) => Some(name.clone()),
+            Self::Expr(expr) => Some(expr.to_string()),
+            _ => None,
+        }
+    }
+
+    /// Returns the dimension as an expression, or `None` if it is unknown.
+    pub fn to_expr(&self) -> Option<DimExpr> {
+        match self {
+            Self::Int(v) => Some(DimExpr::int(*v)),
+            Self::Symbol(Some(name)) => Some(DimExpr::symbol(name.clone())),
+            Self::Symbol(None) => None,
+            Self::Expr(expr) => Some(expr.clone()),
+        }
+    }
+
+    /// Replaces symbols by values and simplifies the result.
+    pub fn substitute(&self, bindings: &HashMap<String, i64>) -> Self {
+        match self.to_expr() {
+            Some(expr) => Self::from_expr(expr.substitute(bindings)),
+            None => Self::Symbol(None),
+        }
+    }
+
+    /// Divides by `divisor`, rounding down.
+    pub fn floor_div(&self, divisor: &SymbolicDim) -> Self {
+        self.binary(divisor, |a, b| a.floor_div(&b))
+    }
+
+    /// Divides by `divisor`, rounding up.
+    pub fn ceil_div(&self, divisor: &SymbolicDim) -> Self {
+        self.binary(divisor, |a, b| a.ceil_div(&b))
+    }
+
+    /// Divides by `divisor` if the division is exact for every value of the
+    /// symbols.
+    pub fn exact_div(&self, divisor: &SymbolicDim) -> Option<Self> {
+        let quotient = self.to_expr()?.exact_div(&divisor.to_expr()?)?;
+        Some(Self::from_expr(quotient))
+    }
+
+    /// Combines two dimensions, giving an unknown dimension if either is
+    /// unknown.
+    fn binary(&self, other: &SymbolicDim, op: impl FnOnce(DimExpr, DimExpr) -> DimExpr) -> Self {
+        match (self.to_expr(), other.to_expr()) {
+            (Some(a), Some(b)) => Self::from_expr(op(a, b)),
+            _ => Self::Symbol(None),
+        }
+    }
+
+    /// Returns true if this is an unnamed symbolic dimension, whose value is
+    /// unknown.
+    pub fn is_unknown(&self) -> bool {
+        matches!(self, Self::Symbol(None))
+    }
+
+    /// Returns true if both dimensions are known to be equal.
+    ///
+    /// An unknown dimension is not known to equal anything, not even another
+    /// unknown dimension.
+    pub fn is_same_as(&self, other: &SymbolicDim) -> bool {
+        !self.is_unknown() && self == other
+    }
+
+    /// Returns true if the dimensions may be equal, which is the case unless
+    /// they are different integers.
+    pub fn is_compatible_with(&self, other: &SymbolicDim) -> bool {
+        match (self, other) {
+            (Self::Int(x), Self::Int(y)) => x == y,
+            _ => true,
+        }
+    }
+
+    /// Merges two descriptions of the same dimension, keeping the more
+    /// precise one.
+    ///
+    /// Integers are preferred over symbols, and names and expressions over
+    /// unknown dimensions. Of two symbolic dimensions, `self` is kept.
+    /// Returns `None` if the dimensions are different integers.
+    pub fn merge(&self, other: &SymbolicDim) -> Option<Self> {
+        match (self, other) {
+            (Self::Int(x), Self::Int(y)) if x != y => None,
+            (Self::Int(_), _) => Some(self.clone()),
+            (_, Self::Int(_)) | (Self::Symbol(None), _) => Some(other.clone()),
+            _ => Some(self.clone()),
+        }
+    }
+
+    /// Broadcasts two dimensions following the multidirectional
+    /// broadcasting rules, or returns `None` if they are incompatible.
+    ///
+    /// A symbolic dimension broadcast against an integer other than 1 must be
+    /// equal to it, so the integer is kept. Two different symbolic dimensions
+    /// broadcast to an unknown one, since either may be 1.
+    pub fn broadcast(&self, other: &SymbolicDim) -> Option<Self> {
+        match (self, other) {
+            (Self::Int(1), dim) | (dim, Self::Int(1)) => Some(dim.clone()),
+            (Self::Int(x), Self::Int(y)) => (x == y).then(|| self.clone()),
+            (Self::Int(_), _) => Some(self.clone()),
+            (_, Self::Int(_)) => Some(other.clone()),
+            _ if self.is_same_as(other) => Some(self.clone()),
+            _ => Some(Self::Symbol(None)),
+        }
+    }
+
     /// Returns true if this is a concrete integer dimension.
     pub fn is_int(&self) -> bool {
         matches!(self, Self::Int(_))
     }
 
-    /// Returns true if this is a symbolic dimension.
+    /// Returns true if this is a symbolic dimension or expression.
     pub fn is_symbol(&self) -> bool {
-        matches!(self, Self::Symbol(_))
+        matches!(self, Self::Symbol(_) | Self::Expr(_))
+    }
+
+    /// Returns true if this is an expression.
+    pub fn is_expr(&self) -> bool {
+        matches!(self, Self::Expr(_))
     }
 
     /// Returns the integer value if this is a concrete dimension.
@@ -56,10 +210,35 @@ impl fmt::Display for SymbolicDim {
             Self::Int(v) => write!(f, "{}", v),
             Self::Symbol(Some(name)) => write!(f, "{}", name),
             Self::Symbol(None) => write!(f, "?"),
+            Self::Expr(expr) => write!(f, "{}", expr),
         }
     }
 }
 
+impl Add for SymbolicDim {
+    type Output = SymbolicDim;
+
+    fn add(self, other: SymbolicDim) -> SymbolicDim {
+        self.binary(&other, |a, b| a + b)
+    }
+}
+
+impl Sub for SymbolicDim {
+    type Output = SymbolicDim;
+
+    fn sub(self, other: SymbolicDim) -> SymbolicDim {
+        self.binary(&other, |a, b| a - b)
+    }
+}
+
+impl Mul for SymbolicDim {
+    type Output = SymbolicDim;
+
+    fn mul(self, other: SymbolicDim) -> SymbolicDim {
+        self.binary(&other, |a, b| a * b)
+    }
+}
+
 impl From<i64> for SymbolicDim {
     fn from(value: i64) -> Self {
         Self::Int(value)
@@ -107,7 +286,8 @@ impl Shape {
     /// Returns the total number of elements (product of all dimensions).
     ///
     /// Returns 1 for scalar shapes (rank 0).
-    /// Returns `None` if any dimension is symbolic.
+    /// Returns `None` if any dimension is symbolic or negative, or if the
+    /// size does not fit in a `usize`.
     pub fn size(&self) -> Option<usize> {
         if self.is_scalar() {
             return Some(1);
@@ -117,9 +297,9 @@ impl Shape {
         for dim in &self.dims {
             match dim {
                 SymbolicDim::Int(v) => {
-                    total = total.saturating_mul(*v as usize);
+                    total = total.checked_mul(usize::try_from(*v).ok()?)?;
                 }
-                SymbolicDim::Symbol(_) => {
+                SymbolicDim::Symbol(_) | SymbolicDim::Expr(_) => {
                     // Symbolic dimensions mean we can't compute a concrete size
                     return None;
                 }
@@ -148,9 +328,32 @@ impl Shape {
     /// # Panics
     ///
     /// Panics if the shape is frozen or the index is out of bounds.
+    /// See [`Shape::try_set_dim`] for a fallible version.
     pub fn set_dim(&mut self, index: usize, dim: impl Into<SymbolicDim>) {
-        assert!(!self.frozen, "Cannot modify frozen shape");
+        self.try_set_dim(index, dim)
+            .unwrap_or_else(|err| panic!("{}", err));
+    }
+
+    /// Sets a dimension at the given index.
+    ///
+    /// Returns an error if the shape is frozen or the index is out of bounds.
+    pub fn try_set_dim(&mut self, index: usize, dim: impl Into<SymbolicDim>) -> Result<()> {
+        self.check_mutable(index)?;
         self.dims[index] = dim.into();
+        Ok(())
+    }
+
+    fn check_mutable(&self, index: usize) -> Result<()> {
+        if self.frozen {
+            return Err(IrError::FrozenShape);
+        }
+        if index >= self.dims.len() {
+            return Err(IrError::DimensionOutOfBounds {
+                index,
+                rank: self.dims.len(),
+            });
+        }
+        Ok(())
     }
 
     /// Gets the denotation at the given index.
@@ -163,9 +366,144 @@ impl Shape {
     /// # Panics
     ///
     /// Panics if the shape is frozen or the index is out of bounds.
+    /// See [`Shape::try_set_denotation`] for a fallible version.
     pub fn set_denotation(&mut self, index: usize, denotation: Option<String>) {
-        assert!(!self.frozen, "Cannot modify frozen shape");
+        self.try_set_denotation(index, denotation)
+            .unwrap_or_else(|err| panic!("{}", err));
+    }
+
+    /// Sets the denotation at the given index.
+    ///
+    /// Returns an error if the shape is frozen or the index is out of bounds.
+    pub fn try_set_denotation(&mut self, index: usize, denotation: Option<String>) -> Result<()> {
+        self.check_mutable(index)?;
         self.denotations[index] = denotation;
+        Ok(())
+    }
+
+    /// Returns true if every dimension is an integer.
+    pub fn is_static(&self) -> bool {
+        self.dims.iter().all(SymbolicDim::is_int)
+    }
+
+    /// Returns true if both shapes are known to be equal, dimension by
+    /// dimension. Unknown dimensions are never known to be equal.
+    pub fn is_same_as(&self, other: &Shape) -> bool {
+        self.rank() == other.rank()
+            && self
+                .dims
+                .iter()
+                .zip(&other.dims)
+                .all(|(a, b)| a.is_same_as(b))
+    }
+
+    /// Returns true if the shapes have the same rank and their dimensions
+    /// may be equal.
+    pub fn is_compatible_with(&self, other: &Shape) -> bool {
+        self.rank() == other.rank()
+            && self
+                .dims
+                .iter()
+                .zip(&other.dims)
+                .all(|(a, b)| a.is_compatible_with(b))
+    }
+
+    /// Merges two descriptions of the same shape, refining unknown
+    /// dimensions with known ones (see [`SymbolicDim::merge`]).
+    ///
+    /// Denotations of `self` are kept, and missing ones are taken from
+    /// `other`. The result is frozen if `self` is. Returns an error if the
+    /// ranks differ or two dimensions are different integers.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use onnx_ir_core::{Shape, SymbolicDim};
+    ///
+    /// let a = Shape::new(vec![SymbolicDim::Symbol(None), 3.into()]);
+    /// let b = Shape::new(vec![2.into(), SymbolicDim::Symbol(Some("C".into()))]);
+    /// assert_eq!(a.merge(&b).unwrap(), Shape::new(vec![2, 3]));
+    /// assert!(a.merge(&Shape::new(vec![2, 4])).is_err());
+    /// ```
+    pub fn merge(&self, other: &Shape) -> Result<Shape> {
+        let incompatible = || IrError::IncompatibleShapes(self.clone(), other.clone());
+        if self.rank() != other.rank() {
+            return Err(incompatible());
+        }
+        let dims = self
+            .dims
+            .iter()
+            .zip(&other.dims)
+            .map(|(a, b)| a.merge(b).ok_or_else(incompatible))
+            .collect::<Result<Vec<_>>>()?;
+        let denotations = self
+            .denotations
+            .iter()
+            .zip(&other.denotations)
+            .map(|(a, b)| a.clone().or_else(|| b.clone()))
+            .collect();
+        Ok(Shape {
+            dims,
+            denotations,
+            frozen: self.frozen,
+        })
+    }
+
+    /// Broadcasts two shapes following the numpy-style multidirectional
+    /// broadcasting rules (see [`SymbolicDim::broadcast`]).
+    ///
+    /// Returns an error if two dimensions are different integers other
+    /// than 1.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use onnx_ir_core::{Shape, SymbolicDim};
+    ///
+    /// let n = SymbolicDim::Symbol(Some("N".into()));
+    /// let a = Shape::new(vec![n.clone(), 1.into(), 4.into()]);
+    /// let b = Shape::new(vec![3, 1]);
+    /// assert_eq!(a.broadcast(&b).unwrap(), Shape::new(vec![n, 3.into(), 4.into()]));
+    /// ```
+    pub fn broadcast(&self, other: &Shape) -> Result<Shape> {
+        let rank = self.rank().max(other.rank());
+        let one = SymbolicDim::Int(1);
+        let dim = |shape: &'_ Shape, i: usize| -> SymbolicDim {
+            match (i + shape.rank()).checked_sub(rank) {
+                Some(j) => shape.dims[j].clone(),
+                None => one.clone(),
+            }
+        };
+        let dims = (0..rank)
+            .map(|i| {
+                dim(self, i)
+                    .broadcast(&dim(other, i))
+                    .ok_or_else(|| IrError::IncompatibleShapes(self.clone(), other.clone()))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Shape::new(dims))
+    }
+
+    /// Broadcasts this shape to `target` following the unidirectional
+    /// broadcasting rules, where only this shape may be stretched.
+    ///
+    /// Returns `target`, with unknown dimensions refined where this shape
+    /// has an integer other than 1. Returns an error if this shape has a
+    /// higher rank or a dimension that cannot be stretched to the target.
+    pub fn broadcast_to(&self, target: &Shape) -> Result<Shape> {
+        let incompatible = || IrError::IncompatibleShapes(self.clone(), target.clone());
+        let offset = target
+            .rank()
+            .checked_sub(self.rank())
+            .ok_or_else(incompatible)?;
+        let mut dims = target.dims.clone();
+        for (dim, target) in self.dims.iter().zip(&mut dims[offset..]) {
+            // A symbolic dimension may be 1, so it never refines the target
+            if dim.is_int() && *dim != SymbolicDim::Int(1) {
+                *target = target.merge(dim).ok_or_else(incompatible)?;
+            }
+        }
+        Ok(Shape::new(dims))
     }
 
     /// Converts to a vector of concrete integers.
@@ -176,6 +514,8 @@ impl Shape {
     }
 }
 
+/// Formats the shape as `[N,3,224,224]`, with unknown dimensions as `?`
+/// and denotations before their dimension, as in `[DATA_BATCH:N,3]`.
 impl fmt::Display for Shape {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "[")?;
@@ -183,12 +523,94 @@ impl fmt::Display for Shape {
             if i > 0 {
                 write!(f, ",")?;
             }
+            if let Some(denotation) = &self.denotations[i] {
+                write!(f, "{}:", denotation)?;
+            }
             write!(f, "{}", dim)?;
         }
         write!(f, "]")
     }
 }
 
+/// Parses a dimension as formatted by [`Display`](fmt::Display): an
+/// integer, `?` for an unknown dimension, or else a symbol name, read like
+/// a `dim_param` by [`SymbolicDim::from_dim_param`].
+impl FromStr for SymbolicDim {
+    type Err = IrError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim() {
+            "" => Err(IrError::Parse {
+                input: s.to_string(),
+                message: "empty dimension".to_string(),
+            }),
+            "?" => Ok(Self::Symbol(None)),
+            dim => Ok(dim
+                .parse()
+                .map_or_else(|_| Self::from_dim_param(dim), Self::Int)),
+        }
+    }
+}
+
+/// Parses a shape as formatted by [`Display`](fmt::Display), allowing
+/// whitespace around dimensions, e.g. `[?, 128]` or `[batch:N, 3]`.
+///
+/// Formatting a shape and parsing it back gives the same shape, including
+/// denotations, as long as symbol names and denotations contain none of
+/// `,:[]`, no symbol is named `?` and no dimension is an expression: like
+/// `dim_param` strings, dimensions are read as opaque symbols, so `seq + 1`
+/// is read back as a symbol of that name.
+///
+/// # Examples
+///
+/// ```
+/// use onnx_ir_core::{Shape, SymbolicDim};
+///
+/// let shape: Shape = "[DATA_BATCH:N, 3, 224, 224]".parse().unwrap();
+/// assert_eq!(shape.dims()[0], SymbolicDim::Symbol(Some("N".into())));
+/// assert_eq!(shape.get_denotation(0), Some("DATA_BATCH"));
+/// assert_eq!(shape.to_string(), "[DATA_BATCH:N,3,224,224]");
+/// ```
+impl FromStr for Shape {
+    type Err = IrError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let error = |message: &str| IrError::Parse {
+            input: s.to_string(),
+            message: message.to_string(),
+        };
+        let inner = s
+            .trim()
+            .strip_prefix('[')
+            .and_then(|s| s.strip_suffix(']'))
+            .ok_or_else(|| error("expected a shape in brackets"))?;
+        if inner.trim().is_empty() {
+            return Ok(Shape::scalar());
+        }
+        let mut dims = Vec::new();
+        let mut denotations = Vec::new();
+        for item in inner.split(',') {
+            let (denotation, dim) = match item.split_once(':') {
+                Some((denotation, dim)) => (Some(denotation.trim()), dim),
+                None => (None, item),
+            };
+            if denotation == Some("") {
+                return Err(error("empty denotation"));
+            }
+            if dim.trim().is_empty() {
+                return Err(error("empty dimension"));
+            }
+            dims.push(dim.parse::<SymbolicDim>()?);
+            denotations.push(denotation.map(str::to_string));
+        }
+        Ok(Shape {
+            dims,
+            denotations,
+            frozen: false,
+        })
+    }
+}
+
 impl<T: Into<SymbolicDim>> FromIterator<T> for Shape {
     fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
         Self::new(iter)
@@ -210,6 +632,42 @@ mod tests {
         assert_eq!(dim2.as_int(), None);
     }
 
+    #[test]
+    fn test_symbolic_dim_expr() {
+        let seq = SymbolicDim::from_symbol(Some("seq".to_string()));
+        let sum = seq.clone() + SymbolicDim::from_int(1);
+        assert!(sum.is_symbol());
+        assert_eq!(sum.to_string(), "seq + 1");
+        assert_eq!(sum.to_dim_param().as_deref(), Some("seq + 1"));
+        assert_eq!(SymbolicDim::parse_expr("1 + seq").unwrap(), sum);
+        assert_eq!(sum.clone() - SymbolicDim::from_int(1), seq);
+        assert_eq!(SymbolicDim::parse_expr("2*3").unwrap(), SymbolicDim::Int(6));
+        assert!(SymbolicDim::parse_expr("batch size").is_err());
+
+        // dim_params are kept as written
+        for param in ["batch-size", "2*3", "1 + N", "batch size"] {
+            let dim = SymbolicDim::from_dim_param(param);
+            assert_eq!(dim, SymbolicDim::Symbol(Some(param.to_string())));
+            assert_eq!(dim.to_dim_param().as_deref(), Some(param));
+        }
+        assert_eq!(
+            sum.clone() * SymbolicDim::Symbol(None),
+            SymbolicDim::Symbol(None)
+        );
+
+        let bindings = HashMap::from([("seq".to_string(), 7)]);
+        assert_eq!(sum.substitute(&bindings), SymbolicDim::Int(8));
+
+        // Expressions whose constants overflow cannot be parsed
+        let param = "9223372036854775807+9223372036854775807";
+        assert!(matches!(
+            SymbolicDim::parse_expr(param),
+            Err(IrError::Parse { .. })
+        ));
+        let max = SymbolicDim::Int(i64::MAX);
+        assert!((max.clone() * max).is_symbol());
+    }
+
     #[test]
     fn test_shape() {
         let shape = Shape::new(vec![1, 2, 3]);
@@ -221,6 +679,126 @@ mod tests {
         assert_eq!(scalar.rank(), 0);
     }
 
+    #[test]
+    fn test_broadcast() {
+        let n = SymbolicDim::Symbol(Some("N".to_string()));
+        let m = SymbolicDim::Symbol(Some("M".to_string()));
+        let a = Shape::new(vec![n.clone(), 1.into(), 4.into()]);
+        let b = Shape::new(vec![3.into(), m.clone()]);
+        assert_eq!(
+            a.broadcast(&b).unwrap(),
+            Shape::new(vec![n.clone(), 3.into(), 4.into()])
+        );
+        assert_eq!(
+            Shape::new(vec![n.clone()])
+                .broadcast(&Shape::new(vec![m.clone()]))
+                .unwrap(),
+            Shape::new(vec![SymbolicDim::Symbol(None)])
+        );
+        assert_eq!(
+            Shape::new(vec![n.clone()])
+                .broadcast(&Shape::new(vec![n.clone()]))
+                .unwrap(),
+            Shape::new(vec![n.clone()])
+        );
+        assert_eq!(
+            Shape::new(vec![2])
+                .broadcast(&Shape::new(vec![3]))
+                .unwrap_err()
+                .to_string(),
+            "Shapes [2] and [3] are incompatible"
+        );
+
+        let target = Shape::new(vec![n.clone(), SymbolicDim::Symbol(None)]);
+        assert_eq!(
+            Shape::new(vec![5]).broadcast_to(&target).unwrap(),
+            Shape::new(vec![n.clone(), 5.into()])
+        );
+        assert_eq!(Shape::new(vec![m]).broadcast_to(&target).unwrap(), target);
+        assert!(Shape::new(vec![1, 1, 1]).broadcast_to(&target).is_err());
+        assert!(Shape::new(vec![2])
+            .broadcast_to(&Shape::new(vec![3]))
+            .is_err());
+    }
+
+    #[test]
+    fn test_merge_and_compare() {
+        let unknown = SymbolicDim::Symbol(None);
+        let n = SymbolicDim::Symbol(Some("N".to_string()));
+        let mut a = Shape::new(vec![unknown.clone(), n.clone(), 3.into()]);
+        a.set_denotation(0, Some("DATA_BATCH".to_string()));
+        let mut b = Shape::new(vec![n.clone(), unknown.clone(), unknown.clone()]);
+        b.set_denotation(2, Some("DATA_CHANNEL".to_string()));
+
+        let merged = a.merge(&b).unwrap();
+        assert_eq!(merged.dims(), &[n.clone(), n.clone(), 3.into()]);
+        assert_eq!(merged.get_denotation(0), Some("DATA_BATCH"));
+        assert_eq!(merged.get_denotation(2), Some("DATA_CHANNEL"));
+        assert!(a.merge(&Shape::new(vec![1, 2])).is_err());
+        assert!(a.merge(&Shape::new(vec![1, 2, 4])).is_err());
+
+        assert!(a.is_compatible_with(&b));
+        assert!(!a.is_compatible_with(&Shape::new(vec![1, 2, 4])));
+        assert!(!a.is_same_as(&a));
+        assert!(merged.is_same_as(&merged));
+        assert!(!unknown.is_same_as(&unknown));
+        assert!(!merged.is_static());
+        assert!(Shape::new(vec![2, 3]).is_static());
+    }
+
+    #[test]
+    fn test_parse() {
+        let n = SymbolicDim::Symbol(Some("N".to_string()));
+        let shape: Shape = "[N,3,224,224]".parse().unwrap();
+        assert_eq!(
+            shape,
+            Shape::new(vec![n.clone(), 3.into(), 224.into(), 224.into()])
+        );
+        let shape: Shape = " [?, 128] ".parse().unwrap();
+        assert_eq!(
+            shape,
+            Shape::new(vec![SymbolicDim::Symbol(None), 128.into()])
+        );
+        assert_eq!("[]".parse::<Shape>().unwrap(), Shape::scalar());
+        assert_eq!("[ ]".parse::<Shape>().unwrap(), Shape::scalar());
+
+        let shape: Shape = "[batch:N, 3]".parse().unwrap();
+        assert_eq!(shape.dims(), &[n, 3.into()]);
+        assert_eq!(shape.get_denotation(0), Some("batch"));
+        assert_eq!(shape.get_denotation(1), None);
+
+        let shape: Shape = "[2*seq + 1, 4]".parse().unwrap();
+        assert_eq!(
+            shape.dims()[0],
+            SymbolicDim::Symbol(Some("2*seq + 1".to_string()))
+        );
+
+        for s in ["N,3", "[N,]", "[:N]", "[batch:]", "[3"] {
+            assert!(s.parse::<Shape>().is_err(), "{}", s);
+        }
+        assert_eq!(
+            "[N,]".parse::<Shape>().unwrap_err().to_string(),
+            "Cannot parse '[N,]': empty dimension"
+        );
+    }
+
+    #[test]
+    fn test_format_round_trip() {
+        let mut shape = Shape::new(vec![
+            SymbolicDim::Symbol(Some("N".to_string())),
+            SymbolicDim::Symbol(None),
+            SymbolicDim::from_dim_param("1 + seq"),
+            7.into(),
+        ]);
+        shape.set_denotation(0, Some("DATA_BATCH".to_string()));
+        shape.set_denotation(1, Some("DATA_CHANNEL".to_string()));
+        assert_eq!(shape.to_string(), "[DATA_BATCH:N,DATA_CHANNEL:?,1 + seq,7]");
+        assert_eq!(shape.to_string().parse::<Shape>().unwrap(), shape);
+        for s in ["[]", "[1]", "[?,?]", "[a:1,b:x]"] {
+            assert_eq!(s.parse::<Shape>().unwrap().to_string(), s);
+        }
+    }
+
     #[test]
     fn test_shape_mutation() {
         let mut shape = Shape::new(vec![1, 2, 3]);
@@ -252,6 +830,29 @@ mod tests {
         assert_eq!(shape.size(), None); // Should return None for symbolic dimensions
     }
 
+    #[test]
+    fn test_shape_try_set() {
+        let mut shape = Shape::new(vec![1, 2, 3]);
+        assert!(shape.try_set_dim(0, 4).is_ok());
+        assert!(shape
+            .try_set_denotation(1, Some("CHANNEL".to_string()))
+            .is_ok());
+        assert_eq!(shape.get_denotation(1), Some("CHANNEL"));
+
+        assert!(matches!(
+            shape.try_set_dim(3, 1),
+            Err(IrError::DimensionOutOfBounds { index: 3, rank: 3 })
+        ));
+
+        shape.freeze();
+        assert!(matches!(shape.try_set_dim(0, 1), Err(IrError::FrozenShape)));
+        assert!(matches!(
+            shape.try_set_denotation(0, None),
+            Err(IrError::FrozenShape)
+        ));
+        assert_eq!(shape.to_vec(), Some(vec![4, 2, 3]));
+    }
+
     #[test]
     #[should_panic(expected = "Cannot modify frozen shape")]
     fn test_frozen_shape() {
@@ -6,10 +6,18 @@
 //! This module provides various tensor implementations with different
 //! storage backends.
 
+use crate::element::{self, Element};
 use crate::enums::DataType;
+use crate::error::{IrError, Result};
 use crate::metadata::MetadataStore;
-use crate::shape::Shape;
+use crate::shape::{Shape, SymbolicDim};
+use std::borrow::Cow;
+use std::cell::RefCell;
 use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, Write};
+use std::ops::{Deref, DerefMut, RangeInclusive};
+use std::rc::Rc;
 
 /// Base trait for all tensor types.
 pub trait TensorProtocol {
@@ -44,40 +52,133 @@ pub struct Tensor {
     pub meta: MetadataStore,
 }
 
+/// Returns the number of bytes needed to store a tensor of the given type and shape.
+fn expected_nbytes(dtype: DataType, shape: &Shape) -> Result<usize> {
+    let size = element_count(shape)?;
+    let bits = dtype.bitwidth().ok_or(IrError::UnknownDtype(dtype))?;
+    size.checked_mul(bits)
+        .map(|bits| bits.div_ceil(8))
+        .ok_or_else(|| IrError::ShapeTooLarge(shape.clone()))
+}
+
+/// Returns the number of elements of a shape.
+///
+/// Unlike [`Shape::size`], this tells apart symbolic, negative and
+/// overflowing shapes.
+fn element_count(shape: &Shape) -> Result<usize> {
+    shape
+        .dims()
+        .iter()
+        .try_fold(1usize, |total, dim| match dim {
+            SymbolicDim::Int(value) => {
+                let value = usize::try_from(*value)
+                    .map_err(|_| IrError::NegativeDimension(shape.clone()))?;
+                total
+                    .checked_mul(value)
+                    .ok_or_else(|| IrError::ShapeTooLarge(shape.clone()))
+            }
+            SymbolicDim::Symbol(_) | SymbolicDim::Expr(_) => {
+                Err(IrError::SymbolicShape(shape.clone()))
+            }
+        })
+}
+
+/// Allocates zero-filled data, failing instead of aborting if the
+/// allocation is too large.
+fn zeroed(nbytes: usize, shape: &Shape) -> Result<Vec<u8>> {
+    let mut data = Vec::new();
+    data.try_reserve_exact(nbytes)
+        .map_err(|_| IrError::ShapeTooLarge(shape.clone()))?;
+    data.resize(nbytes, 0);
+    Ok(data)
+}
+
+/// Packs sub-byte elements, given in the low bits of each byte, in ONNX
+/// order: the first element goes in the lowest bits of the first byte, and
+/// the last byte is padded with zeros.
+pub(crate) fn pack_bits(values: &[u8], bits: u32) -> Vec<u8> {
+    let mask = (1u8 << bits) - 1;
+    values
+        .chunks((8 / bits) as usize)
+        .map(|chunk| {
+            chunk
+                .iter()
+                .zip((0..8).step_by(bits as usize))
+                .fold(0, |byte, (&value, shift)| byte | (value & mask) << shift)
+        })
+        .collect()
+}
+
+/// Unpacks `count` sub-byte elements packed by [`pack_bits`].
+///
+/// Returns an error if `data` holds fewer than `count` elements.
+pub(crate) fn unpack_bits(data: &[u8], bits: u32, count: usize) -> Result<Vec<u8>> {
+    let mask = (1u8 << bits) - 1;
+    let per_byte = (8 / bits) as usize;
+    let expected = count.div_ceil(per_byte);
+    if data.len() < expected {
+        return Err(IrError::ShapeMismatch {
+            expected,
+            actual: data.len(),
+        });
+    }
+    Ok((0..count)
+        .map(|i| (data[i / per_byte] >> ((i % per_byte) as u32 * bits)) & mask)
+        .collect())
+}
+
 impl Tensor {
-    /// Creates a new tensor with the given data type and shape.
+    /// Creates a new zero-filled tensor with the given data type and shape.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the shape is symbolic or the data type has no known size.
+    /// See [`Tensor::try_new`] for a fallible version.
     pub fn new(dtype: DataType, shape: Shape) -> Self {
-        let size = shape
-            .size()
-            .expect("Shape must not contain symbolic dimensions");
-        let itemsize = dtype.itemsize().expect("Data type must have a known size");
-        let nbytes = (size as f64 * itemsize).ceil() as usize;
-        Self {
+        Self::try_new(dtype, shape).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Creates a new zero-filled tensor with the given data type and shape.
+    ///
+    /// Returns an error if the shape is symbolic, negative or too large, or
+    /// the data type has no known size.
+    pub fn try_new(dtype: DataType, shape: Shape) -> Result<Self> {
+        let nbytes = expected_nbytes(dtype, &shape)?;
+        let data = zeroed(nbytes, &shape)?;
+        Ok(Self {
             name: None,
             dtype,
             shape,
-            data: vec![0u8; nbytes],
+            data,
             doc_string: None,
             metadata_props: HashMap::new(),
             meta: MetadataStore::new(),
-        }
+        })
     }
 
     /// Creates a new tensor from raw bytes.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the data length does not match the shape. See
+    /// [`Tensor::try_from_bytes`] for a fallible version.
     pub fn from_bytes(dtype: DataType, shape: Shape, data: Vec<u8>) -> Self {
-        let size = shape
-            .size()
-            .expect("Shape must not contain symbolic dimensions");
-        let itemsize = dtype.itemsize().expect("Data type must have a known size");
-        let expected_nbytes = (size as f64 * itemsize).ceil() as usize;
-        assert_eq!(
-            data.len(),
-            expected_nbytes,
-            "Data length {} does not match expected size {}",
-            data.len(),
-            expected_nbytes
-        );
-        Self {
+        Self::try_from_bytes(dtype, shape, data).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Creates a new tensor from raw bytes.
+    ///
+    /// Returns an error if the shape is symbolic, the data type has no known
+    /// size, or the data length does not match the shape.
+    pub fn try_from_bytes(dtype: DataType, shape: Shape, data: Vec<u8>) -> Result<Self> {
+        let expected = expected_nbytes(dtype, &shape)?;
+        if data.len() != expected {
+            return Err(IrError::ShapeMismatch {
+                expected,
+                actual: data.len(),
+            });
+        }
+        Ok(Self {
             name: None,
             dtype,
             shape,
@@ -85,7 +186,111 @@ impl Tensor {
             doc_string: None,
             metadata_props: HashMap::new(),
             meta: MetadataStore::new(),
+        })
+    }
+
+    /// Creates a new tensor from a vector of elements.
+    ///
+    /// The data type is the one of the element type `T`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the number of elements does not match the shape. See
+    /// [`Tensor::try_from_vec`] for a fallible version.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use onnx_ir_core::{DataType, Shape, Tensor};
+    ///
+    /// let tensor = Tensor::from_vec(Shape::new(vec![2]), vec![1.0f32, 2.0]);
+    /// assert_eq!(tensor.dtype, DataType::Float);
+    /// assert_eq!(tensor.to_vec::<f32>().unwrap(), [1.0, 2.0]);
+    /// ```
+    pub fn from_vec<T: Element>(shape: Shape, values: Vec<T>) -> Self {
+        Self::try_from_vec(shape, values).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Creates a new tensor from a vector of elements.
+    ///
+    /// Returns an error if the shape is symbolic or the number of elements
+    /// does not match the shape.
+    pub fn try_from_vec<T: Element>(shape: Shape, values: Vec<T>) -> Result<Self> {
+        let mut data = Vec::with_capacity(values.len() * T::SIZE);
+        for value in values {
+            value.write_le(&mut data);
         }
+        Self::try_from_bytes(T::DTYPE, shape, data)
+    }
+
+    /// Decodes the elements of the tensor.
+    ///
+    /// Returns an error if the tensor does not hold elements of type `T`.
+    pub fn to_vec<T: Element>(&self) -> Result<Vec<T>> {
+        self.check_dtype::<T>()?;
+        Ok(self.data.chunks_exact(T::SIZE).map(T::read_le).collect())
+    }
+
+    /// Returns the elements of the tensor as a slice.
+    ///
+    /// The data is reinterpreted in place when it is suitably aligned for
+    /// `T`, and copied otherwise, for example when it was sliced out of a
+    /// memory-mapped file at an arbitrary offset.
+    ///
+    /// Returns an error if the tensor does not hold elements of type `T`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use onnx_ir_core::{Shape, Tensor};
+    ///
+    /// let tensor = Tensor::from_vec(Shape::new(vec![3]), vec![1.0f32, 2.0, 3.0]);
+    /// let sum: f32 = tensor.as_slice::<f32>().unwrap().iter().sum();
+    /// assert_eq!(sum, 6.0);
+    /// ```
+    pub fn as_slice<T: Element>(&self) -> Result<Cow<'_, [T]>> {
+        self.check_dtype::<T>()?;
+        Ok(match element::cast_slice(&self.data) {
+            Some(slice) => Cow::Borrowed(slice),
+            None => Cow::Owned(self.data.chunks_exact(T::SIZE).map(T::read_le).collect()),
+        })
+    }
+
+    /// Returns the elements of the tensor as a mutable slice.
+    ///
+    /// Like [`Tensor::as_slice`], this falls back to a copy when the data is
+    /// not aligned for `T`. The copy is written back to the tensor when the
+    /// returned guard is dropped.
+    ///
+    /// Returns an error if the tensor does not hold elements of type `T`.
+    pub fn as_mut_slice<T: Element>(&mut self) -> Result<TensorSliceMut<'_, T>> {
+        self.check_dtype::<T>()?;
+        // Checking first keeps the borrow checker happy about returning the
+        // borrowed slice from one branch only.
+        if element::cast_slice_mut::<T>(&mut self.data).is_some() {
+            let slice = element::cast_slice_mut(&mut self.data).expect("checked above");
+            return Ok(TensorSliceMut {
+                inner: SliceMutInner::Borrowed(slice),
+            });
+        }
+        let values = self.data.chunks_exact(T::SIZE).map(T::read_le).collect();
+        Ok(TensorSliceMut {
+            inner: SliceMutInner::Copied {
+                values,
+                bytes: &mut self.data,
+            },
+        })
+    }
+
+    /// Checks that the tensor holds elements of type `T`.
+    fn check_dtype<T: Element>(&self) -> Result<()> {
+        if self.dtype != T::DTYPE {
+            return Err(IrError::DtypeMismatch {
+                expected: T::DTYPE,
+                actual: self.dtype,
+            });
+        }
+        Ok(())
     }
 
     /// Returns a reference to the raw bytes.
@@ -99,6 +304,59 @@ impl Tensor {
     }
 }
 
+/// Mutable access to the elements of a [`Tensor`], returned by
+/// [`Tensor::as_mut_slice`].
+///
+/// Dereferences to a slice of elements. If the tensor data had to be copied,
+/// the elements are written back when the guard is dropped.
+#[derive(Debug)]
+pub struct TensorSliceMut<'a, T: Element> {
+    inner: SliceMutInner<'a, T>,
+}
+
+#[derive(Debug)]
+enum SliceMutInner<'a, T: Element> {
+    /// The tensor data, reinterpreted in place.
+    Borrowed(&'a mut [T]),
+    /// A decoded copy of the tensor data.
+    Copied {
+        /// The decoded elements.
+        values: Vec<T>,
+        /// The tensor data to write the elements back to.
+        bytes: &'a mut Vec<u8>,
+    },
+}
+
+impl<T: Element> Deref for TensorSliceMut<'_, T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        match &self.inner {
+            SliceMutInner::Borrowed(slice) => slice,
+            SliceMutInner::Copied { values, .. } => values,
+        }
+    }
+}
+
+impl<T: Element> DerefMut for TensorSliceMut<'_, T> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        match &mut self.inner {
+            SliceMutInner::Borrowed(slice) => slice,
+            SliceMutInner::Copied { values, .. } => values,
+        }
+    }
+}
+
+impl<T: Element> Drop for TensorSliceMut<'_, T> {
+    fn drop(&mut self) {
+        if let SliceMutInner::Copied { values, bytes } = &mut self.inner {
+            let mut encoded = Vec::with_capacity(values.len() * T::SIZE);
+            values.iter().for_each(|v| v.write_le(&mut encoded));
+            bytes[..encoded.len()].copy_from_slice(&encoded);
+        }
+    }
+}
+
 impl TensorProtocol for Tensor {
     fn name(&self) -> Option<&str> {
         self.name.as_deref()
@@ -215,25 +473,35 @@ pub struct StringTensor {
 
 impl StringTensor {
     /// Creates a new string tensor.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the number of strings does not match the shape. See
+    /// [`StringTensor::try_new`] for a fallible version.
     pub fn new(shape: Shape, data: Vec<String>) -> Self {
-        let size = shape
-            .size()
-            .expect("Shape must not contain symbolic dimensions");
-        assert_eq!(
-            data.len(),
-            size,
-            "Data length {} does not match shape size {}",
-            data.len(),
-            size
-        );
-        Self {
+        Self::try_new(shape, data).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Creates a new string tensor.
+    ///
+    /// Returns an error if the shape is symbolic or the number of strings
+    /// does not match the shape.
+    pub fn try_new(shape: Shape, data: Vec<String>) -> Result<Self> {
+        let size = element_count(&shape)?;
+        if data.len() != size {
+            return Err(IrError::ShapeMismatch {
+                expected: size,
+                actual: data.len(),
+            });
+        }
+        Ok(Self {
             name: None,
             shape,
             data,
             doc_string: None,
             metadata_props: HashMap::new(),
             meta: MetadataStore::new(),
-        }
+        })
     }
 
     /// Returns a reference to the string data.
@@ -269,8 +537,70 @@ impl TensorProtocol for StringTensor {
     }
 }
 
+/// A sparse tensor in coordinate format.
+///
+/// Corresponds to `SparseTensorProto`. The `values` tensor holds the `NNZ`
+/// non-zero values. The `indices` tensor is `INT64` with shape `[NNZ, rank]`
+/// holding the coordinates of each value, or with shape `[NNZ]` holding
+/// linearized indices.
+#[derive(Debug, Clone)]
+pub struct SparseTensor {
+    pub values: Tensor,
+    pub indices: Tensor,
+    pub dims: Vec<i64>,
+}
+
+impl SparseTensor {
+    /// Creates a new sparse tensor with the given dense shape.
+    pub fn new(values: Tensor, indices: Tensor, dims: Vec<i64>) -> Self {
+        Self {
+            values,
+            indices,
+            dims,
+        }
+    }
+
+    /// Returns the number of non-zero values.
+    pub fn nnz(&self) -> usize {
+        self.values.size()
+    }
+
+    /// Returns the shape of the equivalent dense tensor.
+    pub fn dense_shape(&self) -> Shape {
+        Shape::new(self.dims.clone())
+    }
+}
+
+/// A source of the data of a [`LazyTensor`].
+///
+/// Closures returning the bytes of the tensor implement this trait.
+/// Providers of large tensors should also override
+/// [`TensorProvider::write_to`] to produce the data in chunks.
+pub trait TensorProvider {
+    /// Produces the bytes of the tensor.
+    fn materialize(&self) -> Result<Vec<u8>>;
+
+    /// Writes the bytes of the tensor to `writer`.
+    ///
+    /// The default implementation materializes all the data first.
+    fn write_to(&self, writer: &mut dyn Write) -> Result<()> {
+        writer.write_all(&self.materialize()?)?;
+        Ok(())
+    }
+}
+
+impl<F: Fn() -> Result<Vec<u8>>> TensorProvider for F {
+    fn materialize(&self) -> Result<Vec<u8>> {
+        self()
+    }
+}
+
 /// A lazy tensor that defers computation.
-#[derive(Debug)]
+///
+/// The data is produced by a [`TensorProvider`] when it is accessed. By
+/// default it is produced again on every access, so only the tensors in use
+/// are held in memory. With [`LazyTensor::cached`], the data is kept after
+/// the first access until [`LazyTensor::evict`] is called.
 pub struct LazyTensor {
     pub name: Option<String>,
     pub dtype: DataType,
@@ -278,24 +608,412 @@ pub struct LazyTensor {
     pub doc_string: Option<String>,
     pub metadata_props: HashMap<String, String>,
     pub meta: MetadataStore,
+    provider: Box<dyn TensorProvider>,
+    cache: bool,
+    cached: RefCell<Option<Rc<[u8]>>>,
+}
+
+impl LazyTensor {
+    /// Creates a lazy tensor whose data is produced by `provider`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use onnx_ir_core::{DataType, LazyTensor, Shape};
+    ///
+    /// let tensor = LazyTensor::new(DataType::Uint8, Shape::new(vec![4]), || Ok(vec![7; 4]));
+    /// assert_eq!(&*tensor.data().unwrap(), [7, 7, 7, 7]);
+    /// ```
+    pub fn new(dtype: DataType, shape: Shape, provider: impl TensorProvider + 'static) -> Self {
+        Self {
+            name: None,
+            dtype,
+            shape,
+            doc_string: None,
+            metadata_props: HashMap::new(),
+            meta: MetadataStore::new(),
+            provider: Box::new(provider),
+            cache: false,
+            cached: RefCell::new(None),
+        }
+    }
+
+    /// Sets whether the data is kept after it is first produced.
+    pub fn cached(mut self, cache: bool) -> Self {
+        self.cache = cache;
+        if !cache {
+            self.evict();
+        }
+        self
+    }
+
+    /// Returns the data of the tensor, producing it if it is not cached.
+    ///
+    /// Returns an error if the provider fails or produces data whose length
+    /// does not match the shape.
+    pub fn data(&self) -> Result<Rc<[u8]>> {
+        if let Some(data) = self.cached.borrow().as_ref() {
+            return Ok(Rc::clone(data));
+        }
+        let data = self.provider.materialize()?;
+        self.check_length(data.len())?;
+        let data: Rc<[u8]> = data.into();
+        if self.cache {
+            *self.cached.borrow_mut() = Some(Rc::clone(&data));
+        }
+        Ok(data)
+    }
+
+    /// Returns true if the data is cached.
+    pub fn is_cached(&self) -> bool {
+        self.cached.borrow().is_some()
+    }
+
+    /// Drops the cached data, if any. It is produced again on the next
+    /// access.
+    pub fn evict(&self) {
+        self.cached.borrow_mut().take();
+    }
+
+    /// Produces a [`Tensor`] holding the data.
+    pub fn to_tensor(&self) -> Result<Tensor> {
+        let mut tensor =
+            Tensor::try_from_bytes(self.dtype, self.shape.clone(), self.data()?.to_vec())?;
+        tensor.name = self.name.clone();
+        tensor.doc_string = self.doc_string.clone();
+        tensor.metadata_props = self.metadata_props.clone();
+        Ok(tensor)
+    }
+
+    /// Writes the data of the tensor to `writer` and returns the number of
+    /// bytes written.
+    ///
+    /// Cached data is written directly. Otherwise the provider streams the
+    /// data, so serializing a model does not need to hold all of it in
+    /// memory.
+    ///
+    /// Returns an error if the provider fails, writing fails, or the amount
+    /// of data does not match the shape.
+    pub fn write_to(&self, writer: &mut dyn Write) -> Result<usize> {
+        if let Some(data) = self.cached.borrow().as_ref() {
+            writer.write_all(data)?;
+            return Ok(data.len());
+        }
+        let mut counter = CountingWriter {
+            inner: writer,
+            count: 0,
+        };
+        self.provider.write_to(&mut counter)?;
+        self.check_length(counter.count)?;
+        Ok(counter.count)
+    }
+
+    fn check_length(&self, actual: usize) -> Result<()> {
+        let expected = expected_nbytes(self.dtype, &self.shape)?;
+        if actual != expected {
+            return Err(IrError::ShapeMismatch { expected, actual });
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for LazyTensor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LazyTensor")
+            .field("name", &self.name)
+            .field("dtype", &self.dtype)
+            .field("shape", &self.shape)
+            .field("cache", &self.cache)
+            .field("is_cached", &self.is_cached())
+            .finish_non_exhaustive()
+    }
+}
+
+impl TensorProtocol for LazyTensor {
+    fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    fn shape(&self) -> &Shape {
+        &self.shape
+    }
+
+    fn dtype(&self) -> DataType {
+        self.dtype
+    }
+
+    fn doc_string(&self) -> Option<&str> {
+        self.doc_string.as_deref()
+    }
+
+    fn size(&self) -> usize {
+        self.shape.size().unwrap_or(0)
+    }
+
+    /// Returns the number of bytes the data will have, without producing
+    /// it. Returns 0 for symbolic shapes.
+    fn nbytes(&self) -> usize {
+        expected_nbytes(self.dtype, &self.shape).unwrap_or(0)
+    }
+}
+
+/// A writer counting the bytes written through it.
+struct CountingWriter<'a> {
+    inner: &'a mut dyn Write,
+    count: usize,
+}
+
+impl Write for CountingWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.count += written;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
 }
 
 /// A packed tensor for sub-byte types (2-bit, 4-bit).
-#[derive(Debug)]
+///
+/// Holds `INT4`, `UINT4`, `FLOAT4E2M1`, `INT2` or `UINT2` elements packed
+/// in ONNX order: the first element goes in the lowest bits of the first
+/// byte, and the last byte is padded with zeros.
+///
+/// Elements are exchanged as `i8`. Integers are their value, and
+/// `FLOAT4E2M1` elements are their raw bits, see
+/// [`Float4E2M1`](crate::element::Float4E2M1).
+///
+/// The data type, shape and data are checked against each other on
+/// creation and cannot be changed afterwards, other than through
+/// [`PackedTensor::set`].
+#[derive(Debug, Clone)]
 pub struct PackedTensor {
     pub name: Option<String>,
-    pub dtype: DataType,
-    pub shape: Shape,
+    dtype: DataType,
+    shape: Shape,
+    data: Vec<u8>,
     pub doc_string: Option<String>,
     pub metadata_props: HashMap<String, String>,
     pub meta: MetadataStore,
 }
 
+/// Returns the bit width and range of the elements of a packed data type.
+fn packed_layout(dtype: DataType) -> Result<(u32, RangeInclusive<i8>)> {
+    match dtype {
+        DataType::Int4 => Ok((4, -8..=7)),
+        DataType::Uint4 | DataType::Float4E2M1 => Ok((4, 0..=15)),
+        DataType::Int2 => Ok((2, -2..=1)),
+        DataType::Uint2 => Ok((2, 0..=3)),
+        _ => Err(IrError::NotPacked(dtype)),
+    }
+}
+
+impl PackedTensor {
+    /// Creates a new zero-filled packed tensor.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the shape is symbolic or the data type is not a sub-byte
+    /// type. See [`PackedTensor::try_new`] for a fallible version.
+    pub fn new(dtype: DataType, shape: Shape) -> Self {
+        Self::try_new(dtype, shape).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Creates a new zero-filled packed tensor.
+    ///
+    /// Returns an error if the shape is symbolic, negative or too large, or
+    /// the data type is not a sub-byte type.
+    pub fn try_new(dtype: DataType, shape: Shape) -> Result<Self> {
+        packed_layout(dtype)?;
+        let nbytes = expected_nbytes(dtype, &shape)?;
+        let data = zeroed(nbytes, &shape)?;
+        Self::try_from_bytes(dtype, shape, data)
+    }
+
+    /// Creates a packed tensor from packed bytes.
+    ///
+    /// Returns an error if the shape is symbolic, the data type is not a
+    /// sub-byte type, or the data length does not match the shape.
+    pub fn try_from_bytes(dtype: DataType, shape: Shape, data: Vec<u8>) -> Result<Self> {
+        packed_layout(dtype)?;
+        let expected = expected_nbytes(dtype, &shape)?;
+        if data.len() != expected {
+            return Err(IrError::ShapeMismatch {
+                expected,
+                actual: data.len(),
+            });
+        }
+        Ok(Self {
+            name: None,
+            dtype,
+            shape,
+            data,
+            doc_string: None,
+            metadata_props: HashMap::new(),
+            meta: MetadataStore::new(),
+        })
+    }
+
+    /// Packs elements into a new tensor.
+    ///
+    /// Returns an error if the shape is symbolic, the data type is not a
+    /// sub-byte type, the number of elements does not match the shape, or
+    /// an element is out of range for the data type.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use onnx_ir_core::{DataType, PackedTensor, Shape};
+    ///
+    /// let tensor = PackedTensor::pack(DataType::Int4, Shape::new(vec![3]), &[1, -2, 7]).unwrap();
+    /// assert_eq!(tensor.data(), [0xe1, 0x07]);
+    /// assert_eq!(tensor.unpack(), [1, -2, 7]);
+    /// ```
+    pub fn pack(dtype: DataType, shape: Shape, values: &[i8]) -> Result<Self> {
+        let (bits, range) = packed_layout(dtype)?;
+        let size = element_count(&shape)?;
+        if values.len() != size {
+            return Err(IrError::ShapeMismatch {
+                expected: size,
+                actual: values.len(),
+            });
+        }
+        if let Some(&value) = values.iter().find(|v| !range.contains(v)) {
+            return Err(IrError::OutOfRange {
+                value: value.into(),
+                dtype,
+            });
+        }
+        let values: Vec<u8> = values.iter().map(|&v| v as u8).collect();
+        Self::try_from_bytes(dtype, shape, pack_bits(&values, bits))
+    }
+
+    /// Returns the packed bytes.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Unpacks all elements.
+    pub fn unpack(&self) -> Vec<i8> {
+        let (bits, range) = packed_layout(self.dtype).expect("checked on creation");
+        unpack_bits(&self.data, bits, self.size())
+            .expect("checked on creation")
+            .into_iter()
+            .map(|v| extend_sign(v, bits, &range))
+            .collect()
+    }
+
+    /// Returns the element at a flat index, or `None` if it is out of
+    /// bounds.
+    pub fn get(&self, index: usize) -> Option<i8> {
+        if index >= self.size() {
+            return None;
+        }
+        let (bits, range) = packed_layout(self.dtype).expect("checked on creation");
+        let (byte, shift) = bit_position(index, bits);
+        let value = (self.data[byte] >> shift) & ((1 << bits) - 1);
+        Some(extend_sign(value, bits, &range))
+    }
+
+    /// Sets the element at a flat index.
+    ///
+    /// Returns an error if the index is out of bounds or the value is out
+    /// of range for the data type.
+    pub fn set(&mut self, index: usize, value: i8) -> Result<()> {
+        let (bits, range) = packed_layout(self.dtype).expect("checked on creation");
+        if index >= self.size() {
+            return Err(IrError::IndexOutOfBounds {
+                index,
+                size: self.size(),
+            });
+        }
+        if !range.contains(&value) {
+            return Err(IrError::OutOfRange {
+                value: value.into(),
+                dtype: self.dtype,
+            });
+        }
+        let (byte, shift) = bit_position(index, bits);
+        let mask = ((1u8 << bits) - 1) << shift;
+        self.data[byte] = (self.data[byte] & !mask) | (((value as u8) << shift) & mask);
+        Ok(())
+    }
+
+    /// Creates a packed tensor from a tensor of a sub-byte type, whose data
+    /// is already packed.
+    pub fn from_tensor(tensor: Tensor) -> Result<Self> {
+        let mut packed = Self::try_from_bytes(tensor.dtype, tensor.shape, tensor.data)?;
+        packed.name = tensor.name;
+        packed.doc_string = tensor.doc_string;
+        packed.metadata_props = tensor.metadata_props;
+        packed.meta = tensor.meta;
+        Ok(packed)
+    }
+
+    /// Converts the packed tensor into a [`Tensor`] holding the same bytes.
+    pub fn into_tensor(self) -> Tensor {
+        Tensor {
+            name: self.name,
+            dtype: self.dtype,
+            shape: self.shape,
+            data: self.data,
+            doc_string: self.doc_string,
+            metadata_props: self.metadata_props,
+            meta: self.meta,
+        }
+    }
+}
+
+/// Returns the byte and bit offset of a packed element.
+fn bit_position(index: usize, bits: u32) -> (usize, u32) {
+    let per_byte = (8 / bits) as usize;
+    (index / per_byte, (index % per_byte) as u32 * bits)
+}
+
+/// Sign-extends a packed element if its type is signed.
+fn extend_sign(value: u8, bits: u32, range: &RangeInclusive<i8>) -> i8 {
+    if *range.start() < 0 {
+        ((value << (8 - bits)) as i8) >> (8 - bits)
+    } else {
+        value as i8
+    }
+}
+
+impl TensorProtocol for PackedTensor {
+    fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    fn shape(&self) -> &Shape {
+        &self.shape
+    }
+
+    fn dtype(&self) -> DataType {
+        self.dtype
+    }
+
+    fn doc_string(&self) -> Option<&str> {
+        self.doc_string.as_deref()
+    }
+
+    fn size(&self) -> usize {
+        self.shape.size().unwrap_or(0)
+    }
+
+    /// Returns the number of packed bytes, with two 4-bit or four 2-bit
+    /// elements per byte.
+    fn nbytes(&self) -> usize {
+        self.data.len()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::enums::DataType;
-    use crate::shape::Shape;
+    use crate::shape::{Shape, SymbolicDim};
 
     #[test]
     fn test_tensor_new() {
@@ -327,6 +1045,270 @@ mod tests {
         let _tensor = Tensor::from_bytes(DataType::Float, shape, data);
     }
 
+    #[test]
+    fn test_tensor_try_constructors() {
+        let err = Tensor::try_from_bytes(DataType::Float, Shape::new(vec![2, 2]), vec![0u8; 8])
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            IrError::ShapeMismatch {
+                expected: 16,
+                actual: 8
+            }
+        ));
+
+        let symbolic = Shape::new(vec![SymbolicDim::Symbol(Some("N".to_string()))]);
+        assert!(matches!(
+            Tensor::try_new(DataType::Float, symbolic.clone()),
+            Err(IrError::SymbolicShape(_))
+        ));
+        assert!(matches!(
+            Tensor::try_new(DataType::String, Shape::new(vec![2])),
+            Err(IrError::UnknownDtype(DataType::String))
+        ));
+        assert!(matches!(
+            StringTensor::try_new(symbolic, vec![]),
+            Err(IrError::SymbolicShape(_))
+        ));
+        assert!(matches!(
+            StringTensor::try_new(Shape::new(vec![2]), vec!["a".to_string()]),
+            Err(IrError::ShapeMismatch { .. })
+        ));
+        assert!(Tensor::try_new(DataType::Int4, Shape::new(vec![3])).is_ok());
+    }
+
+    #[test]
+    fn test_tensor_invalid_shapes() {
+        let negative = Shape::new(vec![-1]);
+        let huge = Shape::new(vec![i64::MAX, 2]);
+        assert!(matches!(
+            Tensor::try_new(DataType::Float, negative.clone()),
+            Err(IrError::NegativeDimension(_))
+        ));
+        assert!(matches!(
+            Tensor::try_new(DataType::Float, huge),
+            Err(IrError::ShapeTooLarge(_))
+        ));
+        assert!(matches!(
+            Tensor::try_new(DataType::Uint8, Shape::new(vec![i64::MAX])),
+            Err(IrError::ShapeTooLarge(_))
+        ));
+        assert!(matches!(
+            StringTensor::try_new(Shape::new(vec![i64::MAX, 3]), vec![]),
+            Err(IrError::ShapeTooLarge(_))
+        ));
+        assert_eq!(negative.size(), None);
+    }
+
+    #[test]
+    fn test_tensor_from_vec() {
+        let tensor = Tensor::from_vec(Shape::new(vec![2, 2]), vec![1i64, -2, 3, 4]);
+        assert_eq!(tensor.dtype, DataType::Int64);
+        assert_eq!(tensor.data.len(), 32);
+        assert_eq!(tensor.to_vec::<i64>().unwrap(), [1, -2, 3, 4]);
+
+        let err = Tensor::try_from_vec(Shape::new(vec![3]), vec![true, false]).unwrap_err();
+        assert!(matches!(
+            err,
+            IrError::ShapeMismatch {
+                expected: 3,
+                actual: 2
+            }
+        ));
+
+        let err = tensor.to_vec::<f64>().unwrap_err();
+        assert!(matches!(
+            err,
+            IrError::DtypeMismatch {
+                expected: DataType::Double,
+                actual: DataType::Int64,
+            }
+        ));
+    }
+
+    #[test]
+    fn test_tensor_as_slice() {
+        let mut tensor = Tensor::from_vec(Shape::new(vec![3]), vec![1u16, 2, 3]);
+        assert_eq!(*tensor.as_slice::<u16>().unwrap(), [1, 2, 3]);
+        tensor.as_mut_slice::<u16>().unwrap()[1] = 7;
+        assert_eq!(tensor.to_vec::<u16>().unwrap(), [1, 7, 3]);
+        assert!(tensor.as_slice::<i16>().is_err());
+
+        // Bytes other than 0 and 1 are not valid bools, so they are copied.
+        let data = vec![2, 0, 1];
+        let mut tensor = Tensor::from_bytes(DataType::Bool, Shape::new(vec![3]), data);
+        assert!(matches!(tensor.as_slice::<bool>().unwrap(), Cow::Owned(_)));
+        {
+            let mut slice = tensor.as_mut_slice::<bool>().unwrap();
+            assert!(matches!(slice.inner, SliceMutInner::Copied { .. }));
+            assert_eq!(*slice, [true, false, true]);
+            slice[2] = false;
+        }
+        assert_eq!(tensor.as_bytes(), [1, 0, 0]);
+    }
+
+    #[test]
+    fn test_pack_bits() {
+        assert_eq!(pack_bits(&[0x1, 0x2, 0xf], 4), [0x21, 0x0f]);
+        assert_eq!(unpack_bits(&[0x21, 0x0f], 4, 3).unwrap(), [0x1, 0x2, 0xf]);
+        assert_eq!(pack_bits(&[0, 1, 2, 3, 1], 2), [0b1110_0100, 0b01]);
+        assert_eq!(
+            unpack_bits(&[0b1110_0100, 0b01], 2, 5).unwrap(),
+            [0, 1, 2, 3, 1]
+        );
+        assert!(matches!(
+            unpack_bits(&[0x21], 4, 3),
+            Err(IrError::ShapeMismatch {
+                expected: 2,
+                actual: 1
+            })
+        ));
+    }
+
+    #[test]
+    fn test_packed_tensor() {
+        let shape = Shape::new(vec![5]);
+        let mut tensor =
+            PackedTensor::pack(DataType::Uint4, shape.clone(), &[1, 2, 3, 15, 0]).unwrap();
+        assert_eq!(tensor.data(), [0x21, 0xf3, 0x00]);
+        assert_eq!(tensor.nbytes(), 3);
+        assert_eq!(tensor.size(), 5);
+        assert_eq!(tensor.get(3), Some(15));
+        assert_eq!(tensor.get(5), None);
+        tensor.set(4, 9).unwrap();
+        assert_eq!(tensor.data(), [0x21, 0xf3, 0x09]);
+        assert!(matches!(
+            tensor.set(0, 16),
+            Err(IrError::OutOfRange {
+                value: 16,
+                dtype: DataType::Uint4,
+            })
+        ));
+        assert!(matches!(
+            tensor.set(5, 0),
+            Err(IrError::IndexOutOfBounds { index: 5, size: 5 })
+        ));
+
+        let mut tensor =
+            PackedTensor::pack(DataType::Int2, shape.clone(), &[-2, -1, 0, 1, -1]).unwrap();
+        assert_eq!(tensor.data(), [0b0100_1110, 0b11]);
+        assert_eq!(tensor.unpack(), [-2, -1, 0, 1, -1]);
+        tensor.set(1, 1).unwrap();
+        assert_eq!(tensor.get(1), Some(1));
+        assert_eq!(tensor.get(2), Some(0));
+
+        assert!(PackedTensor::pack(DataType::Int4, shape.clone(), &[8, 0, 0, 0, 0]).is_err());
+        assert!(matches!(
+            PackedTensor::try_new(DataType::Int8, shape),
+            Err(IrError::NotPacked(DataType::Int8))
+        ));
+        assert!(matches!(
+            PackedTensor::try_new(DataType::Int4, Shape::new(vec![-1])),
+            Err(IrError::NegativeDimension(_))
+        ));
+        assert!(matches!(
+            PackedTensor::pack(DataType::Int4, Shape::new(vec![i64::MAX, 4]), &[]),
+            Err(IrError::ShapeTooLarge(_))
+        ));
+    }
+
+    #[test]
+    fn test_packed_tensor_conversion() {
+        let tensor = Tensor::from_bytes(DataType::Int4, Shape::new(vec![2, 2]), vec![0x8f, 0x71]);
+        let packed = PackedTensor::from_tensor(tensor).unwrap();
+        assert_eq!(packed.unpack(), [-1, -8, 1, 7]);
+        let tensor = packed.into_tensor();
+        let cast = tensor.cast(DataType::Int8, false).unwrap();
+        assert_eq!(cast.to_vec::<i8>().unwrap(), [-1, -8, 1, 7]);
+
+        let tensor = Tensor::from_bytes(DataType::Int4, Shape::new(vec![3]), vec![0; 2]);
+        assert!(PackedTensor::from_tensor(tensor).is_ok());
+        let tensor = Tensor::new(DataType::Uint8, Shape::new(vec![3]));
+        assert!(PackedTensor::from_tensor(tensor).is_err());
+    }
+
+    #[test]
+    fn test_lazy_tensor_caching() {
+        let calls = Rc::new(std::cell::Cell::new(0));
+        let counter = Rc::clone(&calls);
+        let provider = move || {
+            counter.set(counter.get() + 1);
+            Ok(vec![1, 2, 3, 4])
+        };
+        let tensor = LazyTensor::new(DataType::Int16, Shape::new(vec![2]), provider);
+        assert_eq!(tensor.nbytes(), 4);
+        assert_eq!(calls.get(), 0);
+        assert_eq!(&*tensor.data().unwrap(), [1, 2, 3, 4]);
+        tensor.data().unwrap();
+        assert_eq!(calls.get(), 2);
+        assert!(!tensor.is_cached());
+
+        let tensor = tensor.cached(true);
+        tensor.data().unwrap();
+        let tensor = tensor.to_tensor().unwrap();
+        assert_eq!(tensor.to_vec::<i16>().unwrap(), [0x0201, 0x0403]);
+        assert_eq!(calls.get(), 3);
+    }
+
+    #[test]
+    fn test_lazy_tensor_eviction() {
+        let tensor =
+            LazyTensor::new(DataType::Uint8, Shape::new(vec![3]), || Ok(vec![5; 3])).cached(true);
+        let mut out = Vec::new();
+        assert_eq!(tensor.write_to(&mut out).unwrap(), 3);
+        assert!(!tensor.is_cached());
+        tensor.data().unwrap();
+        assert!(tensor.is_cached());
+        tensor.evict();
+        assert!(!tensor.is_cached());
+
+        let tensor = LazyTensor::new(DataType::Float, Shape::new(vec![3]), || Ok(vec![0; 8]));
+        assert!(matches!(
+            tensor.data(),
+            Err(IrError::ShapeMismatch {
+                expected: 12,
+                actual: 8
+            })
+        ));
+
+        let huge = Shape::new(vec![i64::MAX, 2]);
+        let tensor = LazyTensor::new(DataType::Float, huge, || Ok(vec![]));
+        assert!(matches!(tensor.data(), Err(IrError::ShapeTooLarge(_))));
+        assert_eq!(tensor.nbytes(), 0);
+        let tensor = LazyTensor::new(DataType::Float, Shape::new(vec![-1]), || Ok(vec![]));
+        assert!(matches!(tensor.data(), Err(IrError::NegativeDimension(_))));
+    }
+
+    #[test]
+    fn test_lazy_tensor_streaming() {
+        /// Produces a large ramp in chunks.
+        struct Ramp(usize);
+
+        impl TensorProvider for Ramp {
+            fn materialize(&self) -> Result<Vec<u8>> {
+                Ok((0..self.0).map(|i| i as u8).collect())
+            }
+
+            fn write_to(&self, writer: &mut dyn Write) -> Result<()> {
+                let mut chunk = Vec::with_capacity(256);
+                for start in (0..self.0).step_by(256) {
+                    chunk.clear();
+                    chunk.extend((start..self.0.min(start + 256)).map(|i| i as u8));
+                    writer.write_all(&chunk)?;
+                }
+                Ok(())
+            }
+        }
+
+        let tensor = LazyTensor::new(DataType::Uint8, Shape::new(vec![1000]), Ramp(1000));
+        let mut out = Vec::new();
+        assert_eq!(tensor.write_to(&mut out).unwrap(), 1000);
+        assert_eq!(out, *tensor.data().unwrap());
+
+        let tensor = LazyTensor::new(DataType::Uint8, Shape::new(vec![999]), Ramp(1000));
+        assert!(tensor.write_to(&mut Vec::new()).is_err());
+    }
+
     #[test]
     fn test_tensor_protocol() {
         let shape = Shape::new(vec![3, 4]);
@@ -384,6 +1366,17 @@ mod tests {
         assert_eq!(tensor.nbytes(), 16);
     }
 
+    #[test]
+    fn test_sparse_tensor() {
+        let values = Tensor::new(DataType::Float, Shape::new(vec![3]));
+        let indices = Tensor::new(DataType::Int64, Shape::new(vec![3, 2]));
+        let sparse = SparseTensor::new(values, indices, vec![4, 5]);
+
+        assert_eq!(sparse.nnz(), 3);
+        assert_eq!(sparse.dense_shape(), Shape::new(vec![4, 5]));
+        assert_eq!(sparse.values.dtype(), DataType::Float);
+    }
+
     #[test]
     #[should_panic(expected = "Data length")]
     fn test_string_tensor_wrong_size() {
@@ -0,0 +1,48 @@
+// Copyright (c) ONNX Project Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Builders shared by the unit tests of this crate.
+
+use crate::enums::DataType;
+use crate::graph::Graph;
+use crate::model::Model;
+use crate::node::Node;
+use crate::shape::{Shape, SymbolicDim};
+use crate::types::TensorType;
+use crate::value::Value;
+
+/// Returns the named symbolic dimension `name`.
+pub(crate) fn sym(name: &str) -> SymbolicDim {
+    SymbolicDim::Symbol(Some(name.to_string()))
+}
+
+/// Returns a tensor value with the given element type and dimensions.
+pub(crate) fn value(name: &str, dtype: DataType, dims: Vec<SymbolicDim>) -> Value {
+    let mut value = Value::new(name);
+    value.type_ = Some(TensorType::new(dtype));
+    value.shape = Some(Shape::new(dims));
+    value
+}
+
+/// Returns a node whose inputs and outputs are untyped values with the given names.
+pub(crate) fn node(op_type: &str, inputs: &[&str], outputs: &[&str]) -> Node {
+    Node::with_io(op_type, inputs.iter().copied(), outputs.iter().copied())
+}
+
+/// Returns a graph of `nodes` with untyped inputs and outputs of the given names.
+pub(crate) fn graph(nodes: Vec<Node>, inputs: &[&str], outputs: &[&str]) -> Graph {
+    let mut graph = Graph::new();
+    graph.inputs = inputs.iter().map(|n| Value::new(*n)).collect();
+    graph.outputs = outputs.iter().map(|n| Value::new(*n)).collect();
+    for node in nodes {
+        graph.append(node);
+    }
+    graph
+}
+
+/// Returns a model of `graph` importing `opset` of the default domain.
+pub(crate) fn model(graph: Graph, opset: i32) -> Model {
+    let mut model = Model::new(graph);
+    model.set_opset_import("", opset);
+    model
+}
@@ -4,9 +4,11 @@
 //! ONNX type system.
 //!
 //! This module defines the type representation for ONNX values, including
-//! tensor types, sequence types, optional types, and sparse tensor types.
+//! tensor types, sequence types, optional types, sparse tensor types, map
+//! types and opaque types.
 
 use crate::enums::DataType;
+use crate::shape::Shape;
 use std::fmt;
 
 /// Base trait for ONNX types.
@@ -85,3 +87,187 @@ pub struct OptionalType {
     pub elem_type: Box<dyn Type>,
     pub denotation: Option<String>,
 }
+
+/// A complete ONNX type, equivalent to `TypeProto`.
+///
+/// Unlike [`TensorType`], this carries the shape of tensor types at every
+/// level of nesting, so any `TypeProto` can be represented without loss.
+/// It is the value of `TYPE_PROTO` attributes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypeProto {
+    pub kind: TypeKind,
+    pub denotation: Option<String>,
+}
+
+/// The kind of a [`TypeProto`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum TypeKind {
+    Tensor {
+        elem_type: DataType,
+        shape: Option<Shape>,
+    },
+    SparseTensor {
+        elem_type: DataType,
+        shape: Option<Shape>,
+    },
+    Sequence(Box<TypeProto>),
+    Optional(Box<TypeProto>),
+    Map {
+        key_type: DataType,
+        value_type: Box<TypeProto>,
+    },
+    /// A type defined outside ONNX, identified by domain and name.
+    Opaque {
+        domain: String,
+        name: String,
+    },
+}
+
+impl TypeProto {
+    pub fn new(kind: TypeKind) -> Self {
+        Self {
+            kind,
+            denotation: None,
+        }
+    }
+
+    /// Creates a tensor type with an optional shape.
+    pub fn tensor(elem_type: DataType, shape: Option<Shape>) -> Self {
+        Self::new(TypeKind::Tensor { elem_type, shape })
+    }
+
+    /// Creates a sparse tensor type with an optional shape.
+    pub fn sparse_tensor(elem_type: DataType, shape: Option<Shape>) -> Self {
+        Self::new(TypeKind::SparseTensor { elem_type, shape })
+    }
+
+    /// Creates a sequence type.
+    pub fn sequence(elem_type: TypeProto) -> Self {
+        Self::new(TypeKind::Sequence(Box::new(elem_type)))
+    }
+
+    /// Creates an optional type.
+    pub fn optional(elem_type: TypeProto) -> Self {
+        Self::new(TypeKind::Optional(Box::new(elem_type)))
+    }
+
+    /// Creates a map type.
+    pub fn map(key_type: DataType, value_type: TypeProto) -> Self {
+        Self::new(TypeKind::Map {
+            key_type,
+            value_type: Box::new(value_type),
+        })
+    }
+
+    /// Creates an opaque type.
+    pub fn opaque(domain: impl Into<String>, name: impl Into<String>) -> Self {
+        Self::new(TypeKind::Opaque {
+            domain: domain.into(),
+            name: name.into(),
+        })
+    }
+
+    /// Returns the shape if this is a tensor or sparse tensor type.
+    pub fn shape(&self) -> Option<&Shape> {
+        match &self.kind {
+            TypeKind::Tensor { shape, .. } | TypeKind::SparseTensor { shape, .. } => shape.as_ref(),
+            _ => None,
+        }
+    }
+}
+
+impl Type for TypeProto {
+    fn denotation(&self) -> Option<&str> {
+        self.denotation.as_deref()
+    }
+
+    /// Returns the element type of the innermost tensor type.
+    ///
+    /// For map types, this is the element type of the value type.
+    fn dtype(&self) -> Option<DataType> {
+        match &self.kind {
+            TypeKind::Tensor { elem_type, .. } | TypeKind::SparseTensor { elem_type, .. } => {
+                Some(*elem_type)
+            }
+            TypeKind::Sequence(elem) | TypeKind::Optional(elem) => elem.dtype(),
+            TypeKind::Map { value_type, .. } => value_type.dtype(),
+            TypeKind::Opaque { .. } => None,
+        }
+    }
+}
+
+impl fmt::Display for TypeProto {
+    /// Formats the type in the notation of the ONNX operator documentation,
+    /// e.g. `seq(tensor(f32)[N,3])`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            TypeKind::Tensor { elem_type, shape } => {
+                write!(f, "tensor({})", elem_type.short_name())?;
+                if let Some(shape) = shape {
+                    write!(f, "{}", shape)?;
+                }
+                Ok(())
+            }
+            TypeKind::SparseTensor { elem_type, shape } => {
+                write!(f, "sparse_tensor({})", elem_type.short_name())?;
+                if let Some(shape) = shape {
+                    write!(f, "{}", shape)?;
+                }
+                Ok(())
+            }
+            TypeKind::Sequence(elem) => write!(f, "seq({})", elem),
+            TypeKind::Optional(elem) => write!(f, "optional({})", elem),
+            TypeKind::Map {
+                key_type,
+                value_type,
+            } => write!(f, "map({},{})", key_type.short_name(), value_type),
+            TypeKind::Opaque { domain, name } => write!(f, "opaque({},{})", domain, name),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tensor_type() {
+        let tensor_type = TensorType::new(DataType::Float);
+        assert_eq!(tensor_type.dtype(), Some(DataType::Float));
+        assert_eq!(tensor_type.denotation(), None);
+    }
+
+    #[test]
+    fn test_type_proto_nested() {
+        let shape = Shape::new(vec![2, 3]);
+        let seq = TypeProto::sequence(TypeProto::tensor(DataType::Float, Some(shape.clone())));
+        let opt = TypeProto::optional(seq.clone());
+
+        assert_eq!(opt.dtype(), Some(DataType::Float));
+        assert_eq!(opt.to_string(), "optional(seq(tensor(f32)[2,3]))");
+        match &opt.kind {
+            TypeKind::Optional(inner) => assert_eq!(**inner, seq),
+            _ => panic!("Expected optional type"),
+        }
+        match &seq.kind {
+            TypeKind::Sequence(inner) => assert_eq!(inner.shape(), Some(&shape)),
+            _ => panic!("Expected sequence type"),
+        }
+    }
+
+    #[test]
+    fn test_type_proto_map() {
+        let map = TypeProto::map(DataType::Int64, TypeProto::tensor(DataType::Double, None));
+        assert_eq!(map.dtype(), Some(DataType::Double));
+        assert_eq!(map.to_string(), "map(i64,tensor(f64))");
+        assert_eq!(map.shape(), None);
+    }
+
+    #[test]
+    fn test_type_proto_opaque() {
+        let opaque = TypeProto::opaque("com.example", "Handle");
+        assert_eq!(opaque.dtype(), None);
+        assert_eq!(opaque.shape(), None);
+        assert_eq!(opaque.to_string(), "opaque(com.example,Handle)");
+    }
+}
@@ -5,15 +5,18 @@
 
 use crate::metadata::MetadataStore;
 use crate::shape::Shape;
+use crate::tensor::Tensor;
 use crate::types::TensorType;
 use std::collections::HashMap;
 
 /// A value represents an input or output of a node or graph.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Value {
     pub name: String,
     pub shape: Option<Shape>,
     pub type_: Option<TensorType>,
+    /// The constant tensor of the value, set for initializers.
+    pub const_value: Option<Tensor>,
     pub doc_string: Option<String>,
     pub metadata_props: HashMap<String, String>,
     pub meta: MetadataStore,
@@ -29,6 +32,7 @@ impl Value {
             name: name.into(),
             shape: None,
             type_: None,
+            const_value: None,
             doc_string: None,
             metadata_props: HashMap::new(),
             meta: MetadataStore::new(),
@@ -85,6 +89,18 @@ impl Value {
     // }
 }
 
+impl From<&str> for Value {
+    fn from(name: &str) -> Self {
+        Value::new(name)
+    }
+}
+
+impl From<String> for Value {
+    fn from(name: String) -> Self {
+        Value::new(name)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -95,5 +111,12 @@ mod tests {
         assert_eq!(value.name, "test_value");
         assert!(value.shape.is_none());
         assert!(value.type_.is_none());
+        assert!(value.const_value.is_none());
+    }
+
+    #[test]
+    fn test_value_from_name() {
+        assert_eq!(Value::from("x").name, "x");
+        assert_eq!(Value::from("y".to_string()).name, "y");
     }
 }
@@ -0,0 +1,140 @@
+// Copyright (c) ONNX Project Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Tests for the invariant checks enabled by `onnx_ir_core::DEBUG`.
+//!
+//! These live in their own test binary because `DEBUG` is process-wide.
+
+use onnx_ir_core::{function::Function, graph::Graph, node::Node, value::Value, DEBUG};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::atomic::Ordering;
+
+fn node(op_type: &str, inputs: &[&str], outputs: &[&str]) -> Node {
+    let mut node = Node::with_io(op_type, inputs.iter().copied(), outputs.iter().copied());
+    node.name = Some(op_type.to_lowercase());
+    node
+}
+
+fn graph() -> Graph {
+    DEBUG.store(true, Ordering::Relaxed);
+    let mut graph = Graph::new();
+    graph.inputs.push(Value::new("x"));
+    graph.append(node("Relu", &["x"], &["y"]));
+    graph.append(node("Neg", &["y"], &["z"]));
+    graph.outputs.push(Value::new("z"));
+    graph
+}
+
+#[test]
+fn test_valid_mutations() {
+    let mut graph = graph();
+    graph.append(node("Abs", &["z"], &["w"]));
+    graph.prepend(node("Exp", &["x"], &["e"]));
+    assert_eq!(graph.pop_last().unwrap().op_type, "Abs");
+    assert_eq!(graph.pop_first().unwrap().op_type, "Exp");
+
+    graph.append(node("Sigmoid", &["x"], &["s"]));
+    assert_eq!(graph.replace_all_uses_with("s", &Value::new("z")), 0);
+    assert_eq!(graph.num_nodes(), 3);
+}
+
+#[test]
+#[should_panic(expected = "Invalid graph: Identity(identity) redefines value 'y'")]
+fn test_append_duplicate_definition() {
+    let mut graph = graph();
+    graph.append(node("Identity", &["x"], &["y"]));
+}
+
+#[test]
+#[should_panic(expected = "output 'y' of Relu(relu) is used by Neg(neg)")]
+fn test_remove_node_with_consumers() {
+    let mut graph = graph();
+    graph.pop_first();
+}
+
+#[test]
+#[should_panic(expected = "output 'z' of Neg(neg) is a graph output")]
+fn test_remove_node_producing_graph_output() {
+    let mut graph = graph();
+    graph.pop_last();
+}
+
+#[test]
+#[should_panic(expected = "Abs(abs) uses value 'z' before Neg(neg) defines it")]
+fn test_prepend_consumer() {
+    let mut graph = graph();
+    graph.prepend(node("Abs", &["z"], &["w"]));
+}
+
+#[test]
+fn test_valid_insertions() {
+    let mut graph = graph();
+    graph.insert_after(0, node("Abs", &["y"], &["a"]));
+    graph.insert_before(0, node("Exp", &["x"], &["e"]));
+    let op_types: Vec<_> = graph.iter_nodes().map(|n| n.op_type.as_str()).collect();
+    assert_eq!(op_types, vec!["Exp", "Relu", "Abs", "Neg"]);
+}
+
+#[test]
+#[should_panic(expected = "Abs(abs) uses value 'z' before Neg(neg) defines it")]
+fn test_insert_before_producer() {
+    let mut graph = graph();
+    graph.insert_after(0, node("Abs", &["z"], &["w"]));
+}
+
+#[test]
+#[should_panic(expected = "Neg(neg) uses value 'late' before Cos(cos) defines it")]
+fn test_replace_uses_with_later_value() {
+    let mut graph = graph();
+    graph.append(node("Cos", &["z"], &["late"]));
+    graph.replace_all_uses_with("y", &Value::new("late"));
+}
+
+#[test]
+fn test_failed_checks_leave_graph_unchanged() {
+    let mut graph = graph();
+    assert!(catch_unwind(AssertUnwindSafe(|| graph.pop_first())).is_err());
+    assert!(catch_unwind(AssertUnwindSafe(|| graph.pop_last())).is_err());
+    assert_eq!(graph.num_nodes(), 2);
+
+    graph.append(node("Cos", &["z"], &["late"]));
+    let replace = || graph.replace_all_uses_with("y", &Value::new("late"));
+    assert!(catch_unwind(AssertUnwindSafe(replace)).is_err());
+    assert_eq!(graph.iter_nodes().nth(1).unwrap().inputs[0].name, "y");
+}
+
+#[test]
+#[should_panic(expected = "output 'y' of Relu(relu) is used by Neg(neg)")]
+fn test_function_checks() {
+    let mut function = Function::new("F", "custom");
+    function.inputs.push(Value::new("x"));
+    function.outputs.push(Value::new("z"));
+    function.append(node("Relu", &["x"], &["y"]));
+    function.append(node("Neg", &["y"], &["z"]));
+    let redefine = || function.append(node("Identity", &["x"], &["y"]));
+    assert!(catch_unwind(AssertUnwindSafe(redefine)).is_err());
+    let prepend = || function.prepend(node("Abs", &["z"], &["w"]));
+    assert!(catch_unwind(AssertUnwindSafe(prepend)).is_err());
+    assert_eq!(function.num_nodes(), 2);
+    function.pop_first();
+}
+
+#[test]
+fn test_function_mutations() {
+    let mut function = Function::new("F", "custom");
+    function.inputs.push(Value::new("x"));
+    function.outputs.push(Value::new("z"));
+    function.append(node("Relu", &["x"], &["y"]));
+    function.append(node("Neg", &["y"], &["z"]));
+
+    let insert = || function.insert_before(1, node("Abs", &["z"], &["w"]));
+    assert!(catch_unwind(AssertUnwindSafe(insert)).is_err());
+    function.insert_after(0, node("Cos", &["y"], &["late"]));
+    let replace = || function.replace_all_uses_with("x", &Value::new("late"));
+    assert!(catch_unwind(AssertUnwindSafe(replace)).is_err());
+    assert_eq!(function.replace_all_uses_with("y", &Value::new("x")), 2);
+
+    let clear = || function.clear_nodes();
+    assert!(catch_unwind(AssertUnwindSafe(clear)).is_err());
+    assert_eq!(function.num_nodes(), 3);
+}
@@ -145,7 +145,7 @@ fn test_attribute_values() {
     let string_attr = Attr::string("mode", "NOTSET".to_string());
     assert!(matches!(
         string_attr.value,
-        AttrValue::String(ref s) if s == "NOTSET"
+        AttrValue::String(ref s) if s == b"NOTSET"
     ));
 
     let ints_attr = Attr::ints("shape", vec![1, 2, 3]);
@@ -0,0 +1,191 @@
+#!/usr/bin/env python3
+# Copyright (c) ONNX Project Contributors
+# SPDX-License-Identifier: Apache-2.0
+"""Generates the operator tables of the built-in schemas.
+
+Writes every version of every operator in the default domain and in
+`ai.onnx.ml`, as defined by the installed `onnx` package, to
+`crates/onnx-ir-core/src/schema/onnx.txt` and `ml.txt`. The format is
+described in `crates/onnx-ir-core/src/schema/table.rs`.
+
+Usage:
+
+    pip install onnx numpy
+    python scripts/gen_schema_tables.py
+"""
+
+from __future__ import annotations
+
+import pathlib
+import re
+
+SCHEMA_DIR = pathlib.Path(__file__).parent.parent / "crates/onnx-ir-core/src/schema"
+
+# Named sets of element types, used to shorten the type constraints. A set
+# may refer to the sets defined before it.
+TYPE_SETS = [
+    ("FLOAT", "float16 float double"),
+    ("FLOAT_BF16", "@FLOAT bfloat16"),
+    ("FLOAT8", "float8e4m3fn float8e4m3fnuz float8e5m2 float8e5m2fnuz"),
+    ("INDEX", "int32 int64"),
+    ("UINT", "uint8 uint16 uint32 uint64"),
+    ("INT", "int8 int16 int32 int64 @UINT"),
+    ("NUMERIC_V6", "uint32 uint64 int32 int64 @FLOAT"),
+    ("NUMERIC_V13", "@NUMERIC_V6 bfloat16"),
+    ("NUMERIC_V12", "@INT @FLOAT"),
+    ("NUMERIC", "@NUMERIC_V12 bfloat16"),
+    ("SIGNED_V6", "int8 int16 int32 int64 @FLOAT"),
+    ("SIGNED", "@SIGNED_V6 bfloat16"),
+    ("CAST_V6", "@NUMERIC_V12 bool"),
+    ("CAST_V13", "@CAST_V6 string bfloat16"),
+    ("ALL_V1", "@NUMERIC_V12 string bool complex64 complex128"),
+    ("ALL_V13", "@ALL_V1 bfloat16"),
+    ("ALL_V19", "@ALL_V13 @FLOAT8"),
+    ("ALL_V21", "@ALL_V19 uint4 int4"),
+    ("ALL_V23", "@ALL_V21 float4e2m1"),
+    ("ALL_V24", "@ALL_V23 float8e8m0"),
+]
+
+DOMAINS = [
+    ("", "onnx.txt", "the default ONNX domain"),
+    ("ai.onnx.ml", "ml.txt", "the ai.onnx.ml domain"),
+]
+
+# A type string with a tensor element type, e.g. `seq(tensor(float))`
+TENSOR_TYPE = re.compile(r"^(.*?)(?<![a-z_])tensor\((\w+)\)(.*)$")
+
+
+def expand_sets() -> dict[str, list[str]]:
+    sets: dict[str, list[str]] = {}
+    for name, definition in TYPE_SETS:
+        types: list[str] = []
+        for word in definition.split():
+            types.extend(sets[word[1:]] if word.startswith("@") else [word])
+        sets[name] = types
+    return sets
+
+
+def compress(types: list[str]) -> list[str]:
+    """Writes a list of type strings with the named type sets.
+
+    Types are grouped by the structure around their tensor type, such as
+    `seq(...)`, and tensor types are written as their bare element type.
+    Each group is covered by the largest named sets it contains, and the
+    remaining element types are listed one by one.
+    """
+    sets = expand_sets()
+    groups: dict[str, list[str]] = {}
+    words: list[str] = []
+    for type_str in types:
+        type_str = type_str.replace(" ", "")
+        match = TENSOR_TYPE.match(type_str)
+        if match is None:
+            words.append(type_str)
+            continue
+        prefix, dtype, suffix = match.groups()
+        groups.setdefault(prefix + "{}" + suffix, []).append(dtype)
+    result: list[str] = []
+    for pattern, dtypes in groups.items():
+        remaining = list(dtypes)
+        while True:
+            candidates = [
+                name
+                for name, members in sets.items()
+                if set(members) <= set(remaining)
+            ]
+            if not candidates:
+                break
+            name = max(candidates, key=lambda n: len(sets[n]))
+            result.append(pattern.format("@" + name))
+            remaining = [d for d in remaining if d not in sets[name]]
+        for dtype in remaining:
+            result.append(pattern.format(dtype))
+    return result + words
+
+
+def format_param(param) -> str:
+    from onnx import defs
+
+    option = defs.OpSchema.FormalParameterOption
+    words = [param.name, param.type_str.replace(" ", "")]
+    if param.option == option.Optional:
+        words.append("optional")
+    elif param.option == option.Variadic:
+        words.append("variadic" if param.min_arity == 1 else f"variadic({param.min_arity})")
+        if not param.is_homogeneous:
+            words.append("heterogeneous")
+    return " ".join(words)
+
+
+def format_default(attr) -> str | None:
+    import numpy as np
+    from onnx import AttributeProto, helper
+
+    value = attr.default_value
+    if value is None or value.type == AttributeProto.UNDEFINED:
+        return None
+    value = helper.get_attribute_value(value)
+
+    def scalar(v) -> str:
+        if isinstance(v, bytes):
+            return '"' + v.decode() + '"'
+        if isinstance(v, float):
+            return str(np.float32(v))
+        return str(v)
+
+    if isinstance(value, list):
+        return "[" + ", ".join(scalar(v) for v in value) + "]"
+    return scalar(value)
+
+
+def format_schema(schema) -> str:
+    from onnx import AttributeProto
+
+    lines = [f"op {schema.name} {schema.since_version}"]
+    if schema.deprecated:
+        lines[0] += " deprecated"
+    lines += [f"  input {format_param(p)}" for p in schema.inputs]
+    lines += [f"  output {format_param(p)}" for p in schema.outputs]
+    for name, attr in sorted(schema.attributes.items()):
+        line = f"  attr {name} {AttributeProto.AttributeType.Name(attr.type)}"
+        default = format_default(attr)
+        if attr.required:
+            line += " required"
+        elif default is not None:
+            line += f" = {default}"
+        lines.append(line)
+    for constraint in schema.type_constraints:
+        types = " ".join(compress(list(constraint.allowed_type_strs)))
+        lines.append(f"  type {constraint.type_param_str} {types}")
+    return "\n".join(lines)
+
+
+def header(description: str, max_version: int) -> str:
+    lines = [
+        f"# Operator schemas of {description}, opsets 1 to {max_version}.",
+        "#",
+        "# Generated by scripts/gen_schema_tables.py from the ONNX operator",
+        "# definitions; see schema/table.rs for the format.",
+        "",
+    ]
+    lines += [f"@{name} = {definition}" for name, definition in TYPE_SETS]
+    return "\n".join(lines)
+
+
+def main() -> None:
+    from onnx import defs
+
+    all_schemas = defs.get_all_schemas_with_history()
+    for domain, file_name, description in DOMAINS:
+        schemas = sorted(
+            (s for s in all_schemas if s.domain == domain),
+            key=lambda s: (s.name, s.since_version),
+        )
+        max_version = max(s.since_version for s in schemas)
+        blocks = [header(description, max_version)]
+        blocks += [format_schema(s) for s in schemas]
+        (SCHEMA_DIR / file_name).write_text("\n\n".join(blocks) + "\n")
+
+
+if __name__ == "__main__":
+    main()